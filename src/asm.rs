@@ -0,0 +1,837 @@
+// Saturn assembler — a SASM/MASD subset, the inverse of disasm.rs.
+//
+// Accepts everything the disassembler prints, plus:
+//   - labels: SASM style in column 1 (`LOOP  A=A+1  A`) or MASD style on a
+//     line of their own (`*LOOP`); `NAME  EQU  expr` for constants
+//   - LC(n)/LA(n)/D0=(n)/D1=(n) with an expression operand; LCHEX/LAHEX
+//     with literal digits
+//   - GOYES/RTNYES either on the test's line or on the following line
+//   - NIBHEX, CON(n) and REL(n) data directives
+//
+// Numbers are decimal unless prefixed with `#` (hex). Expressions are labels
+// and numbers joined with `+`/`-`. `*` at the start of a line and `;`
+// anywhere start a comment. Field selectors map onto the P_FIELD..A_FIELD
+// codes of types.rs; a missing field on a register op selects the A-field
+// shorthand (Cx-Fx) like the disassembler prints it.
+//
+// Two passes: every statement's size is known from its mnemonic, so pass 1
+// lays out addresses and labels, pass 2 resolves jumps and constants and
+// checks that relative displacements fit.
+
+use std::collections::HashMap;
+
+use crate::disasm::{ADD, ADDR_OPS, COPY, DAT, EQ_TESTS, LOGIC, ORD_TESTS, REGS, SHIFT, SUB};
+use crate::types::*;
+
+/// Prolog of a Code object.
+pub const DOCODE: i32 = 0x02dcc;
+
+/// Assemble `source` for loading at `origin`, returning one nibble per byte.
+pub fn assemble(source: &str, origin: i32) -> Result<Vec<u8>, String> {
+    let mut asm = Assembler::new(origin);
+    for (i, line) in source.lines().enumerate() {
+        asm.line(line)
+            .map_err(|e| format!("line {}: {}", i + 1, e))?;
+    }
+    asm.finish()
+}
+
+/// Assemble `source` into a Code object whose prolog sits at `origin`.
+pub fn assemble_code(source: &str, origin: i32) -> Result<Vec<u8>, String> {
+    Ok(code_object(&assemble(source, origin + 10)?))
+}
+
+/// Wrap machine code in a Code object: DOCODE prolog, 5-nibble length
+/// (counting itself), body.
+pub fn code_object(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 10);
+    push_value(&mut out, DOCODE, 5);
+    push_value(&mut out, body.len() as i32 + 5, 5);
+    out.extend_from_slice(body);
+    out
+}
+
+/// Append `width` nibbles of `value`, low nibble first.
+fn push_value(out: &mut Vec<u8>, value: i32, width: usize) {
+    out.extend((0..width).map(|i| ((value >> (4 * i)) & 0xf) as u8));
+}
+
+/// Zero-operand instructions and their opcodes.
+#[rustfmt::skip]
+const FIXED: [(&str, &str); 62] = [
+    ("RTNSXM", "00"), ("RTN", "01"), ("RTNSC", "02"), ("RTNCC", "03"),
+    ("SETHEX", "04"), ("SETDEC", "05"), ("RSTK=C", "06"), ("C=RSTK", "07"),
+    ("CLRST", "08"), ("C=ST", "09"), ("ST=C", "0A"), ("CSTEX", "0B"),
+    ("P=P+1", "0C"), ("P=P-1", "0D"), ("RTI", "0F"),
+    ("RTNC", "400"), ("RTNNC", "500"), ("NOP3", "420"), ("NOP4", "6300"), ("NOP5", "64000"),
+    ("OUT=CS", "800"), ("OUT=C", "801"), ("A=IN", "802"), ("C=IN", "803"),
+    ("UNCNFG", "804"), ("CONFIG", "805"), ("C=ID", "806"), ("SHUTDN", "807"),
+    ("INTON", "8080"), ("RSI", "80810"), ("BUSCB", "8083"), ("PC=(A)", "808C"),
+    ("BUSCD", "808D"), ("PC=(C)", "808E"), ("INTOFF", "808F"),
+    ("C+P+1", "809"), ("RESET", "80A"), ("BUSCC", "80B"), ("SREQ?", "80E"),
+    ("ASLC", "810"), ("BSLC", "811"), ("CSLC", "812"), ("DSLC", "813"),
+    ("ASRC", "814"), ("BSRC", "815"), ("CSRC", "816"), ("DSRC", "817"),
+    ("PC=A", "81B2"), ("PC=C", "81B3"), ("A=PC", "81B4"), ("C=PC", "81B5"),
+    ("APCEX", "81B6"), ("CPCEX", "81B7"),
+    ("ASRB", "81C"), ("BSRB", "81D"), ("CSRB", "81E"), ("DSRB", "81F"),
+    ("XM=0", "821"), ("SB=0", "822"), ("SR=0", "824"), ("MP=0", "828"), ("CLRHST", "82F"),
+];
+
+/// Zero-operand tests, followed by GOYES/RTNYES.
+const FIXED_TESTS: [(&str, &str); 4] = [
+    ("?XM=0", "831"),
+    ("?SB=0", "832"),
+    ("?SR=0", "834"),
+    ("?MP=0", "838"),
+];
+
+enum Fix {
+    /// Unsigned value.
+    Abs,
+    /// Signed displacement from the statement address plus `base`.
+    Rel { base: i32, what: &'static str },
+}
+
+struct Fixup {
+    at: usize,
+    width: usize,
+    expr: String,
+    kind: Fix,
+}
+
+/// One statement's nibbles, with operands still to be resolved.
+#[derive(Default)]
+struct Emit {
+    nibs: Vec<u8>,
+    fixups: Vec<Fixup>,
+    /// A test that still needs its GOYES/RTNYES.
+    test: bool,
+}
+
+impl Emit {
+    fn hex(code: &str) -> Self {
+        let mut e = Emit::default();
+        e.push_hex(code);
+        e
+    }
+
+    fn test(code: &str) -> Self {
+        let mut e = Emit::hex(code);
+        e.test = true;
+        e
+    }
+
+    fn push(&mut self, nibs: &[u8]) -> &mut Self {
+        self.nibs.extend_from_slice(nibs);
+        self
+    }
+
+    fn push_hex(&mut self, code: &str) -> &mut Self {
+        self.nibs
+            .extend(code.chars().map(|c| c.to_digit(16).unwrap() as u8));
+        self
+    }
+
+    fn abs(&mut self, width: usize, expr: &str) -> &mut Self {
+        self.fixup(width, expr, Fix::Abs)
+    }
+
+    /// Displacement relative to the statement address plus `base`.
+    fn rel(&mut self, width: usize, base: i32, expr: &str, what: &'static str) -> &mut Self {
+        self.fixup(width, expr, Fix::Rel { base, what })
+    }
+
+    /// Displacement relative to its own position (GOYES, REL(n)).
+    fn rel_here(&mut self, width: usize, expr: &str, what: &'static str) -> &mut Self {
+        let base = self.nibs.len() as i32;
+        self.rel(width, base, expr, what)
+    }
+
+    fn fixup(&mut self, width: usize, expr: &str, kind: Fix) -> &mut Self {
+        self.fixups.push(Fixup {
+            at: self.nibs.len(),
+            width,
+            expr: expr.to_string(),
+            kind,
+        });
+        self.nibs.extend(std::iter::repeat_n(0, width));
+        self
+    }
+
+    fn done(&mut self) -> Self {
+        std::mem::take(self)
+    }
+}
+
+struct Stmt {
+    line: usize,
+    addr: i32,
+    emit: Emit,
+}
+
+struct Assembler {
+    origin: i32,
+    pc: i32,
+    line_no: usize,
+    labels: HashMap<String, i32>,
+    stmts: Vec<Stmt>,
+}
+
+impl Assembler {
+    fn new(origin: i32) -> Self {
+        Assembler {
+            origin,
+            pc: origin,
+            line_no: 0,
+            labels: HashMap::new(),
+            stmts: Vec::new(),
+        }
+    }
+
+    fn line(&mut self, raw: &str) -> Result<(), String> {
+        self.line_no += 1;
+        let text = raw.split(';').next().unwrap_or("");
+        if let Some(rest) = text.strip_prefix('*') {
+            // MASD label if it is a lone word, SASM comment otherwise.
+            let rest = rest.trim_end();
+            if is_label(rest) && !rest.contains(char::is_whitespace) {
+                self.define(rest, self.pc)?;
+            }
+            return Ok(());
+        }
+        let mut tokens: Vec<&str> = text.split_whitespace().collect();
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        if tokens.len() >= 3 && tokens[1].eq_ignore_ascii_case("EQU") {
+            let value = self.eval(&tokens[2..].concat())?;
+            return self.define(tokens[0], value);
+        }
+        // A column-1 word that is not an instruction is a label.
+        if !text.starts_with(char::is_whitespace) && self.parse(&tokens)?.is_none() {
+            self.define(tokens[0], self.pc)?;
+            tokens.remove(0);
+            if tokens.is_empty() {
+                return Ok(());
+            }
+        }
+        let emit = self
+            .parse(&tokens)?
+            .ok_or_else(|| format!("unknown instruction '{}'", tokens[0]))?;
+        let size = emit.nibs.len() as i32;
+        self.stmts.push(Stmt {
+            line: self.line_no,
+            addr: self.pc,
+            emit,
+        });
+        self.pc = (self.pc + size) & 0xfffff;
+        Ok(())
+    }
+
+    fn define(&mut self, name: &str, value: i32) -> Result<(), String> {
+        if !is_label(name) {
+            return Err(format!("bad label '{name}'"));
+        }
+        if self.labels.insert(name.to_string(), value).is_some() {
+            return Err(format!("label '{name}' defined twice"));
+        }
+        Ok(())
+    }
+
+    /// Whether the last statement is a test waiting for its GOYES.
+    fn pending_test(&self) -> bool {
+        self.stmts.last().is_some_and(|s| s.emit.test)
+    }
+
+    /// Encode one statement. `Ok(None)` means the mnemonic is unknown.
+    fn parse(&self, tokens: &[&str]) -> Result<Option<Emit>, String> {
+        let mn = tokens[0].to_ascii_uppercase();
+        let mut rest = &tokens[1..];
+
+        // GOYES / RTNYES on their own line complete the previous test.
+        if mn == "GOYES" || mn == "RTNYES" {
+            if !self.pending_test() {
+                return Err(format!("{mn} without a preceding test"));
+            }
+            let mut e = Emit::default();
+            match (mn.as_str(), rest) {
+                ("GOYES", [to]) => e.rel_here(2, to, "GOYES"),
+                ("RTNYES", []) => e.push(&[0, 0]),
+                _ => return Err(format!("bad {mn} operand")),
+            };
+            return Ok(Some(e.done()));
+        }
+        if self.pending_test() {
+            return Err("test without GOYES or RTNYES".to_string());
+        }
+
+        // Instructions that take no operand may still carry a GOYES.
+        let takes_operand = !(FIXED.iter().chain(&FIXED_TESTS).any(|(m, _)| *m == mn)
+            || ADDR_OPS.contains(&mn.as_str())
+            || rn_op(&mn).is_some_and(|(_, _, dotf)| !dotf));
+        let arg = if takes_operand && !rest.is_empty() && !is_branch_word(rest[0]) {
+            let a = rest[0];
+            rest = &rest[1..];
+            Some(a)
+        } else {
+            None
+        };
+
+        let Some(mut emit) = encode(&mn, arg)? else {
+            return Ok(None);
+        };
+
+        match rest {
+            [] => {}
+            [word, to] if emit.test && word.eq_ignore_ascii_case("GOYES") => {
+                emit.rel_here(2, to, "GOYES");
+                emit.test = false;
+            }
+            [word] if emit.test && word.eq_ignore_ascii_case("RTNYES") => {
+                emit.push(&[0, 0]);
+                emit.test = false;
+            }
+            _ => return Err(format!("unexpected '{}'", rest.join(" "))),
+        }
+        Ok(Some(emit))
+    }
+
+    /// Value of `expr`: numbers and labels joined with `+`/`-`.
+    fn eval(&self, expr: &str) -> Result<i32, String> {
+        let mut total = 0i32;
+        let mut sign = 1;
+        let mut term = String::new();
+        let flush = |term: &mut String, sign: i32, total: &mut i32| -> Result<(), String> {
+            if term.is_empty() {
+                return Err(format!("bad expression '{expr}'"));
+            }
+            let v = match number(term) {
+                Some(v) => v,
+                None => *self
+                    .labels
+                    .get(term.as_str())
+                    .ok_or_else(|| format!("undefined label '{term}'"))?,
+            };
+            *total = total.wrapping_add(sign * v);
+            term.clear();
+            Ok(())
+        };
+        for (i, c) in expr.char_indices() {
+            if c == '+' || c == '-' {
+                if i > 0 {
+                    flush(&mut term, sign, &mut total)?;
+                }
+                sign = if c == '+' { 1 } else { -1 };
+            } else {
+                term.push(c);
+            }
+        }
+        flush(&mut term, sign, &mut total)?;
+        Ok(total)
+    }
+
+    fn finish(self) -> Result<Vec<u8>, String> {
+        if self.pending_test() {
+            return Err(format!(
+                "line {}: test without GOYES or RTNYES",
+                self.line_no
+            ));
+        }
+        let mut out = Vec::with_capacity((self.pc - self.origin).max(0) as usize);
+        for stmt in &self.stmts {
+            let mut nibs = stmt.emit.nibs.clone();
+            for fix in &stmt.emit.fixups {
+                let value = self
+                    .resolve(stmt, fix)
+                    .map_err(|e| format!("line {}: {}", stmt.line, e))?;
+                for i in 0..fix.width {
+                    nibs[fix.at + i] = ((value >> (4 * i)) & 0xf) as u8;
+                }
+            }
+            out.extend(nibs);
+        }
+        Ok(out)
+    }
+
+    fn resolve(&self, stmt: &Stmt, fix: &Fixup) -> Result<i32, String> {
+        let value = self.eval(&fix.expr)?;
+        let bits = 4 * fix.width as u32;
+        match fix.kind {
+            Fix::Abs => {
+                if bits < 32 && (value >> bits) != 0 && (value >> (bits - 1)) != -1 {
+                    return Err(format!("#{value:X} does not fit in {} nibbles", fix.width));
+                }
+                Ok(value)
+            }
+            Fix::Rel { base, what } => {
+                let disp = value.wrapping_sub(stmt.addr + base);
+                let half = 1i32 << (bits - 1);
+                if disp < -half || disp >= half {
+                    return Err(format!(
+                        "{what} target '{}' out of range ({disp} nibbles)",
+                        fix.expr
+                    ));
+                }
+                if disp == 0 && fix.width == 2 {
+                    return Err(format!("{what} cannot target its own offset"));
+                }
+                Ok(disp)
+            }
+        }
+    }
+}
+
+/// GOYES / RTNYES after a test's operand.
+fn is_branch_word(word: &str) -> bool {
+    word.eq_ignore_ascii_case("GOYES") || word.eq_ignore_ascii_case("RTNYES")
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '=')
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_=.".contains(c))
+}
+
+/// `#hex` or decimal.
+fn number(s: &str) -> Option<i32> {
+    match s.strip_prefix('#') {
+        Some(hex) => i32::from_str_radix(hex, 16).ok(),
+        None if s.starts_with(|c: char| c.is_ascii_digit()) => s.parse().ok(),
+        None => None,
+    }
+}
+
+/// Field selector name to its types.rs code.
+fn field(name: &str) -> Result<u8, String> {
+    Ok(match name.to_ascii_uppercase().as_str() {
+        "P" => P_FIELD,
+        "WP" => WP_FIELD,
+        "XS" => XS_FIELD,
+        "X" => X_FIELD,
+        "S" => S_FIELD,
+        "M" => M_FIELD,
+        "B" => B_FIELD,
+        "W" => W_FIELD,
+        "A" => A_FIELD,
+        _ => return Err(format!("bad field '{name}'")),
+    })
+}
+
+/// Number in `lo..=hi`.
+fn small(arg: Option<&str>, lo: i32, hi: i32) -> Result<i32, String> {
+    let arg = arg.ok_or("missing operand")?;
+    match number(arg) {
+        Some(n) if (lo..=hi).contains(&n) => Ok(n),
+        _ => Err(format!("operand '{arg}' must be {lo}..{hi}")),
+    }
+}
+
+/// Hex digits of an LCHEX/LAHEX/NIBHEX operand, in the order written.
+fn digits(arg: Option<&str>, max: usize) -> Result<Vec<u8>, String> {
+    let arg = arg.ok_or("missing operand")?;
+    let arg = arg.strip_prefix('#').unwrap_or(arg);
+    let nibs: Option<Vec<u8>> = arg
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect();
+    match nibs {
+        Some(n) if !n.is_empty() && n.len() <= max => Ok(n),
+        _ => Err(format!("bad hex constant '{arg}'")),
+    }
+}
+
+/// `(n)` suffix of LC(n), CON(n), D0=(n) and friends.
+fn width_suffix(mn: &str, prefix: &str) -> Option<usize> {
+    mn.strip_prefix(prefix)?
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+/// Low opcode nibble of D0=(n); D1=(n) is 4 higher.
+fn pointer_load(mn: &str, width: usize) -> Result<u8, String> {
+    match width {
+        2 => Ok(9),
+        4 => Ok(0xa),
+        5 => Ok(0xb),
+        _ => Err(format!("{mn}: width must be 2, 4 or 5")),
+    }
+}
+
+/// `Rn=A`, `A=Rn`, `ARnEX` with optional `.F`:
+/// (group 0/1/2, register nibble with the C bit, has field).
+fn rn_op(mn: &str) -> Option<(u8, u8, bool)> {
+    let (base, dotf) = match mn.strip_suffix(".F") {
+        Some(b) => (b, true),
+        None => (mn, false),
+    };
+    let b = base.as_bytes();
+    let ac = |c: u8| match c {
+        b'A' => Some(0),
+        b'C' => Some(8),
+        _ => None,
+    };
+    let rn = |c: u8| (b'0'..=b'4').contains(&c).then(|| c - b'0');
+    match b {
+        [b'R', n, b'=', r] => Some((0, ac(*r)? | rn(*n)?, dotf)),
+        [r, b'=', b'R', n] => Some((1, ac(*r)? | rn(*n)?, dotf)),
+        [r, b'R', n, b'E', b'X'] => Some((2, ac(*r)? | rn(*n)?, dotf)),
+        _ => None,
+    }
+}
+
+fn reg_index(c: u8) -> Option<u8> {
+    REGS.iter()
+        .position(|r| r.as_bytes()[0] == c)
+        .map(|i| i as u8)
+}
+
+fn lookup(table: &[&str], mn: &str) -> Option<u8> {
+    table.iter().position(|m| *m == mn).map(|i| i as u8)
+}
+
+/// Encode mnemonic `mn` (upper case) with its optional operand.
+fn encode(mn: &str, arg: Option<&str>) -> Result<Option<Emit>, String> {
+    let mut e = Emit::default();
+    let need = || arg.ok_or_else(|| format!("{mn} needs an operand"));
+
+    if let Some((_, code)) = FIXED.iter().find(|(m, _)| *m == mn) {
+        return Ok(Some(Emit::hex(code)));
+    }
+    if let Some((_, code)) = FIXED_TESTS.iter().find(|(m, _)| *m == mn) {
+        return Ok(Some(Emit::test(code)));
+    }
+    if let Some(i) = lookup(&ADDR_OPS, mn) {
+        return Ok(Some(e.push(&[1, 3, i]).done()));
+    }
+    if let Some((group, reg, dotf)) = rn_op(mn) {
+        if dotf {
+            let f = field(need()?)?;
+            return Ok(Some(e.push(&[8, 1, 0xa, f, group, reg]).done()));
+        }
+        return Ok(Some(e.push(&[1, group, reg]).done()));
+    }
+
+    // Register arithmetic: explicit field (Axx/Bxx/9xx) or A-field shorthand.
+    let tables: [(&[&str; 16], u8, u8, bool); 4] = [
+        (&ADD, 0xc, 0xa, false),
+        (&COPY, 0xd, 0xa, true),
+        (&SUB, 0xe, 0xb, false),
+        (&SHIFT, 0xf, 0xb, true),
+    ];
+    for (table, short, long, high) in tables {
+        if let Some(i) = lookup(table, mn) {
+            let f = arg.map(field).transpose()?.unwrap_or(A_FIELD);
+            return Ok(Some(if f == A_FIELD {
+                e.push(&[short, i]).done()
+            } else {
+                e.push(&[long, if high { 8 | f } else { f }, i]).done()
+            }));
+        }
+    }
+    for (table, short, high) in [(&EQ_TESTS, 0xa, false), (&ORD_TESTS, 0xb, true)] {
+        if let Some(i) = lookup(table, mn) {
+            let f = field(need()?)?;
+            let mut e = if f == A_FIELD {
+                Emit::test(&format!("8{short:X}"))
+            } else {
+                Emit::test(&format!("9{:X}", if high { 8 | f } else { f }))
+            };
+            return Ok(Some(e.push(&[i]).done()));
+        }
+    }
+    if let Some(i) = lookup(&LOGIC, mn) {
+        let f = field(need()?)?;
+        return Ok(Some(e.push(&[0, 0xe, f, i]).done()));
+    }
+    if let Some(i) = lookup(&DAT, mn) {
+        let a = need()?;
+        if number(a).is_some() {
+            let n = small(Some(a), 1, 16)? as u8;
+            return Ok(Some(e.push(&[1, 5, 8 | i, n - 1]).done()));
+        }
+        return Ok(Some(match field(a)? {
+            A_FIELD => e.push(&[1, 4, i]).done(),
+            B_FIELD => e.push(&[1, 4, 8 | i]).done(),
+            f => e.push(&[1, 5, i, f]).done(),
+        }));
+    }
+
+    let b = mn.as_bytes();
+    // r=r+CON / r=r-CON  f,n
+    if b.len() == 7
+        && b[1] == b'='
+        && b[0] == b[2]
+        && mn.ends_with("CON")
+        && (b[3] == b'+' || b[3] == b'-')
+    {
+        if let Some(r) = reg_index(b[0]) {
+            let a = need()?;
+            let (f, n) = a
+                .split_once(',')
+                .ok_or_else(|| format!("{mn} needs 'field,count'"))?;
+            let f = field(f)?;
+            let n = small(Some(n), 1, 16)? as u8;
+            let r = if b[3] == b'-' { 8 | r } else { r };
+            return Ok(Some(e.push(&[8, 1, 8, f, r, n - 1]).done()));
+        }
+    }
+    // rSRB.F  f
+    if b.len() == 6 && &mn[1..] == "SRB.F" {
+        if let Some(r) = reg_index(b[0]) {
+            let f = field(need()?)?;
+            return Ok(Some(e.push(&[8, 1, 9, f, r]).done()));
+        }
+    }
+
+    let emit = match mn {
+        "P=" => e.push(&[2, small(arg, 0, 15)? as u8]).done(),
+        "LCHEX" | "LAHEX" => {
+            let d = digits(arg, 16)?;
+            let n = d.len() as u8 - 1;
+            e.push_hex(if mn == "LCHEX" { "3" } else { "8082" })
+                .push(&[n]);
+            e.push(&d.iter().rev().copied().collect::<Vec<_>>()).done()
+        }
+        "D0=D0+" => e.push(&[1, 6, small(arg, 1, 16)? as u8 - 1]).done(),
+        "D1=D1+" => e.push(&[1, 7, small(arg, 1, 16)? as u8 - 1]).done(),
+        "D0=D0-" => e.push(&[1, 8, small(arg, 1, 16)? as u8 - 1]).done(),
+        "D1=D1-" => e.push(&[1, 0xc, small(arg, 1, 16)? as u8 - 1]).done(),
+        "GOC" => e.push(&[4]).rel(2, 1, need()?, "GOC").done(),
+        "GONC" => e.push(&[5]).rel(2, 1, need()?, "GONC").done(),
+        "GOTO" => e.push(&[6]).rel(3, 1, need()?, "GOTO").done(),
+        "GOSUB" => e.push(&[7]).rel(3, 4, need()?, "GOSUB").done(),
+        "GOLONG" => e.push(&[8, 0xc]).rel(4, 2, need()?, "GOLONG").done(),
+        "GOVLNG" => e.push(&[8, 0xd]).abs(5, need()?).done(),
+        "GOSUBL" => e.push(&[8, 0xe]).rel(4, 6, need()?, "GOSUBL").done(),
+        "GOSBVL" => e.push(&[8, 0xf]).abs(5, need()?).done(),
+        "HST=0" => e.push(&[8, 2, small(arg, 0, 15)? as u8]).done(),
+        "?HST=0" => Emit::test("83").push(&[small(arg, 0, 15)? as u8]).done(),
+        "ST=0" => e.push(&[8, 4, small(arg, 0, 15)? as u8]).done(),
+        "ST=1" => e.push(&[8, 5, small(arg, 0, 15)? as u8]).done(),
+        "?ST=0" => Emit::test("86").push(&[small(arg, 0, 15)? as u8]).done(),
+        "?ST=1" => Emit::test("87").push(&[small(arg, 0, 15)? as u8]).done(),
+        "?P#" => Emit::test("88").push(&[small(arg, 0, 15)? as u8]).done(),
+        "?P=" => Emit::test("89").push(&[small(arg, 0, 15)? as u8]).done(),
+        "C=P" => e.push(&[8, 0, 0xc, small(arg, 0, 15)? as u8]).done(),
+        "P=C" => e.push(&[8, 0, 0xd, small(arg, 0, 15)? as u8]).done(),
+        "CPEX" => e.push(&[8, 0, 0xf, small(arg, 0, 15)? as u8]).done(),
+        "ABIT=0" => e.push(&[8, 0, 8, 4, small(arg, 0, 15)? as u8]).done(),
+        "ABIT=1" => e.push(&[8, 0, 8, 5, small(arg, 0, 15)? as u8]).done(),
+        "?ABIT=0" => Emit::test("8086").push(&[small(arg, 0, 15)? as u8]).done(),
+        "?ABIT=1" => Emit::test("8087").push(&[small(arg, 0, 15)? as u8]).done(),
+        "CBIT=0" => e.push(&[8, 0, 8, 8, small(arg, 0, 15)? as u8]).done(),
+        "CBIT=1" => e.push(&[8, 0, 8, 9, small(arg, 0, 15)? as u8]).done(),
+        "?CBIT=0" => Emit::test("808A").push(&[small(arg, 0, 15)? as u8]).done(),
+        "?CBIT=1" => Emit::test("808B").push(&[small(arg, 0, 15)? as u8]).done(),
+        "NIBHEX" => e.push(&digits(arg, usize::MAX)?).done(),
+        _ => {
+            if let Some(n) = width_suffix(mn, "LC").filter(|n| (1..=8).contains(n)) {
+                e.push(&[3, n as u8 - 1]).abs(n, need()?).done()
+            } else if let Some(n) = width_suffix(mn, "LA").filter(|n| (1..=8).contains(n)) {
+                e.push(&[8, 0, 8, 2, n as u8 - 1]).abs(n, need()?).done()
+            } else if let Some(n) = width_suffix(mn, "D0=") {
+                e.push(&[1, pointer_load(mn, n)?]).abs(n, need()?).done()
+            } else if let Some(n) = width_suffix(mn, "D1=") {
+                e.push(&[1, pointer_load(mn, n)? + 4])
+                    .abs(n, need()?)
+                    .done()
+            } else if let Some(n) = width_suffix(mn, "CON").filter(|n| (1..=8).contains(n)) {
+                e.abs(n, need()?).done()
+            } else if let Some(n) = width_suffix(mn, "REL").filter(|n| (1..=8).contains(n)) {
+                e.rel_here(n, need()?, "REL").done()
+            } else {
+                return Ok(None);
+            }
+        }
+    };
+    Ok(Some(emit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{disassemble, Flow};
+
+    fn hex(nibs: &[u8]) -> String {
+        nibs.iter().map(|n| format!("{n:X}")).collect()
+    }
+
+    fn asm(source: &str) -> String {
+        hex(&assemble(source, 0).unwrap())
+    }
+
+    #[test]
+    fn test_encodings() {
+        let cases = [
+            ("  RTN", "01"),
+            ("  A=A&B B", "0E60"),
+            ("  R0=C", "108"),
+            ("  CR4EX", "12C"),
+            ("  A=DAT0 A", "142"),
+            ("  C=DAT1 B", "14F"),
+            ("  DAT0=C S", "1544"),
+            ("  A=DAT0 16", "15AF"),
+            ("  D1=D1- 1", "1C0"),
+            ("  D0=(5) #806F3", "1B3F608"),
+            ("  P= 10", "2A"),
+            ("  LCHEX BCDEF", "34FEDCB"),
+            ("  LC(5) #12345", "3454321"),
+            ("  LA(3) 291", "80822321"),
+            ("  C=C+CON A,11", "818F2A"),
+            ("  DSRB.F X", "81933"),
+            ("  R2=C.F W", "81A70A"),
+            ("  ABEX W", "AFC"),
+            ("  ABEX", "DC"),
+            ("  ASR X", "BB4"),
+            ("  A=-A-1", "FC"),
+            ("  ?A=0 A RTNYES", "8A800"),
+            ("  ?A<=B W RTNYES", "9FC00"),
+            ("  ?SR=0 RTNYES", "83400"),
+            ("  GOVLNG #0679B", "8DB9760"),
+            ("  NIBHEX 12AB", "12AB"),
+            ("  CON(5) #2DCC", "CCD20"),
+        ];
+        for (src, code) in cases {
+            assert_eq!(asm(src), code, "{src}");
+        }
+    }
+
+    #[test]
+    fn test_labels_and_branches() {
+        let src = "
+* count to ten
+        A=0     W
+        LC(2)   10
+LOOP    A=A+1   A
+        C=C-1   B
+        ?C#0    B
+        GOYES   LOOP
+*DONE
+        GOC     DONE2
+        GOSUB   SUB
+DONE2   RTN
+SUB     GOTO    DONE      ; far enough back
+";
+        let code = assemble(src, 0x1000).unwrap();
+        let mut addr = 0x1000;
+        let mut texts = Vec::new();
+        while addr < 0x1000 + code.len() as i32 {
+            let insn = disassemble(code.as_slice(), addr - 0x1000);
+            texts.push((insn.text.clone(), insn.target.map(|t| t + 0x1000)));
+            addr += insn.size;
+        }
+        assert_eq!(texts[2], ("A=A+1".to_string(), None));
+        assert_eq!(
+            texts[4],
+            ("?C#0    B  GOYES #00007".to_string(), Some(0x1007))
+        );
+        assert_eq!(texts[5].1, Some(0x1018));
+        assert_eq!(texts[6].1, Some(0x101a));
+        assert_eq!(texts[8].1, Some(0x1011));
+    }
+
+    #[test]
+    fn test_errors() {
+        let far = format!("  GOC FAR\n  NIBHEX {}\nFAR RTN", "0".repeat(200));
+        let err = assemble(&far, 0).unwrap_err();
+        assert!(
+            err.starts_with("line 1: GOC target 'FAR' out of range"),
+            "{err}"
+        );
+        assert_eq!(
+            assemble("  GOTO NOWHERE", 0).unwrap_err(),
+            "line 1: undefined label 'NOWHERE'"
+        );
+        assert_eq!(
+            assemble("  FOO A", 0).unwrap_err(),
+            "line 1: unknown instruction 'FOO'"
+        );
+        assert_eq!(
+            assemble("  ?A=B A\n  RTN", 0).unwrap_err(),
+            "line 2: test without GOYES or RTNYES"
+        );
+        assert_eq!(
+            assemble("  ?A=B A", 0).unwrap_err(),
+            "line 1: test without GOYES or RTNYES"
+        );
+        assert_eq!(
+            assemble("  A=A+B Q", 0).unwrap_err(),
+            "line 1: bad field 'Q'"
+        );
+        assert_eq!(
+            assemble("  P= 16", 0).unwrap_err(),
+            "line 1: operand '16' must be 0..15"
+        );
+        assert_eq!(
+            assemble("X RTN\nX RTN", 0).unwrap_err(),
+            "line 2: label 'X' defined twice"
+        );
+        assert!(assemble("  GOSUB X\n  NIBHEX 0\nX EQU #100", 0).is_ok());
+        assert_eq!(asm("A EQU 10\n  CON(2) -A+#20"), "61");
+    }
+
+    #[test]
+    fn test_code_object() {
+        let obj = assemble_code("  GOVLNG #05143", 0x80000).unwrap();
+        assert_eq!(hex(&obj), "CCD20C0000".to_string() + "8D34150");
+        // Labels inside the body resolve past the 10-nibble header.
+        let obj = assemble_code("HERE LC(5) HERE", 0x80000).unwrap();
+        assert_eq!(hex(&obj[10..]), "34A0008");
+    }
+
+    /// Everything the disassembler prints for code the CPU actually runs
+    /// assembles back to the same nibbles.
+    #[test]
+    fn test_round_trip_rom() {
+        let mut emu = crate::test_support::boot_gx();
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..20000 {
+            let insn = emu.disassemble(emu.saturn.pc);
+            if insn.flow != Flow::Illegal && seen.insert(insn.addr) {
+                let code = assemble(&format!("  {}", insn.text), insn.addr)
+                    .unwrap_or_else(|e| panic!("{insn}: {e}"));
+                assert_eq!(code, insn.nibbles, "{insn}");
+            }
+            emu.step_instruction();
+        }
+        assert!(seen.len() > 100);
+    }
+
+    /// Assembled code runs on the emulated CPU.
+    #[test]
+    fn test_runs_on_cpu() {
+        let src = "
+        A=0     W
+        LC(2)   10
+LOOP    A=A+1   A
+        C=C-1   B
+        ?C#0    B
+        GOYES   LOOP
+        GOSUB   DOUBLE
+        LC(5)   #ABCDE
+END     GOTO    END
+DOUBLE  A=A+A   A
+        RTN
+";
+        let mut emu = crate::test_support::boot_gx();
+        let base = 0x90000;
+        let code = assemble(src, base).unwrap();
+        for (i, &n) in code.iter().enumerate() {
+            emu.write_nibble(base + i as i32, n as i32);
+        }
+        let end = base + code.len() as i32 - 8;
+        emu.saturn.pc = base;
+        for _ in 0..200 {
+            if emu.saturn.pc == end {
+                break;
+            }
+            assert!(!emu.step_instruction());
+        }
+        assert_eq!(emu.saturn.pc, end);
+        assert_eq!(emu.saturn.a[0], 4);
+        assert_eq!(emu.saturn.a[1], 1);
+        assert_eq!(&emu.saturn.c[..5], &[0xe, 0xd, 0xc, 0xb, 0xa]);
+    }
+}
@@ -0,0 +1,470 @@
+// HP binary transfer files and variables in HOME. Port of binio.c
+// (read_bin_file), extended to write files and to store and recall
+// variables.
+//
+// A binary file, as Kermit and XModem move objects, is the 8 bytes
+// "HPHP48-" plus the ROM revision letter of the calculator that wrote it,
+// then the object's nibbles packed two to a byte, low nibble first, padded
+// with a zero nibble to a whole byte.
+//
+// A directory holds entries of a name (length byte, characters, length byte
+// again; the empty name is just its length byte) followed by the object,
+// with a 5-nibble link back to the previous entry's name between entries.
+// A field before the first entry holds the offset to the last. HOME, at the
+// top of RAM where USEROB points, has a table of attached libraries (a
+// 3-nibble count, then 13 nibbles each) ahead of that field, and a nameless
+// hidden directory (alarms, user keys) as its first entry.
+//
+// Recalling reads the object straight out of HOME. Storing leaves memory
+// management to the firmware: the object, its name and a compiled program
+// doing `PATH 3 ROLLD HOME STO EVAL` go onto the stack
+// (`Emulator::push_object`), and one tap of EVAL runs the program as
+// frames go by (`start_store`), storing the object in HOME and returning
+// to the current directory. STO runs under IFERR, so when it fails the
+// stack and directory are put back as they were. Flag -55 is set around
+// the program, so LASTARG still holds what the user's last command took.
+// The tap is refused in USER mode, where EVAL may be reassigned, and so on
+// revisions whose system flags are not known.
+
+use crate::disasm::NibbleSource;
+use crate::emulator::Emulator;
+use crate::keyboard::key_code;
+use crate::keymacro::KeyMacro;
+use crate::rpl::*;
+
+pub const HEADER: &[u8] = b"HPHP48-";
+
+/// Emulated time allowed for the firmware to store a variable.
+const STORE_TIMEOUT_SECS: f64 = 10.0;
+
+/// Links, names and the offset to the last entry are this long.
+const LINK: u32 = 5;
+
+/// A RAM nibble and the bit in it that holds some firmware state.
+type StateBit = (u32, u8);
+
+/// System flags the store reads or sets: No last arguments, User mode.
+const NO_LAST_ARGS: u32 = 55;
+const USER_MODE: u32 = 62;
+
+/// A store `start_store` began, which `poll_store` checks on.
+pub(crate) struct StoreRun {
+    name: String,
+    object: Vec<u8>,
+    /// Emulated time by which the firmware must be done.
+    deadline: f64,
+}
+
+fn unpack(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|&b| [b & 0xf, b >> 4]).collect()
+}
+
+pub(crate) fn read<S: NibbleSource + ?Sized>(src: &S, addr: u32, n: u32) -> u32 {
+    (0..n).rev().fold(0, |v, i| {
+        v << 4 | src.nibble((addr.wrapping_add(i) & 0xfffff) as i32) as u32
+    })
+}
+
+/// The object in an `HPHP48-` binary, as stored in memory, and the ROM
+/// letter from its header. What follows the object is ignored, as
+/// binio.c does.
+pub fn read_binary(data: &[u8]) -> Result<(Vec<u8>, char), String> {
+    if data.len() < 8 || !data.starts_with(HEADER) {
+        return Err("not an HPHP48- binary".to_string());
+    }
+    let mut object = unpack(&data[8..]);
+    let size = object_size(object.as_slice(), 0)? as usize;
+    if size > object.len() {
+        return Err("the file ends inside its object".to_string());
+    }
+    object.truncate(size);
+    Ok((object, data[7] as char))
+}
+
+/// `object` as an `HPHP48-` binary from a calculator with ROM `letter`.
+pub fn write_binary(object: &[u8], letter: char) -> Vec<u8> {
+    let mut data = HEADER.to_vec();
+    data.push(letter as u8);
+    data.extend(
+        object
+            .chunks(2)
+            .map(|pair| pair[0] & 0xf | pair.get(1).map_or(0, |n| n << 4)),
+    );
+    data
+}
+
+/// The object in a file: an `HPHP48-` binary, or else the file's bytes as
+/// a string, the way binio.c loads files.
+pub fn file_object(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.starts_with(HEADER) {
+        return read_binary(data).map(|(object, _)| object);
+    }
+    let size = 5 + 2 * data.len() as u32;
+    let mut object: Vec<u8> = (0..5)
+        .map(|i| (DOCSTR >> (4 * i)) as u8 & 0xf)
+        .chain((0..5).map(|i| (size >> (4 * i)) as u8 & 0xf))
+        .collect();
+    object.extend(unpack(data));
+    Ok(object)
+}
+
+/// The calculator's name for objects with `prolog`, as the TYPE command
+/// tells them apart; `None` if it is not a prolog.
+pub fn type_name(prolog: u32) -> Option<&'static str> {
+    Some(match prolog {
+        DOBINT => "System Binary",
+        DOREAL => "Real Number",
+        DOEREL => "Long Real",
+        DOCMP => "Complex Number",
+        DOECMP => "Long Complex",
+        DOCHAR => "Character",
+        DOARRY => "Array",
+        DOLNKARRY => "Linked Array",
+        DOCSTR => "String",
+        DOHSTR => "Binary Integer",
+        DOLIST => "List",
+        DORRP => "Directory",
+        DOSYMB => "Algebraic",
+        DOEXT => "Unit",
+        DOTAG => "Tagged",
+        DOGROB => "Graphic",
+        DOLIB => "Library",
+        DOBAK => "Backup",
+        DOEXT0 => "Library Data",
+        DOACPTR | DOEXT2 | DOEXT3 | DOEXT4 => "External",
+        DOCOL => "Program",
+        DOCODE => "Code",
+        DOIDNT => "Global Name",
+        DOLAM => "Local Name",
+        DOROMP => "XLIB Name",
+        _ => return None,
+    })
+}
+
+impl Emulator {
+    /// The letter the ROM's version string ends in ("Version HP48-R"),
+    /// which binaries written here carry in their header; the model's
+    /// last revision if the string is not found.
+    pub fn rom_letter(&self) -> char {
+        self.revision.letter
+    }
+
+    /// Address of HOME, which USEROB points to.
+    pub fn home_directory(&self) -> Result<u32, String> {
+        let home = read(self, self.entry_points().userob, 5);
+        if !self.ram_range().contains(&home) || read(self, home, 5) != DORRP {
+            return Err("HOME not found".to_string());
+        }
+        Ok(home)
+    }
+
+    /// Names and object addresses in directory `dir`, in memory order
+    /// (oldest first). HOME's hidden directory has the empty name.
+    pub fn directory_entries(&self, dir: u32) -> Result<Vec<(String, u32)>, String> {
+        if read(self, dir, 5) != DORRP {
+            return Err(format!("no directory at {dir:05X}"));
+        }
+        let mut field = dir + 8;
+        if dir == self.home_directory()? {
+            field += 13 * read(self, dir + 5, 3);
+        }
+        let offset = read(self, field, LINK);
+        if offset == 0 {
+            return Ok(Vec::new());
+        }
+        let last = field + offset;
+        let mut name = field + 2 * LINK;
+        let mut entries = Vec::new();
+        loop {
+            let len = read(self, name, 2);
+            let text = (0..len)
+                .map(|i| hp_char(read(self, name + 2 + 2 * i, 2) as u8))
+                .collect();
+            let object = name + if len == 0 { 2 } else { 4 + 2 * len };
+            entries.push((text, object));
+            if name == last {
+                return Ok(entries);
+            }
+            name = object + object_size(self, object)? + LINK;
+            if name > last {
+                return Err(format!("directory at {dir:05X} is damaged"));
+            }
+        }
+    }
+
+    /// The object stored in HOME as `name`, as stored in memory.
+    pub fn recall_variable(&self, name: &str) -> Result<Vec<u8>, String> {
+        let home = self.home_directory()?;
+        let (_, object) = self
+            .directory_entries(home)?
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| format!("no variable {name} in HOME"))?;
+        let size = object_size(self, object)?;
+        Ok((object..object + size)
+            .map(|at| self.peek_nibble(at as i32))
+            .collect())
+    }
+
+    /// Whether the RAM nibble and bit `bit` is set.
+    fn state_bit(&self, (at, mask): StateBit) -> bool {
+        self.peek_nibble(at as i32) & mask != 0
+    }
+
+    /// The nibble and bit holding system flag -`n`, if the revision's
+    /// flags are known.
+    fn system_flag(&self, n: u32) -> Option<StateBit> {
+        let base = self.entry_points().sysflags?;
+        Some((base + (n - 1) / 4, 1 << ((n - 1) % 4)))
+    }
+
+    /// Start storing `object` in HOME as `name`, replacing what was there;
+    /// returns at once, and `poll_store` tells how it ended. The calculator
+    /// must be at the stack prompt (`check_push`) and not in USER mode. If
+    /// the firmware refuses, say because `name` holds a directory, the
+    /// stack and current directory are left as they were.
+    pub fn start_store(&mut self, name: &str, object: &[u8]) -> Result<(), String> {
+        if self.store.is_some() {
+            return Err("a store is already running".to_string());
+        }
+        if self.macro_playing() {
+            return Err("a macro is already playing".to_string());
+        }
+        if name.is_empty() || name.contains(|c: char| " '\"#«»{}[]()<>:,_".contains(c)) {
+            return Err(format!("bad variable name {name:?}"));
+        }
+        let global = Object::Global(name.to_string()).to_nibbles()?;
+        self.check_push()?;
+        let (Some(user_mode), Some(no_last_args)) =
+            (self.system_flag(USER_MODE), self.system_flag(NO_LAST_ARGS))
+        else {
+            return Err(format!(
+                "cannot tell USER mode on revision {}",
+                self.revision.letter
+            ));
+        };
+        if self.state_bit(user_mode) {
+            return Err("USER mode is on".to_string());
+        }
+        // A failed STO gives its arguments back unless flag -55 is set, so
+        // the program drops down to the saved path by depth.
+        let depth = self.stack_addresses()?.len();
+        let last_args = !self.state_bit(no_last_args);
+        let program = self.compile(&format!(
+            "« PATH 3 ROLLD HOME IFERR STO THEN DEPTH {} - DROPN END EVAL {}»",
+            depth + 1,
+            if last_args { "-55 CF " } else { "" }
+        ))?;
+        let p = self.entry_points();
+        let need: u32 = [object, &global, &program]
+            .iter()
+            .map(|o| o.len() as u32 + 11)
+            .sum();
+        if read(self, p.avmem, 5) * 5 < need {
+            return Err("not enough memory".to_string());
+        }
+        self.push_object(object)?;
+        self.push_object(&global)?;
+        self.push_object(&program)?;
+        let (at, mask) = no_last_args;
+        let flags = self.peek_nibble(at as i32);
+        self.write_nibble(at as i32, (flags | mask) as i32);
+        let eval = KeyMacro::taps(&[key_code("EVAL").unwrap()], self.macro_ips());
+        self.play_macro(eval);
+        self.store = Some(StoreRun {
+            name: name.to_string(),
+            object: object.to_vec(),
+            deadline: self.now + STORE_TIMEOUT_SECS,
+        });
+        Ok(())
+    }
+
+    /// How the running store ended, once the firmware is back at rest:
+    /// `None` while it runs, or if none was started.
+    pub fn poll_store(&mut self) -> Option<Result<(), String>> {
+        let store = self.store.as_ref()?;
+        if self.macro_playing() || !self.is_idle() {
+            if self.now < store.deadline {
+                return None;
+            }
+            self.store = None;
+            return Some(Err("timed out waiting for the calculator".to_string()));
+        }
+        let store = self.store.take()?;
+        Some(match self.recall_variable(&store.name) {
+            Ok(stored) if stored == store.object => Ok(()),
+            _ => Err(format!("the calculator did not store {}", store.name)),
+        })
+    }
+
+    /// Store `object` in HOME as `name` (`start_store`), running headless
+    /// until the firmware is done.
+    pub fn store_variable(&mut self, name: &str, object: &[u8]) -> Result<(), String> {
+        self.start_store(name, object)?;
+        let mut result = None;
+        self.run_headless(STORE_TIMEOUT_SECS + 1.0, |emu| {
+            result = emu.poll_store();
+            result.is_some()
+        });
+        result.unwrap_or_else(|| {
+            self.store = None;
+            Err("timed out waiting for the calculator".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, boot_virtual, press, run, ALPHA, ENTER};
+
+    #[test]
+    fn test_binary_files() {
+        let real = Object::Real(Real::from_f64(15.0).unwrap());
+        let nibbles = real.to_nibbles().unwrap();
+        let data = write_binary(&nibbles, 'R');
+        assert_eq!(&data[..8], b"HPHP48-R");
+        assert_eq!(data.len(), 8 + 11);
+        assert_eq!(read_binary(&data), Ok((nibbles.clone(), 'R')));
+        assert_eq!(file_object(&data), Ok(nibbles));
+        assert_eq!(
+            read_binary(&data[..18]).unwrap_err(),
+            "the file ends inside its object"
+        );
+        assert_eq!(
+            read_binary(b"HPHP49-X").unwrap_err(),
+            "not an HPHP48- binary"
+        );
+        assert_eq!(
+            file_object(b"A\xab").unwrap(),
+            Object::String("A«".into()).to_nibbles().unwrap()
+        );
+        assert_eq!(type_name(DOCOL), Some("Program"));
+        assert_eq!(type_name(0x12345), None);
+    }
+
+    #[test]
+    fn test_store_and_recall() {
+        let mut emu = boot_gx();
+        assert_eq!(emu.rom_letter(), 'R');
+        let names = |emu: &Emulator| -> Vec<String> {
+            let home = emu.home_directory().unwrap();
+            let entries = emu.directory_entries(home).unwrap();
+            entries.into_iter().map(|(name, _)| name).collect()
+        };
+        assert_eq!(names(&emu), [""]);
+        let blank = vec![0; crate::types::ROM_SIZE_GX];
+        let blank = Emulator::new(&blank, None, None, crate::types::Model::Gx);
+        assert_eq!(blank.home_directory().unwrap_err(), "HOME not found");
+
+        // Stored from a subdirectory, into HOME.
+        emu.type_text("'WORK' CRDIR WORK").unwrap();
+        press(&mut emu, &[ENTER]);
+        let program = Object::Program(vec![
+            Object::Real(Real::from_f64(2.0).unwrap()),
+            Object::String("x".into()),
+        ]);
+        let nibbles = program.to_nibbles().unwrap();
+        emu.store_variable("P1", &nibbles).unwrap();
+        assert_eq!(emu.recall_variable("P1"), Ok(nibbles.clone()));
+        assert_eq!(emu.stack_text(), Ok(String::new()));
+
+        let text = Object::String("hello".into()).to_nibbles().unwrap();
+        emu.store_variable("P1", &text).unwrap();
+        assert_eq!(emu.recall_variable("P1"), Ok(text));
+        assert_eq!(names(&emu), ["", "WORK", "P1"]);
+        assert_eq!(
+            emu.recall_variable("P2").unwrap_err(),
+            "no variable P2 in HOME"
+        );
+        assert!(emu.store_variable("A B", &nibbles).is_err());
+
+        // The firmware sees it, and is still in WORK.
+        emu.type_text("P1 3 'Q' STO").unwrap();
+        press(&mut emu, &[ENTER]);
+        assert_eq!(emu.stack_text(), Ok("1: \"hello\"\n".to_string()));
+        assert!(emu.recall_variable("Q").is_err());
+    }
+
+    #[test]
+    fn test_store_refused() {
+        let mut emu = boot_gx();
+        run(&mut emu, "'WORK' CRDIR WORK 7 8");
+        let one = Object::Real(Real::from_f64(1.0).unwrap())
+            .to_nibbles()
+            .unwrap();
+        let path = |emu: &mut Emulator| {
+            run(emu, "PATH");
+            let text = emu.stack()?.remove(0).to_string();
+            run(emu, "DROP");
+            emu.stack_text().map(|stack| (text, stack))
+        };
+        let at_rest = Ok(("{ HOME WORK }".to_string(), "2: 7\n1: 8\n".to_string()));
+        assert_eq!(path(&mut emu), at_rest);
+
+        // STO will not replace a directory; with or without last arguments
+        // the firmware goes back to WORK and leaves the stack alone.
+        for flags in ["55 NEG CF", "55 NEG SF"] {
+            run(&mut emu, flags);
+            assert_eq!(
+                emu.store_variable("WORK", &one).unwrap_err(),
+                "the calculator did not store WORK"
+            );
+            assert_eq!(path(&mut emu), at_rest, "{flags}");
+        }
+        assert_eq!(
+            emu.directory_entries(emu.home_directory().unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // USER mode, locked with USER USER or set as flag -62.
+        let user = key_code("SHL").unwrap();
+        press(&mut emu, &[user, ALPHA, user, ALPHA]);
+        assert_eq!(
+            emu.store_variable("X", &one).unwrap_err(),
+            "USER mode is on"
+        );
+        press(&mut emu, &[user, ALPHA]);
+        run(&mut emu, "62 NEG SF");
+        assert_eq!(
+            emu.store_variable("X", &one).unwrap_err(),
+            "USER mode is on"
+        );
+        run(&mut emu, "62 NEG CF");
+        emu.store_variable("X", &one).unwrap();
+        assert_eq!(path(&mut emu), at_rest);
+    }
+
+    #[test]
+    fn test_store_in_frames() {
+        let mut emu = boot_virtual();
+        emu.run_headless(3.0, |_| false);
+        run(&mut emu, "7 SQ");
+        let one = Object::Real(Real::from_f64(1.0).unwrap())
+            .to_nibbles()
+            .unwrap();
+        emu.start_store("X", &one).unwrap();
+        assert_eq!(
+            emu.start_store("Y", &one).unwrap_err(),
+            "a store is already running"
+        );
+        let mut frames = 0;
+        let result = loop {
+            if let Some(result) = emu.poll_store() {
+                break result;
+            }
+            emu.run_frame(16.0, 0.0);
+            frames += 1;
+        };
+        assert_eq!(result, Ok(()));
+        assert!(frames > 1);
+        assert_eq!(emu.recall_variable("X"), Ok(one));
+
+        // The last argument is still that of SQ, and flag -55 is clear.
+        assert!(!emu.state_bit(emu.system_flag(NO_LAST_ARGS).unwrap()));
+        run(&mut emu, "LASTARG");
+        assert_eq!(emu.stack_text(), Ok("2: 49\n1: 7\n".to_string()));
+    }
+}
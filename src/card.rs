@@ -0,0 +1,588 @@
+// Plug-in memory cards — port of the port1/port2 handling in x48's init.c
+// (read_files / write_files), as insert/eject calls on a live emulator.
+// Card images use the x48 port file format: packed bytes, two nibbles per
+// byte, low nibble first — the same layout as the RAM file. Each card has
+// a write-protect switch, reported in CARD STATUS like a ROM card.
+
+use std::path::Path;
+
+use crate::emulator::Emulator;
+use crate::persist;
+use crate::types::Model;
+
+/// Smallest card, in bytes.
+pub const CARD_SIZE_MIN: usize = 32 * 1024;
+/// Largest card the MMU can map without bank switching, in bytes.
+pub const CARD_SIZE_UNBANKED: usize = 128 * 1024;
+/// Largest GX port-2 card: 32 banks of 128K, selected via `bank_switch`.
+pub const CARD_SIZE_MAX: usize = 4 * 1024 * 1024;
+
+/// CARD CONTROL bit 3: raise an interrupt when a card is inserted or
+/// pulled.
+const CARD_CTRL_ECDT: u8 = 0x08;
+
+/// A card slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Port {
+    Port1,
+    Port2,
+}
+
+/// What the card is built from. ROM cards ignore writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardKind {
+    Ram,
+    Rom,
+}
+
+/// CARD STATUS bits for a port: (present, writable). The SX and GX wire
+/// the two slots the other way round (init.c).
+fn status_bits(model: Model, port: Port) -> (u8, u8) {
+    match (model, port) {
+        (Model::Sx, Port::Port1) | (Model::Gx, Port::Port2) => (0x1, 0x4),
+        (Model::Sx, Port::Port2) | (Model::Gx, Port::Port1) => (0x2, 0x8),
+    }
+}
+
+/// Largest card accepted in `port`, in bytes.
+pub fn max_card_size(model: Model, port: Port) -> usize {
+    match (model, port) {
+        (Model::Gx, Port::Port2) => CARD_SIZE_MAX,
+        _ => CARD_SIZE_UNBANKED,
+    }
+}
+
+/// A library build padded with zeros up to the next card size.
+pub fn rom_card_image(image: &[u8]) -> Vec<u8> {
+    let size = image.len().next_power_of_two().max(CARD_SIZE_MIN);
+    let mut padded = image.to_vec();
+    padded.resize(size, 0);
+    padded
+}
+
+impl Emulator {
+    /// Plug a card into `port`. `image` is an x48 port file; its length
+    /// sets the card size, which must be a power of two from 32K up to
+    /// `max_card_size`. Any card already in the port is replaced.
+    pub fn insert_card(&mut self, port: Port, image: &[u8], kind: CardKind) -> Result<(), String> {
+        let bytes = image.len();
+        let max = max_card_size(self.model, port);
+        if !bytes.is_power_of_two() || !(CARD_SIZE_MIN..=max).contains(&bytes) {
+            return Err(format!(
+                "card image is {} bytes; port {} takes a power of two from {}K to {}K",
+                bytes,
+                port_number(port),
+                CARD_SIZE_MIN / 1024,
+                max / 1024
+            ));
+        }
+        let nibbles = persist::load_ram(image, bytes * 2);
+        let mask = (nibbles.len() - 1) as i32;
+        let is_ram = kind == CardKind::Ram;
+        match port {
+            Port::Port1 => {
+                self.mem.port1 = nibbles;
+                self.mem.port1_mask = mask;
+                self.mem.port1_is_ram = is_ram;
+                self.mem.port1_write_protect = false;
+            }
+            Port::Port2 => {
+                self.mem.port2 = nibbles;
+                self.mem.port2_mask = mask;
+                self.mem.port2_is_ram = is_ram;
+                self.mem.port2_write_protect = false;
+            }
+        }
+        self.update_card_status();
+        Ok(())
+    }
+
+    /// Plug a blank RAM card of `bytes` into `port`.
+    pub fn insert_ram_card(&mut self, port: Port, bytes: usize) -> Result<(), String> {
+        self.insert_card(port, &vec![0u8; bytes], CardKind::Ram)
+    }
+
+    /// Plug in a read-only library card. `image` is padded with zeros up
+    /// to the next card size, so a library build need not fill the card.
+    pub fn insert_rom_card(&mut self, port: Port, image: &[u8]) -> Result<(), String> {
+        self.insert_card(port, &rom_card_image(image), CardKind::Rom)
+    }
+
+    /// Plug in the x48 port file at `path`. As in x48, the card is RAM
+    /// when the file is writable and ROM otherwise.
+    pub fn insert_card_file(&mut self, port: Port, path: &Path) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        let image = std::fs::read(path).map_err(err)?;
+        let read_only = std::fs::metadata(path)
+            .map_err(err)?
+            .permissions()
+            .readonly();
+        let kind = if read_only {
+            CardKind::Rom
+        } else {
+            CardKind::Ram
+        };
+        self.insert_card(port, &image, kind)
+    }
+
+    /// Write the card in `port` back to an x48 port file. Does nothing if
+    /// the port is empty.
+    pub fn save_card_file(&self, port: Port, path: &Path) -> Result<(), String> {
+        match self.save_card(port) {
+            Some(image) => {
+                std::fs::write(path, image).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Pull the card out of `port`, returning its image.
+    pub fn eject_card(&mut self, port: Port) -> Option<Vec<u8>> {
+        let image = self.save_card(port);
+        match port {
+            Port::Port1 => {
+                self.mem.port1 = Vec::new();
+                self.mem.port1_mask = 0;
+                self.mem.port1_is_ram = false;
+                self.mem.port1_write_protect = false;
+            }
+            Port::Port2 => {
+                self.mem.port2 = Vec::new();
+                self.mem.port2_mask = 0;
+                self.mem.port2_is_ram = false;
+                self.mem.port2_write_protect = false;
+            }
+        }
+        self.update_card_status();
+        image
+    }
+
+    /// Insert a card into a running calculator. The firmware is told
+    /// through the card-detect interrupt, if it has enabled it.
+    pub fn hot_insert_card(
+        &mut self,
+        port: Port,
+        image: &[u8],
+        kind: CardKind,
+    ) -> Result<(), String> {
+        self.insert_card(port, image, kind)?;
+        self.card_detect();
+        Ok(())
+    }
+
+    /// Pull a card out of a running calculator. Sets the module-pulled
+    /// flag and raises the card-detect interrupt.
+    pub fn hot_eject_card(&mut self, port: Port) -> Option<Vec<u8>> {
+        let image = self.eject_card(port)?;
+        self.saturn.mp = 1;
+        self.card_detect();
+        Some(image)
+    }
+
+    /// Raise the card-detect interrupt, if the firmware has enabled it.
+    /// The hot_* calls do this after changing a card.
+    pub fn card_detect(&mut self) {
+        if self.saturn.card_ctrl & CARD_CTRL_ECDT == 0 {
+            return;
+        }
+        // Like a key press: pending if interrupts are off, and wakes the
+        // CPU from SHUTDN.
+        self.do_kbd_int();
+        self.wake_up(self.now);
+    }
+
+    /// Image of the card in `port` in x48 port file format.
+    pub fn save_card(&self, port: Port) -> Option<Vec<u8>> {
+        let nibbles = self.card_nibbles(port);
+        (!nibbles.is_empty()).then(|| persist::pack_nibbles(nibbles))
+    }
+
+    pub fn card_present(&self, port: Port) -> bool {
+        !self.card_nibbles(port).is_empty()
+    }
+
+    fn card_nibbles(&self, port: Port) -> &[u8] {
+        match port {
+            Port::Port1 => &self.mem.port1,
+            Port::Port2 => &self.mem.port2,
+        }
+    }
+
+    /// Flip the write-protect switch of the card in `port`. While it is
+    /// on, the firmware sees the card as read-only and writes are dropped.
+    pub fn set_card_write_protect(&mut self, port: Port, on: bool) {
+        match port {
+            Port::Port1 => self.mem.port1_write_protect = on,
+            Port::Port2 => self.mem.port2_write_protect = on,
+        }
+        self.update_card_status();
+    }
+
+    pub fn card_write_protected(&self, port: Port) -> bool {
+        match port {
+            Port::Port1 => self.mem.port1_write_protect,
+            Port::Port2 => self.mem.port2_write_protect,
+        }
+    }
+
+    fn card_writable(&self, port: Port) -> bool {
+        match port {
+            Port::Port1 => self.mem.port1_is_ram && !self.mem.port1_write_protect,
+            Port::Port2 => self.mem.port2_is_ram && !self.mem.port2_write_protect,
+        }
+    }
+
+    /// Recompute the card-detect and write-enable bits of CARD STATUS from
+    /// the cards plugged in.
+    pub fn update_card_status(&mut self) {
+        let mut status = 0;
+        for port in [Port::Port1, Port::Port2] {
+            let (present, writable) = status_bits(self.model, port);
+            if self.card_present(port) {
+                status |= present;
+                if self.card_writable(port) {
+                    status |= writable;
+                }
+            }
+        }
+        self.saturn.card_status = status;
+        self.device.card_status_touched = true;
+    }
+}
+
+fn port_number(port: Port) -> u8 {
+    match port {
+        Port::Port1 => 1,
+        Port::Port2 => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::key_code;
+    use crate::rpl::Object;
+    use crate::test_support::{boot_gx, press, run, ALPHA, ENTER, ON};
+    use crate::types::*;
+
+    fn bare(model: Model) -> Emulator {
+        let rom_size = match model {
+            Model::Sx => ROM_SIZE_SX,
+            Model::Gx => ROM_SIZE_GX,
+        };
+        let rom = vec![0u8; rom_size];
+        Emulator::new(&rom, None, None, model)
+    }
+
+    #[test]
+    fn test_card_status_bits() {
+        let mut gx = bare(Model::Gx);
+        gx.insert_ram_card(Port::Port1, 128 * 1024).unwrap();
+        assert_eq!(gx.saturn.card_status, 0x0a);
+        gx.insert_card(Port::Port2, &[0; 32 * 1024], CardKind::Rom)
+            .unwrap();
+        assert_eq!(gx.saturn.card_status, 0x0b);
+        gx.set_card_write_protect(Port::Port1, true);
+        assert_eq!(gx.saturn.card_status, 0x03);
+        gx.eject_card(Port::Port1);
+        assert_eq!(gx.saturn.card_status, 0x01);
+        assert!(!gx.card_write_protected(Port::Port1));
+
+        let mut sx = bare(Model::Sx);
+        sx.insert_ram_card(Port::Port1, 32 * 1024).unwrap();
+        assert_eq!(sx.saturn.card_status, 0x05);
+        assert!(sx.insert_ram_card(Port::Port2, 256 * 1024).is_err());
+        assert!(sx.insert_ram_card(Port::Port2, 96 * 1024).is_err());
+    }
+
+    #[test]
+    fn test_image_round_trip() {
+        let mut emu = bare(Model::Gx);
+        let mut image = vec![0u8; 32 * 1024];
+        image[0] = 0x21;
+        emu.insert_card(Port::Port1, &image, CardKind::Ram).unwrap();
+        assert_eq!(&emu.mem.port1[..2], &[1, 2]);
+        assert_eq!(emu.eject_card(Port::Port1), Some(image));
+        assert!(!emu.card_present(Port::Port1));
+        assert_eq!(emu.save_card(Port::Port1), None);
+
+        // Library cards are padded up to a card size.
+        emu.insert_rom_card(Port::Port2, &[0x55; 40 * 1024])
+            .unwrap();
+        assert_eq!(emu.mem.port2.len(), 2 * 64 * 1024);
+        assert!(!emu.mem.port2_is_ram);
+    }
+
+    #[test]
+    fn test_hot_plug_with_firmware() {
+        let mut emu = boot_gx();
+        let recv = [ALPHA, ALPHA, 0x60, 0x81, 0x83, 0x52, ALPHA, ENTER];
+        press(&mut emu, &[0x8000]);
+        press(&mut emu, &recv);
+        emu.send_file("P", b"%%HP: T(3)A(D)F(.);\r\n\\<< 2 :1:A STO \\>>\r\n")
+            .unwrap();
+        press(&mut emu, &[0x8000]);
+        press(&mut emu, &recv);
+        emu.send_file(
+            "Q",
+            b"%%HP: T(3)A(D)F(.);\r\n\\<< :1:A RCL 'B' STO 'B' SEND \\>>\r\n",
+        )
+        .unwrap();
+
+        // Plug a card into the running calculator and store into it (P).
+        emu.hot_insert_card(Port::Port1, &[0; 128 * 1024], CardKind::Ram)
+            .unwrap();
+        press(&mut emu, &[0x8000, ALPHA, 0x62, ENTER]);
+        let image = emu.hot_eject_card(Port::Port1).unwrap();
+        assert!(image.iter().any(|&b| b != 0));
+        assert_eq!(emu.saturn.card_status, 0);
+        emu.run_headless(2.0, |_| false);
+
+        // Put it back and recall the object through Kermit (Q).
+        emu.hot_insert_card(Port::Port1, &image, CardKind::Ram)
+            .unwrap();
+        emu.run_headless(2.0, |_| false);
+        press(&mut emu, &[ALPHA, 0x61, ENTER]);
+        let (name, data) = emu.receive_file().unwrap();
+        assert_eq!(name, "B");
+        assert!(data.ends_with(b"\r\n2\r\n"));
+    }
+
+    #[test]
+    fn test_write_protect_with_firmware() {
+        let mut emu = boot_gx();
+        let recv = [ALPHA, ALPHA, 0x60, 0x81, 0x83, 0x52, ALPHA, ENTER];
+        let programs: [(&str, &[u8]); 3] = [
+            ("P", b"%%HP: T(3)A(D)F(.);\r\n\\<< 2 :1:A STO \\>>\r\n"),
+            ("R", b"%%HP: T(3)A(D)F(.);\r\n\\<< :1:A PURGE \\>>\r\n"),
+            (
+                "Q",
+                b"%%HP: T(3)A(D)F(.);\r\n\\<< :1:A RCL 'B' STO 'B' SEND \\>>\r\n",
+            ),
+        ];
+        for (name, program) in programs {
+            press(&mut emu, &[0x8000]);
+            press(&mut emu, &recv);
+            emu.send_file(name, program).unwrap();
+        }
+        let (run_p, run_r, run_q) = (0x62, 0x60, 0x61);
+
+        // STO into a protected card is refused and leaves it blank.
+        emu.hot_insert_card(Port::Port1, &[0; 128 * 1024], CardKind::Ram)
+            .unwrap();
+        emu.set_card_write_protect(Port::Port1, true);
+        press(&mut emu, &[0x8000, ALPHA, run_p, ENTER]);
+        let blank = emu.save_card(Port::Port1).unwrap();
+        assert!(blank.iter().all(|&b| b == 0));
+
+        emu.set_card_write_protect(Port::Port1, false);
+        press(&mut emu, &[0x8000, ALPHA, run_p, ENTER]);
+        let stored = emu.save_card(Port::Port1).unwrap();
+        assert_ne!(stored, blank);
+
+        // So is PURGE; the object stays readable.
+        emu.set_card_write_protect(Port::Port1, true);
+        press(&mut emu, &[0x8000, ALPHA, run_r, ENTER]);
+        assert_eq!(emu.save_card(Port::Port1).unwrap(), stored);
+        press(&mut emu, &[0x8000, ALPHA, run_q, ENTER]);
+        let (_, data) = emu.receive_file().unwrap();
+        assert!(data.ends_with(b"\r\n2\r\n"));
+
+        // The firmware says why. STO, PURGE, purging every object in the
+        // port at once (the GX has no PGALL; PVARS lists them) and MERGE all
+        // fail the same way.
+        for command in ["3 :1:C STO", ":1:A PURGE", "1 PVARS DROP PURGE", "1 MERGE"] {
+            assert_eq!(
+                error_after(&mut emu, command),
+                "Port Not Available",
+                "{command}"
+            );
+        }
+        assert_eq!(emu.save_card(Port::Port1).unwrap(), stored);
+    }
+
+    /// Wait out a card change: the firmware drops the first key after one.
+    fn settle(emu: &mut Emulator) {
+        emu.run_headless(5.0, |emu| emu.is_idle());
+        press(emu, &[ON]);
+        emu.run_headless(5.0, |emu| emu.is_idle());
+    }
+
+    /// Run `text` and return ERRM afterwards, the stack cleared.
+    fn error_after(emu: &mut Emulator, text: &str) -> String {
+        run(emu, text);
+        press(emu, &[ON]);
+        emu.run_headless(5.0, |emu| emu.is_idle());
+        run(emu, "ERRM");
+        let message = match emu.stack().unwrap().first() {
+            Some(Object::String(s)) => s.clone(),
+            other => panic!("ERRM gave {other:?}"),
+        };
+        run(emu, "CLEAR");
+        message
+    }
+
+    /// Levels of the stack as text, level 1 first.
+    fn stack(emu: &Emulator) -> Vec<String> {
+        emu.stack().unwrap().iter().map(|o| o.to_string()).collect()
+    }
+
+    #[test]
+    fn test_invalid_card_with_firmware() {
+        let mut emu = boot_gx();
+        emu.hot_insert_card(Port::Port1, &[0; 128 * 1024], CardKind::Ram)
+            .unwrap();
+        settle(&mut emu);
+        run(&mut emu, "\"HELLO\" :1:A STO");
+        let image = emu.hot_eject_card(Port::Port1).unwrap();
+        settle(&mut emu);
+
+        // A backup object that fails its checks, and a card of noise. The
+        // GX R firmware raises no Invalid Card Data for either on
+        // card-detect: it starts the card afresh, empty and usable.
+        let mut corrupt = image.clone();
+        corrupt[16] ^= 0x11;
+        let noise: Vec<u8> = (0..128 * 1024).map(|i| (i * 7 + 3) as u8).collect();
+        for bad in [corrupt, noise] {
+            emu.hot_insert_card(Port::Port1, &bad, CardKind::Ram)
+                .unwrap();
+            settle(&mut emu);
+            assert_eq!(error_after(&mut emu, ":1:A RCL"), "Undefined Name");
+            run(&mut emu, "1 PVARS");
+            assert_eq!(stack(&emu), ["131072", "{ }"]);
+            run(&mut emu, "CLEAR 7 :1:B STO :1:B RCL");
+            assert_eq!(stack(&emu), ["7"]);
+            run(&mut emu, "CLEAR");
+            emu.hot_eject_card(Port::Port1);
+            settle(&mut emu);
+        }
+
+        // The intact card still reads back.
+        emu.hot_insert_card(Port::Port1, &image, CardKind::Ram)
+            .unwrap();
+        settle(&mut emu);
+        run(&mut emu, ":1:A RCL");
+        assert_eq!(stack(&emu), ["\"HELLO\""]);
+    }
+
+    #[test]
+    fn test_invalid_card_data_with_firmware() {
+        let mut emu = boot_gx();
+        emu.hot_insert_card(Port::Port1, &[0; 128 * 1024], CardKind::Ram)
+            .unwrap();
+        settle(&mut emu);
+        run(&mut emu, "\"HELLO\" :1:A STO");
+        let image = emu.save_card(Port::Port1).unwrap();
+
+        // A bad header under a port the firmware has already set up, with
+        // no card-detect to make it look again, is Invalid Card Data.
+        let mut corrupt = image.clone();
+        corrupt[0] ^= 0x11;
+        emu.insert_card(Port::Port1, &corrupt, CardKind::Ram)
+            .unwrap();
+        for command in [":1:A RCL", "1 PVARS", "3 :1:C STO"] {
+            assert_eq!(
+                error_after(&mut emu, command),
+                "Invalid Card Data",
+                "{command}"
+            );
+        }
+        assert_eq!(emu.save_card(Port::Port1).unwrap(), corrupt);
+
+        emu.insert_card(Port::Port1, &image, CardKind::Ram).unwrap();
+        run(&mut emu, ":1:A RCL");
+        assert_eq!(stack(&emu), ["\"HELLO\""]);
+    }
+
+    /// Free memory as MEM reports it.
+    fn mem(emu: &mut Emulator) -> f64 {
+        run(emu, "MEM");
+        let free = stack(emu)[0].parse().unwrap();
+        run(emu, "CLEAR");
+        free
+    }
+
+    #[test]
+    fn test_merge_and_free_with_firmware() {
+        let mut emu = boot_gx();
+        emu.hot_insert_card(Port::Port1, &[0; 128 * 1024], CardKind::Ram)
+            .unwrap();
+        settle(&mut emu);
+        run(&mut emu, "\"HELLO\" :1:A STO");
+        let before = mem(&mut emu);
+
+        // MERGE adds the card's 128K to main memory: port 1 is system RAM,
+        // and its object moves to port 0.
+        run(&mut emu, "1 MERGE");
+        let merged = mem(&mut emu);
+        assert!((130_000.0..131_072.0).contains(&(merged - before)));
+        run(&mut emu, "1 PVARS");
+        assert_eq!(stack(&emu), ["\"SYSRAM\"", "{ }"]);
+        run(&mut emu, "CLEAR :0:A RCL");
+        assert_eq!(stack(&emu), ["\"HELLO\""]);
+        run(&mut emu, "CLEAR");
+
+        // FREE gives it back as an independent port, taking the object.
+        run(&mut emu, "{ A } 1 FREE");
+        assert!((mem(&mut emu) - before).abs() < 100.0);
+        run(&mut emu, "0 PVARS 1 PVARS");
+        assert_eq!(stack(&emu)[1], "{ :1:A }");
+        assert_eq!(stack(&emu)[3], "{ }");
+        run(&mut emu, "CLEAR");
+
+        // The card comes out and goes back in with it.
+        let image = emu.hot_eject_card(Port::Port1).unwrap();
+        settle(&mut emu);
+        emu.hot_insert_card(Port::Port1, &image, CardKind::Ram)
+            .unwrap();
+        settle(&mut emu);
+        run(&mut emu, ":1:A RCL");
+        assert_eq!(stack(&emu), ["\"HELLO\""]);
+    }
+
+    #[test]
+    fn test_hot_eject_merged_card_with_firmware() {
+        let mut emu = boot_gx();
+        emu.hot_insert_card(Port::Port1, &[0; 128 * 1024], CardKind::Ram)
+            .unwrap();
+        settle(&mut emu);
+        run(&mut emu, "1 MERGE 42 'X' STO");
+        assert!(mem(&mut emu) > 250_000.0);
+
+        // Pulling merged memory out: "Replace RAM, Press ON", then "Try To
+        // Recover Memory?". Without the card nothing can be recovered, so
+        // YES leaves memory cleared.
+        emu.hot_eject_card(Port::Port1).unwrap();
+        emu.run_headless(3.0, |_| false);
+        press(&mut emu, &[ON]);
+        emu.run_headless(3.0, |_| false);
+        assert!(!emu.is_idle());
+        press(&mut emu, &[key_code("A").unwrap()]);
+        settle(&mut emu);
+        assert!(mem(&mut emu) < 131_072.0);
+        run(&mut emu, "VARS");
+        assert_eq!(stack(&emu), ["{ }"]);
+    }
+
+    #[test]
+    fn test_gx_port2_bank_switching() {
+        let mut emu = bare(Model::Gx);
+        emu.insert_ram_card(Port::Port2, 1024 * 1024).unwrap();
+        emu.saturn.mem_cntl[MCTL_BANK_GX].config[0] = 0x7f000;
+        emu.saturn.mem_cntl[MCTL_PORT2_GX].config = [0xc0000, 0xc0000];
+
+        // Reading 0x7f040 + 2n selects bank n.
+        emu.read_nibble(0x7f046);
+        assert_eq!(emu.saturn.bank_switch, 3);
+        emu.write_nibble(0xc0010, 0x9);
+        assert_eq!(emu.mem.port2[(3 << 18) + 0x10], 0x9);
+        emu.read_nibble(0x7f000);
+        assert_eq!(emu.saturn.bank_switch, 0);
+        assert_eq!(emu.read_nibble(0xc0010), 0);
+
+        // The write-protect switch drops writes on the bus.
+        emu.set_card_write_protect(Port::Port2, true);
+        emu.write_nibble(0xc0010, 0x5);
+        assert_eq!(emu.read_nibble(0xc0010), 0);
+    }
+}
@@ -0,0 +1,1430 @@
+// UserRPL compiler: source text to the object the calculator's parser
+// builds from it (OBJ→ on a string), nibble for nibble, so that programs
+// kept as text on the host can be stored in HOME or written as binaries.
+//
+// Commands come from the loaded ROM's command table
+// (`Emulator::command_table`): a name compiles to its first entry there, as
+// the parser looks it up, except that the functions listed twice (XROOT)
+// take their second in algebraics. A command in ROM compiles to a pointer,
+// a library command outside it to an XLIB name. Words that are neither
+// numbers, commands nor names are rejected.
+//
+// Where the firmware shares objects with the ROM, so does the compiler:
+//
+// - The reals -9 to 9 are pointers to the ROM's constants inside programs,
+//   lists, algebraics and units, though not in arrays or complex numbers.
+// - A program inside a program follows xSILENT' (`SILENT_QUOTE`), and 'A'
+//   in a program is the name between the ' and ' commands of library 1792,
+//   whose commands are also the structure words. THEN is three of them (in
+//   IF, IFERR and CASE) and END four.
+// - A clause after THEN, ELSE or REPEAT, and an IFERR body, is compiled as
+//   a secondary of its own, a program without « », unless it is a single
+//   object; an empty one is an empty secondary, and a quoted program or
+//   name is more than one. A CASE keeps its whole body in one. → and FOR make their names local names up to the
+//   end of their body, inside algebraics and nested programs too.
+//
+// Algebraics are stored postfix. Precedence from the lowest: =, OR XOR,
+// AND, NOT, the comparisons, + -, * / MOD, negation, ^, !. A negated
+// number is a negative number unless it is raised to a power. A user
+// function F(A,B) is its arguments, 'F', the argument count as a ROM bint
+// and FCNAPPLY. ∂, ∫ and | keep forms of their own and are not compiled.
+//
+// Units are stored as the parser rewrites them: the factors with positive
+// powers multiplied, then those with negative powers multiplied and divided
+// into them (1_m*s^-1 is 1_m/s; 1_s^-1 divides the ROM's "1"), powers of
+// groups multiplied out. A prefix letter splits off a unit in the ROM's
+// table, kilo always as k.
+//
+// Like the parser, the compiler reads a number run into a word as two
+// words (1A is 1 A), compiles a structure in a list as a secondary of its
+// own, and closes what is left open at the end of the source where the
+// parser would: », }, ], END, strings, algebraics and complex numbers.
+// Reals round half up to 12 digits; a binary integer without a base
+// letter takes the current base.
+
+use std::collections::HashMap;
+
+use crate::decompile::arity;
+use crate::emulator::Emulator;
+use crate::rpl::*;
+
+/// The library of the structure words.
+const STRUCTURES: u32 = 1792;
+
+// Library 1792 commands
+const IF: u32 = 0;
+const THEN: u32 = 1;
+const ELSE: u32 = 2;
+const END: u32 = 3;
+const ARROW_ALGEBRAIC: u32 = 4;
+const WHILE: u32 = 5;
+const REPEAT: u32 = 6;
+const DO: u32 = 7;
+const UNTIL: u32 = 8;
+const START: u32 = 9;
+const FOR: u32 = 10;
+const NEXT: u32 = 11;
+const STEP: u32 = 12;
+const IFERR: u32 = 13;
+const ARROW: u32 = 16;
+const ARROW_END: u32 = 17;
+const OPEN: u32 = 18;
+const CLOSE: u32 = 19;
+const QUOTE: u32 = 20;
+const UNQUOTE: u32 = 21;
+const WHILE_END: u32 = 22;
+const DO_END: u32 = 23;
+const IFERR_THEN: u32 = 24;
+const CASE: u32 = 25;
+const CASE_THEN: u32 = 26;
+
+/// Library 2's FCNAPPLY, which applies a user function in an algebraic.
+const FCNAPPLY: (u32, u32) = (2, 260);
+
+/// Words that open, divide or close a structure.
+const STRUCTURE_WORDS: [&str; 16] = [
+    "IF", "THEN", "ELSE", "END", "IFERR", "CASE", "DO", "UNTIL", "WHILE", "REPEAT", "START", "FOR",
+    "NEXT", "STEP", "→", "DIR",
+];
+
+/// Structure words that open a structure.
+const OPENERS: [&str; 8] = ["IF", "IFERR", "CASE", "DO", "WHILE", "START", "FOR", "→"];
+
+/// What the parser supplies when the source ends before them.
+const CLOSERS: [&str; 4] = ["END", "»", "}", "]"];
+
+/// Units in the ROM's unit table, which a prefix letter may precede.
+const UNITS: [&str; 121] = [
+    "m", "yd", "ft", "in", "pc", "lyr", "au", "mi", "nmi", "miUS", "chain", "rd", "fath", "ftUS",
+    "mil", "µ", "Å", "fermi", "b", "a", "acre", "st", "l", "galUK", "galC", "gal", "qt", "pt",
+    "cu", "ozUK", "ozfl", "tbsp", "tsp", "bbl", "bu", "pk", "fbm", "yr", "d", "h", "min", "s",
+    "Hz", "kph", "mph", "knot", "c", "ga", "g", "lb", "oz", "slug", "lbt", "ton", "tonUK", "t",
+    "ozt", "ct", "grain", "u", "mol", "N", "dyn", "gf", "kip", "lbf", "pdl", "J", "erg", "cal",
+    "Btu", "therm", "eV", "W", "hp", "Pa", "atm", "bar", "psi", "torr", "mmHg", "inHg", "inH2O",
+    "°C", "°F", "K", "°R", "V", "A", "C", "Ω", "F", "Fdy", "H", "mho", "S", "T", "Wb", "°", "r",
+    "grad", "arcmin", "arcs", "sr", "fc", "flam", "lx", "ph", "sb", "lm", "cd", "lam", "Gy", "rad",
+    "rem", "Sv", "Bq", "Ci", "R", "P", "St",
+];
+
+/// Unit prefixes, from yotta down to yocto.
+const PREFIXES: &str = "YZEPTGMkKhHDdcmµnpfazy";
+
+/// Characters that end a word, as well as spaces.
+const DELIMITERS: &str = "«»{}[]()\"'@";
+
+/// Characters no name holds.
+const NOT_IN_NAMES: &str = "«»{}[]()\"'@#:,_+-*/^=<>≠≤≥!√∂∫|";
+
+/// The ROM's bints run up to this many arguments of a user function.
+const MAX_ARGUMENTS: u32 = 21;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// « » { } [ ]
+    Bracket(char),
+    Word(String),
+    Text(String),
+    Quoted(String),
+    Tag(String),
+    /// What follows #.
+    Binary(String),
+    /// What is between ( and ).
+    Complex(String),
+}
+
+/// Characters up to `end`, which is consumed, and whether it came.
+fn until(chars: &mut std::iter::Peekable<std::str::Chars>, end: char) -> (String, bool) {
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        if c == end {
+            return (text, true);
+        }
+        text.push(c);
+    }
+    (text, false)
+}
+
+fn tokens(source: &str) -> Result<Vec<Token>, String> {
+    let mut chars = source.chars().peekable();
+    let mut tokens = Vec::new();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            // A comment runs to the next @ or the end of the line.
+            '@' => {
+                while chars.next().is_some_and(|c| c != '@' && c != '\n') {}
+                continue;
+            }
+            // Strings, algebraics and complex numbers close themselves at
+            // the end, as the parser closes them; tags do not.
+            '"' => Token::Text(until(&mut chars, '"').0),
+            '\'' => Token::Quoted(until(&mut chars, '\'').0),
+            '(' => Token::Complex(until(&mut chars, ')').0),
+            ':' => match until(&mut chars, ':') {
+                (tag, true) => Token::Tag(tag),
+                _ => return Err("unterminated tag".to_string()),
+            },
+            ')' => return Err("unexpected )".to_string()),
+            '«' | '»' | '{' | '}' | '[' | ']' => Token::Bracket(c),
+            '#' => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric()) {
+                    digits.push(c);
+                }
+                Token::Binary(digits)
+            }
+            _ => {
+                // Parentheses group a unit's factors: 1_(m/s).
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|&c| {
+                    !c.is_whitespace()
+                        && (!DELIMITERS.contains(c) || word.contains('_') && "()".contains(c))
+                }) {
+                    word.push(c);
+                }
+                // A number runs into what follows it: 1A is 1 and A.
+                if !word.contains('_') && matches!(parse_real(&word), Ok(None)) {
+                    let number = (1..word.len()).rev().find(|&at| {
+                        word.is_char_boundary(at) && matches!(parse_real(&word[..at]), Ok(Some(_)))
+                    });
+                    if let Some(at) = number {
+                        tokens.push(Token::Word(word[..at].to_string()));
+                        word.drain(..at);
+                    }
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// `text` as a real, rounded half up to 12 digits; `Ok(None)` if it is not
+/// a number, an error if it is one out of range.
+fn parse_real(text: &str) -> Result<Option<Real>, String> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (number, exponent) = match unsigned.split_once('E') {
+        Some((number, exponent)) => {
+            let digits = exponent.strip_prefix('-').unwrap_or(exponent);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return Ok(None);
+            }
+            (number, exponent.parse::<i64>().map_err(|e| e.to_string())?)
+        }
+        None => (unsigned, 0),
+    };
+    let (int, frac) = number.split_once('.').unwrap_or((number, ""));
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if int.len() + frac.len() == 0 || !all_digits(int) || !all_digits(frac) {
+        return Ok(None);
+    }
+    let digits = format!("{int}{frac}");
+    let significant = digits.trim_start_matches('0');
+    if significant.is_empty() {
+        return Ok(Some(zero()));
+    }
+    let mut exponent = exponent + int.len() as i64 - (digits.len() - significant.len()) as i64 - 1;
+    let mut mantissa: u64 = format!("{significant:0<12}")[..12].parse().unwrap();
+    if significant.as_bytes().get(12).is_some_and(|&d| d >= b'5') {
+        mantissa += 1;
+        if mantissa == 1_000_000_000_000 {
+            mantissa /= 10;
+            exponent += 1;
+        }
+    }
+    if !(-499..=499).contains(&exponent) {
+        return Err(format!("{text} is out of range"));
+    }
+    Ok(Some(Real {
+        negative,
+        mantissa,
+        exponent: exponent as i32,
+        digits: 12,
+    }))
+}
+
+fn zero() -> Real {
+    Real {
+        negative: false,
+        mantissa: 0,
+        exponent: 0,
+        digits: 12,
+    }
+}
+
+fn valid_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .is_some_and(|c| !c.is_ascii_digit() && c != '.')
+        && word.chars().count() <= 127
+        && !word
+            .chars()
+            .any(|c| c.is_whitespace() || NOT_IN_NAMES.contains(c))
+}
+
+/// A unit's factors after its _, each a name (with its prefix) and its
+/// power, powers of groups multiplied out and divisors' negated.
+struct UnitParser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+type Factors = Vec<(Vec<Object>, f64)>;
+
+impl UnitParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn product(&mut self) -> Result<Factors, String> {
+        let mut factors = self.power()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.at += 1;
+            let mut right = self.power()?;
+            if op == '/' {
+                right.iter_mut().for_each(|(_, power)| *power = -*power);
+            }
+            factors.extend(right);
+        }
+        Ok(factors)
+    }
+
+    fn power(&mut self) -> Result<Factors, String> {
+        let mut factors = self.factor()?;
+        while self.peek() == Some('^') {
+            self.at += 1;
+            let start = self.at;
+            while self
+                .peek()
+                .is_some_and(|c| c.is_ascii_digit() || c == '.' || c == '-')
+            {
+                self.at += 1;
+            }
+            let text: String = self.chars[start..self.at].iter().collect();
+            let power = parse_real(&text)?
+                .ok_or_else(|| format!("bad unit exponent {text:?}"))?
+                .to_f64();
+            factors.iter_mut().for_each(|(_, p)| *p *= power);
+        }
+        Ok(factors)
+    }
+
+    fn factor(&mut self) -> Result<Factors, String> {
+        if self.peek() == Some('(') {
+            self.at += 1;
+            let factors = self.product()?;
+            if self.peek() != Some(')') {
+                return Err("missing ) in unit".to_string());
+            }
+            self.at += 1;
+            return Ok(factors);
+        }
+        let start = self.at;
+        while self.peek().is_some_and(|c| !"*/^()".contains(c)) {
+            self.at += 1;
+        }
+        let name: String = self.chars[start..self.at].iter().collect();
+        let mut chars = name.chars();
+        let items = match chars.next() {
+            None => return Err("missing unit".to_string()),
+            Some(_) if UNITS.contains(&name.as_str()) => vec![Object::String(name)],
+            // The parser keeps kilo as k, however it is written.
+            Some(prefix) if PREFIXES.contains(prefix) && UNITS.contains(&chars.as_str()) => vec![
+                Object::Char(if prefix == 'K' { 'k' } else { prefix }),
+                Object::String(chars.as_str().to_string()),
+                Object::UnitOp(' '),
+            ],
+            // Units the ROM does not know are kept, as the parser keeps them.
+            Some(_) => vec![Object::String(name)],
+        };
+        Ok(vec![(items, 1.0)])
+    }
+}
+
+/// A word of an algebraic.
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Number(Real),
+    Unit(Object),
+    Name(String),
+    Operator(String),
+    Open,
+    Close,
+    Comma,
+}
+
+/// An algebraic being parsed.
+struct Expression {
+    lexemes: Vec<Lexeme>,
+    at: usize,
+}
+
+impl Expression {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.at)
+    }
+
+    /// Takes the operator `op` if it comes next.
+    fn operator(&mut self, ops: &[&str]) -> Option<String> {
+        match self.peek() {
+            Some(Lexeme::Operator(op)) if ops.contains(&op.as_str()) => {
+                let op = op.clone();
+                self.at += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where an object is compiled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Context {
+    Program,
+    List,
+    /// The object itself, or a tagged one: it cannot be a command.
+    Object,
+}
+
+struct Compiler<'a> {
+    emu: &'a Emulator,
+    /// First and last address of each command name.
+    commands: HashMap<String, (u32, u32)>,
+    /// Library 1792's commands, by number.
+    structures: Vec<Option<u32>>,
+    /// Where the ROM keeps its reals 0-9, then -1 to -9.
+    reals: Option<u32>,
+    /// Where the ROM keeps its bints from 0 up.
+    bints: Option<u32>,
+    /// The ROM's string "1", the dividend of a unit such as 1/s.
+    one: Option<u32>,
+    base: u32,
+    tokens: Vec<Token>,
+    at: usize,
+    /// Local names in scope, innermost last.
+    locals: Vec<String>,
+}
+
+/// Where `pattern` first appears in `rom`.
+fn find(rom: &[u8], pattern: &[u8]) -> Option<u32> {
+    rom.windows(pattern.len())
+        .position(|w| w == pattern)
+        .map(|at| at as u32)
+}
+
+impl<'a> Compiler<'a> {
+    fn new(emu: &'a Emulator, source: &str) -> Result<Compiler<'a>, String> {
+        let mut commands = HashMap::new();
+        for (addr, name) in emu.command_table() {
+            commands
+                .entry(name)
+                .and_modify(|(_, last)| *last = addr)
+                .or_insert((addr, addr));
+        }
+        let reals: Vec<u8> = (0..=9)
+            .chain((1..=9).map(|k| -k))
+            .flat_map(|k| {
+                Object::Real(Real::from_f64(k as f64).unwrap())
+                    .to_nibbles()
+                    .unwrap()
+            })
+            .collect();
+        let bints: Vec<u8> = (0..=MAX_ARGUMENTS)
+            .flat_map(|n| Object::Bint(n).to_nibbles().unwrap())
+            .collect();
+        Ok(Compiler {
+            emu,
+            commands,
+            structures: (0..=CASE_THEN)
+                .map(|n| emu.command_address(STRUCTURES, n))
+                .collect(),
+            reals: find(&emu.mem.rom, &reals),
+            bints: find(&emu.mem.rom, &bints),
+            one: find(&emu.mem.rom, &Object::String("1".to_string()).to_nibbles()?),
+            base: emu.display_modes().base,
+            tokens: tokens(source)?,
+            at: 0,
+            locals: Vec::new(),
+        })
+    }
+}
+
+impl Compiler<'_> {
+    fn structure(&self, command: u32) -> Result<Object, String> {
+        self.structures[command as usize]
+            .map(Object::Pointer)
+            .ok_or_else(|| "the ROM has no structure words".to_string())
+    }
+
+    /// The command at `addr`: a pointer into ROM, or an XLIB name.
+    fn command(&self, addr: u32) -> Result<Object, String> {
+        if addr < self.emu.ram_range().start {
+            return Ok(Object::Pointer(addr));
+        }
+        let (library, command) = self
+            .emu
+            .library_command(addr)
+            .ok_or_else(|| format!("no library command at {addr:05X}"))?;
+        Ok(Object::Xlib {
+            library,
+            command,
+            name: None,
+        })
+    }
+
+    /// `real` as the parser leaves it in a composite: the ROM's constant
+    /// if it has one.
+    fn constant(&self, real: Real) -> Object {
+        let Some(base) = self.reals else {
+            return Object::Real(real);
+        };
+        let digit = 100_000_000_000;
+        match real.mantissa / digit {
+            _ if real.mantissa == 0 => Object::Pointer(base),
+            k @ 1..=9 if real.exponent == 0 && real.mantissa.is_multiple_of(digit) => {
+                let index = if real.negative { 9 + k } else { k };
+                Object::Pointer(base + 21 * index as u32)
+            }
+            _ => Object::Real(real),
+        }
+    }
+
+    fn number(&self, real: Real, context: Context) -> Object {
+        match context {
+            Context::Object => Object::Real(real),
+            _ => self.constant(real),
+        }
+    }
+
+    fn name(&self, name: &str) -> Object {
+        match self.locals.iter().any(|local| local == name) {
+            true => Object::Local(name.to_string()),
+            false => Object::Global(name.to_string()),
+        }
+    }
+
+    /// `items` as a clause: as they are if a single object, otherwise a
+    /// secondary of their own.
+    fn clause(&self, items: Vec<Object>) -> Vec<Object> {
+        match items.len() {
+            1 => items,
+            _ => vec![Object::Program(items)],
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    /// Items in `context` up to one of the words or brackets `ends`, and
+    /// which one it was.
+    fn until(&mut self, context: Context, ends: &[&str]) -> Result<(Vec<Object>, String), String> {
+        let mut items = Vec::new();
+        loop {
+            let end = match self.tokens.get(self.at) {
+                None => match ends.iter().find(|end| CLOSERS.contains(end)) {
+                    Some(end) => return Ok((items, end.to_string())),
+                    None => return Err(format!("missing {}", ends.join(" or "))),
+                },
+                Some(Token::Word(word)) => word.clone(),
+                Some(Token::Bracket(c)) => c.to_string(),
+                Some(_) => String::new(),
+            };
+            if ends.contains(&end.as_str()) {
+                self.at += 1;
+                return Ok((items, end));
+            }
+            self.item(context, &mut items)?;
+        }
+    }
+
+    /// The body of a program, up to one of `ends`.
+    fn body(&mut self, ends: &[&str]) -> Result<(Vec<Object>, String), String> {
+        self.until(Context::Program, ends)
+    }
+
+    /// One object, as at the top level or after a tag.
+    fn object(&mut self) -> Result<Object, String> {
+        let mut items = Vec::new();
+        self.item(Context::Object, &mut items)?;
+        Ok(items.pop().unwrap())
+    }
+
+    /// The next object, command or structure, onto `items`.
+    fn item(&mut self, context: Context, items: &mut Vec<Object>) -> Result<(), String> {
+        let token = self.next().ok_or("missing object")?;
+        let object = match token {
+            Token::Bracket('«') => {
+                let (body, _) = self.body(&["»"])?;
+                let mut program = vec![self.structure(OPEN)?];
+                program.extend(body);
+                program.push(self.structure(CLOSE)?);
+                if context == Context::Program {
+                    items.push(Object::Pointer(SILENT_QUOTE));
+                }
+                Object::Program(program)
+            }
+            Token::Bracket('{') => Object::List(self.until(Context::List, &["}"])?.0),
+            Token::Bracket('[') => self.array()?,
+            Token::Bracket(c) => return Err(format!("unexpected {c}")),
+            Token::Text(text) => Object::String(text),
+            Token::Quoted(text) => {
+                let object = self.algebraic(&text, context)?;
+                if context == Context::Program
+                    && matches!(object, Object::Global(_) | Object::Local(_))
+                {
+                    items.push(self.structure(QUOTE)?);
+                    items.push(object);
+                    items.push(self.structure(UNQUOTE)?);
+                    return Ok(());
+                }
+                object
+            }
+            Token::Tag(tag) => {
+                let object = match self.object()? {
+                    Object::Real(real) => self.constant(real),
+                    object => object,
+                };
+                Object::Tagged(tag, Box::new(object))
+            }
+            Token::Binary(digits) => self.binary(&digits)?,
+            Token::Complex(text) => complex(&text)?,
+            Token::Word(word) if STRUCTURE_WORDS.contains(&word.as_str()) => {
+                match context {
+                    Context::Program => return self.structured(&word, items),
+                    // A structure in a list is a secondary of its own.
+                    Context::List if OPENERS.contains(&word.as_str()) => {
+                        let mut structure = Vec::new();
+                        self.structured(&word, &mut structure)?;
+                        Object::Program(structure)
+                    }
+                    _ => return Err(format!("{word} outside a program")),
+                }
+            }
+            Token::Word(word) => self.word(&word, context)?,
+        };
+        items.push(object);
+        Ok(())
+    }
+
+    /// A number, unit, command or name.
+    fn word(&self, word: &str, context: Context) -> Result<Object, String> {
+        if let Some(real) = parse_real(word)? {
+            return Ok(self.number(real, context));
+        }
+        if let Some((number, unit)) = word.split_once('_') {
+            if let Some(real) = parse_real(number)? {
+                let mut items = vec![self.constant(real)];
+                items.extend(self.unit(unit)?);
+                items.push(Object::UnitOp('_'));
+                return Ok(Object::Unit(items));
+            }
+        }
+        if let Some(&(addr, _)) = self.commands.get(word) {
+            if context == Context::Object {
+                return Err(format!("{word} is a command, not an object"));
+            }
+            return self.command(addr);
+        }
+        if valid_name(word) {
+            return Ok(self.name(word));
+        }
+        Err(format!("unknown command {word}"))
+    }
+
+    /// A unit after its _, as stored: the factors with positive powers
+    /// multiplied, divided by those with negative ones.
+    fn unit(&self, text: &str) -> Result<Vec<Object>, String> {
+        let mut parser = UnitParser {
+            chars: text.chars().collect(),
+            at: 0,
+        };
+        let factors = parser.product()?;
+        if let Some(c) = parser.peek() {
+            return Err(format!("unexpected {c} in unit {text}"));
+        }
+        let product = |negative: bool| -> Result<Vec<Object>, String> {
+            let mut items = Vec::new();
+            let factors = factors
+                .iter()
+                .filter(|(_, power)| (*power < 0.0) == negative);
+            for (i, (name, power)) in factors.enumerate() {
+                items.extend(name.iter().cloned());
+                let power = Real::from_f64(power.abs()).ok_or("unit exponent out of range")?;
+                if (power.mantissa, power.exponent) != (100_000_000_000, 0) {
+                    items.push(self.constant(power));
+                    items.push(Object::UnitOp('^'));
+                }
+                if i > 0 {
+                    items.push(Object::UnitOp('*'));
+                }
+            }
+            Ok(items)
+        };
+        let mut items = product(false)?;
+        let divisor = product(true)?;
+        if !divisor.is_empty() {
+            if items.is_empty() {
+                let one = self.one.ok_or("the ROM has no unit 1")?;
+                items.push(Object::Pointer(one));
+            }
+            items.extend(divisor);
+            items.push(Object::UnitOp('/'));
+        }
+        Ok(items)
+    }
+
+    fn binary(&self, digits: &str) -> Result<Object, String> {
+        let (digits, base) = match digits.chars().last() {
+            Some('h') => (&digits[..digits.len() - 1], 16),
+            Some('d') => (&digits[..digits.len() - 1], 10),
+            Some('o') => (&digits[..digits.len() - 1], 8),
+            Some('b') => (&digits[..digits.len() - 1], 2),
+            _ => (digits, self.base),
+        };
+        let value = u64::from_str_radix(digits, base)
+            .ok()
+            .filter(|_| {
+                digits
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+            })
+            .ok_or_else(|| format!("bad binary integer #{digits}"))?;
+        Ok(Object::Binary(
+            (0..16).map(|i| (value >> (4 * i)) as u8 & 0xf).collect(),
+        ))
+    }
+
+    /// A vector or matrix of reals or complex numbers.
+    fn array(&mut self) -> Result<Object, String> {
+        let mut rows = Vec::new();
+        let matrix = self.tokens.get(self.at) == Some(&Token::Bracket('['));
+        loop {
+            match self.next() {
+                None | Some(Token::Bracket(']')) => break,
+                Some(Token::Bracket('[')) if matrix => rows.push(self.row()?),
+                Some(Token::Word(word)) if !matrix => {
+                    let real = parse_real(&word)?.ok_or_else(|| format!("{word} in an array"))?;
+                    rows.push(vec![Object::Real(real)]);
+                }
+                Some(Token::Complex(text)) if !matrix => rows.push(vec![complex(&text)?]),
+                _ => return Err("arrays hold numbers".to_string()),
+            }
+        }
+        if !matrix {
+            let items: Vec<Object> = rows.into_iter().flatten().collect();
+            return Ok(Object::Array {
+                dims: vec![items.len() as u32],
+                items,
+            });
+        }
+        let columns = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != columns) {
+            return Err("matrix rows of different lengths".to_string());
+        }
+        Ok(Object::Array {
+            dims: vec![rows.len() as u32, columns as u32],
+            items: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    fn row(&mut self) -> Result<Vec<Object>, String> {
+        let mut row = Vec::new();
+        loop {
+            match self.next() {
+                None | Some(Token::Bracket(']')) => return Ok(row),
+                Some(Token::Word(word)) => {
+                    let real = parse_real(&word)?.ok_or_else(|| format!("{word} in an array"))?;
+                    row.push(Object::Real(real));
+                }
+                Some(Token::Complex(text)) => row.push(complex(&text)?),
+                _ => return Err("arrays hold numbers".to_string()),
+            }
+        }
+    }
+
+    /// The names after → or FOR, made local.
+    fn local(&mut self, word: &str) -> Result<Object, String> {
+        match self.next() {
+            Some(Token::Word(name)) if valid_name(&name) && !self.commands.contains_key(&name) => {
+                self.locals.push(name.clone());
+                Ok(Object::Local(name))
+            }
+            _ => Err(format!("{word} needs a name")),
+        }
+    }
+
+    /// The structure opened by `word`, onto `items`.
+    fn structured(&mut self, word: &str, items: &mut Vec<Object>) -> Result<(), String> {
+        match word {
+            "IF" | "IFERR" => {
+                items.push(self.structure(if word == "IF" { IF } else { IFERR })?);
+                let (first, _) = self.body(&["THEN"])?;
+                if word == "IF" {
+                    items.extend(first);
+                    items.push(self.structure(THEN)?);
+                } else {
+                    items.extend(self.clause(first));
+                    items.push(self.structure(IFERR_THEN)?);
+                }
+                let (then, end) = self.body(&["ELSE", "END"])?;
+                items.extend(self.clause(then));
+                if end == "ELSE" {
+                    items.push(self.structure(ELSE)?);
+                    let (otherwise, _) = self.body(&["END"])?;
+                    items.extend(self.clause(otherwise));
+                }
+                items.push(self.structure(END)?);
+            }
+            "CASE" => {
+                items.push(self.structure(CASE)?);
+                let mut body = Vec::new();
+                loop {
+                    let (test, end) = self.body(&["THEN", "END"])?;
+                    body.extend(test);
+                    if end == "END" {
+                        break;
+                    }
+                    body.push(self.structure(CASE_THEN)?);
+                    let (then, _) = self.body(&["END"])?;
+                    body.extend(self.clause(then));
+                    body.push(self.structure(END)?);
+                }
+                items.push(Object::Program(body));
+                items.push(self.structure(END)?);
+            }
+            "DO" => {
+                items.push(self.structure(DO)?);
+                items.extend(self.body(&["UNTIL"])?.0);
+                items.push(self.structure(UNTIL)?);
+                items.extend(self.body(&["END"])?.0);
+                items.push(self.structure(DO_END)?);
+            }
+            "WHILE" => {
+                items.push(self.structure(WHILE)?);
+                items.extend(self.body(&["REPEAT"])?.0);
+                items.push(self.structure(REPEAT)?);
+                let (body, _) = self.body(&["END"])?;
+                items.extend(self.clause(body));
+                items.push(self.structure(WHILE_END)?);
+            }
+            "START" | "FOR" => {
+                let scope = self.locals.len();
+                if word == "START" {
+                    items.push(self.structure(START)?);
+                } else {
+                    items.push(self.structure(FOR)?);
+                    items.push(self.local(word)?);
+                }
+                let (body, end) = self.body(&["NEXT", "STEP"])?;
+                self.locals.truncate(scope);
+                items.extend(body);
+                items.push(self.structure(if end == "NEXT" { NEXT } else { STEP })?);
+            }
+            "→" => {
+                let scope = self.locals.len();
+                let mut names = Vec::new();
+                while let Some(Token::Word(_)) = self.tokens.get(self.at) {
+                    names.push(self.local(word)?);
+                }
+                if names.is_empty() {
+                    return Err("→ needs a name".to_string());
+                }
+                match self.next() {
+                    Some(Token::Bracket('«')) => {
+                        items.push(self.structure(ARROW)?);
+                        items.extend(names);
+                        items.push(self.structure(OPEN)?);
+                        items.extend(self.body(&["»"])?.0);
+                        items.push(self.structure(ARROW_END)?);
+                    }
+                    Some(Token::Quoted(text)) => {
+                        items.push(self.structure(ARROW_ALGEBRAIC)?);
+                        items.extend(names);
+                        let expression = self.expression(&text)?;
+                        items.push(Object::Algebraic(expression));
+                    }
+                    _ => return Err("→ needs a program or an algebraic".to_string()),
+                }
+                self.locals.truncate(scope);
+            }
+            _ => return Err(format!("unexpected {word}")),
+        }
+        Ok(())
+    }
+}
+
+/// A complex number, (1,2) or (1 2); its parts are never ROM constants.
+fn complex(text: &str) -> Result<Object, String> {
+    let parts: Vec<&str> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let part = |text: &str| parse_real(text)?.ok_or_else(|| format!("bad complex number ({text})"));
+    match parts.as_slice() {
+        [re, im] => Ok(Object::Complex(part(re)?, part(im)?)),
+        _ => Err(format!("bad complex number ({text})")),
+    }
+}
+
+/// The words of an algebraic.
+fn lexemes(text: &str) -> Result<Vec<Lexeme>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut lexemes = Vec::new();
+    let mut at = 0;
+    let is_name_char = |c: char| !c.is_whitespace() && !"+-*/^=<>≠≤≥!√(),".contains(c);
+    while let Some(&c) = chars.get(at) {
+        let start = at;
+        at += 1;
+        let lexeme = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Lexeme::Open,
+            ')' => Lexeme::Close,
+            ',' => Lexeme::Comma,
+            '=' if chars.get(at) == Some(&'=') => {
+                at += 1;
+                Lexeme::Operator("==".to_string())
+            }
+            c if "+-*/^=<>≠≤≥!√".contains(c) => Lexeme::Operator(c.to_string()),
+            c if c.is_ascii_digit() || c == '.' => {
+                while chars
+                    .get(at)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    at += 1;
+                }
+                // An exponent, E5 or E-5.
+                let digit = |i: usize| chars.get(i).is_some_and(|c| c.is_ascii_digit());
+                if chars.get(at) == Some(&'E') {
+                    if digit(at + 1) {
+                        at += 1;
+                    } else if chars.get(at + 1) == Some(&'-') && digit(at + 2) {
+                        at += 2;
+                    }
+                    while digit(at) {
+                        at += 1;
+                    }
+                }
+                let number: String = chars[start..at].iter().collect();
+                let real = parse_real(&number)?.ok_or_else(|| format!("bad number {number}"))?;
+                if chars.get(at) != Some(&'_') {
+                    Lexeme::Number(real)
+                } else {
+                    // The unit runs to the next operator, a power of it
+                    // included: 3_m^2.
+                    let unit = at + 1;
+                    at = unit;
+                    while chars.get(at).is_some_and(|&c| is_name_char(c) || c == '^') {
+                        if chars[at] == '^' {
+                            at += 1;
+                            if chars.get(at) == Some(&'-') {
+                                at += 1;
+                            }
+                            while chars
+                                .get(at)
+                                .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                            {
+                                at += 1;
+                            }
+                        } else {
+                            at += 1;
+                        }
+                    }
+                    let unit: String = chars[unit..at].iter().collect();
+                    Lexeme::Unit(Object::Unit(vec![Object::Real(real), Object::String(unit)]))
+                }
+            }
+            _ => {
+                while chars.get(at).is_some_and(|&c| is_name_char(c)) {
+                    at += 1;
+                }
+                let name: String = chars[start..at].iter().collect();
+                match name.as_str() {
+                    "AND" | "OR" | "XOR" | "NOT" | "MOD" => Lexeme::Operator(name),
+                    _ => Lexeme::Name(name),
+                }
+            }
+        };
+        lexemes.push(lexeme);
+    }
+    Ok(lexemes)
+}
+
+/// A parsed part of an algebraic, and whether it is a number as written,
+/// which a minus sign before it negates.
+type Part = (Vec<Object>, bool);
+
+impl Compiler<'_> {
+    /// `text` between quotes: a name or number as itself, anything else
+    /// as an algebraic.
+    fn algebraic(&self, text: &str, context: Context) -> Result<Object, String> {
+        let mut items = self.parse(text)?;
+        match items.as_slice() {
+            [Object::Real(real)] => Ok(self.number(*real, context)),
+            [Object::Global(_) | Object::Local(_)] => Ok(items.pop().unwrap()),
+            [Object::Unit(_)] => self.stored(items.pop().unwrap()),
+            _ => self.stored(Object::Algebraic(items)),
+        }
+    }
+
+    /// An algebraic's items, as stored.
+    fn expression(&self, text: &str) -> Result<Vec<Object>, String> {
+        self.parse(text)?
+            .into_iter()
+            .map(|item| self.stored(item))
+            .collect()
+    }
+
+    /// An algebraic's items in postfix order, numbers and units as parsed.
+    fn parse(&self, text: &str) -> Result<Vec<Object>, String> {
+        let mut expression = Expression {
+            lexemes: lexemes(text)?,
+            at: 0,
+        };
+        if expression.lexemes.is_empty() {
+            return Err("empty algebraic".to_string());
+        }
+        let items = self.equation(&mut expression)?;
+        if expression.at < expression.lexemes.len() {
+            return Err(format!("bad algebraic '{text}'"));
+        }
+        Ok(items)
+    }
+
+    /// A parsed item as stored: ROM constants for numbers, units compiled.
+    fn stored(&self, item: Object) -> Result<Object, String> {
+        Ok(match item {
+            Object::Real(real) => self.constant(real),
+            Object::Unit(parts) => match parts.as_slice() {
+                [Object::Real(real), Object::String(unit)] => {
+                    let mut items = vec![self.constant(*real)];
+                    items.extend(self.unit(unit)?);
+                    items.push(Object::UnitOp('_'));
+                    Object::Unit(items)
+                }
+                _ => Object::Unit(parts),
+            },
+            Object::Algebraic(items) => Object::Algebraic(
+                items
+                    .into_iter()
+                    .map(|item| self.stored(item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            item => item,
+        })
+    }
+
+    /// The command `name` as an operator or function of an algebraic.
+    fn function(&self, name: &str) -> Result<Object, String> {
+        let &(first, last) = self
+            .commands
+            .get(name)
+            .ok_or_else(|| format!("unknown function {name}"))?;
+        self.command(if name == "XROOT" { last } else { first })
+    }
+
+    /// `left op right`, stored.
+    fn binary_op(
+        &self,
+        mut left: Vec<Object>,
+        right: Vec<Object>,
+        op: &str,
+    ) -> Result<Part, String> {
+        left.extend(right);
+        left.push(self.function(op)?);
+        Ok((left, false))
+    }
+
+    /// Operands joined left to right by the operators `ops`, each parsed
+    /// by `operand`.
+    fn chain(
+        &self,
+        e: &mut Expression,
+        ops: &[&str],
+        operand: fn(&Self, &mut Expression) -> Result<Part, String>,
+    ) -> Result<Part, String> {
+        let mut part = operand(self, e)?;
+        while let Some(op) = e.operator(ops) {
+            let right = operand(self, e)?;
+            part = self.binary_op(part.0, right.0, &op)?;
+        }
+        Ok(part)
+    }
+
+    fn equation(&self, e: &mut Expression) -> Result<Vec<Object>, String> {
+        Ok(self.chain(e, &["="], Self::disjunction)?.0)
+    }
+
+    fn disjunction(&self, e: &mut Expression) -> Result<Part, String> {
+        self.chain(e, &["OR", "XOR"], Self::conjunction)
+    }
+
+    fn conjunction(&self, e: &mut Expression) -> Result<Part, String> {
+        self.chain(e, &["AND"], Self::negation)
+    }
+
+    fn negation(&self, e: &mut Expression) -> Result<Part, String> {
+        if e.operator(&["NOT"]).is_none() {
+            return self.comparison(e);
+        }
+        let (mut items, _) = self.negation(e)?;
+        items.push(self.function("NOT")?);
+        Ok((items, false))
+    }
+
+    fn comparison(&self, e: &mut Expression) -> Result<Part, String> {
+        self.chain(e, &["==", "≠", "<", ">", "≤", "≥"], Self::sum)
+    }
+
+    fn sum(&self, e: &mut Expression) -> Result<Part, String> {
+        self.chain(e, &["+", "-"], Self::product)
+    }
+
+    fn product(&self, e: &mut Expression) -> Result<Part, String> {
+        self.chain(e, &["*", "/", "MOD"], Self::minus)
+    }
+
+    fn minus(&self, e: &mut Expression) -> Result<Part, String> {
+        self.signed(e, Self::power)
+    }
+
+    /// `operand`, or minus it; a number as written becomes a negative one.
+    fn signed(
+        &self,
+        e: &mut Expression,
+        operand: fn(&Self, &mut Expression) -> Result<Part, String>,
+    ) -> Result<Part, String> {
+        if e.operator(&["-"]).is_none() {
+            return operand(self, e);
+        }
+        let (mut items, number) = self.signed(e, operand)?;
+        if let (true, [Object::Real(real)]) = (number, items.as_mut_slice()) {
+            real.negative = !real.negative && real.mantissa != 0;
+            return Ok((items, true));
+        }
+        items.push(self.function("NEG")?);
+        Ok((items, false))
+    }
+
+    /// Powers group to the left: A^B^C is (A^B)^C.
+    fn power(&self, e: &mut Expression) -> Result<Part, String> {
+        let mut part = self.factorial(e)?;
+        while e.operator(&["^"]).is_some() {
+            let right = self.signed(e, Self::factorial)?;
+            part = self.binary_op(part.0, right.0, "^")?;
+        }
+        Ok(part)
+    }
+
+    fn factorial(&self, e: &mut Expression) -> Result<Part, String> {
+        let mut part = self.primary(e)?;
+        while e.operator(&["!"]).is_some() {
+            part.0.push(self.function("!")?);
+            part.1 = false;
+        }
+        Ok(part)
+    }
+
+    fn primary(&self, e: &mut Expression) -> Result<Part, String> {
+        let lexeme = e.peek().cloned().ok_or("missing operand in algebraic")?;
+        e.at += 1;
+        match lexeme {
+            Lexeme::Number(real) => Ok((vec![Object::Real(real)], true)),
+            Lexeme::Unit(unit) => Ok((vec![unit], false)),
+            Lexeme::Open => {
+                let items = self.equation(e)?;
+                match e.peek() {
+                    Some(Lexeme::Close) => e.at += 1,
+                    _ => return Err("missing ) in algebraic".to_string()),
+                }
+                Ok((items, false))
+            }
+            Lexeme::Operator(op) if op == "√" => {
+                let (mut items, _) = self.factorial(e)?;
+                items.push(self.function("√")?);
+                Ok((items, false))
+            }
+            Lexeme::Name(name) if e.peek() == Some(&Lexeme::Open) => {
+                e.at += 1;
+                let mut args = vec![self.equation(e)?];
+                loop {
+                    match e.peek() {
+                        Some(Lexeme::Comma) => {
+                            e.at += 1;
+                            args.push(self.equation(e)?);
+                        }
+                        Some(Lexeme::Close) => {
+                            e.at += 1;
+                            break;
+                        }
+                        _ => return Err(format!("missing ) after {name}(")),
+                    }
+                }
+                self.call(&name, args)
+            }
+            Lexeme::Name(name) => match self.commands.get(&name) {
+                Some(_) if arity(&name) == 0 => Ok((vec![self.function(&name)?], false)),
+                Some(_) => Err(format!("{name} needs arguments")),
+                None if valid_name(&name) => Ok((vec![self.name(&name)], false)),
+                None => Err(format!("unknown command {name}")),
+            },
+            _ => Err("bad algebraic".to_string()),
+        }
+    }
+
+    /// `name(args)`: a built-in function, or a user function.
+    fn call(&self, name: &str, args: Vec<Vec<Object>>) -> Result<Part, String> {
+        let count = args.len() as u32;
+        if ["∂", "∫", "|"].contains(&name) {
+            return Err(format!("{name} is not supported in algebraics"));
+        }
+        if self.commands.contains_key(name) {
+            if arity(name) != args.len() {
+                return Err(format!("{name} takes {} arguments", arity(name)));
+            }
+            let mut items = Vec::new();
+            for (i, arg) in args.into_iter().enumerate() {
+                // IFTE's branches are algebraics of their own.
+                match name == "IFTE" && i > 0 {
+                    true => items.push(Object::Algebraic(arg)),
+                    false => items.extend(arg),
+                }
+            }
+            items.push(self.function(name)?);
+            return Ok((items, false));
+        }
+        if !valid_name(name) {
+            return Err(format!("unknown function {name}"));
+        }
+        let bint = self
+            .bints
+            .filter(|_| count <= MAX_ARGUMENTS)
+            .ok_or_else(|| format!("too many arguments to {name}"))?;
+        let (library, command) = FCNAPPLY;
+        let apply = self
+            .emu
+            .command_address(library, command)
+            .ok_or("the ROM has no FCNAPPLY")?;
+        let mut items: Vec<Object> = args.into_iter().flatten().collect();
+        items.push(Object::Algebraic(vec![self.name(name)]));
+        items.push(Object::Pointer(bint + 10 * count));
+        items.push(Object::Pointer(apply));
+        Ok((items, false))
+    }
+}
+
+impl Emulator {
+    /// UserRPL `source` compiled to one object, as the calculator's
+    /// parser would store it, one nibble per byte. Commands are those of
+    /// the loaded ROM; binary integers without a base letter take the
+    /// current base.
+    pub fn compile(&self, source: &str) -> Result<Vec<u8>, String> {
+        let mut compiler = Compiler::new(self, source)?;
+        if compiler.tokens.is_empty() {
+            return Err("nothing to compile".to_string());
+        }
+        let object = compiler.object()?;
+        if compiler.at < compiler.tokens.len() {
+            return Err("more than one object: put them in a program or list".to_string());
+        }
+        object.to_nibbles()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, press, ENTER};
+
+    /// PRG, then TYPE and OBJ→ in its menu.
+    const OBJ_FROM: [u32; 3] = [0x74, 0x83, 0x14];
+    const DROP: u32 = 0x40;
+
+    /// `source` compiled by the firmware, OBJ→ on it as a string, or
+    /// `None` if it would not compile. Leaves the stack empty.
+    fn firmware(emu: &mut Emulator, source: &str) -> Option<Vec<u8>> {
+        let text = Object::String(source.to_string());
+        emu.push(&text).unwrap();
+        emu.run_headless(1.0, |emu| emu.is_idle());
+        press(emu, &OBJ_FROM);
+        emu.run_headless(5.0, |emu| emu.is_idle());
+        let level1 = *emu.stack_addresses().unwrap().first()?;
+        let size = object_size(emu, level1).unwrap();
+        let object: Vec<u8> = (level1..level1 + size)
+            .map(|at| emu.peek_nibble(at as i32))
+            .collect();
+        press(emu, &[DROP]);
+        emu.run_headless(1.0, |emu| emu.is_idle());
+        assert_eq!(emu.stack(), Ok(Vec::new()), "{source}");
+        Some(object).filter(|object| *object != text.to_nibbles().unwrap())
+    }
+
+    #[test]
+    fn test_parse_real() {
+        let real = |text| parse_real(text).unwrap().map(|r| r.to_string());
+        assert_eq!(real("1.5"), Some("1.5".to_string()));
+        assert_eq!(real("-.25E-3"), Some("-.00025".to_string()));
+        assert_eq!(real("1.E2"), Some("100".to_string()));
+        assert_eq!(real("-0"), Some("0".to_string()));
+        assert_eq!(real("000123.4500"), Some("123.45".to_string()));
+        assert_eq!(real("1.234567890125"), Some("1.23456789013".to_string()));
+        assert_eq!(real("9.999999999995"), Some("10".to_string()));
+        assert_eq!(
+            real("9.99999999999E499"),
+            Some("9.99999999999E499".to_string())
+        );
+        assert_eq!(real("1E-499"), Some("1.E-499".to_string()));
+        assert!(parse_real("1E500").is_err());
+        assert!(parse_real("1E-500").is_err());
+        for word in ["A", "-", ".", "E5", "1.5e2", "1-2", "1.2.3", "1E", "1E+5"] {
+            assert_eq!(real(word), None, "{word}");
+        }
+    }
+
+    #[test]
+    fn test_matches_firmware() {
+        let mut emu = boot_gx();
+        let sources = [
+            // Numbers and the ROM's constants.
+            "5",
+            "« 0 1 9 -1 -9 -0 .000 10 2.5 -.1 1E5 1.234567890125 9.999999999995 »",
+            "{ 1.5 -3 7 }",
+            "(1,2)",
+            "« (1,2) (-3 4) »",
+            "[ 1 2 3 ]",
+            "[[ 1 -2 ][ 3 4 ]]",
+            "[ (1,2) (3,4) ]",
+            "« #5 #5d # 17o #101b #FFFFh »",
+            "\"hello\"",
+            "« \"\" \"a b\" \"«x»\" »",
+            // Names, commands and XLIB names.
+            "'A'",
+            "« A 'A' 'A+1' '2' '2.5' 'π' »",
+            "{ A 'A' 'A+1' SIN + }",
+            "« DUP2 + XROOT HEAD HALT PROMPT »",
+            ":T:5",
+            ":A B:« 1 »",
+            "« :T:« 1 » 2 »",
+            // Comments and delimiters without spaces.
+            "« 1 @ one @ 2 @ to the end\n 3 »",
+            "«1{2 3}[4]'A'\"s\"(1,2)5»",
+            // Programs and structures.
+            "« »",
+            "« « » « 1 » { « 2 » } »",
+            "« IF A THEN 1 ELSE 2 END »",
+            "« IF A THEN « 1 » END IF B THEN 'X' ELSE IF C THEN 3 END END »",
+            "« IFERR 1 THEN 2 ELSE 3 END IFERR IF A THEN 1 END THEN 'A' ELSE « » END »",
+            "« CASE END CASE A THEN 1 END B THEN IF C THEN 2 END END 3 END »",
+            "« DO 1 UNTIL 2 END DO 'A' UNTIL « » END »",
+            "« WHILE A REPEAT 1 END WHILE « » REPEAT 'A' END »",
+            "« IF A THEN 1 2 ELSE END IFERR STO DUP THEN DEPTH DROPN END »",
+            "« CASE A THEN B C END END WHILE A REPEAT B C END »",
+            "« 1 5 START 2 NEXT 1 5 START « 1 » 2 STEP »",
+            "« 1 10 FOR I I 'I' 'I+J' « I » NEXT I »",
+            "« → A B « A B + 'A*B' « A » » A »",
+            "« → X 'X^2+1' »",
+            "« IF A THEN → X « X » END »",
+            // Algebraics.
+            "'A+B*C-D/E'",
+            "'A-(B+C)'",
+            "'(A+B)*C'",
+            "'A^B^C'",
+            "'A^-B'",
+            "'-A*B'",
+            "'-2^2'",
+            "'2*-3'",
+            "'-2.5+A'",
+            "'A=B+1'",
+            "'A==B AND NOT C OR D'",
+            "'NOT A AND B'",
+            "'A≠B XOR A≤B'",
+            "'A MOD B*C'",
+            "'SIN(X)^2+COS(X)^2'",
+            "'MAX(A,B)+XROOT(3,A)'",
+            "'√X+X!+ABS(-X)'",
+            "'IFTE(A>0,1,B)'",
+            "'F(X,2)+G(1)'",
+            "'π*e+i'",
+            "'2*3_m'",
+            "'1.5E-3*X'",
+            // Units.
+            "3_m/s^2",
+            "1_m*s",
+            "1_(m/s)",
+            "« 1_cm 2_km^2 5_kg 1_MeV 1_min 1_mmHg 1_xyz 2.5_°C »",
+            "{ 1_Kcal 1_ha 1_ml 1_s^-1 }",
+            "{ 1_m/s/kg 1_m/s*kg 1_(m*s)^2 1_m^-2/s 1_(m/s)^-2 1_m^2^3 1_m^.5 1_m^1 }",
+            "{ 1_kg*m/s^2 1_m*s/kg*A 1_m/(s/kg) 1_m*(s*kg) }",
+            // What the parser splits, or closes at the end.
+            "« 1 1A 1.5e2 »",
+            "{ IF A THEN 1 END 1 5 FOR I I NEXT → A « A » }",
+            "« CASE",
+            "« IF A THEN 1 ELSE { 2",
+            "'A+1",
+            "(1,2",
+            "\"abc",
+        ];
+        for source in sources {
+            let theirs = firmware(&mut emu, source).unwrap_or_else(|| panic!("{source}"));
+            assert_eq!(emu.compile(source), Ok(theirs), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut emu = boot_gx();
+        let sources = [
+            "« 1 A+B »",
+            "« A:B »",
+            "« 1E500 »",
+            "{ 1 5 START 1 }",
+            "« IF A THEN 1 »",
+            "« 1 END »",
+            "« IF A",
+            "« 1 5 FOR I",
+            ":T",
+            "#1fh",
+            "[[ 1 2 ][ 3",
+            "'A+'",
+            "'SIN'",
+            "'2(3)'",
+            "« → « 1 » »",
+        ];
+        for source in sources {
+            assert_eq!(firmware(&mut emu, source), None, "{source}");
+            assert!(emu.compile(source).is_err(), "{source}");
+        }
+        assert!(emu.compile("").is_err());
+        assert!(emu.compile("1 2").is_err());
+        assert_eq!(
+            emu.compile("SIN"),
+            Err("SIN is a command, not an object".to_string())
+        );
+        assert_eq!(
+            emu.compile("'∫(0,1,X,X)'"),
+            Err("∫ is not supported in algebraics".to_string())
+        );
+    }
+
+    #[test]
+    fn test_current_base() {
+        let mut emu = boot_gx();
+        assert_eq!(emu.display_modes().base, 10);
+        let sixteen = emu.compile("#16").unwrap();
+        assert_eq!(firmware(&mut emu, "#16"), Some(sixteen.clone()));
+        emu.type_text("HEX").unwrap();
+        press(&mut emu, &[ENTER]);
+        emu.run_headless(5.0, |emu| emu.is_idle());
+        assert_eq!(emu.compile("#10"), Ok(sixteen.clone()));
+        assert_eq!(emu.compile("#16d"), Ok(sixteen.clone()));
+        assert_eq!(firmware(&mut emu, "#10"), Some(sixteen));
+    }
+}
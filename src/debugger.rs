@@ -0,0 +1,1008 @@
+// Debugger — PC breakpoints (optionally conditional on register values),
+// single-step, step-over / step-out by return-stack depth, run-until-return.
+//
+// Takes the place of the x48 debugger (debugger.h, stubbed out in the web
+// build): `run_frame` checks the debugger before each instruction while it
+// is armed, and halts with a `StopReason` on a hit. Illegal opcodes and
+// TRAPs halt only while it is armed or the host has asked for it with
+// `set_stop_on_illegal`; otherwise the machine runs through them as x48
+// does, since the firmware's own code hits them: a GOTO to the instruction
+// five nibbles on assembles as 6400x, which decodes as a TRAP. When no
+// breakpoint is set and no step is pending the hot loop pays a single bool
+// test.
+//
+// Watchpoints hook `Emulator::read_nibble`, `write_nibble` and
+// `read_nibble_crc`, which is also the only way into `Memory::read_dev_mem`
+// and `write_dev_mem`, so MMIO registers are watched like any other address.
+// Instruction fetches are reads too. A halting watchpoint lets the
+// instruction finish and stops before the next one.
+//
+// Depth tracking uses `rstkp`. The Saturn return stack holds 8 levels and
+// drops the oldest on overflow, so step-out from deeper than that stops at
+// the first return that brings the pointer below its starting value.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::alu::{get_end, get_start, RegId};
+use crate::cpu::Saturn;
+use crate::disasm::Flow;
+use crate::emulator::Emulator;
+use crate::types::*;
+
+/// Why execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Breakpoint `id` hit at `pc`.
+    Breakpoint { id: u32, pc: i32 },
+    /// Watchpoint `id` hit by the instruction at `pc` accessing `addr`.
+    Watchpoint { id: u32, pc: i32, addr: i32 },
+    /// A step, step-over, step-out or run-until-return finished.
+    Step { pc: i32 },
+    /// `halt()` was called.
+    Halt { pc: i32 },
+    /// Unknown opcode at `pc`.
+    Illegal { pc: i32 },
+    /// TRAP (6400x, x != 0) executed at `pc`.
+    Trap { pc: i32 },
+}
+
+impl StopReason {
+    pub fn pc(&self) -> i32 {
+        match *self {
+            StopReason::Breakpoint { pc, .. }
+            | StopReason::Watchpoint { pc, .. }
+            | StopReason::Step { pc }
+            | StopReason::Halt { pc }
+            | StopReason::Illegal { pc }
+            | StopReason::Trap { pc } => pc,
+        }
+    }
+
+    /// Short name for frontends: breakpoint, watchpoint, step, halt,
+    /// illegal, trap.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StopReason::Breakpoint { .. } => "breakpoint",
+            StopReason::Watchpoint { .. } => "watchpoint",
+            StopReason::Step { .. } => "step",
+            StopReason::Halt { .. } => "halt",
+            StopReason::Illegal { .. } => "illegal",
+            StopReason::Trap { .. } => "trap",
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint { id, pc } => write!(f, "breakpoint {id} at {pc:05X}"),
+            StopReason::Watchpoint { id, pc, addr } => {
+                write!(f, "watchpoint {id} at {pc:05X} ({addr:05X})")
+            }
+            other => write!(f, "{} at {:05X}", other.kind(), other.pc()),
+        }
+    }
+}
+
+/// What a breakpoint condition looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A-D or R0-R4, restricted to a field (types.rs field code).
+    Reg(RegId, u8),
+    D0,
+    D1,
+    P,
+    Carry,
+    /// Program status bits ST0-ST15 as one 16-bit value.
+    St,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A register test such as `C.A=#80000` or `P>=8`.
+///
+/// Syntax: `REG[.FIELD] OP VALUE` where REG is A-D, R0-R4, D0, D1, P,
+/// CARRY or ST; FIELD is a SASM field name (default A); OP is one of
+/// `= == # != < <= > >=`; VALUE is decimal or `#`-prefixed hex, or with
+/// `Emulator::condition` the name of an entry point such as DSKTOP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub cmp: Cmp,
+    pub value: u64,
+}
+
+impl Condition {
+    pub fn eval(&self, saturn: &Saturn) -> bool {
+        let v = match self.operand {
+            Operand::Reg(id, field) => {
+                let reg = saturn.get_reg(id);
+                let (s, e) = (get_start(field, saturn.p), get_end(field, saturn.p));
+                (s..=e).rev().fold(0u64, |v, i| (v << 4) | reg[i] as u64)
+            }
+            Operand::D0 => saturn.d0 as u64,
+            Operand::D1 => saturn.d1 as u64,
+            Operand::P => saturn.p as u64,
+            Operand::Carry => saturn.carry as u64,
+            Operand::St => (0..NR_PSTAT)
+                .rev()
+                .fold(0u64, |v, i| (v << 1) | (saturn.pstat[i] & 1) as u64),
+        };
+        match self.cmp {
+            Cmp::Eq => v == self.value,
+            Cmp::Ne => v != self.value,
+            Cmp::Lt => v < self.value,
+            Cmp::Le => v <= self.value,
+            Cmp::Gt => v > self.value,
+            Cmp::Ge => v >= self.value,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Condition::parse_with(s, |_| None)
+    }
+}
+
+impl Condition {
+    /// Parse `s`, looking up a VALUE that is not a number with `symbol`.
+    pub fn parse_with(s: &str, symbol: impl Fn(&str) -> Option<u32>) -> Result<Self, String> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let s = s.to_ascii_uppercase();
+        let at = s
+            .find(['=', '#', '!', '<', '>'])
+            .ok_or_else(|| format!("no comparison in '{s}'"))?;
+        let (lhs, rest) = s.split_at(at);
+        let ops = [
+            ("==", Cmp::Eq),
+            ("!=", Cmp::Ne),
+            ("<=", Cmp::Le),
+            (">=", Cmp::Ge),
+            ("=", Cmp::Eq),
+            ("#", Cmp::Ne),
+            ("<", Cmp::Lt),
+            (">", Cmp::Gt),
+        ];
+        let (value, cmp) = ops
+            .iter()
+            .find_map(|(op, cmp)| rest.strip_prefix(op).map(|v| (v, *cmp)))
+            .ok_or_else(|| format!("bad comparison in '{s}'"))?;
+        let value = match value.strip_prefix('#') {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok().or_else(|| symbol(value).map(u64::from)),
+        }
+        .ok_or_else(|| format!("bad value '{value}'"))?;
+
+        let (reg, field) = match lhs.split_once('.') {
+            Some((r, f)) => (r, Some(f)),
+            None => (lhs, None),
+        };
+        let field_code = match field.unwrap_or("A") {
+            "P" => P_FIELD,
+            "WP" => WP_FIELD,
+            "XS" => XS_FIELD,
+            "X" => X_FIELD,
+            "S" => S_FIELD,
+            "M" => M_FIELD,
+            "B" => B_FIELD,
+            "W" => W_FIELD,
+            "A" => A_FIELD,
+            f => return Err(format!("bad field '{f}'")),
+        };
+        let operand = match reg {
+            "A" => Operand::Reg(RegId::A, field_code),
+            "B" => Operand::Reg(RegId::B, field_code),
+            "C" => Operand::Reg(RegId::C, field_code),
+            "D" => Operand::Reg(RegId::D, field_code),
+            "R0" => Operand::Reg(RegId::R0, field_code),
+            "R1" => Operand::Reg(RegId::R1, field_code),
+            "R2" => Operand::Reg(RegId::R2, field_code),
+            "R3" => Operand::Reg(RegId::R3, field_code),
+            "R4" => Operand::Reg(RegId::R4, field_code),
+            "D0" => Operand::D0,
+            "D1" => Operand::D1,
+            "P" => Operand::P,
+            "CARRY" | "CY" => Operand::Carry,
+            "ST" => Operand::St,
+            r => return Err(format!("bad register '{r}'")),
+        };
+        if field.is_some() && !matches!(operand, Operand::Reg(..)) {
+            return Err(format!("{reg} takes no field"));
+        }
+        Ok(Condition {
+            operand,
+            cmp,
+            value,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: u32,
+    /// Stop at this PC; `None` checks the condition before every instruction.
+    pub addr: Option<i32>,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+    pub hits: u32,
+}
+
+/// Which accesses a watchpoint fires on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// How a watched nibble was accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    /// Read through `read_nibble_crc` (feeds the CRC register).
+    CrcRead,
+    Write,
+}
+
+impl Access {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::CrcRead => "crc read",
+            Access::Write => "write",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub id: u32,
+    /// Watched range `start..end`, 20-bit addresses.
+    pub start: i32,
+    pub end: i32,
+    pub watch: Watch,
+    /// Stop execution after the accessing instruction.
+    pub halt: bool,
+    pub enabled: bool,
+    pub hits: u32,
+}
+
+impl Watchpoint {
+    fn matches(&self, addr: i32, access: Access) -> bool {
+        self.enabled
+            && addr >= self.start
+            && addr < self.end
+            && match self.watch {
+                Watch::Read => access != Access::Write,
+                Watch::Write => access == Access::Write,
+                Watch::ReadWrite => true,
+            }
+    }
+}
+
+/// One recorded watchpoint hit. For reads `old` and `new` are both the
+/// value read; for writes `new` is the value written (ROM writes included).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub id: u32,
+    /// Address of the instruction that made the access.
+    pub pc: i32,
+    pub addr: i32,
+    pub access: Access,
+    pub old: u8,
+    pub new: u8,
+    /// The access went to a memory-mapped I/O register.
+    pub mmio: bool,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:05X}  {:<8} {:05X}{}  ",
+            self.pc,
+            self.access.name(),
+            self.addr,
+            if self.mmio { " (io)" } else { "" }
+        )?;
+        match self.access {
+            Access::Write => write!(f, "{:X} -> {:X}  #{}", self.old, self.new, self.id),
+            _ => write!(f, "{:X}  #{}", self.new, self.id),
+        }
+    }
+}
+
+/// Watchpoint hits kept for `watch_hits`; older ones are dropped.
+pub const WATCH_LOG_LEN: usize = 1024;
+
+/// Pending step-over / step-out / run-until-return.
+#[derive(Debug, Clone, Copy)]
+enum RunTo {
+    /// Back at `addr` with the return stack no deeper than `depth`.
+    Over { addr: i32, depth: i16 },
+    /// Return stack shallower than `depth`.
+    Out { depth: i16 },
+    /// About to execute a return at `depth`.
+    Return { depth: i16 },
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    watch_log: VecDeque<WatchHit>,
+    /// Set by a halting watchpoint, reported before the next instruction.
+    watch_stop: Option<StopReason>,
+    /// PC of the instruction being executed, for watch hits.
+    pub(crate) insn_pc: i32,
+    next_id: u32,
+    run_to: Option<RunTo>,
+    halted: Option<StopReason>,
+    /// Don't stop before the first instruction after a resume.
+    skip_once: bool,
+    /// Any breakpoint or pending step — checked per instruction.
+    pub(crate) armed: bool,
+    /// Any watchpoint — checked per memory access.
+    pub(crate) watching: bool,
+    /// Halt on illegal opcodes and TRAPs while disarmed too.
+    pub(crate) stop_on_illegal: bool,
+}
+
+impl Debugger {
+    fn rearm(&mut self) {
+        self.armed =
+            !self.breakpoints.is_empty() || self.run_to.is_some() || self.watch_stop.is_some();
+        self.watching = !self.watchpoints.is_empty();
+    }
+}
+
+impl Emulator {
+    /// Parse a breakpoint condition, taking the ROM revision's entry points
+    /// by name as values: `D1=DSKTOP`.
+    pub fn condition(&self, text: &str) -> Result<Condition, String> {
+        let points = self.entry_points();
+        Condition::parse_with(text, |name| points.symbol(name))
+    }
+
+    /// Add a breakpoint at `addr`, conditional on `condition` if given. With
+    /// no address the condition is tested before every instruction.
+    pub fn add_breakpoint(
+        &mut self,
+        addr: Option<i32>,
+        condition: Option<Condition>,
+    ) -> Result<u32, String> {
+        if addr.is_none() && condition.is_none() {
+            return Err("breakpoint needs an address or a condition".to_string());
+        }
+        let dbg = &mut self.debugger;
+        dbg.next_id += 1;
+        dbg.breakpoints.push(Breakpoint {
+            id: dbg.next_id,
+            addr: addr.map(|a| a & 0xfffff),
+            condition,
+            enabled: true,
+            hits: 0,
+        });
+        dbg.rearm();
+        Ok(dbg.next_id)
+    }
+
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        let dbg = &mut self.debugger;
+        let before = dbg.breakpoints.len();
+        dbg.breakpoints.retain(|b| b.id != id);
+        dbg.rearm();
+        dbg.breakpoints.len() != before
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.debugger.breakpoints.iter_mut().find(|b| b.id == id) {
+            Some(bp) => {
+                bp.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.breakpoints.clear();
+        self.debugger.rearm();
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.debugger.breakpoints
+    }
+
+    /// Watch `len` nibbles from `start` for `watch` accesses. Hits are
+    /// logged; with `halt` they also stop execution.
+    pub fn add_watchpoint(
+        &mut self,
+        start: i32,
+        len: i32,
+        watch: Watch,
+        halt: bool,
+    ) -> Result<u32, String> {
+        if len < 1 || start < 0 || start + len > 0x100000 {
+            return Err(format!("bad watch range {start:05X}+{len:X}"));
+        }
+        let dbg = &mut self.debugger;
+        dbg.next_id += 1;
+        dbg.watchpoints.push(Watchpoint {
+            id: dbg.next_id,
+            start,
+            end: start + len,
+            watch,
+            halt,
+            enabled: true,
+            hits: 0,
+        });
+        dbg.rearm();
+        Ok(dbg.next_id)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: u32) -> bool {
+        let dbg = &mut self.debugger;
+        let before = dbg.watchpoints.len();
+        dbg.watchpoints.retain(|w| w.id != id);
+        dbg.rearm();
+        dbg.watchpoints.len() != before
+    }
+
+    pub fn set_watchpoint_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.debugger.watchpoints.iter_mut().find(|w| w.id == id) {
+            Some(wp) => {
+                wp.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.debugger.watchpoints.clear();
+        self.debugger.rearm();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.debugger.watchpoints
+    }
+
+    /// Recorded hits, oldest first (at most `WATCH_LOG_LEN`).
+    pub fn watch_hits(&self) -> impl Iterator<Item = &WatchHit> {
+        self.debugger.watch_log.iter()
+    }
+
+    /// Take the recorded hits, leaving the log empty.
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        self.debugger.watch_log.drain(..).collect()
+    }
+
+    /// Value a write to `addr` would replace, read without side effects.
+    pub(crate) fn watch_old(&self, addr: i32) -> u8 {
+        let a = addr & 0xfffff;
+        if self.mmio_mapped(a) {
+            self.mem.peek_dev_mem(&self.saturn, a)
+        } else {
+            self.peek_nibble(a)
+        }
+    }
+
+    /// Called on every memory access while a watchpoint is set.
+    pub(crate) fn watch_access(&mut self, addr: i32, access: Access, old: u8, new: u8) {
+        let addr = addr & 0xfffff;
+        let mmio = self.mmio_mapped(addr);
+        let dbg = &mut self.debugger;
+        let pc = dbg.insn_pc;
+        for wp in dbg.watchpoints.iter_mut() {
+            if !wp.matches(addr, access) {
+                continue;
+            }
+            wp.hits += 1;
+            if dbg.watch_log.len() == WATCH_LOG_LEN {
+                dbg.watch_log.pop_front();
+            }
+            dbg.watch_log.push_back(WatchHit {
+                id: wp.id,
+                pc,
+                addr,
+                access,
+                old,
+                new,
+                mmio,
+            });
+            if wp.halt && dbg.watch_stop.is_none() {
+                dbg.watch_stop = Some(StopReason::Watchpoint {
+                    id: wp.id,
+                    pc,
+                    addr,
+                });
+                dbg.armed = true;
+            }
+        }
+    }
+
+    /// Why execution is stopped, or `None` while running.
+    pub fn halted(&self) -> Option<StopReason> {
+        self.debugger.halted
+    }
+
+    /// Stop before the next instruction (user interrupt).
+    pub fn halt(&mut self) {
+        self.debugger.run_to = None;
+        self.debugger.rearm();
+        self.debugger.halted = Some(StopReason::Halt { pc: self.saturn.pc });
+    }
+
+    /// Halt on illegal opcodes and TRAPs even with no breakpoint set or
+    /// step pending. Off by default.
+    pub fn set_stop_on_illegal(&mut self, on: bool) {
+        self.debugger.stop_on_illegal = on;
+    }
+
+    /// Continue running; a breakpoint at the current PC is stepped over.
+    pub fn resume(&mut self) {
+        self.debugger.halted = None;
+        self.debugger.skip_once = true;
+        self.drop_watch_stop();
+    }
+
+    /// Forget a halting watch hit made while stopped (by a debugger front
+    /// end poking memory rather than by an instruction).
+    fn drop_watch_stop(&mut self) {
+        self.debugger.watch_stop = None;
+        self.debugger.rearm();
+    }
+
+    /// Execute one instruction and stay halted.
+    pub fn step_into(&mut self) -> StopReason {
+        self.drop_watch_stop();
+        self.record_clock();
+        let pc = self.saturn.pc;
+        let reason = if self.execute_instruction(self.now) {
+            self.illegal_stop(pc)
+        } else if let Some(reason) = self.debugger.watch_stop.take() {
+            reason
+        } else {
+            StopReason::Step { pc: self.saturn.pc }
+        };
+        self.debugger.run_to = None;
+        self.debugger.rearm();
+        self.debugger.halted = Some(reason);
+        reason
+    }
+
+    /// Step, running a GOSUB/GOSUBL/GOSBVL through to its return. Calls
+    /// complete in later frames; other instructions step immediately.
+    pub fn step_over(&mut self) -> Option<StopReason> {
+        let insn = self.disassemble(self.saturn.pc);
+        if insn.flow != Flow::Call {
+            return Some(self.step_into());
+        }
+        self.run_to(RunTo::Over {
+            addr: insn.next_addr(),
+            depth: self.saturn.rstkp,
+        });
+        None
+    }
+
+    /// Run until the current subroutine has returned to its caller.
+    pub fn step_out(&mut self) {
+        self.run_to(RunTo::Out {
+            depth: self.saturn.rstkp,
+        });
+    }
+
+    /// Run until the current subroutine is about to return, stopping on the
+    /// RTN-class instruction itself.
+    pub fn run_until_return(&mut self) {
+        self.run_to(RunTo::Return {
+            depth: self.saturn.rstkp,
+        });
+    }
+
+    fn run_to(&mut self, target: RunTo) {
+        self.debugger.run_to = Some(target);
+        self.debugger.rearm();
+        self.resume();
+    }
+
+    /// Checked before each instruction while the debugger is armed.
+    pub(crate) fn debug_check(&mut self) -> Option<StopReason> {
+        if let Some(reason) = self.debugger.watch_stop.take() {
+            self.debugger.rearm();
+            return Some(reason);
+        }
+        if std::mem::take(&mut self.debugger.skip_once) {
+            return None;
+        }
+        let pc = self.saturn.pc;
+        let depth = self.saturn.rstkp;
+        if let Some(run_to) = self.debugger.run_to {
+            let done = match run_to {
+                RunTo::Over { addr, depth: d } => pc == addr && depth <= d,
+                RunTo::Out { depth: d } => depth < d,
+                RunTo::Return { depth: d } => {
+                    depth == d && matches!(self.disassemble(pc).flow, Flow::Return | Flow::ReturnIf)
+                }
+            };
+            if done {
+                self.debugger.run_to = None;
+                self.debugger.rearm();
+                return Some(StopReason::Step { pc });
+            }
+        }
+        let saturn = &self.saturn;
+        let hit = self.debugger.breakpoints.iter_mut().find(|bp| {
+            bp.enabled
+                && bp.addr.is_none_or(|a| a == pc)
+                && bp.condition.as_ref().is_none_or(|c| c.eval(saturn))
+        })?;
+        hit.hits += 1;
+        Some(StopReason::Breakpoint { id: hit.id, pc })
+    }
+
+    /// Stop reason for an instruction `step_instruction` refused at `pc`.
+    pub(crate) fn illegal_stop(&self, pc: i32) -> StopReason {
+        if self.disassemble(pc).flow == Flow::Illegal && self.peek_nibble(pc) == 6 {
+            StopReason::Trap { pc }
+        } else {
+            StopReason::Illegal { pc }
+        }
+    }
+
+    /// Halt with `reason` and hand it back.
+    pub(crate) fn stop(&mut self, reason: StopReason) -> StopReason {
+        self.debugger.halted = Some(reason);
+        reason
+    }
+
+    /// Register summary for debugger views.
+    pub fn register_dump(&self) -> String {
+        let s = &self.saturn;
+        let hex = |r: &[u8; 16]| r.iter().rev().map(|n| format!("{n:X}")).collect::<String>();
+        let st: String = (0..NR_PSTAT)
+            .rev()
+            .map(|i| if s.pstat[i] != 0 { '1' } else { '0' })
+            .collect();
+        let rstk: Vec<String> = (0..=s.rstkp)
+            .rev()
+            .map(|i| format!("{:05X}", s.rstk[i as usize]))
+            .collect();
+        format!(
+            "PC={:05X}  P={:X}  CY={}  {}\n\
+             A={}  R0={}\n\
+             B={}  R1={}\n\
+             C={}  R2={}\n\
+             D={}  R3={}\n\
+             D0={:05X}  D1={:05X}      R4={}\n\
+             ST={}  XM={} SB={} SR={} MP={}\n\
+             RSTK: {}\n",
+            s.pc,
+            s.p,
+            s.carry,
+            if s.hexmode == HEX { "HEX" } else { "DEC" },
+            hex(&s.a),
+            hex(&s.r0),
+            hex(&s.b),
+            hex(&s.r1),
+            hex(&s.c),
+            hex(&s.r2),
+            hex(&s.d),
+            hex(&s.r3),
+            s.d0,
+            s.d1,
+            hex(&s.r4),
+            st,
+            s.xm,
+            s.sb,
+            s.sr,
+            s.mp,
+            rstk.join(" "),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROG: &str = "
+START   A=0     W
+        GOSUB   SUB
+AFTER   A=A+1   A
+END     GOTO    END
+SUB     A=A+1   A
+        GOSUB   INNER
+        RTN
+INNER   A=A+1   A
+        RTN
+";
+
+    /// Boot, load `src` at 0x90000, wake the CPU there and return the
+    /// address of each instruction.
+    fn load(src: &str) -> (Emulator, Vec<i32>) {
+        let mut emu = crate::test_support::boot_gx();
+        let base = 0x90000;
+        let code = crate::asm::assemble(src, base).unwrap();
+        for (i, &n) in code.iter().enumerate() {
+            emu.write_nibble(base + i as i32, n as i32);
+        }
+        let mut addrs = vec![base];
+        while *addrs.last().unwrap() < base + code.len() as i32 {
+            let next = emu.disassemble(*addrs.last().unwrap()).next_addr();
+            addrs.push(next);
+        }
+        emu.saturn.pc = base;
+        emu.is_shutdown = false;
+        (emu, addrs)
+    }
+
+    fn run(emu: &mut Emulator) -> StopReason {
+        assert!(emu.run_headless(1.0, |e| e.halted().is_some()));
+        emu.halted().unwrap()
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        let c: Condition = "C.A=#80000".parse().unwrap();
+        assert_eq!(c.operand, Operand::Reg(RegId::C, A_FIELD));
+        assert_eq!((c.cmp, c.value), (Cmp::Eq, 0x80000));
+        let c: Condition = "p >= 8".parse().unwrap();
+        assert_eq!((c.operand, c.cmp, c.value), (Operand::P, Cmp::Ge, 8));
+        let c: Condition = "R2.W#0".parse().unwrap();
+        assert_eq!(c.operand, Operand::Reg(RegId::R2, W_FIELD));
+        assert_eq!(c.cmp, Cmp::Ne);
+        assert!("A".parse::<Condition>().is_err());
+        assert!("E=1".parse::<Condition>().is_err());
+        assert!("A.Q=1".parse::<Condition>().is_err());
+        assert!("D0.A=1".parse::<Condition>().is_err());
+        assert!("A=#xyz".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_condition_symbols() {
+        let (mut emu, at) = load("        D1=(5)  #806F8\nEND     GOTO    END\n");
+        let condition = emu.condition("D1=dsktop").unwrap();
+        assert_eq!(condition.value, 0x806f8);
+        let id = emu.add_breakpoint(Some(at[1]), Some(condition)).unwrap();
+        assert_eq!(run(&mut emu), StopReason::Breakpoint { id, pc: at[1] });
+        assert!("D1=DSKTOP".parse::<Condition>().is_err());
+        assert!(emu.condition("D1=NOWHERE").is_err());
+    }
+
+    #[test]
+    fn test_pc_breakpoints() {
+        let (mut emu, at) = load(PROG);
+        let after = emu.add_breakpoint(Some(at[2]), None).unwrap();
+        let end = emu.add_breakpoint(Some(at[3]), None).unwrap();
+        assert_eq!(
+            run(&mut emu),
+            StopReason::Breakpoint {
+                id: after,
+                pc: at[2]
+            }
+        );
+        assert_eq!(emu.saturn.a[0], 2);
+
+        // Halted: frames run nothing until resumed.
+        assert_eq!(emu.run_frame(16.0, emu.now + 0.016), None);
+        assert_eq!(emu.saturn.pc, at[2]);
+
+        emu.resume();
+        assert_eq!(run(&mut emu), StopReason::Breakpoint { id: end, pc: at[3] });
+        assert_eq!(emu.saturn.a[0], 3);
+
+        // GOTO END loops onto its own breakpoint.
+        emu.resume();
+        assert_eq!(run(&mut emu), StopReason::Breakpoint { id: end, pc: at[3] });
+        assert_eq!(emu.breakpoints()[1].hits, 2);
+
+        assert!(emu.set_breakpoint_enabled(end, false));
+        assert!(emu.remove_breakpoint(after));
+        assert!(!emu.remove_breakpoint(after));
+        emu.resume();
+        assert!(!emu.run_headless(0.1, |e| e.halted().is_some()));
+        assert!(emu.add_breakpoint(None, None).is_err());
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let (mut emu, at) = load(PROG);
+        let never = emu
+            .add_breakpoint(Some(at[3]), Some("A=7".parse().unwrap()))
+            .unwrap();
+        let anywhere = emu
+            .add_breakpoint(None, Some("A.A=2".parse().unwrap()))
+            .unwrap();
+        // A reaches 2 after the A=A+1 in INNER, before its RTN.
+        assert_eq!(
+            run(&mut emu),
+            StopReason::Breakpoint {
+                id: anywhere,
+                pc: at[8]
+            }
+        );
+        emu.remove_breakpoint(anywhere);
+        emu.resume();
+        assert!(!emu.run_headless(0.1, |e| e.halted().is_some()));
+        assert_eq!(emu.saturn.a[0], 3);
+        assert_eq!(emu.breakpoints()[0].id, never);
+        assert_eq!(emu.breakpoints()[0].hits, 0);
+        emu.clear_breakpoints();
+        assert!(!emu.debugger.armed);
+    }
+
+    #[test]
+    fn test_step_into_and_over() {
+        let (mut emu, at) = load(PROG);
+        emu.halt();
+        assert_eq!(emu.halted(), Some(StopReason::Halt { pc: at[0] }));
+        assert_eq!(emu.step_into(), StopReason::Step { pc: at[1] });
+        // Stepping over the GOSUB finishes in a later frame.
+        assert_eq!(emu.step_over(), None);
+        assert_eq!(run(&mut emu), StopReason::Step { pc: at[2] });
+        assert_eq!(emu.saturn.a[0], 2);
+        // Anything else steps at once.
+        assert_eq!(emu.step_over(), Some(StopReason::Step { pc: at[3] }));
+
+        let (mut emu, at) = load(PROG);
+        let depth = emu.saturn.rstkp;
+        emu.step_into();
+        assert_eq!(emu.step_into(), StopReason::Step { pc: at[4] });
+        assert_eq!(emu.saturn.rstkp, depth + 1);
+        assert!(emu.register_dump().contains("PC=90"));
+    }
+
+    #[test]
+    fn test_step_out_and_run_until_return() {
+        let (mut emu, at) = load(PROG);
+        emu.step_into();
+        emu.step_into();
+        emu.run_until_return();
+        // Stops on SUB's own RTN, not INNER's one level deeper.
+        assert_eq!(run(&mut emu), StopReason::Step { pc: at[6] });
+        assert_eq!(emu.saturn.a[0], 2);
+
+        let (mut emu, at) = load(PROG);
+        let depth = emu.saturn.rstkp;
+        emu.step_into();
+        emu.step_into();
+        emu.step_out();
+        assert_eq!(run(&mut emu), StopReason::Step { pc: at[2] });
+        assert_eq!(emu.saturn.rstkp, depth);
+    }
+
+    const SKIP: &str = "
+        A=0     W
+        GOTO    OVER
+        NIBHEX  1
+OVER    LC(5)   #12345
+END     GOTO    END
+";
+
+    #[test]
+    fn test_illegal_and_trap_stop() {
+        // A GOTO five nibbles on is 64001, a TRAP: run through it unless
+        // asked to stop.
+        let (mut emu, at) = load(SKIP);
+        let goto: Vec<u8> = (0..5).map(|i| emu.peek_nibble(at[1] + i)).collect();
+        assert_eq!(goto, [6, 4, 0, 0, 1]);
+        assert!(!emu.run_headless(0.01, |e| e.halted().is_some()));
+        assert_eq!(emu.saturn.c[..5], [5, 4, 3, 2, 1]);
+
+        // Armed by a breakpoint elsewhere, or asked to by the host.
+        let (mut emu, at) = load(SKIP);
+        emu.add_breakpoint(Some(at[3]), None).unwrap();
+        assert_eq!(run(&mut emu), StopReason::Trap { pc: at[1] });
+        let (mut emu, at) = load(SKIP);
+        emu.set_stop_on_illegal(true);
+        // The stop reports the TRAP itself; the PC has moved past it.
+        assert_eq!(run(&mut emu), StopReason::Trap { pc: at[1] });
+        assert_eq!(emu.saturn.pc, at[2]);
+        assert_eq!(emu.halted().unwrap().to_string(), "trap at 90003");
+    }
+
+    const POKE: &str = "
+        D0=(5)  #90100
+        LC(2)   #5A
+        DAT0=C  B
+        A=DAT0  B
+        P=      0
+        D1=(5)  #0011E
+        DAT1=C  P
+END     GOTO    END
+";
+
+    #[test]
+    fn test_watchpoints_log() {
+        let (mut emu, at) = load(POKE);
+        let w = emu
+            .add_watchpoint(0x90100, 2, Watch::ReadWrite, false)
+            .unwrap();
+        let io = emu
+            .add_watchpoint(0x100, 0x40, Watch::Write, false)
+            .unwrap();
+        emu.run_headless(0.05, |_| false);
+        assert_eq!(emu.halted(), None);
+
+        let hits = emu.take_watch_hits();
+        let write = |addr, old, new| WatchHit {
+            id: w,
+            pc: at[2],
+            addr,
+            access: Access::Write,
+            old,
+            new,
+            mmio: false,
+        };
+        let read = |addr, val| WatchHit {
+            id: w,
+            pc: at[3],
+            addr,
+            access: Access::CrcRead,
+            old: val,
+            new: val,
+            mmio: false,
+        };
+        assert_eq!(
+            &hits[..4],
+            &[
+                write(0x90100, 0, 0xa),
+                write(0x90101, 0, 5),
+                read(0x90100, 0xa),
+                read(0x90101, 5)
+            ]
+        );
+        assert_eq!(hits[4].id, io);
+        assert_eq!((hits[4].pc, hits[4].addr), (at[6], 0x11e));
+        assert!(hits[4].mmio);
+        assert_eq!(hits[4].new, 0xa);
+        assert_eq!(emu.watchpoints()[0].hits, 4);
+        assert_eq!(emu.watch_hits().count(), 0);
+        assert_eq!(
+            hits[0].to_string(),
+            format!("{:05X}  write    90100  0 -> A  #{w}", at[2])
+        );
+    }
+
+    #[test]
+    fn test_watchpoint_halts() {
+        let (mut emu, at) = load(POKE);
+        let w = emu.add_watchpoint(0x90101, 1, Watch::Write, true).unwrap();
+        emu.add_watchpoint(0x90100, 1, Watch::Read, true).unwrap();
+        // The instruction finishes; the stop comes before the next one.
+        let stop = StopReason::Watchpoint {
+            id: w,
+            pc: at[2],
+            addr: 0x90101,
+        };
+        assert_eq!(run(&mut emu), stop);
+        assert_eq!(emu.saturn.pc, at[3]);
+        assert_eq!(stop.to_string(), "watchpoint 1 at 9000B (90101)");
+
+        // Stepping reports the watchpoint the step ran into.
+        assert!(matches!(
+            emu.step_into(),
+            StopReason::Watchpoint { pc, addr: 0x90100, .. } if pc == at[3]
+        ));
+        assert!(emu.remove_watchpoint(w));
+        emu.clear_watchpoints();
+        emu.resume();
+        assert!(!emu.run_headless(0.05, |e| e.halted().is_some()));
+        assert!(emu.add_watchpoint(0xfffff, 2, Watch::Read, false).is_err());
+    }
+}
@@ -0,0 +1,659 @@
+// UserRPL decompiler: objects in memory as the text ->STR makes of them, so
+// that programs on the calculator can be read and diffed on the host.
+//
+// Command names come from the libraries in the loaded ROM
+// (`Emulator::command_table`), so each ROM revision is read with its own
+// table of command pointers. Reals, complex numbers and binary integers
+// follow the display modes in the system flags, as ->STR does: STD, FIX,
+// SCI or ENG with 0-11 digits, and the binary base and wordsize. Only the
+// GX flags' address is known; SX objects decompile in STD and HEX, 64 bits.
+//
+// Layout is ->STR's, which never wraps long lines:
+//
+// - A program's « and », and the structure words, start a new line indented
+//   two spaces per open structure. « and the openers (IF, IFERR, CASE, DO,
+//   WHILE, FOR, START) open a level; THEN, ELSE, UNTIL and REPEAT go at
+//   their opener's indent, and END, NEXT, STEP and » close the level. In a
+//   CASE, each THEN opens a clause that its END closes. Everything else,
+//   including what follows a closing word, continues the line.
+// - Lists do not indent: a program in a top-level list starts at column 0.
+// - Arrays stand on lines of their own at column 0, a matrix a row a line.
+// - Algebraics are written infix with as few parentheses as the precedence
+//   rules allow; an argument on the right of an operator of the same
+//   precedence keeps its parentheses ('A-(B+C)'), a negation never does.
+// - Units are quoted everywhere but inside algebraics, strings everywhere
+//   but at the top level, where ->STR returns the string itself.
+
+use crate::emulator::Emulator;
+use crate::rpl::*;
+use crate::types::Model;
+
+/// GX RAM address of the system flags: flag -n is bit (n-1) % 4 of the
+/// nibble (n-1) / 4 on.
+pub const GX_SYSTEM_FLAGS: u32 = 0x80843;
+
+/// How reals are shown: the display mode and its digit count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealFormat {
+    Std,
+    Fix(u8),
+    Sci(u8),
+    Eng(u8),
+}
+
+/// The display modes objects decompile in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modes {
+    pub reals: RealFormat,
+    /// Binary integer base: 2, 8, 10 or 16.
+    pub base: u32,
+    /// Binary integer wordsize in bits, 1-64.
+    pub wordsize: u32,
+}
+
+impl Default for Modes {
+    /// The modes after a memory reset.
+    fn default() -> Modes {
+        Modes {
+            reals: RealFormat::Std,
+            base: 16,
+            wordsize: 64,
+        }
+    }
+}
+
+/// The 12-digit mantissa `m` times 10^`shift`, rounded half up.
+fn scaled(m: u64, shift: i32) -> u128 {
+    match shift {
+        0.. => m as u128 * 10u128.pow(shift as u32),
+        -12..=-1 => {
+            let div = 10u128.pow(-shift as u32);
+            (m as u128 + div / 2) / div
+        }
+        _ => 0,
+    }
+}
+
+/// The first `sig` digits of `r`'s mantissa, rounded, and the exponent,
+/// which grows when the rounding carries.
+fn significant(r: &Real, sig: u32) -> (u128, i32) {
+    let digits = scaled(r.mantissa, sig as i32 - r.digits as i32);
+    if digits == 10u128.pow(sig) {
+        (digits / 10, r.exponent + 1)
+    } else {
+        (digits, r.exponent)
+    }
+}
+
+/// Scientific (`eng` false) or engineering notation with `n` digits after
+/// the first.
+fn scientific(r: &Real, n: u8, eng: bool) -> String {
+    let sig = (n as u32 + 1).min(r.digits as u32);
+    let (digits, exp) = match r.mantissa {
+        0 => (0, 0),
+        _ => significant(r, sig),
+    };
+    let shift = if eng { exp.rem_euclid(3) } else { 0 };
+    let int = shift as usize + 1;
+    let mut text = format!("{digits:0width$}", width = sig as usize);
+    while text.len() < int {
+        text.push('0');
+    }
+    let sign = if r.negative { "-" } else { "" };
+    let (whole, frac) = text.split_at(int);
+    format!("{sign}{whole}.{frac}E{}", exp - shift)
+}
+
+/// `n` digits after the point, or SCI when the number has more than 12
+/// digits before it or rounds to zero.
+fn fixed(r: &Real, n: u8) -> String {
+    let n = n as i32;
+    if r.mantissa == 0 {
+        return format!("0.{}", "0".repeat(n as usize));
+    }
+    let int = (r.exponent + 1).max(1);
+    if int > 12 {
+        return scientific(r, n as u8, false);
+    }
+    let decimals = n.min(12 - int);
+    let value = scaled(r.mantissa, r.exponent - (r.digits as i32 - 1) + decimals);
+    let unit = 10u128.pow(decimals as u32);
+    if value == 0 || value / unit >= 10u128.pow(12) {
+        return scientific(r, n as u8, false);
+    }
+    let sign = if r.negative { "-" } else { "" };
+    match decimals {
+        0 => format!("{sign}{value}."),
+        _ => format!(
+            "{sign}{}.{:0width$}",
+            value / unit,
+            value % unit,
+            width = decimals as usize
+        ),
+    }
+}
+
+/// `r` as the calculator shows it in `format`.
+pub fn real_text(r: &Real, format: RealFormat) -> String {
+    if r.digits != 12 {
+        return r.to_string();
+    }
+    match format {
+        RealFormat::Std => r.to_string(),
+        RealFormat::Fix(n) => fixed(r, n),
+        RealFormat::Sci(n) => scientific(r, n, false),
+        RealFormat::Eng(n) => scientific(r, n, true),
+    }
+}
+
+/// A binary integer's nibbles, least significant first, as `# 18d`: cut
+/// to the wordsize and in the base.
+fn binary_text(nibbles: &[u8], modes: &Modes) -> String {
+    let value = nibbles
+        .iter()
+        .rev()
+        .fold(0u64, |v, &n| v << 4 | (n & 0xf) as u64);
+    let value = match modes.wordsize {
+        64.. => value,
+        bits => value & ((1 << bits) - 1),
+    };
+    match modes.base {
+        2 => format!("# {value:b}b"),
+        8 => format!("# {value:o}o"),
+        10 => format!("# {value}d"),
+        _ => format!("# {value:X}h"),
+    }
+}
+
+/// Precedence of an expression whose last operation is a negation.
+const NEGATION: u8 = 7;
+
+/// Precedence of a name, a number or a function call.
+const ATOM: u8 = 9;
+
+/// Precedence of an infix operator.
+fn infix(op: &str) -> Option<u8> {
+    Some(match op {
+        "=" => 1,
+        "OR" | "XOR" => 2,
+        "AND" => 3,
+        "==" | "≠" | "<" | ">" | "≤" | "≥" => 4,
+        "+" | "-" => 5,
+        "*" | "/" | "MOD" => 6,
+        "^" => 8,
+        _ => return None,
+    })
+}
+
+/// Arguments taken by a function in an algebraic, written F(A,B).
+pub(crate) fn arity(function: &str) -> usize {
+    match function {
+        "π" | "i" | "e" | "MAXR" | "MINR" => 0,
+        "MAX" | "MIN" | "%" | "%CH" | "%T" | "COMB" | "PERM" | "RND" | "TRNC" | "XROOT" => 2,
+        "IFTE" => 3,
+        "∫" => 4,
+        _ => 1,
+    }
+}
+
+fn parenthesized(text: String, wanted: bool) -> String {
+    if wanted {
+        format!("({text})")
+    } else {
+        text
+    }
+}
+
+/// An algebraic's stored (postfix) items written infix, with the
+/// precedence of the last operation; `None` if the items do not make one
+/// expression.
+fn algebraic_text(items: &[Object], modes: &Modes) -> Option<(String, u8)> {
+    let mut stack: Vec<(String, u8)> = Vec::new();
+    let mut items = items.iter();
+    while let Some(item) = items.next() {
+        let name = match item {
+            Object::Command(name) => name.as_str(),
+            Object::Xlib {
+                name: Some(name), ..
+            } => name.as_str(),
+            // A user function: its name, then the argument count and the
+            // command applying it.
+            Object::Bint(n) => {
+                items.next()?;
+                let args = stack.split_off(stack.len().checked_sub(*n as usize)?);
+                let (name, _) = stack.pop()?;
+                let args: Vec<String> = args.into_iter().map(|(text, _)| text).collect();
+                stack.push((format!("{name}({})", args.join(",")), ATOM));
+                continue;
+            }
+            Object::Algebraic(inner) => {
+                stack.push(algebraic_text(inner, modes)?);
+                continue;
+            }
+            Object::Unit(_) => {
+                stack.push((item.to_string(), ATOM));
+                continue;
+            }
+            _ => {
+                stack.push((item_text(item, modes), ATOM));
+                continue;
+            }
+        };
+        let expression = if let Some(p) = infix(name) {
+            let (b, pb) = stack.pop()?;
+            let (a, pa) = stack.pop()?;
+            let a = parenthesized(a, pa < p);
+            let b = parenthesized(b, pb <= p && pb != NEGATION);
+            match name {
+                "AND" | "OR" | "XOR" | "MOD" => (format!("{a} {name} {b}"), p),
+                _ => (format!("{a}{name}{b}"), p),
+            }
+        } else {
+            match name {
+                "NEG" | "√" => {
+                    let (a, pa) = stack.pop()?;
+                    let sign = if name == "NEG" { "-" } else { name };
+                    (
+                        format!("{sign}{}", parenthesized(a, pa < NEGATION)),
+                        NEGATION,
+                    )
+                }
+                "NOT" => {
+                    let (a, pa) = stack.pop()?;
+                    (format!("NOT {}", parenthesized(a, pa < 4)), 3)
+                }
+                "!" => {
+                    let (a, pa) = stack.pop()?;
+                    (format!("{}!", parenthesized(a, pa < ATOM)), ATOM)
+                }
+                _ => match arity(name) {
+                    0 => (name.to_string(), ATOM),
+                    n => {
+                        let args = stack.split_off(stack.len().checked_sub(n)?);
+                        let args: Vec<String> = args.into_iter().map(|(text, _)| text).collect();
+                        (format!("{name}({})", args.join(",")), ATOM)
+                    }
+                },
+            }
+        };
+        stack.push(expression);
+    }
+    match stack.len() {
+        1 => stack.pop(),
+        _ => None,
+    }
+}
+
+/// A number, name or other object that stands as one word.
+fn item_text(object: &Object, modes: &Modes) -> String {
+    match object {
+        Object::Real(r) => real_text(r, modes.reals),
+        Object::Complex(re, im) => format!(
+            "({},{})",
+            real_text(re, modes.reals),
+            real_text(im, modes.reals)
+        ),
+        Object::Binary(nibbles) if nibbles.len() <= 16 => binary_text(nibbles, modes),
+        Object::Algebraic(items) => match algebraic_text(items, modes) {
+            Some((text, _)) => format!("'{text}'"),
+            None => object.to_string(),
+        },
+        Object::Unit(_) => format!("'{object}'"),
+        _ => object.to_string(),
+    }
+}
+
+/// The text being laid out, and where in the program structure it is.
+struct Layout<'a> {
+    modes: &'a Modes,
+    out: String,
+    /// Open programs and structures, innermost last: their opening words.
+    open: Vec<String>,
+    /// The next word starts a line at column 0, as after an array.
+    break_next: bool,
+    /// Inside a quoted name in a program, between its two ' commands.
+    quoting: bool,
+}
+
+impl Layout<'_> {
+    /// `text` on the current line.
+    fn word(&mut self, text: &str) {
+        if self.break_next {
+            self.out.push('\n');
+        } else if !self.out.is_empty() {
+            self.out.push(' ');
+        }
+        self.break_next = false;
+        self.out.push_str(text);
+    }
+
+    /// `text` at the start of a line indented for `depth` open structures.
+    fn line(&mut self, depth: usize, text: &str) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            self.out.push_str(&"  ".repeat(depth));
+        }
+        self.break_next = false;
+        self.out.push_str(text);
+    }
+
+    fn opening(&mut self, word: &str) {
+        self.line(self.open.len(), word);
+        self.open.push(word.to_string());
+    }
+
+    fn middle(&mut self, word: &str) {
+        self.line(self.open.len().saturating_sub(1), word);
+    }
+
+    fn closing(&mut self, word: &str) {
+        self.open.pop();
+        self.line(self.open.len(), word);
+    }
+
+    fn command(&mut self, name: &str) {
+        match name {
+            "«" | "IF" | "IFERR" | "CASE" | "DO" | "WHILE" | "FOR" | "START" => self.opening(name),
+            "THEN" if self.open.last().is_some_and(|w| w == "CASE") => self.opening(name),
+            "THEN" | "ELSE" | "UNTIL" | "REPEAT" => self.middle(name),
+            "»" | "END" | "NEXT" | "STEP" => self.closing(name),
+            "'" => self.quoting = !self.quoting,
+            _ => self.word(name),
+        }
+    }
+
+    fn object(&mut self, object: &Object) {
+        match object {
+            Object::Command(name) => self.command(name),
+            // A user program holds its « and »; other programs in one are
+            // structure clauses (CASE), shown inline.
+            Object::Program(items) => items.iter().for_each(|item| self.object(item)),
+            Object::Pointer(SILENT_QUOTE) => {}
+            Object::List(items) => {
+                self.word("{");
+                items.iter().for_each(|item| self.object(item));
+                self.word("}");
+            }
+            Object::Array { dims, items } => {
+                let items: Vec<String> = items.iter().map(|o| item_text(o, self.modes)).collect();
+                let text = match dims.as_slice() {
+                    [_, columns, ..] => {
+                        let rows: Vec<String> = items
+                            .chunks((*columns).max(1) as usize)
+                            .map(|row| format!("[ {} ]", row.join(" ")))
+                            .collect();
+                        format!("[{}]", rows.join("\n "))
+                    }
+                    _ => format!("[ {} ]", items.join(" ")),
+                };
+                self.line(0, &text);
+                self.break_next = true;
+            }
+            Object::Tagged(tag, inner) => {
+                self.word(&format!(":{tag}:"));
+                self.object(inner);
+            }
+            Object::String(text) => self.word(&format!("\"{text}\"")),
+            Object::Global(name) | Object::Local(name) if self.quoting => {
+                self.word(&format!("'{name}'"))
+            }
+            _ => self.word(&item_text(object, self.modes)),
+        }
+    }
+}
+
+/// `object` as ->STR writes it in `modes`.
+pub fn decompile(object: &Object, modes: &Modes) -> String {
+    if let Object::String(text) = object {
+        return text.clone();
+    }
+    let mut layout = Layout {
+        modes,
+        out: String::new(),
+        open: Vec::new(),
+        break_next: false,
+        quoting: false,
+    };
+    match object {
+        Object::Program(items) if !matches!(items.first(), Some(Object::Command(c)) if c == "«") =>
+        {
+            layout.command("«");
+            layout.object(object);
+            layout.command("»");
+        }
+        _ => layout.object(object),
+    }
+    layout.out
+}
+
+impl Emulator {
+    /// The display modes in the system flags; the defaults on the SX.
+    pub fn display_modes(&self) -> Modes {
+        if self.model != Model::Gx {
+            return Modes::default();
+        }
+        let flag = |n: u32| {
+            let nibble = self.peek_nibble((GX_SYSTEM_FLAGS + (n - 1) / 4) as i32);
+            nibble >> ((n - 1) % 4) & 1 != 0
+        };
+        let field =
+            |first: u32, bits: u32| (0..bits).fold(0, |v, i| v | (flag(first + i) as u32) << i);
+        let digits = field(45, 4) as u8;
+        Modes {
+            reals: match (flag(49), flag(50)) {
+                (false, false) => RealFormat::Std,
+                (true, false) => RealFormat::Fix(digits),
+                (false, true) => RealFormat::Sci(digits),
+                (true, true) => RealFormat::Eng(digits),
+            },
+            base: match (flag(11), flag(12)) {
+                (true, true) => 16,
+                (true, false) => 8,
+                (false, true) => 2,
+                (false, false) => 10,
+            },
+            wordsize: field(5, 6) + 1,
+        }
+    }
+
+    /// The object at `addr` as ->STR writes it in the current modes.
+    pub fn decompile_at(&self, addr: u32) -> Result<String, String> {
+        Ok(decompile(&self.object_at(addr)?, &self.display_modes()))
+    }
+
+    /// The variable `name` in HOME, decompiled.
+    pub fn decompile_variable(&self, name: &str) -> Result<String, String> {
+        let home = self.home_directory()?;
+        let (_, addr) = self
+            .directory_entries(home)?
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| format!("no variable {name} in HOME"))?;
+        self.decompile_at(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, press, ENTER};
+
+    /// PRG, then TYPE and ->STR in its menu.
+    const TO_STR: [u32; 3] = [0x74, 0x83, 0x82];
+    const DROP: u32 = 0x40;
+
+    fn run(emu: &mut Emulator, text: &str) {
+        emu.type_text(text).unwrap();
+        press(emu, &[ENTER]);
+        emu.run_headless(5.0, |emu| emu.is_idle());
+    }
+
+    /// Level 1 decompiled here, and by the firmware's ->STR; drops it.
+    fn both(emu: &mut Emulator) -> (String, String) {
+        let level1 = emu.stack_addresses().unwrap()[0];
+        let ours = emu.decompile_at(level1).unwrap();
+        press(emu, &TO_STR);
+        emu.run_headless(5.0, |emu| emu.is_idle());
+        let theirs = match emu.stack().unwrap().first() {
+            Some(Object::String(text)) => text.clone(),
+            other => panic!("->STR left {other:?}"),
+        };
+        press(emu, &[DROP]);
+        (ours, theirs)
+    }
+
+    #[test]
+    fn test_real_text() {
+        // From ->STR on the calculator, in each mode.
+        let values = [
+            1.5,
+            -1.5,
+            0.5,
+            12345.678,
+            1e15,
+            1e-4,
+            0.0001234,
+            123456789012.0,
+            1.23456789012e-20,
+            0.0,
+            999.9996,
+            9.9999999999e300,
+            1234567890123.0,
+            0.0005,
+            0.00049,
+            2.5e-300,
+            99999.5,
+        ];
+        for (format, shown) in [
+            (
+                RealFormat::Std,
+                "1.5 -1.5 .5 12345.678 1.E15 .0001 .0001234 123456789012 1.23456789012E-20 \
+                 0 999.9996 9.9999999999E300 1.23456789012E12 .0005 .00049 2.5E-300 99999.5",
+            ),
+            (
+                RealFormat::Fix(3),
+                "1.500 -1.500 0.500 12345.678 1.000E15 1.000E-4 1.234E-4 123456789012. \
+                 1.235E-20 0.000 1000.000 1.000E301 1.235E12 0.001 4.900E-4 2.500E-300 \
+                 99999.500",
+            ),
+            (
+                RealFormat::Fix(0),
+                "2. -2. 1. 12346. 1.E15 1.E-4 1.E-4 123456789012. 1.E-20 0. 1000. 1.E301 \
+                 1.E12 5.E-4 5.E-4 3.E-300 100000.",
+            ),
+            (
+                RealFormat::Fix(11),
+                "1.50000000000 -1.50000000000 0.50000000000 12345.6780000 1.00000000000E15 \
+                 0.00010000000 0.00012340000 123456789012. 1.23456789012E-20 0.00000000000 \
+                 999.999600000 9.99999999990E300 1.23456789012E12 0.00050000000 \
+                 0.00049000000 2.50000000000E-300 99999.5000000",
+            ),
+            (
+                RealFormat::Sci(3),
+                "1.500E0 -1.500E0 5.000E-1 1.235E4 1.000E15 1.000E-4 1.234E-4 1.235E11 \
+                 1.235E-20 0.000E0 1.000E3 1.000E301 1.235E12 5.000E-4 4.900E-4 2.500E-300 \
+                 1.000E5",
+            ),
+            (
+                RealFormat::Sci(0),
+                "2.E0 -2.E0 5.E-1 1.E4 1.E15 1.E-4 1.E-4 1.E11 1.E-20 0.E0 1.E3 1.E301 1.E12 \
+                 5.E-4 5.E-4 3.E-300 1.E5",
+            ),
+            (
+                RealFormat::Eng(3),
+                "1.500E0 -1.500E0 500.0E-3 12.35E3 1.000E15 100.0E-6 123.4E-6 123.5E9 \
+                 12.35E-21 0.000E0 1.000E3 10.00E300 1.235E12 500.0E-6 490.0E-6 2.500E-300 \
+                 100.0E3",
+            ),
+            (
+                RealFormat::Eng(1),
+                "1.5E0 -1.5E0 500.E-3 12.E3 1.0E15 100.E-6 120.E-6 120.E9 12.E-21 0.0E0 1.0E3 \
+                 10.E300 1.2E12 500.E-6 490.E-6 2.5E-300 100.E3",
+            ),
+        ] {
+            let ours: Vec<String> = values
+                .iter()
+                .map(|&v| real_text(&Real::from_f64(v).unwrap(), format))
+                .collect();
+            assert_eq!(ours.join(" "), shown, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_matches_firmware() {
+        let mut emu = boot_gx();
+        let sources = [
+            "<< 1 2 + >>",
+            "<< >>",
+            "<< IF X THEN 1 ELSE 2 END >>",
+            "<< 1 10 FOR I IF I 2 == THEN I ELSE 0 END NEXT >>",
+            "<< IFERR 1 THEN 2 END CASE 1 THEN 2 END 3 THEN 4 END END >>",
+            "<< DO 1 UNTIL 2 END WHILE 1 REPEAT 2 END >>",
+            "<< 1 5 START 2 1 STEP >>",
+            "<< → A B << A B + >> >>",
+            "<< { 1 2 } << 3 >> [ 1 2 ] 4 >>",
+            "<< IF 1 THEN << 2 >> END 3 >>",
+            "{ 1 << 2 >> 3 }",
+            "{ \"a\" #5d 'c' }",
+            "[[ 1 2 ][ 3 4 ]]",
+            ":T:<< 1 >>",
+            ":T:{ 1 }",
+            "3_m/s^2",
+            "'SIN(A+B)*2'",
+            "'A-(B+C)'",
+            "'A-B-(C-D)'",
+            "'A^(B^C)'",
+            "'-A^2'",
+            "'(-A)^2'",
+            "'A*-B'",
+            "'X=Y+1'",
+            "'MAX(A,B)'",
+            "'A*(B+C)/D'",
+            "'IFTE(A,B,C)'",
+            "'A AND B OR C'",
+            "'2*3_m'",
+            "<< 'A' STO 1_cm >>",
+            "'SIN(A)^2+π'",
+        ];
+        for source in sources {
+            run(&mut emu, source);
+            let (ours, theirs) = both(&mut emu);
+            assert_eq!(ours, theirs, "{source}");
+        }
+        assert_eq!(emu.stack(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut emu = boot_gx();
+        assert_eq!(emu.display_modes().reals, RealFormat::Std);
+        run(&mut emu, "3 FIX 16 STWS OCT");
+        assert_eq!(
+            emu.display_modes(),
+            Modes {
+                reals: RealFormat::Fix(3),
+                base: 8,
+                wordsize: 16,
+            }
+        );
+        run(&mut emu, "{ 1.5 (1,2) #1234567o 3_m '2.5*A' [ 1 2 ] 4 }");
+        let (ours, theirs) = both(&mut emu);
+        assert_eq!(
+            ours,
+            "{ 1.500 (1.000,2.000) # 34567o '3_m' '2.500*A'\n[ 1.000 2.000 ]\n4.000 }"
+        );
+        assert_eq!(ours, theirs);
+        run(&mut emu, "1 ENG BIN #5d");
+        assert_eq!(both(&mut emu), ("# 101b".to_string(), "# 101b".to_string()));
+        run(&mut emu, "12345");
+        assert_eq!(both(&mut emu), ("12.E3".to_string(), "12.E3".to_string()));
+
+        run(&mut emu, "STD << \"a b\" 'A+1' >> 'P' STO \"a b\"");
+        assert_eq!(
+            emu.decompile_variable("P"),
+            Ok("« \"a b\" 'A+1'\n»".to_string())
+        );
+        assert_eq!(both(&mut emu), ("a b".to_string(), "a b".to_string()));
+        assert!(emu.decompile_variable("Q").is_err());
+    }
+}
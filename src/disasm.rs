@@ -0,0 +1,729 @@
+// Saturn disassembler — renders instructions as HP (SASM) mnemonics.
+//
+// Covers the same opcode space as decode.rs (step_instruction,
+// decode_group_1, decode_group_80, decode_8_thru_f), including the
+// emulator-specific encodings: field codes 8-E alias 0-6 exactly as the
+// START_FIELDS/END_FIELDS tables do, and 6400x with x != 0 is the TRAP
+// that stops the CPU.
+//
+// Instructions are read from any `NibbleSource` — a nibble slice (ROM
+// image, RAM dump) or a live `Emulator`, which is read through the
+// side-effect-free peek path so disassembling never latches a bank switch
+// or touches MMIO.
+//
+// Syntax follows SASM: mnemonic column, then operand. Branch targets and
+// D0=/D1= addresses are `#`-prefixed hex, LCHEX/LAHEX digits are printed
+// most significant first, bit/status/pointer numbers are decimal.
+// Conditional tests carry their GOYES (or RTNYES) on the same line.
+
+use std::fmt;
+
+use crate::emulator::Emulator;
+use crate::types::A_FIELD;
+
+/// Anything the disassembler can read nibbles from.
+pub trait NibbleSource {
+    /// Nibble at `addr` (20-bit address, only the low 4 bits are used).
+    fn nibble(&self, addr: i32) -> u8;
+}
+
+/// A nibble-per-byte image based at address 0. Reads past the end give 0.
+impl NibbleSource for [u8] {
+    fn nibble(&self, addr: i32) -> u8 {
+        self.get((addr & 0xfffff) as usize).map_or(0, |n| n & 0xf)
+    }
+}
+
+impl NibbleSource for Emulator {
+    fn nibble(&self, addr: i32) -> u8 {
+        self.peek_nibble(addr)
+    }
+}
+
+/// How control leaves an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Falls through to the next instruction.
+    Next,
+    /// Unconditional jump to `target`.
+    Jump,
+    /// Subroutine call to `target`; returns to the next instruction.
+    Call,
+    /// Conditional jump to `target`, otherwise falls through.
+    Branch,
+    /// Conditional return (RTNC, RTNNC, RTNYES), otherwise falls through.
+    ReturnIf,
+    /// Return from subroutine or interrupt.
+    Return,
+    /// Jump to an address computed at run time (PC=A, PC=(C), APCEX, ...).
+    Computed,
+    /// Not a valid instruction; `step_instruction` stops here.
+    Illegal,
+}
+
+/// One decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: i32,
+    /// Length in nibbles.
+    pub size: i32,
+    /// The raw opcode nibbles, `size` of them.
+    pub nibbles: Vec<u8>,
+    /// Mnemonic and operands, e.g. `A=DAT0  A` or `GOSBVL  #0679B`.
+    pub text: String,
+    /// Destination of a jump, call or conditional branch.
+    pub target: Option<i32>,
+    pub flow: Flow,
+}
+
+impl Instruction {
+    /// Address of the instruction that follows in memory.
+    pub fn next_addr(&self) -> i32 {
+        (self.addr + self.size) & 0xfffff
+    }
+
+    /// Opcode nibbles as a hex string, in memory order.
+    pub fn opcode(&self) -> String {
+        self.nibbles.iter().map(|n| format!("{n:X}")).collect()
+    }
+}
+
+/// Listing line: address, opcode nibbles, mnemonic.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:05X}  {:<12} {}", self.addr, self.opcode(), self.text)
+    }
+}
+
+/// Decode the instruction at `addr`.
+pub fn disassemble<S: NibbleSource + ?Sized>(src: &S, addr: i32) -> Instruction {
+    let addr = addr & 0xfffff;
+    let op = Decoder { src, addr }.decode();
+    Instruction {
+        addr,
+        size: op.size,
+        nibbles: (0..op.size)
+            .map(|i| src.nibble((addr + i) & 0xfffff) & 0xf)
+            .collect(),
+        text: op.text,
+        target: op.target,
+        flow: op.flow,
+    }
+}
+
+/// Decode `count` consecutive instructions starting at `addr`.
+pub fn disassemble_n<S: NibbleSource + ?Sized>(
+    src: &S,
+    addr: i32,
+    count: usize,
+) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(count);
+    let mut addr = addr & 0xfffff;
+    for _ in 0..count {
+        let insn = disassemble(src, addr);
+        addr = insn.next_addr();
+        out.push(insn);
+    }
+    out
+}
+
+/// Listing of `count` instructions from `addr`, one per line.
+pub fn listing<S: NibbleSource + ?Sized>(src: &S, addr: i32, count: usize) -> String {
+    disassemble_n(src, addr, count)
+        .iter()
+        .map(|insn| format!("{insn}\n"))
+        .collect()
+}
+
+impl Emulator {
+    /// Decode the instruction at `addr` in the current memory map.
+    pub fn disassemble(&self, addr: i32) -> Instruction {
+        disassemble(self, addr)
+    }
+}
+
+pub(crate) const REGS: [&str; 4] = ["A", "B", "C", "D"];
+
+/// Field selector names by code; 8-E alias 0-6 like the ALU tables do.
+fn field(code: u8) -> &'static str {
+    const NAMES: [&str; 8] = ["P", "WP", "XS", "X", "S", "M", "B", "W"];
+    if code == A_FIELD {
+        "A"
+    } else {
+        NAMES[(code & 7) as usize]
+    }
+}
+
+/// Scratch register for the Rn=A / A=Rn / ARnEX groups (5-7 alias R1-R3).
+fn scratch(op: u8) -> u8 {
+    match op & 7 {
+        5..=7 => (op & 7) - 4,
+        n => n,
+    }
+}
+
+/// A or C, from the high bit of the Rn-group register nibble.
+fn ac(op: u8) -> &'static str {
+    if op < 8 {
+        "A"
+    } else {
+        "C"
+    }
+}
+
+/// `mnemonic operand` in SASM columns.
+fn with(mnemonic: &str, operand: impl fmt::Display) -> String {
+    format!("{mnemonic:<7} {operand}")
+}
+
+// Mnemonics indexed by the last opcode nibble, shared by the field groups
+// (9xx-Bxx, 0Exx, 1xx) and the A-field shorthands (Cx-Fx).
+
+#[rustfmt::skip]
+pub(crate) const ADD: [&str; 16] = [
+    "A=A+B", "B=B+C", "C=C+A", "D=D+C", "A=A+A", "B=B+B", "C=C+C", "D=D+D",
+    "B=B+A", "C=C+B", "A=A+C", "C=C+D", "A=A-1", "B=B-1", "C=C-1", "D=D-1",
+];
+#[rustfmt::skip]
+pub(crate) const COPY: [&str; 16] = [
+    "A=0", "B=0", "C=0", "D=0", "A=B", "B=C", "C=A", "D=C",
+    "B=A", "C=B", "A=C", "C=D", "ABEX", "BCEX", "ACEX", "CDEX",
+];
+#[rustfmt::skip]
+pub(crate) const SUB: [&str; 16] = [
+    "A=A-B", "B=B-C", "C=C-A", "D=D-C", "A=A+1", "B=B+1", "C=C+1", "D=D+1",
+    "B=B-A", "C=C-B", "A=A-C", "C=C-D", "A=B-A", "B=C-B", "C=A-C", "D=C-D",
+];
+#[rustfmt::skip]
+pub(crate) const SHIFT: [&str; 16] = [
+    "ASL", "BSL", "CSL", "DSL", "ASR", "BSR", "CSR", "DSR",
+    "A=-A", "B=-B", "C=-C", "D=-D", "A=-A-1", "B=-B-1", "C=-C-1", "D=-D-1",
+];
+#[rustfmt::skip]
+pub(crate) const EQ_TESTS: [&str; 16] = [
+    "?A=B", "?B=C", "?A=C", "?C=D", "?A#B", "?B#C", "?A#C", "?C#D",
+    "?A=0", "?B=0", "?C=0", "?D=0", "?A#0", "?B#0", "?C#0", "?D#0",
+];
+#[rustfmt::skip]
+pub(crate) const ORD_TESTS: [&str; 16] = [
+    "?A>B", "?B>C", "?C>A", "?D>C", "?A<B", "?B<C", "?C<A", "?D<C",
+    "?A>=B", "?B>=C", "?C>=A", "?D>=C", "?A<=B", "?B<=C", "?C<=A", "?D<=C",
+];
+#[rustfmt::skip]
+pub(crate) const LOGIC: [&str; 16] = [
+    "A=A&B", "B=B&C", "C=C&A", "D=D&C", "B=B&A", "C=C&B", "A=A&C", "C=C&D",
+    "A=A!B", "B=B!C", "C=C!A", "D=D!C", "B=B!A", "C=C!B", "A=A!C", "C=C!D",
+];
+#[rustfmt::skip]
+pub(crate) const DAT: [&str; 8] = [
+    "DAT0=A", "DAT1=A", "A=DAT0", "A=DAT1", "DAT0=C", "DAT1=C", "C=DAT0", "C=DAT1",
+];
+#[rustfmt::skip]
+pub(crate) const ADDR_OPS: [&str; 16] = [
+    "D0=A", "D1=A", "AD0EX", "AD1EX", "D0=C", "D1=C", "CD0EX", "CD1EX",
+    "D0=AS", "D1=AS", "AD0XS", "AD1XS", "D0=CS", "D1=CS", "CD0XS", "CD1XS",
+];
+
+struct Op {
+    size: i32,
+    text: String,
+    target: Option<i32>,
+    flow: Flow,
+}
+
+impl Op {
+    fn next(size: i32, text: impl Into<String>) -> Self {
+        Op {
+            size,
+            text: text.into(),
+            target: None,
+            flow: Flow::Next,
+        }
+    }
+
+    fn flow(size: i32, text: impl Into<String>, flow: Flow) -> Self {
+        Op {
+            size,
+            text: text.into(),
+            target: None,
+            flow,
+        }
+    }
+
+    fn illegal(size: i32) -> Self {
+        Op::flow(size, "???", Flow::Illegal)
+    }
+}
+
+struct Decoder<'a, S: ?Sized> {
+    src: &'a S,
+    addr: i32,
+}
+
+impl<S: NibbleSource + ?Sized> Decoder<'_, S> {
+    fn n(&self, off: i32) -> u8 {
+        self.src.nibble((self.addr + off) & 0xfffff) & 0xf
+    }
+
+    /// `count` nibbles at `off`, low nibble first (same as read_nibbles).
+    fn val(&self, off: i32, count: i32) -> i32 {
+        (0..count)
+            .rev()
+            .fold(0, |v, i| (v << 4) | self.n(off + i) as i32)
+    }
+
+    /// Sign-extended `count`-nibble displacement at `off`.
+    fn rel(&self, off: i32, count: i32) -> i32 {
+        let v = self.val(off, count);
+        let bits = 4 * count;
+        (v << (32 - bits)) >> (32 - bits)
+    }
+
+    /// Hex digits of a constant, most significant first.
+    fn hex(&self, off: i32, count: i32) -> String {
+        (0..count)
+            .rev()
+            .map(|i| format!("{:X}", self.n(off + i)))
+            .collect()
+    }
+
+    fn target(&self, base: i32, disp: i32) -> i32 {
+        (self.addr + base + disp) & 0xfffff
+    }
+
+    fn jump(&self, size: i32, mnemonic: &str, to: i32, flow: Flow) -> Op {
+        Op {
+            size,
+            text: with(mnemonic, format!("#{to:05X}")),
+            target: Some(to),
+            flow,
+        }
+    }
+
+    /// Test instruction whose 2-nibble GOYES displacement sits at `base`.
+    fn test(&self, base: i32, text: String) -> Op {
+        let disp = self.rel(base, 2);
+        if disp == 0 {
+            return Op::flow(base + 2, format!("{text}  RTNYES"), Flow::ReturnIf);
+        }
+        let to = self.target(base, disp);
+        Op {
+            size: base + 2,
+            text: format!("{text}  GOYES #{to:05X}"),
+            target: Some(to),
+            flow: Flow::Branch,
+        }
+    }
+
+    fn decode(&self) -> Op {
+        match self.n(0) {
+            0 => self.group_0(),
+            1 => self.group_1(),
+            2 => Op::next(2, with("P=", self.n(1))),
+            3 => {
+                let len = self.n(1) as i32 + 1;
+                Op::next(2 + len, with("LCHEX", self.hex(2, len)))
+            }
+            4 => {
+                let raw = self.val(1, 2);
+                if raw == 0x02 {
+                    Op::next(3, "NOP3")
+                } else if raw == 0 {
+                    Op::flow(3, "RTNC", Flow::ReturnIf)
+                } else {
+                    self.jump(3, "GOC", self.target(1, self.rel(1, 2)), Flow::Branch)
+                }
+            }
+            5 => {
+                if self.val(1, 2) == 0 {
+                    Op::flow(3, "RTNNC", Flow::ReturnIf)
+                } else {
+                    self.jump(3, "GONC", self.target(1, self.rel(1, 2)), Flow::Branch)
+                }
+            }
+            6 => match self.val(1, 3) {
+                0x003 => Op::next(4, "NOP4"),
+                0x004 if self.n(4) == 0 => Op::next(5, "NOP5"),
+                0x004 => Op::flow(5, with("TRAP", self.n(4)), Flow::Illegal),
+                _ => self.jump(4, "GOTO", self.target(1, self.rel(1, 3)), Flow::Jump),
+            },
+            7 => self.jump(4, "GOSUB", self.target(4, self.rel(1, 3)), Flow::Call),
+            8 => self.group_8(),
+            op => self.field_group(op),
+        }
+    }
+
+    fn group_0(&self) -> Op {
+        match self.n(1) {
+            0 => Op::flow(2, "RTNSXM", Flow::Return),
+            1 => Op::flow(2, "RTN", Flow::Return),
+            2 => Op::flow(2, "RTNSC", Flow::Return),
+            3 => Op::flow(2, "RTNCC", Flow::Return),
+            4 => Op::next(2, "SETHEX"),
+            5 => Op::next(2, "SETDEC"),
+            6 => Op::next(2, "RSTK=C"),
+            7 => Op::next(2, "C=RSTK"),
+            8 => Op::next(2, "CLRST"),
+            9 => Op::next(2, "C=ST"),
+            0xa => Op::next(2, "ST=C"),
+            0xb => Op::next(2, "CSTEX"),
+            0xc => Op::next(2, "P=P+1"),
+            0xd => Op::next(2, "P=P-1"),
+            0xe => Op::next(4, with(LOGIC[self.n(3) as usize], field(self.n(2)))),
+            _ => Op::flow(2, "RTI", Flow::Return),
+        }
+    }
+
+    fn group_1(&self) -> Op {
+        let op3 = self.n(2);
+        match self.n(1) {
+            0 => Op::next(3, format!("R{}={}", scratch(op3), ac(op3))),
+            1 => Op::next(3, format!("{}=R{}", ac(op3), scratch(op3))),
+            2 => Op::next(3, format!("{}R{}EX", ac(op3), scratch(op3))),
+            3 => Op::next(3, ADDR_OPS[op3 as usize]),
+            4 => {
+                let f = if op3 < 8 { "A" } else { "B" };
+                Op::next(3, with(DAT[(op3 & 7) as usize], f))
+            }
+            5 => {
+                let op4 = self.n(3);
+                let operand = if op3 >= 8 {
+                    (op4 as i32 + 1).to_string()
+                } else {
+                    field(op4).to_string()
+                };
+                Op::next(4, with(DAT[(op3 & 7) as usize], operand))
+            }
+            6 => Op::next(3, with("D0=D0+", op3 as i32 + 1)),
+            7 => Op::next(3, with("D1=D1+", op3 as i32 + 1)),
+            8 => Op::next(3, with("D0=D0-", op3 as i32 + 1)),
+            0xc => Op::next(3, with("D1=D1-", op3 as i32 + 1)),
+            op2 => {
+                let reg = if op2 < 0xc { "D0" } else { "D1" };
+                let len = match op2 & 3 {
+                    1 => 2,
+                    2 => 4,
+                    _ => 5,
+                };
+                let text = with(&format!("{reg}=({len})"), format!("#{}", self.hex(2, len)));
+                Op::next(2 + len, text)
+            }
+        }
+    }
+
+    fn group_8(&self) -> Op {
+        let op3 = self.n(2);
+        match self.n(1) {
+            0 => self.group_80(),
+            1 => self.group_81(),
+            2 => {
+                let text = match op3 {
+                    1 => "XM=0".to_string(),
+                    2 => "SB=0".to_string(),
+                    4 => "SR=0".to_string(),
+                    8 => "MP=0".to_string(),
+                    0xf => "CLRHST".to_string(),
+                    n => with("HST=0", n),
+                };
+                Op::next(3, text)
+            }
+            3 => {
+                let text = match op3 {
+                    1 => "?XM=0".to_string(),
+                    2 => "?SB=0".to_string(),
+                    4 => "?SR=0".to_string(),
+                    8 => "?MP=0".to_string(),
+                    n => with("?HST=0", n),
+                };
+                self.test(3, text)
+            }
+            4 => Op::next(3, with("ST=0", op3)),
+            5 => Op::next(3, with("ST=1", op3)),
+            6 => self.test(3, with("?ST=0", op3)),
+            7 => self.test(3, with("?ST=1", op3)),
+            8 => self.test(3, with("?P#", op3)),
+            9 => self.test(3, with("?P=", op3)),
+            0xa => self.test(3, with(EQ_TESTS[op3 as usize], "A")),
+            0xb => self.test(3, with(ORD_TESTS[op3 as usize], "A")),
+            0xc => self.jump(6, "GOLONG", self.target(2, self.rel(2, 4)), Flow::Jump),
+            0xd => self.jump(7, "GOVLNG", self.val(2, 5), Flow::Jump),
+            0xe => self.jump(6, "GOSUBL", self.target(6, self.rel(2, 4)), Flow::Call),
+            _ => self.jump(7, "GOSBVL", self.val(2, 5), Flow::Call),
+        }
+    }
+
+    fn group_80(&self) -> Op {
+        let op4 = self.n(3);
+        match self.n(2) {
+            0 => Op::next(3, "OUT=CS"),
+            1 => Op::next(3, "OUT=C"),
+            2 => Op::next(3, "A=IN"),
+            3 => Op::next(3, "C=IN"),
+            4 => Op::next(3, "UNCNFG"),
+            5 => Op::next(3, "CONFIG"),
+            6 => Op::next(3, "C=ID"),
+            7 => Op::next(3, "SHUTDN"),
+            8 => self.group_808(),
+            9 => Op::next(3, "C+P+1"),
+            0xa => Op::next(3, "RESET"),
+            0xb => Op::next(3, "BUSCC"),
+            0xc => Op::next(4, with("C=P", op4)),
+            0xd => Op::next(4, with("P=C", op4)),
+            0xe => Op::next(3, "SREQ?"),
+            _ => Op::next(4, with("CPEX", op4)),
+        }
+    }
+
+    fn group_808(&self) -> Op {
+        let op5 = self.n(4);
+        match self.n(3) {
+            0 => Op::next(4, "INTON"),
+            1 => Op::next(5, "RSI"),
+            2 => {
+                let len = op5 as i32 + 1;
+                Op::next(5 + len, with("LAHEX", self.hex(5, len)))
+            }
+            3 => Op::next(4, "BUSCB"),
+            4 => Op::next(5, with("ABIT=0", op5)),
+            5 => Op::next(5, with("ABIT=1", op5)),
+            6 => self.test(5, with("?ABIT=0", op5)),
+            7 => self.test(5, with("?ABIT=1", op5)),
+            8 => Op::next(5, with("CBIT=0", op5)),
+            9 => Op::next(5, with("CBIT=1", op5)),
+            0xa => self.test(5, with("?CBIT=0", op5)),
+            0xb => self.test(5, with("?CBIT=1", op5)),
+            0xc => Op::flow(4, "PC=(A)", Flow::Computed),
+            0xd => Op::next(4, "BUSCD"),
+            0xe => Op::flow(4, "PC=(C)", Flow::Computed),
+            _ => Op::next(4, "INTOFF"),
+        }
+    }
+
+    fn group_81(&self) -> Op {
+        let op3 = self.n(2);
+        let op4 = self.n(3);
+        let op5 = self.n(4);
+        match op3 {
+            0..=3 => Op::next(3, format!("{}SLC", REGS[op3 as usize])),
+            4..=7 => Op::next(3, format!("{}SRC", REGS[(op3 & 3) as usize])),
+            8 => {
+                let r = REGS[(op5 & 3) as usize];
+                let sign = if op5 < 8 { '+' } else { '-' };
+                let operand = format!("{},{}", field(op4), self.n(5) as i32 + 1);
+                Op::next(6, with(&format!("{r}={r}{sign}CON"), operand))
+            }
+            9 => Op::next(
+                5,
+                with(&format!("{}SRB.F", REGS[(op5 & 3) as usize]), field(op4)),
+            ),
+            0xa => {
+                let op6 = self.n(5);
+                let (rn, r) = (scratch(op6), ac(op6));
+                let mnemonic = match op5 {
+                    0 => format!("R{rn}={r}.F"),
+                    1 => format!("{r}=R{rn}.F"),
+                    2 => format!("{r}R{rn}EX.F"),
+                    _ => return Op::illegal(6),
+                };
+                Op::next(6, with(&mnemonic, field(op4)))
+            }
+            0xb => match op4 {
+                2 => Op::flow(4, "PC=A", Flow::Computed),
+                3 => Op::flow(4, "PC=C", Flow::Computed),
+                4 => Op::next(4, "A=PC"),
+                5 => Op::next(4, "C=PC"),
+                6 => Op::flow(4, "APCEX", Flow::Computed),
+                7 => Op::flow(4, "CPCEX", Flow::Computed),
+                _ => Op::illegal(4),
+            },
+            _ => Op::next(3, format!("{}SRB", REGS[(op3 & 3) as usize])),
+        }
+    }
+
+    /// 9xx-Fxx: register arithmetic and tests. 9/A/B take an explicit field
+    /// (op2 & 7, the high half of op2 selecting the second table); C-F are
+    /// the A-field shorthands.
+    fn field_group(&self, op1: u8) -> Op {
+        let op2 = self.n(1);
+        if op1 >= 0xc {
+            let table = match op1 {
+                0xc => &ADD,
+                0xd => &COPY,
+                0xe => &SUB,
+                _ => &SHIFT,
+            };
+            return Op::next(2, table[op2 as usize]);
+        }
+        let op3 = self.n(2) as usize;
+        let f = field(op2 & 7);
+        let high = op2 >= 8;
+        match (op1, high) {
+            (9, false) => self.test(3, with(EQ_TESTS[op3], f)),
+            (9, true) => self.test(3, with(ORD_TESTS[op3], f)),
+            (0xa, false) => Op::next(3, with(ADD[op3], f)),
+            (0xa, true) => Op::next(3, with(COPY[op3], f)),
+            (_, false) => Op::next(3, with(SUB[op3], f)),
+            (_, true) => Op::next(3, with(SHIFT[op3], f)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nibbles from a hex string in memory order.
+    fn nibs(hex: &str) -> Vec<u8> {
+        hex.chars().map(|c| c.to_digit(16).unwrap() as u8).collect()
+    }
+
+    fn dis(hex: &str) -> Instruction {
+        disassemble(nibs(hex).as_slice(), 0)
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let cases = [
+            ("01", "RTN"),
+            ("0E60", "A=A&B   B"),
+            ("0EFE", "A=A!C   A"),
+            ("108", "R0=C"),
+            ("115", "A=R1"),
+            ("12C", "CR4EX"),
+            ("13A", "AD0XS"),
+            ("142", "A=DAT0  A"),
+            ("14F", "C=DAT1  B"),
+            ("1544", "DAT0=C  S"),
+            ("15AF", "A=DAT0  16"),
+            ("164", "D0=D0+  5"),
+            ("1C0", "D1=D1-  1"),
+            ("1B3F608", "D0=(5)  #806F3"),
+            ("1D21", "D1=(2)  #12"),
+            ("2A", "P=      10"),
+            ("34FEDCB", "LCHEX   BCDEF"),
+            ("80822321", "LAHEX   123"),
+            ("420", "NOP3"),
+            ("6300", "NOP4"),
+            ("64000", "NOP5"),
+            ("808F", "INTOFF"),
+            ("80C7", "C=P     7"),
+            ("80810", "RSI"),
+            ("8085B", "ABIT=1  11"),
+            ("810", "ASLC"),
+            ("81E", "CSRB"),
+            ("818F2A", "C=C+CON A,11"),
+            ("818F9A", "B=B-CON A,11"),
+            ("81933", "DSRB.F  X"),
+            ("81A70A", "R2=C.F  W"),
+            ("81AF14", "A=R4.F  A"),
+            ("81B6", "APCEX"),
+            ("82F", "CLRHST"),
+            ("828", "MP=0"),
+            ("825", "HST=0   5"),
+            ("84B", "ST=0    11"),
+            ("AFC", "ABEX    W"),
+            ("AEA", "A=C     B"),
+            ("A02", "C=C+A   P"),
+            ("B1C", "A=B-A   WP"),
+            ("BFF", "D=-D-1  W"),
+            ("BB4", "ASR     X"),
+            ("C4", "A=A+A"),
+            ("D7", "D=C"),
+            ("EC", "A=B-A"),
+            ("F9", "B=-B"),
+        ];
+        for (hex, text) in cases {
+            let insn = dis(hex);
+            assert_eq!(insn.text, text, "{hex}");
+            assert_eq!(insn.size as usize, hex.len(), "{hex} length");
+            assert_eq!(insn.opcode(), hex);
+        }
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        // GOC +0x10 from 0: displacement is relative to the offset field.
+        let insn = dis("401");
+        assert_eq!(
+            (insn.text.as_str(), insn.target, insn.flow),
+            ("GOC     #00011", Some(0x11), Flow::Branch)
+        );
+        assert_eq!(dis("400").flow, Flow::ReturnIf);
+        assert_eq!(dis("500").text, "RTNNC");
+
+        // GOTO backwards: 0xFFE = -2 relative to addr+1.
+        let code = nibs("0000006EFF");
+        let insn = disassemble(code.as_slice(), 6);
+        assert_eq!((insn.target, insn.flow), (Some(0x5), Flow::Jump));
+
+        // GOSUB is relative to the return address.
+        let insn = dis("7100");
+        assert_eq!(
+            (insn.target, insn.flow, insn.next_addr()),
+            (Some(0x5), Flow::Call, 4)
+        );
+
+        let insn = dis("8C0100");
+        assert_eq!(insn.target, Some(0x12));
+        let insn = dis("8E0100");
+        assert_eq!((insn.target, insn.flow), (Some(0x16), Flow::Call));
+        assert_eq!(dis("8DB9760").text, "GOVLNG  #0679B");
+        let insn = dis("8F14150");
+        assert_eq!(
+            (insn.text.as_str(), insn.flow),
+            ("GOSBVL  #05141", Flow::Call)
+        );
+
+        // Tests carry their GOYES; a zero displacement is RTNYES.
+        let insn = dis("8A8F0");
+        assert_eq!(insn.text, "?A=0    A  GOYES #00012");
+        assert_eq!((insn.size, insn.target), (5, Some(0x12)));
+        let insn = dis("9FC00");
+        assert_eq!(
+            (insn.text.as_str(), insn.flow),
+            ("?A<=B   W  RTNYES", Flow::ReturnIf)
+        );
+        let insn = dis("8086312");
+        assert_eq!(
+            (insn.text.as_str(), insn.size, insn.target),
+            ("?ABIT=0 3  GOYES #00026", 7, Some(0x26))
+        );
+        assert_eq!(dis("834F1").text, "?SR=0  GOYES #00022");
+
+        assert_eq!(dis("81B2").flow, Flow::Computed);
+        assert_eq!(dis("808C").flow, Flow::Computed);
+        assert_eq!(dis("0F").flow, Flow::Return);
+    }
+
+    #[test]
+    fn test_illegal() {
+        assert_eq!(dis("81A030").flow, Flow::Illegal);
+        assert_eq!(dis("81B0").flow, Flow::Illegal);
+        let insn = dis("64001");
+        assert_eq!((insn.size, insn.flow), (5, Flow::Illegal));
+    }
+
+    /// Every decodable instruction in the GX ROM start-up path must agree in
+    /// length with what the CPU actually executes.
+    #[test]
+    fn test_lengths_match_cpu() {
+        let mut emu = crate::test_support::boot_gx();
+        for _ in 0..20000 {
+            let pc = emu.saturn.pc;
+            let insn = emu.disassemble(pc);
+            let stop = emu.step_instruction();
+            assert!(!stop, "illegal instruction at {pc:05X}");
+            if insn.flow == Flow::Next {
+                assert_eq!(emu.saturn.pc, insn.next_addr(), "{insn}");
+            } else if let (Flow::Jump | Flow::Call, Some(to)) = (insn.flow, insn.target) {
+                assert_eq!(emu.saturn.pc, to, "{insn}");
+            } else if insn.flow == Flow::Branch {
+                let to = insn.target.unwrap();
+                assert!(
+                    emu.saturn.pc == to || emu.saturn.pc == insn.next_addr(),
+                    "{insn}"
+                );
+            }
+        }
+    }
+}
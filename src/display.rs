@@ -261,4 +261,81 @@ impl Display {
             }
         }
     }
+
+    /// Whether the pixel at (`x`, `y`) is dark.
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        self.rgba[((y * DISPLAY_WIDTH + x) * 4) as usize] == PIXEL_ON_R
+    }
+
+    /// The LCD as a binary PBM (P4) image, dark pixels set.
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut out = format!("P4\n{} {}\n", DISPLAY_WIDTH, DISPLAY_HEIGHT).into_bytes();
+        for y in 0..DISPLAY_HEIGHT {
+            let mut row = vec![0u8; DISPLAY_WIDTH.div_ceil(8) as usize];
+            for x in 0..DISPLAY_WIDTH {
+                if self.pixel(x, y) {
+                    row[(x / 8) as usize] |= 0x80 >> (x % 8);
+                }
+            }
+            out.extend_from_slice(&row);
+        }
+        out
+    }
+
+    /// Whether the LCD shows exactly the image in `pbm`, a binary PBM as
+    /// written by `to_pbm`.
+    pub fn matches_pbm(&self, pbm: &[u8]) -> Result<bool, String> {
+        let (width, height, bits) = parse_pbm(pbm)?;
+        if (width, height) != (DISPLAY_WIDTH, DISPLAY_HEIGHT) {
+            return Err(format!(
+                "image is {width}x{height}, the LCD is {DISPLAY_WIDTH}x{DISPLAY_HEIGHT}"
+            ));
+        }
+        let stride = DISPLAY_WIDTH.div_ceil(8);
+        Ok((0..DISPLAY_HEIGHT).all(|y| {
+            (0..DISPLAY_WIDTH).all(|x| {
+                let byte = bits[(y * stride + x / 8) as usize];
+                (byte & (0x80 >> (x % 8)) != 0) == self.pixel(x, y)
+            })
+        }))
+    }
+}
+
+/// Split a binary PBM into width, height and packed rows.
+fn parse_pbm(data: &[u8]) -> Result<(u32, u32, &[u8]), String> {
+    if !data.starts_with(b"P4") {
+        return Err("not a binary PBM (P4) image".to_string());
+    }
+    // Header fields are separated by whitespace and may carry # comments.
+    let mut pos = 2;
+    let mut fields = [0u32; 2];
+    for field in &mut fields {
+        loop {
+            match data.get(pos) {
+                Some(b'#') => {
+                    while data.get(pos).is_some_and(|&c| c != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+        }
+        *field = std::str::from_utf8(&data[start..pos])
+            .unwrap()
+            .parse()
+            .map_err(|_| "bad PBM header".to_string())?;
+    }
+    // Exactly one whitespace byte before the raster.
+    pos += 1;
+    let (width, height) = (fields[0], fields[1]);
+    let len = (width.div_ceil(8) * height) as usize;
+    match data.get(pos..pos + len) {
+        Some(bits) if pos + len == data.len() => Ok((width, height, bits)),
+        _ => Err("PBM raster has the wrong length".to_string()),
+    }
 }
@@ -2,19 +2,25 @@
 // Port of main_wasm.c frame_callback + emulate.c schedule() + device.c check_devices()
 
 use crate::alu::{get_end, get_start, RegId};
+use crate::binio::StoreRun;
 use crate::cpu::{DisplayState, Saturn};
+use crate::debugger::{Access, Debugger, StopReason};
 use crate::device::DeviceFlags;
 use crate::display::Display;
 use crate::keyboard::Keyboard;
+use crate::keymacro::MacroRun;
 use crate::memory::Memory;
 use crate::persist;
+use crate::rom::Revision;
 use crate::scheduler::*;
 use crate::serial::Serial;
 use crate::speaker::Speaker;
 use crate::timer::*;
+use crate::timetravel::TimeTravel;
+use crate::trace::Tracer;
 use crate::types::*;
 
-const TARGET_IPS: f64 = 5_000_000.0; // ~27x real Saturn speed for snappy UI
+pub const TARGET_IPS: f64 = 5_000_000.0; // ~27x real Saturn speed for snappy UI
 const TARGET_IPS_BEEP: f64 = 184000.0; // Original speed during speaker activity
 const MAX_INSTRUCTIONS_PER_FRAME: i32 = 100_000;
 
@@ -30,6 +36,19 @@ pub struct Emulator {
     pub sched: Scheduler,
     pub timers: Timers,
     pub model: Model,
+    /// The firmware revision, from the ROM's version string.
+    pub revision: Revision,
+    pub debugger: Debugger,
+    /// Execution trace, `None` unless tracing.
+    pub tracer: Option<Box<Tracer>>,
+    /// Snapshot and input history for reverse execution, `None` when off.
+    pub(crate) timetravel: Option<Box<TimeTravel>>,
+    /// Key macro being recorded, `None` when not recording.
+    pub(crate) macro_recording: Option<MacroRun>,
+    /// Key macro being played back, `None` when not playing.
+    pub(crate) macro_playback: Option<MacroRun>,
+    /// Variable being stored by `start_store`, `None` when not storing.
+    pub(crate) store: Option<StoreRun>,
 
     // Runtime flags
     pub got_alarm: bool,
@@ -44,8 +63,12 @@ pub struct Emulator {
     // Time offset: maps monotonic now (performance.now/1000) to local epoch seconds
     epoch_offset: f64,
     // HP-48 absolute time state (port of C globals time_offset, set_0_time)
-    time_offset: u64, // unix_0_time + set_0_time (HP-48 epoch + user adjustment)
-    set_0_time: u64,  // user time adjustment (normally 0, modified by drift correction)
+    pub(crate) time_offset: u64, // unix_0_time + set_0_time (HP-48 epoch + user adjustment)
+    pub(crate) set_0_time: u64, // user time adjustment (normally 0, modified by drift correction)
+    // Emulated time run ahead of the host clock by run_headless
+    clock_skew: f64,
+    /// Time source when it is the instruction count, `None` on the host clock.
+    pub(crate) vclock: Option<VirtualClock>,
 }
 
 impl Emulator {
@@ -65,6 +88,7 @@ impl Emulator {
         };
 
         let rom = persist::load_rom(rom_data, rom_size);
+        let revision = Revision::guess(&rom, model);
         let ram = match ram_data {
             Some(data) => persist::load_ram(data, ram_size),
             None => vec![0u8; ram_size],
@@ -134,6 +158,13 @@ impl Emulator {
             sched,
             timers: Timers::new(),
             model,
+            revision,
+            debugger: Debugger::default(),
+            tracer: None,
+            timetravel: None,
+            macro_recording: None,
+            macro_playback: None,
+            store: None,
             got_alarm: false,
             interrupt_called: false,
             is_shutdown: false,
@@ -143,6 +174,8 @@ impl Emulator {
             epoch_offset: 0.0,
             time_offset: 0,
             set_0_time: 0,
+            clock_skew: 0.0,
+            vclock: None,
         }
     }
 
@@ -169,12 +202,47 @@ impl Emulator {
         self.timers.start_timer(RUN_TIMER, now);
     }
 
+    /// Start on the virtual clock instead of `start`: emulated time is the
+    /// instruction count over `ips`, and the calendar begins at
+    /// `start_epoch` (local epoch seconds). Identical inputs then give
+    /// identical runs, whatever the host's frame timing.
+    pub fn start_virtual(&mut self, ips: f64, start_epoch: f64) {
+        self.vclock = Some(VirtualClock::new(ips, start_epoch));
+        self.now = 0.0;
+        self.clock_skew = 0.0;
+        self.start(0.0, start_epoch);
+    }
+
+    /// The virtual clock, `None` when time comes from the host.
+    pub fn virtual_clock(&self) -> Option<&VirtualClock> {
+        self.vclock.as_ref()
+    }
+
     // -----------------------------------------------------------------------
     // Memory access
     // -----------------------------------------------------------------------
 
+    /// Whether `a` (masked) hits the memory-mapped I/O registers.
+    #[inline]
+    pub(crate) fn mmio_mapped(&self, a: i32) -> bool {
+        let mmio_idx = match self.model {
+            Model::Sx => MCTL_MMIO_SX,
+            Model::Gx => MCTL_MMIO_GX,
+        };
+        (0x100..0x140).contains(&a) && self.saturn.mem_cntl[mmio_idx].config[0] == 0x100
+    }
+
     #[inline]
     pub fn read_nibble(&mut self, addr: i32) -> u8 {
+        let val = self.read_nibble_unwatched(addr);
+        if self.debugger.watching {
+            self.watch_access(addr, Access::Read, val, val);
+        }
+        val
+    }
+
+    #[inline]
+    fn read_nibble_unwatched(&mut self, addr: i32) -> u8 {
         let a = addr & 0xfffff;
         // Check for MMIO range
         if a >= 0x100 && a < 0x140 {
@@ -198,6 +266,15 @@ impl Emulator {
         }
     }
 
+    /// Read a nibble without side effects: no MMIO access, no bank-switch
+    /// latch. Used by the disassembler and other inspection tools.
+    pub fn peek_nibble(&self, addr: i32) -> u8 {
+        match self.model {
+            Model::Sx => self.mem.read_nibble_sx(&self.saturn, addr),
+            Model::Gx => self.mem.read_nibble_gx_display(&self.saturn, addr),
+        }
+    }
+
     /// Read n nibbles from addr, assembling into an i32 (low nibble first).
     #[inline]
     pub fn read_nibbles(&mut self, addr: i32, n: i32) -> i32 {
@@ -217,6 +294,10 @@ impl Emulator {
 
     #[inline]
     pub fn write_nibble(&mut self, addr: i32, val: i32) {
+        if self.debugger.watching {
+            let old = self.watch_old(addr);
+            self.watch_access(addr, Access::Write, old, (val & 0xf) as u8);
+        }
         let needs_display_check = match self.model {
             Model::Sx => self.mem.write_nibble_sx(
                 &mut self.saturn,
@@ -271,8 +352,18 @@ impl Emulator {
 
     #[inline]
     pub fn read_nibble_crc(&mut self, addr: i32) -> u8 {
+        let val = self.read_nibble_crc_unwatched(addr);
+        if self.debugger.watching {
+            self.watch_access(addr, Access::CrcRead, val, val);
+        }
+        val
+    }
+
+    #[inline]
+    fn read_nibble_crc_unwatched(&mut self, addr: i32) -> u8 {
         let a = addr & 0xfffff;
-        // Check for MMIO range — read value and apply CRC
+        // Check for MMIO range — I/O reads do not feed the CRC: x48's
+        // read_nibble_crc_sx/gx return read_dev_mem without calc_crc
         if a >= 0x100 && a < 0x140 {
             let mmio_idx = match self.model {
                 Model::Sx => MCTL_MMIO_SX,
@@ -286,10 +377,6 @@ impl Emulator {
                     &mut self.sched.schedule_event,
                     a,
                 );
-                // Apply CRC (same formula as Memory::calc_crc)
-                self.saturn.crc = ((self.saturn.crc >> 4)
-                    ^ (((self.saturn.crc ^ val as u16) & 0xf) * 0x1081))
-                    as u16;
                 return val;
             }
         }
@@ -435,10 +522,6 @@ impl Emulator {
     /// T1: from the T1 timer in 512 Hz encoding.
     /// T2: computed from ACCESSTIME in RAM vs current wall-clock time.
     pub fn get_t1_t2(&mut self) -> T1T2Ticks {
-        // RAM offsets for ACCESSTIME
-        const ACCESSTIME_SX: usize = 0x52;
-        const ACCESSTIME_GX: usize = 0x58;
-
         let now = self.now;
 
         // --- T1: get T1 timer value in T1 encoding ---
@@ -459,10 +542,7 @@ impl Emulator {
         stop = stop.wrapping_add(self.time_offset);
 
         // Read 13-nibble ACCESSTIME from RAM
-        let accesstime_loc = match self.model {
-            Model::Gx => ACCESSTIME_GX,
-            Model::Sx => ACCESSTIME_SX,
-        };
+        let accesstime_loc = (self.entry_points().accesstime - self.ram_range().start) as usize;
         let mut access_time: u64 = 0;
         for i in (0..13).rev() {
             access_time <<= 4;
@@ -534,6 +614,15 @@ impl Emulator {
     // Keyboard — do_in (port of actions.c)
     // -----------------------------------------------------------------------
 
+    /// Queue a key event from the host (see `Keyboard::push_key_event`).
+    /// Recorded for replay while time travel is on, and into the key
+    /// macro being recorded.
+    pub fn push_key_event(&mut self, code: u32) {
+        self.record_key(code);
+        self.record_macro_key(code);
+        self.keyboard.push_key_event(code);
+    }
+
     pub fn do_in(&mut self) {
         let mut out: i32 = 0;
         for i in (0..=2).rev() {
@@ -632,7 +721,7 @@ impl Emulator {
 
             if !wake {
                 self.interrupt_called = false;
-                self.serial.receive_char();
+                self.serial_receive();
                 if self.interrupt_called {
                     wake = true;
                 }
@@ -649,12 +738,28 @@ impl Emulator {
         }
 
         if wake {
-            self.is_shutdown = false;
-            self.timers.stop_timer(IDLE_TIMER, now);
-            self.timers.start_timer(RUN_TIMER, now);
+            self.wake_up(now);
         }
     }
 
+    /// Leave SHUTDN and restart the run timer.
+    pub fn wake_up(&mut self, now: f64) {
+        if !self.is_shutdown {
+            return;
+        }
+        self.is_shutdown = false;
+        self.timers.stop_timer(IDLE_TIMER, now);
+        self.timers.start_timer(RUN_TIMER, now);
+    }
+
+    /// Asleep with no key held or queued: the calculator is waiting for
+    /// input.
+    pub fn is_idle(&self) -> bool {
+        self.is_shutdown
+            && self.keyboard.event_queue.is_empty()
+            && self.saturn.keybuf.rows.iter().all(|&r| r == 0)
+    }
+
     // -----------------------------------------------------------------------
     // Interrupts (port of actions.c)
     // -----------------------------------------------------------------------
@@ -709,6 +814,22 @@ impl Emulator {
         }
     }
 
+    // -----------------------------------------------------------------------
+    // Serial (port of serial.c transmit_char / receive_char)
+    // -----------------------------------------------------------------------
+
+    pub fn serial_transmit(&mut self) {
+        if self.serial.transmit_char(&mut self.saturn) {
+            self.do_interrupt();
+        }
+    }
+
+    pub fn serial_receive(&mut self) {
+        if self.serial.receive_char(&mut self.saturn) {
+            self.do_interrupt();
+        }
+    }
+
     // -----------------------------------------------------------------------
     // Display
     // -----------------------------------------------------------------------
@@ -758,6 +879,15 @@ impl Emulator {
             self.device.baud_touched = false;
             self.serial.set_baud(self.saturn.baud);
         }
+        if self.device.card_ctrl_touched {
+            self.device.card_ctrl_touched = false;
+            if self.saturn.card_ctrl & 0x01 != 0 {
+                self.do_interrupt();
+            }
+        }
+        if self.device.card_status_touched {
+            self.device.card_status_touched = false;
+        }
         if self.device.ioc_touched {
             self.device.ioc_touched = false;
             if (self.saturn.io_ctrl & 0x02 != 0) && (self.saturn.rcs & 0x01 != 0) {
@@ -766,11 +896,11 @@ impl Emulator {
         }
         if self.device.rbr_touched {
             self.device.rbr_touched = false;
-            self.serial.receive_char();
+            self.serial_receive();
         }
         if self.device.tbr_touched {
             self.device.tbr_touched = false;
-            self.serial.transmit_char();
+            self.serial_transmit();
         }
         if self.device.t1_touched {
             self.saturn.t1_instr = 0;
@@ -844,9 +974,9 @@ impl Emulator {
         // Receive
         self.sched.sched_receive -= steps;
         if self.sched.sched_receive <= 0 {
-            self.sched.sched_receive = SCHED_RECEIVE;
+            self.sched.sched_receive = self.serial.receive_period();
             if (self.saturn.rcs & 0x01) == 0 {
-                self.serial.receive_char();
+                self.serial_receive();
             }
         }
         if self.sched.sched_receive < self.sched.schedule_event {
@@ -970,7 +1100,25 @@ impl Emulator {
     // Frame callback (port of main_wasm.c frame_callback)
     // -----------------------------------------------------------------------
 
-    pub fn run_frame(&mut self, elapsed_ms: f64, now: f64) {
+    /// Returns why execution stopped when the debugger halts it: a
+    /// breakpoint, a finished step-over/step-out, or an illegal opcode.
+    /// On the virtual clock `now` is ignored and `elapsed_ms` only decides
+    /// how many frames of emulated time to run.
+    pub fn run_frame(&mut self, elapsed_ms: f64, now: f64) -> Option<StopReason> {
+        if self.vclock.is_some() {
+            return self.run_virtual_frame(elapsed_ms);
+        }
+        // Time run ahead by run_headless is paid back as the host catches
+        // up: emulated time moves at half speed until the calendar agrees
+        // with the host again, and never backwards.
+        if self.clock_skew > 0.0 {
+            self.clock_skew = (self.clock_skew - elapsed_ms.max(0.0) / 2000.0).max(0.0);
+        }
+        self.host_frame(elapsed_ms, (now + self.clock_skew).max(self.now))
+    }
+
+    /// A host-clock frame at emulated time `now`.
+    fn host_frame(&mut self, elapsed_ms: f64, now: f64) -> Option<StopReason> {
         self.now = now;
 
         // Cap elapsed time to avoid huge bursts after tab switch
@@ -994,29 +1142,131 @@ impl Emulator {
         if target < 1 {
             target = 1;
         }
+        self.run_slice(target, now)
+    }
+
+    /// The body of a frame once its time is known: run up to `target`
+    /// instructions unless halted or asleep.
+    fn run_slice(&mut self, target: i32, now: f64) -> Option<StopReason> {
+        if self.halted().is_some() {
+            return None;
+        }
+        // Macro keys go in first, as host keys pushed before the frame do.
+        self.macro_frame();
+        self.record_frame();
+        if self.serial.has_transfer() {
+            self.poll_transfer();
+        }
 
         self.got_alarm = true;
 
         if self.is_shutdown {
             self.do_shutdown_check(now);
-            return;
+            return None;
         }
 
         for _ in 0..target {
-            self.speaker.instr_count += 1;
-            self.sched.instructions += 1;
-            self.step_instruction();
-
-            if self.sched.schedule_event <= 0 {
-                self.schedule(now);
-            } else {
-                self.sched.schedule_event -= 1;
+            if self.debugger.armed {
+                if let Some(reason) = self.debug_check() {
+                    return Some(self.stop(reason));
+                }
+            }
+            let pc = self.saturn.pc;
+            if self.execute_instruction(now)
+                && (self.debugger.armed || self.debugger.stop_on_illegal)
+            {
+                let reason = self.illegal_stop(pc);
+                return Some(self.stop(reason));
             }
 
             if self.is_shutdown {
                 break;
             }
         }
+        None
+    }
+
+    fn run_virtual_frame(&mut self, elapsed_ms: f64) -> Option<StopReason> {
+        if self.halted().is_some() {
+            return None;
+        }
+        let clock = self.vclock.as_mut().unwrap();
+        clock.add_host_time(elapsed_ms.min(100.0));
+        while let Some(end) = self.vclock.as_mut().unwrap().next_frame() {
+            let cycles = self.vclock.as_ref().unwrap().cycles;
+            let target = i32::try_from(end - cycles).unwrap_or(i32::MAX);
+            if let Some(reason) = self.run_slice(target, self.now) {
+                self.vclock.as_mut().unwrap().clear_host_time();
+                return Some(reason);
+            }
+            // Asleep or not, the frame lasts until `end`.
+            let clock = self.vclock.as_mut().unwrap();
+            clock.cycles = end;
+            self.now = clock.now();
+        }
+        None
+    }
+
+    /// Execute one instruction and run the scheduler after it. Returns
+    /// true if the opcode was illegal or a TRAP.
+    pub fn execute_instruction(&mut self, now: f64) -> bool {
+        let now = match self.vclock.as_mut() {
+            Some(clock) => {
+                clock.cycles += 1;
+                self.now = clock.now();
+                self.now
+            }
+            None => now,
+        };
+        self.speaker.instr_count += 1;
+        self.sched.instructions += 1;
+        self.debugger.insn_pc = self.saturn.pc;
+        let stop = if self.tracer.is_some() {
+            self.step_traced()
+        } else {
+            self.step_instruction()
+        };
+
+        if self.sched.schedule_event <= 0 {
+            self.schedule(now);
+        } else {
+            self.sched.schedule_event -= 1;
+        }
+        if self.timetravel.is_some() {
+            self.time_travel_tick();
+        }
+        stop
+    }
+
+    /// Run frames back to back on a fabricated clock until `done` returns
+    /// true or `max_secs` of emulated time have passed. Returns whether
+    /// `done` was reached. Later host-driven frames continue from the
+    /// emulated time reached here, at half speed until the host has caught
+    /// up, so the calendar does not stay ahead.
+    pub fn run_headless(
+        &mut self,
+        max_secs: f64,
+        mut done: impl FnMut(&mut Emulator) -> bool,
+    ) -> bool {
+        const FRAME_MS: f64 = 16.0;
+        let start = self.now;
+        let mut reached = done(self);
+        while !reached && self.now - start < max_secs {
+            if self.vclock.is_some() {
+                self.run_virtual_frame(FRAME_MS);
+            } else {
+                self.host_frame(FRAME_MS, self.now + FRAME_MS / 1000.0);
+            }
+            reached = done(self);
+            // The virtual clock stands still while halted.
+            if self.vclock.is_some() && self.halted().is_some() {
+                break;
+            }
+        }
+        if self.vclock.is_none() {
+            self.clock_skew += self.now - start;
+        }
+        reached
     }
 
     // -----------------------------------------------------------------------
@@ -1055,3 +1305,27 @@ impl Emulator {
         self.speaker.get_frequency()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::boot_gx;
+    use crate::vars::crc;
+
+    #[test]
+    fn test_io_reads_skip_crc() {
+        let mut emu = boot_gx();
+        // Memory reads feed the CRC register, as DAT0/DAT1 reads do.
+        emu.saturn.crc = 0;
+        let nibbles: Vec<u8> = (0x80000..0x80010).map(|a| emu.read_nibble_crc(a)).collect();
+        assert_eq!(emu.saturn.crc, crc(&nibbles));
+        // I/O register reads leave it alone, the CRC register included.
+        let before = emu.saturn.crc;
+        let read: Vec<u8> = (0x104..0x108).map(|a| emu.read_nibble_crc(a)).collect();
+        let expect: Vec<u8> = (0..4).map(|i| (before >> (4 * i)) as u8 & 0xf).collect();
+        assert_eq!(read, expect);
+        for a in 0x100..0x140 {
+            emu.read_nibble_crc(a);
+        }
+        assert_eq!(emu.saturn.crc, before);
+    }
+}
@@ -0,0 +1,718 @@
+// GDB remote serial protocol stub — lets gdb and other RSP front-ends drive
+// the Saturn core over a local TCP socket (native builds only).
+//
+// Registers are described by a custom target description (target.xml,
+// served through qXfer): A-D and R0-R4 as 64-bit values (nibble 0 in the low
+// bits, little-endian), D0/D1/PC 32-bit, then P, ST, carry, the hardware
+// status bits, the return stack pointer and the eight return stack slots.
+//
+// Memory is addressed in nibbles: GDB address N is Saturn address N and each
+// "byte" holds one nibble. Reads go through the MMU without side effects
+// (`peek_nibble`); writes go through `write_nibble`, so ROM stays read-only.
+//
+// Z0/Z1 map to debugger breakpoints and Z2-Z4 to halting watchpoints. While
+// continuing the emulator runs headless frames paced to real time and a
+// ^C (0x03) from the client halts it.
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::debugger::{StopReason, Watch};
+use crate::emulator::Emulator;
+use crate::types::NR_PSTAT;
+
+/// Emulated (and real) time per frame while continuing.
+const FRAME_SECS: f64 = 0.016;
+
+/// Register name, size in bits and GDB type, in register-number order.
+const REGISTERS: [(&str, u32, &str); 25] = [
+    ("a", 64, "uint64"),
+    ("b", 64, "uint64"),
+    ("c", 64, "uint64"),
+    ("d", 64, "uint64"),
+    ("r0", 64, "uint64"),
+    ("r1", 64, "uint64"),
+    ("r2", 64, "uint64"),
+    ("r3", 64, "uint64"),
+    ("r4", 64, "uint64"),
+    ("d0", 32, "data_ptr"),
+    ("d1", 32, "data_ptr"),
+    ("pc", 32, "code_ptr"),
+    ("p", 8, "uint8"),
+    ("st", 16, "uint16"),
+    ("carry", 8, "uint8"),
+    ("hst", 8, "uint8"),
+    ("rstkp", 8, "int8"),
+    ("rstk0", 32, "code_ptr"),
+    ("rstk1", 32, "code_ptr"),
+    ("rstk2", 32, "code_ptr"),
+    ("rstk3", 32, "code_ptr"),
+    ("rstk4", 32, "code_ptr"),
+    ("rstk5", 32, "code_ptr"),
+    ("rstk6", 32, "code_ptr"),
+    ("rstk7", 32, "code_ptr"),
+];
+
+const PC_REGNUM: usize = 11;
+
+/// The target description served as `target.xml`.
+pub fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n\
+         <feature name=\"org.rust48.saturn\">\n",
+    );
+    for (i, (name, bits, ty)) in REGISTERS.iter().enumerate() {
+        xml += &format!("<reg name=\"{name}\" bitsize=\"{bits}\" type=\"{ty}\" regnum=\"{i}\"/>\n");
+    }
+    xml + "</feature>\n</target>\n"
+}
+
+/// What the connection loop should do after a packet.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Send this reply.
+    Reply(String),
+    /// Resume and reply when the emulator stops.
+    Continue,
+    /// Reply OK and close the connection, leaving the emulator running.
+    Detach,
+    /// Close the connection without a reply.
+    Kill,
+}
+
+/// Protocol state for one connection.
+#[derive(Default)]
+pub struct GdbStub {
+    no_ack: bool,
+    /// Debugger ids of the breakpoints and watchpoints GDB inserted, by
+    /// Z-packet type and address.
+    points: HashMap<(u8, i32), u32>,
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(s: &str) -> Option<i32> {
+    u32::from_str_radix(s, 16).ok().map(|v| v as i32)
+}
+
+/// `addr,len` as in m/M/Z packets.
+fn addr_len(s: &str) -> Option<(i32, i32)> {
+    let (a, l) = s.split_once(',')?;
+    Some((parse_hex(a)?, parse_hex(l)?))
+}
+
+fn checksum(payload: &[u8]) -> u8 {
+    payload.iter().fold(0u8, |s, &b| s.wrapping_add(b))
+}
+
+/// Frame `payload` as `$...#cs`, escaping the protocol's special bytes.
+pub fn frame(payload: &str) -> Vec<u8> {
+    let mut body = Vec::with_capacity(payload.len() + 4);
+    for &b in payload.as_bytes() {
+        if matches!(b, b'#' | b'$' | b'}' | b'*') {
+            body.extend_from_slice(&[b'}', b ^ 0x20]);
+        } else {
+            body.push(b);
+        }
+    }
+    let mut out = vec![b'$'];
+    out.extend_from_slice(&body);
+    out.extend_from_slice(format!("#{:02x}", checksum(&body)).as_bytes());
+    out
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `n` as little-endian bytes.
+    fn read_register(emu: &Emulator, n: usize) -> Option<Vec<u8>> {
+        let s = &emu.saturn;
+        let regs = [s.a, s.b, s.c, s.d, s.r0, s.r1, s.r2, s.r3, s.r4];
+        Some(match n {
+            0..=8 => regs[n].chunks(2).map(|p| p[0] | (p[1] << 4)).collect(),
+            9 => (s.d0 as u32).to_le_bytes().to_vec(),
+            10 => (s.d1 as u32).to_le_bytes().to_vec(),
+            11 => (s.pc as u32).to_le_bytes().to_vec(),
+            12 => vec![s.p],
+            13 => {
+                let st = (0..NR_PSTAT).fold(0u16, |v, i| v | ((s.pstat[i] & 1) as u16) << i);
+                st.to_le_bytes().to_vec()
+            }
+            14 => vec![s.carry],
+            15 => vec![s.xm | (s.sb << 1) | (s.sr << 2) | (s.mp << 3)],
+            16 => vec![s.rstkp as i8 as u8],
+            17..=24 => (s.rstk[n - 17] as u32).to_le_bytes().to_vec(),
+            _ => return None,
+        })
+    }
+
+    fn write_register(emu: &mut Emulator, n: usize, bytes: &[u8]) -> bool {
+        let Some(&(_, bits, _)) = REGISTERS.get(n) else {
+            return false;
+        };
+        if bytes.len() != bits as usize / 8 {
+            return false;
+        }
+        let word = bytes.iter().rev().fold(0u64, |v, &b| (v << 8) | b as u64);
+        let s = &mut emu.saturn;
+        match n {
+            0..=8 => {
+                let reg = match n {
+                    0 => &mut s.a,
+                    1 => &mut s.b,
+                    2 => &mut s.c,
+                    3 => &mut s.d,
+                    4 => &mut s.r0,
+                    5 => &mut s.r1,
+                    6 => &mut s.r2,
+                    7 => &mut s.r3,
+                    _ => &mut s.r4,
+                };
+                for (i, nib) in reg.iter_mut().enumerate() {
+                    *nib = ((word >> (4 * i)) & 0xf) as u8;
+                }
+            }
+            9 => s.d0 = (word & 0xfffff) as i32,
+            10 => s.d1 = (word & 0xfffff) as i32,
+            11 => s.pc = (word & 0xfffff) as i32,
+            12 => s.p = (word & 0xf) as u8,
+            13 => {
+                for i in 0..NR_PSTAT {
+                    s.pstat[i] = ((word >> i) & 1) as u8;
+                }
+            }
+            14 => s.carry = (word & 1) as u8,
+            15 => {
+                s.xm = (word & 1) as u8;
+                s.sb = ((word >> 1) & 1) as u8;
+                s.sr = ((word >> 2) & 1) as u8;
+                s.mp = ((word >> 3) & 1) as u8;
+            }
+            16 => s.rstkp = (bytes[0] as i8).clamp(-1, 7) as i16,
+            _ => s.rstk[n - 17] = (word & 0xfffff) as i32,
+        }
+        true
+    }
+
+    /// Stop reply for `reason`, with the PC expedited.
+    pub fn stop_reply(&self, emu: &Emulator, reason: StopReason) -> String {
+        let signal = match reason {
+            StopReason::Halt { .. } => 2,
+            StopReason::Illegal { .. } => 4,
+            _ => 5,
+        };
+        let mut reply = format!("T{signal:02x}");
+        if let StopReason::Watchpoint { id, addr, .. } = reason {
+            let kind = match emu.watchpoints().iter().find(|w| w.id == id) {
+                Some(w) if w.watch == Watch::Read => "rwatch",
+                Some(w) if w.watch == Watch::ReadWrite => "awatch",
+                _ => "watch",
+            };
+            reply += &format!("{kind}:{addr:x};");
+        } else if let StopReason::Breakpoint { .. } = reason {
+            reply += "swbreak:;";
+        }
+        let pc = Self::read_register(emu, PC_REGNUM).unwrap_or_default();
+        reply + &format!("{PC_REGNUM:02x}:{};thread:1;", hex_bytes(&pc))
+    }
+
+    fn last_stop(&self, emu: &Emulator) -> String {
+        match emu.halted() {
+            Some(reason) => self.stop_reply(emu, reason),
+            None => "S05".to_string(),
+        }
+    }
+
+    /// Insert (`insert`) or remove a Z-packet point.
+    fn point(&mut self, emu: &mut Emulator, args: &str, insert: bool) -> String {
+        let mut parts = args.splitn(3, ',');
+        let (Some(kind), Some(addr), Some(len)) = (parts.next(), parts.next(), parts.next()) else {
+            return "E01".to_string();
+        };
+        let (Some(kind), Some(addr), Some(len)) =
+            (kind.parse::<u8>().ok(), parse_hex(addr), parse_hex(len))
+        else {
+            return "E01".to_string();
+        };
+        let key = (kind, addr & 0xfffff);
+        if !insert {
+            let removed = match self.points.remove(&key) {
+                Some(id) if kind <= 1 => emu.remove_breakpoint(id),
+                Some(id) => emu.remove_watchpoint(id),
+                None => false,
+            };
+            return if removed { "OK" } else { "E02" }.to_string();
+        }
+        let added = match kind {
+            0 | 1 => emu.add_breakpoint(Some(key.1), None),
+            2 => emu.add_watchpoint(key.1, len.max(1), Watch::Write, true),
+            3 => emu.add_watchpoint(key.1, len.max(1), Watch::Read, true),
+            4 => emu.add_watchpoint(key.1, len.max(1), Watch::ReadWrite, true),
+            _ => return String::new(),
+        };
+        match added {
+            Ok(id) => {
+                if let Some(old) = self.points.insert(key, id) {
+                    // Re-inserted: drop the duplicate.
+                    match kind {
+                        0 | 1 => emu.remove_breakpoint(old),
+                        _ => emu.remove_watchpoint(old),
+                    };
+                }
+                "OK".to_string()
+            }
+            Err(_) => "E03".to_string(),
+        }
+    }
+
+    /// Remove everything GDB inserted.
+    pub fn clear_points(&mut self, emu: &mut Emulator) {
+        for ((kind, _), id) in self.points.drain() {
+            if kind <= 1 {
+                emu.remove_breakpoint(id);
+            } else {
+                emu.remove_watchpoint(id);
+            }
+        }
+    }
+
+    /// Handle one packet payload.
+    pub fn handle(&mut self, emu: &mut Emulator, packet: &str) -> Action {
+        let reply = |s: String| Action::Reply(s);
+        let ok = || Action::Reply("OK".to_string());
+        let err = |n: u8| Action::Reply(format!("E{n:02x}"));
+        let (cmd, args) = packet.split_at(packet.len().min(1));
+        match cmd {
+            "?" => reply(self.last_stop(emu)),
+            "g" => reply(
+                (0..REGISTERS.len())
+                    .filter_map(|n| Self::read_register(emu, n))
+                    .map(|b| hex_bytes(&b))
+                    .collect(),
+            ),
+            "G" => {
+                let Some(mut bytes) = parse_hex_bytes(args) else {
+                    return err(1);
+                };
+                for (n, &(_, bits, _)) in REGISTERS.iter().enumerate() {
+                    let len = bits as usize / 8;
+                    if bytes.len() < len {
+                        break;
+                    }
+                    let rest = bytes.split_off(len);
+                    Self::write_register(emu, n, &bytes);
+                    bytes = rest;
+                }
+                ok()
+            }
+            "p" => match parse_hex(args).and_then(|n| Self::read_register(emu, n as usize)) {
+                Some(b) => reply(hex_bytes(&b)),
+                None => err(1),
+            },
+            "P" => {
+                let Some((n, v)) = args.split_once('=') else {
+                    return err(1);
+                };
+                match (parse_hex(n), parse_hex_bytes(v)) {
+                    (Some(n), Some(v)) if Self::write_register(emu, n as usize, &v) => ok(),
+                    _ => err(1),
+                }
+            }
+            "m" => match addr_len(args) {
+                Some((addr, len)) if (0..=0x10000).contains(&len) => reply(
+                    (0..len)
+                        .map(|i| format!("{:02x}", emu.peek_nibble(addr + i)))
+                        .collect(),
+                ),
+                _ => err(1),
+            },
+            "M" => {
+                let Some((range, data)) = args.split_once(':') else {
+                    return err(1);
+                };
+                match (addr_len(range), parse_hex_bytes(data)) {
+                    (Some((addr, len)), Some(data)) if data.len() == len as usize => {
+                        for (i, b) in data.iter().enumerate() {
+                            emu.write_nibble(addr + i as i32, (b & 0xf) as i32);
+                        }
+                        ok()
+                    }
+                    _ => err(1),
+                }
+            }
+            "c" => {
+                if let Some(addr) = parse_hex(args) {
+                    emu.saturn.pc = addr & 0xfffff;
+                }
+                Action::Continue
+            }
+            "s" => {
+                if let Some(addr) = parse_hex(args) {
+                    emu.saturn.pc = addr & 0xfffff;
+                }
+                let reason = emu.step_into();
+                reply(self.stop_reply(emu, reason))
+            }
+            "Z" => reply(self.point(emu, args, true)),
+            "z" => reply(self.point(emu, args, false)),
+            "H" | "T" => ok(),
+            "D" => Action::Detach,
+            "k" => Action::Kill,
+            _ => self.query(packet),
+        }
+    }
+
+    /// General queries (q/Q packets).
+    fn query(&mut self, packet: &str) -> Action {
+        let reply = |s: &str| Action::Reply(s.to_string());
+        if packet.starts_with("qSupported") {
+            return reply(
+                "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+",
+            );
+        }
+        if let Some(rest) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((off, len)) = addr_len(rest) else {
+                return reply("E01");
+            };
+            let xml = target_xml();
+            let (off, len) = (off as usize, len as usize);
+            let chunk = xml.get(off.min(xml.len())..(off + len).min(xml.len()));
+            let chunk = chunk.unwrap_or("");
+            let more = off + len < xml.len();
+            return Action::Reply(format!("{}{chunk}", if more { 'm' } else { 'l' }));
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                reply("OK")
+            }
+            "qAttached" => reply("1"),
+            "qC" => reply("QC1"),
+            "qfThreadInfo" => reply("m1"),
+            "qsThreadInfo" => reply("l"),
+            _ => reply(""),
+        }
+    }
+}
+
+/// A read from the client: a packet payload or a ^C interrupt.
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut b = [0u8];
+    match stream.read(&mut b)? {
+        0 => Ok(None),
+        _ => Ok(Some(b[0])),
+    }
+}
+
+impl GdbStub {
+    /// Next packet from the client, acknowledging it unless in no-ack
+    /// mode. `None` when the client hung up.
+    fn read_packet(&mut self, stream: &mut TcpStream) -> io::Result<Option<Incoming>> {
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(0x03) => return Ok(Some(Incoming::Interrupt)),
+                Some(b'$') => {}
+                Some(_) => continue, // acks and line noise
+            }
+            let mut body = Vec::new();
+            let sum = loop {
+                match read_byte(stream)? {
+                    None => return Ok(None),
+                    Some(b'#') => {
+                        let (Some(h), Some(l)) = (read_byte(stream)?, read_byte(stream)?) else {
+                            return Ok(None);
+                        };
+                        let hex = [h, l];
+                        break std::str::from_utf8(&hex)
+                            .ok()
+                            .and_then(|s| u8::from_str_radix(s, 16).ok());
+                    }
+                    Some(b) => body.push(b),
+                }
+            };
+            if !self.no_ack {
+                let good = sum == Some(checksum(&body));
+                stream.write_all(if good { b"+" } else { b"-" })?;
+                if !good {
+                    continue;
+                }
+            }
+            // Unescape }x and drop run-length encoding, which clients
+            // don't send.
+            let mut payload = Vec::with_capacity(body.len());
+            let mut it = body.into_iter();
+            while let Some(b) = it.next() {
+                payload.push(if b == b'}' {
+                    it.next().unwrap_or(0) ^ 0x20
+                } else {
+                    b
+                });
+            }
+            return Ok(Some(Incoming::Packet(
+                String::from_utf8_lossy(&payload).into_owned(),
+            )));
+        }
+    }
+
+    fn send(&self, stream: &mut TcpStream, payload: &str) -> io::Result<()> {
+        stream.write_all(&frame(payload))?;
+        if !self.no_ack {
+            // Wait for the client's ack; a NAK asks for a resend.
+            loop {
+                match read_byte(stream)? {
+                    Some(b'+') | None => break,
+                    Some(b'-') => stream.write_all(&frame(payload))?,
+                    Some(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run until the debugger stops or the client sends ^C. `None` if the
+    /// client hung up meanwhile.
+    fn run(
+        &mut self,
+        emu: &mut Emulator,
+        stream: &mut TcpStream,
+    ) -> io::Result<Option<StopReason>> {
+        emu.resume();
+        stream.set_nonblocking(true)?;
+        let result = loop {
+            emu.run_headless(FRAME_SECS, |_| false);
+            if let Some(reason) = emu.halted() {
+                break Ok(Some(reason));
+            }
+            match read_byte(stream) {
+                Ok(Some(0x03)) => {
+                    emu.halt();
+                    break Ok(emu.halted());
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => break Ok(None),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_secs_f64(FRAME_SECS));
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        stream.set_nonblocking(false)?;
+        result
+    }
+
+    /// Serve one client until it detaches, kills or hangs up. The emulator
+    /// is halted while the client is attached and left running after; it
+    /// stops on illegal opcodes only while attached.
+    pub fn serve_connection(
+        &mut self,
+        emu: &mut Emulator,
+        mut stream: TcpStream,
+    ) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        // The client hears of illegal opcodes and TRAPs as signals.
+        let stop_on_illegal = emu.debugger.stop_on_illegal;
+        emu.set_stop_on_illegal(true);
+        emu.halt();
+        let result = self.connection_loop(emu, &mut stream);
+        self.clear_points(emu);
+        emu.set_stop_on_illegal(stop_on_illegal);
+        emu.resume();
+        result
+    }
+
+    fn connection_loop(&mut self, emu: &mut Emulator, stream: &mut TcpStream) -> io::Result<()> {
+        loop {
+            let packet = match self.read_packet(stream)? {
+                None => return Ok(()),
+                Some(Incoming::Interrupt) => {
+                    // Already halted between packets.
+                    continue;
+                }
+                Some(Incoming::Packet(p)) => p,
+            };
+            match self.handle(emu, &packet) {
+                Action::Reply(r) => self.send(stream, &r)?,
+                Action::Continue => match self.run(emu, stream)? {
+                    Some(reason) => {
+                        let reply = self.stop_reply(emu, reason);
+                        self.send(stream, &reply)?;
+                    }
+                    None => return Ok(()),
+                },
+                Action::Detach => {
+                    self.send(stream, "OK")?;
+                    return Ok(());
+                }
+                Action::Kill => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Listen on `addr` (e.g. "127.0.0.1:1234") and serve one GDB client.
+pub fn serve(emu: &mut Emulator, addr: impl ToSocketAddrs) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+    GdbStub::new().serve_connection(emu, stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(frame("OK"), b"$OK#9a");
+        assert_eq!(frame("a#b"), b"$a}\x03b#43".to_vec());
+        assert_eq!(parse_hex_bytes("0a1B"), Some(vec![0x0a, 0x1b]));
+        assert_eq!(parse_hex_bytes("0a1"), None);
+        assert!(target_xml().contains("<reg name=\"rstk7\" bitsize=\"32\""));
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let mut emu = crate::test_support::boot_gx();
+        let mut gdb = GdbStub::new();
+        let mut ask = |emu: &mut Emulator, p: &str| match gdb.handle(emu, p) {
+            Action::Reply(r) => r,
+            other => panic!("{p}: {other:?}"),
+        };
+
+        emu.saturn.a = [1, 2, 3, 4, 5, 6, 7, 8, 9, 0xa, 0xb, 0xc, 0xd, 0xe, 0xf, 0];
+        emu.saturn.pc = 0x12345;
+        assert_eq!(ask(&mut emu, "p0"), "21436587a9cbed0f");
+        assert_eq!(ask(&mut emu, "pb"), "45230100");
+        assert_eq!(ask(&mut emu, "Pb=00000900"), "OK");
+        assert_eq!(emu.saturn.pc, 0x90000);
+        assert_eq!(ask(&mut emu, "P2=efcdab8967452301"), "OK");
+        assert_eq!(&emu.saturn.c[..4], &[0xf, 0xe, 0xd, 0xc]);
+        assert_eq!(ask(&mut emu, "pd").len(), 4);
+        assert_eq!(ask(&mut emu, "p99"), "E01");
+
+        let g = ask(&mut emu, "g");
+        assert_eq!(g.len(), 2 * (9 * 8 + 3 * 4 + 1 + 2 + 1 + 1 + 1 + 8 * 4));
+        assert_eq!(ask(&mut emu, &format!("G{g}")), "OK");
+        assert_eq!(ask(&mut emu, "g"), g);
+
+        assert_eq!(ask(&mut emu, "M90000,3:0a0b0c"), "OK");
+        assert_eq!(ask(&mut emu, "m90000,3"), "0a0b0c");
+        // ROM is not writable through the MMU.
+        let rom = ask(&mut emu, "m0,2");
+        assert_eq!(ask(&mut emu, "M0,2:0f0f"), "OK");
+        assert_eq!(ask(&mut emu, "m0,2"), rom);
+
+        let xml = ask(&mut emu, "qXfer:features:read:target.xml:0,40");
+        assert!(xml.starts_with("m<?xml"));
+        assert_eq!(xml.len(), 0x41);
+        let tail = ask(&mut emu, "qXfer:features:read:target.xml:0,10000");
+        assert!(tail.starts_with('l') && tail.ends_with("</target>\n"));
+        assert_eq!(ask(&mut emu, "vMustReplyEmpty"), "");
+    }
+
+    /// Scripted client: raw packets over a real socket.
+    #[test]
+    fn test_scripted_session() {
+        let src = "
+        A=0     W
+LOOP    A=A+1   A
+        GOTO    LOOP
+";
+        let mut emu = crate::test_support::boot_gx();
+        let base = 0x90000;
+        let code = crate::asm::assemble(src, base).unwrap();
+        for (i, &n) in code.iter().enumerate() {
+            emu.write_nibble(base + i as i32, n as i32);
+        }
+        emu.saturn.pc = base;
+        emu.is_shutdown = false;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = std::thread::spawn(move || {
+            let mut s = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let mut transcript = Vec::new();
+            let mut send = |s: &mut TcpStream, p: &str, acked: bool| {
+                s.write_all(&frame(p)).unwrap();
+                let mut buf = Vec::new();
+                let mut b = [0u8];
+                if acked {
+                    s.read_exact(&mut b).unwrap();
+                    assert_eq!(b[0], b'+');
+                }
+                loop {
+                    s.read_exact(&mut b).unwrap();
+                    if b[0] == b'$' {
+                        break;
+                    }
+                }
+                loop {
+                    s.read_exact(&mut b).unwrap();
+                    if b[0] == b'#' {
+                        break;
+                    }
+                    buf.push(b[0]);
+                }
+                let mut sum = [0u8; 2];
+                s.read_exact(&mut sum).unwrap();
+                if acked {
+                    s.write_all(b"+").unwrap();
+                }
+                transcript.push(String::from_utf8(buf).unwrap());
+            };
+            send(&mut s, "qSupported:swbreak+", true);
+            send(&mut s, "QStartNoAckMode", true);
+            send(&mut s, "?", false);
+            send(&mut s, "s", false);
+            send(&mut s, "Z0,90003,2", false);
+            send(&mut s, "c", false);
+            send(&mut s, "z0,90003,2", false);
+            send(&mut s, "Z2,90000,1", false);
+            send(&mut s, "M90000,1:05", false);
+            send(&mut s, "p0", false);
+            s.write_all(&frame("D")).unwrap();
+            transcript
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new().serve_connection(&mut emu, stream).unwrap();
+        let t = client.join().unwrap();
+        assert!(t[0].contains("qXfer:features:read+"));
+        assert_eq!(t[1], "OK");
+        assert_eq!(t[2], "T020b:00000900;thread:1;");
+        assert_eq!(t[3], "T050b:03000900;thread:1;");
+        assert_eq!(t[4], "OK");
+        assert_eq!(t[5], "T05swbreak:;0b:03000900;thread:1;");
+        assert_eq!(t[6], "OK");
+        assert_eq!(t[7], "OK");
+        assert_eq!(t[8], "OK");
+        // A was incremented once before the breakpoint stopped it.
+        assert_eq!(t[9], "0100000000000000");
+        // Detached: our points are gone and the emulator runs again.
+        assert!(emu.breakpoints().is_empty() && emu.watchpoints().is_empty());
+        assert_eq!(emu.halted(), None);
+    }
+}
@@ -0,0 +1,757 @@
+// Kermit host — the far end of the firmware's SEND / RECV / SERVER commands.
+// Runs as a `SerialTransport` on the wire port. `Emulator::send_file` and
+// `Emulator::receive_file` attach it and drive the emulator headless until
+// the transfer finishes; `start_send_file` and `start_receive_files` attach
+// it and return, leaving frames to carry the transfer on.
+//
+// Supports block check types 1-3, control quoting, 8th-bit prefixing and
+// repeat counts as negotiated in the S / Y exchange. Extended-length and
+// sliding-window packets are not used by the HP-48.
+
+use std::collections::VecDeque;
+
+use crate::emulator::Emulator;
+use crate::serial::{HostTransfer, SerialTransport, TransferStatus};
+
+const SOH: u8 = 0x01;
+const CR: u8 = 0x0d;
+
+/// Longest packet we accept (value of the LEN field).
+const MAXL: u8 = 94;
+/// Emulated seconds without an answer before a packet is retransmitted.
+const TIMEOUT_SECS: f64 = 5.0;
+const MAX_RETRIES: u32 = 10;
+/// Emulated seconds a blocking transfer may take before giving up.
+const TRANSFER_BUDGET_SECS: f64 = 600.0;
+
+fn tochar(x: u8) -> u8 {
+    x + 32
+}
+
+fn unchar(c: u8) -> u8 {
+    c.wrapping_sub(32)
+}
+
+fn ctl(c: u8) -> u8 {
+    c ^ 64
+}
+
+/// Printable characters allowed as 8th-bit and repeat prefixes.
+fn is_prefix_char(c: u8) -> bool {
+    matches!(c, 33..=62 | 96..=126)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    SendInit,
+    SendFile,
+    SendData,
+    SendEof,
+    SendBreak,
+    RecvInit,
+    RecvFile,
+    RecvData,
+    Complete,
+}
+
+/// Negotiated link parameters.
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    maxl: u8,    // longest packet the calculator accepts
+    rx_qctl: u8, // control prefix used by the calculator
+    qbin: Option<u8>,
+    rept: Option<u8>,
+    chkt: u8,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            maxl: 80,
+            rx_qctl: b'#',
+            qbin: None,
+            rept: None,
+            chkt: 1,
+        }
+    }
+}
+
+/// Our send-init parameters: MAXL TIME NPAD PADC EOL QCTL QBIN CHKT REPT.
+fn our_init(qbin: u8, chkt: u8, rept: u8) -> Vec<u8> {
+    vec![
+        tochar(MAXL),
+        tochar(10),
+        tochar(0),
+        ctl(0),
+        tochar(CR),
+        b'#',
+        qbin,
+        chkt,
+        rept,
+    ]
+}
+
+// ---------------------------------------------------------------------------
+// Packet framing
+// ---------------------------------------------------------------------------
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &b in data {
+        crc ^= b as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn block_check(chars: &[u8], chkt: u8) -> Vec<u8> {
+    match chkt {
+        2 => {
+            let s = chars.iter().map(|&c| c as u32).sum::<u32>() & 0xfff;
+            vec![tochar((s >> 6) as u8 & 0x3f), tochar(s as u8 & 0x3f)]
+        }
+        3 => {
+            let crc = crc16(chars);
+            vec![
+                tochar((crc >> 12) as u8 & 0x0f),
+                tochar((crc >> 6) as u8 & 0x3f),
+                tochar(crc as u8 & 0x3f),
+            ]
+        }
+        _ => {
+            let s = chars.iter().map(|&c| c as u32).sum::<u32>();
+            vec![tochar(((s + ((s & 0xc0) >> 6)) & 0x3f) as u8)]
+        }
+    }
+}
+
+fn build_packet(seq: u8, ptype: u8, data: &[u8], chkt: u8) -> Vec<u8> {
+    let mut p = vec![SOH, tochar(2 + data.len() as u8 + chkt), tochar(seq), ptype];
+    p.extend_from_slice(data);
+    let check = block_check(&p[1..], chkt);
+    p.extend(check);
+    p.push(CR);
+    p
+}
+
+struct Packet {
+    seq: u8,
+    ptype: u8,
+    data: Vec<u8>,
+    good: bool,
+}
+
+/// Split a complete packet (SOH through the last check character).
+fn parse_packet(buf: &[u8], chkt: u8) -> Packet {
+    let len = buf.len();
+    let chk = chkt as usize;
+    let body = &buf[1..len - chk];
+    Packet {
+        seq: unchar(buf[2]) & 0x3f,
+        ptype: buf[3],
+        data: buf[4..len - chk].to_vec(),
+        good: block_check(body, chkt) == buf[len - chk..],
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Data encoding
+// ---------------------------------------------------------------------------
+
+fn encode_byte(out: &mut Vec<u8>, byte: u8, p: &Params) {
+    let mut c = byte;
+    if let Some(q) = p.qbin {
+        if c & 0x80 != 0 {
+            out.push(q);
+            c &= 0x7f;
+        }
+    }
+    let c7 = c & 0x7f;
+    if c7 < 32 || c7 == 127 {
+        out.push(b'#');
+        c = ctl(c);
+    } else if c7 == b'#' || Some(c7) == p.qbin || Some(c7) == p.rept {
+        out.push(b'#');
+    }
+    out.push(c);
+}
+
+/// Encode as much of `data[pos..]` as fits in `limit` characters.
+fn encode_data(data: &[u8], mut pos: usize, limit: usize, p: &Params) -> (Vec<u8>, usize) {
+    let mut out = Vec::new();
+    let mut unit = Vec::new();
+    while pos < data.len() {
+        unit.clear();
+        let byte = data[pos];
+        let mut run = 1;
+        if let Some(r) = p.rept {
+            while pos + run < data.len() && data[pos + run] == byte && run < 94 {
+                run += 1;
+            }
+            if run >= 3 {
+                unit.push(r);
+                unit.push(tochar(run as u8));
+            } else {
+                run = 1;
+            }
+        }
+        encode_byte(&mut unit, byte, p);
+        if out.len() + unit.len() > limit {
+            break;
+        }
+        out.extend_from_slice(&unit);
+        pos += run;
+    }
+    (out, pos)
+}
+
+fn decode_data(data: &[u8], p: &Params) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    let next = |i: &mut usize| {
+        let c = data.get(*i).copied().unwrap_or(b' ');
+        *i += 1;
+        c
+    };
+    while i < data.len() {
+        let mut c = next(&mut i);
+        let mut count = 1;
+        if Some(c) == p.rept {
+            count = unchar(next(&mut i)) as usize;
+            c = next(&mut i);
+        }
+        let mut hi = 0;
+        if Some(c) == p.qbin {
+            hi = 0x80;
+            c = next(&mut i);
+        }
+        if c == p.rx_qctl {
+            c = next(&mut i);
+            if matches!(c & 0x7f, 63..=95) {
+                c = ctl(c);
+            }
+        }
+        out.extend(std::iter::repeat_n(c | hi, count));
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Protocol engine
+// ---------------------------------------------------------------------------
+
+pub struct Kermit {
+    stage: Stage,
+    status: TransferStatus,
+    seq: u8,
+    params: Params,
+    /// Block check in effect; 1 until the S / Y exchange completes.
+    chkt: u8,
+    rx: Vec<u8>,
+    out: VecDeque<u8>,
+    last: Vec<u8>,
+    last_sent: f64,
+    now: f64,
+    retries: u32,
+    // Outgoing file
+    name: String,
+    data: Vec<u8>,
+    pos: usize,
+    // Incoming files
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl Kermit {
+    fn new(stage: Stage) -> Self {
+        Self {
+            stage,
+            status: TransferStatus::Busy,
+            seq: 0,
+            params: Params::default(),
+            chkt: 1,
+            rx: Vec::new(),
+            out: VecDeque::new(),
+            last: Vec::new(),
+            last_sent: 0.0,
+            now: 0.0,
+            retries: 0,
+            name: String::new(),
+            data: Vec::new(),
+            pos: 0,
+            files: Vec::new(),
+        }
+    }
+
+    /// Send `data` to a calculator running RECV or SERVER; it is stored
+    /// under `name`.
+    pub fn sender(name: &str, data: &[u8]) -> Self {
+        let mut k = Self::new(Stage::SendInit);
+        k.name = name.to_string();
+        k.data = data.to_vec();
+        k.send(b'S', &our_init(b'Y', b'3', b'~'));
+        k
+    }
+
+    /// Accept the files a calculator sends with SEND.
+    pub fn receiver() -> Self {
+        Self::new(Stage::RecvInit)
+    }
+
+    /// Files received so far as (name, contents).
+    pub fn files(&self) -> &[(String, Vec<u8>)] {
+        &self.files
+    }
+
+    pub fn take_files(&mut self) -> Vec<(String, Vec<u8>)> {
+        std::mem::take(&mut self.files)
+    }
+
+    fn bump_retries(&mut self) -> bool {
+        self.retries += 1;
+        if self.retries > MAX_RETRIES {
+            self.fail("too many retries".to_string());
+            return false;
+        }
+        true
+    }
+
+    fn fail(&mut self, msg: String) {
+        self.stage = Stage::Complete;
+        self.status = TransferStatus::Failed(msg);
+    }
+
+    fn transmit_packet(&mut self, p: Vec<u8>) {
+        self.out.extend(p.iter().copied());
+        self.last = p;
+        self.last_sent = self.now;
+    }
+
+    /// NAKs are not kept for retransmission: a duplicate packet must be
+    /// answered with the last ACK.
+    fn send_nak(&mut self) {
+        self.out
+            .extend(build_packet(self.seq, b'N', &[], self.chkt));
+        self.last_sent = self.now;
+    }
+
+    fn send(&mut self, ptype: u8, data: &[u8]) {
+        let p = build_packet(self.seq, ptype, data, self.chkt);
+        self.retries = 0;
+        self.transmit_packet(p);
+    }
+
+    fn next_seq(&mut self) {
+        self.seq = (self.seq + 1) & 0x3f;
+    }
+
+    /// Data characters available per packet at the current check type.
+    fn data_limit(&self) -> usize {
+        (self.params.maxl as usize).saturating_sub(2 + self.chkt as usize)
+    }
+
+    /// Parse the calculator's send-init parameters.
+    fn parse_init(&mut self, d: &[u8]) -> Params {
+        let field = |i: usize| d.get(i).copied().unwrap_or(b' ');
+        Params {
+            maxl: match unchar(field(0)) {
+                0 => 80,
+                n => n.min(MAXL),
+            },
+            rx_qctl: match field(5) {
+                b' ' => b'#',
+                c => c,
+            },
+            qbin: None,
+            rept: None,
+            chkt: 1,
+        }
+    }
+
+    fn handle_packet(&mut self) {
+        let ptype = self.rx[3];
+        let chkt = if ptype == b'S' || matches!(self.stage, Stage::SendInit | Stage::RecvInit) {
+            1
+        } else {
+            self.chkt
+        };
+        if self.rx.len() != 2 + unchar(self.rx[1]) as usize || self.rx.len() < 4 + chkt as usize {
+            return;
+        }
+        let pkt = parse_packet(&self.rx, chkt);
+        if pkt.ptype == b'E' && pkt.good {
+            let msg = decode_data(&pkt.data, &self.params);
+            self.fail(String::from_utf8_lossy(&msg).into_owned());
+            return;
+        }
+        match self.stage {
+            Stage::SendInit
+            | Stage::SendFile
+            | Stage::SendData
+            | Stage::SendEof
+            | Stage::SendBreak => self.sender_packet(pkt),
+            Stage::RecvInit | Stage::RecvFile | Stage::RecvData => self.receiver_packet(pkt),
+            Stage::Complete => {}
+        }
+    }
+
+    fn sender_packet(&mut self, pkt: Packet) {
+        if !pkt.good {
+            return;
+        }
+        let acked = match pkt.ptype {
+            b'Y' => pkt.seq == self.seq,
+            // NAK for the next packet implies ACK of this one.
+            b'N' if pkt.seq == (self.seq + 1) & 0x3f => true,
+            b'N' if pkt.seq == self.seq => {
+                if self.bump_retries() {
+                    let p = self.last.clone();
+                    self.transmit_packet(p);
+                }
+                return;
+            }
+            _ => return,
+        };
+        if !acked {
+            return;
+        }
+        match self.stage {
+            Stage::SendInit => {
+                let mut p = self.parse_init(&pkt.data);
+                let field = |i: usize| pkt.data.get(i).copied().unwrap_or(b' ');
+                p.qbin = if is_prefix_char(field(6)) {
+                    Some(field(6))
+                } else {
+                    None
+                };
+                p.chkt = if field(7) == b'3' || field(7) == b'2' {
+                    field(7) - b'0'
+                } else {
+                    1
+                };
+                p.rept = if field(8) == b'~' { Some(b'~') } else { None };
+                self.params = p;
+                self.chkt = p.chkt;
+                self.next_seq();
+                self.stage = Stage::SendFile;
+                let (name, _) = encode_data(self.name.as_bytes(), 0, self.data_limit(), &p);
+                self.send(b'F', &name);
+            }
+            Stage::SendFile | Stage::SendData => {
+                self.next_seq();
+                if self.pos < self.data.len() {
+                    let (chunk, pos) =
+                        encode_data(&self.data, self.pos, self.data_limit(), &self.params);
+                    self.pos = pos;
+                    self.stage = Stage::SendData;
+                    self.send(b'D', &chunk);
+                } else {
+                    self.stage = Stage::SendEof;
+                    self.send(b'Z', &[]);
+                }
+            }
+            Stage::SendEof => {
+                self.next_seq();
+                self.stage = Stage::SendBreak;
+                self.send(b'B', &[]);
+            }
+            Stage::SendBreak => {
+                self.stage = Stage::Complete;
+                self.status = TransferStatus::Done;
+            }
+            _ => {}
+        }
+    }
+
+    fn receiver_packet(&mut self, pkt: Packet) {
+        if !pkt.good {
+            if self.stage != Stage::RecvInit {
+                self.send_nak();
+            }
+            return;
+        }
+        if pkt.seq == (self.seq + 63) & 0x3f && self.stage != Stage::RecvInit {
+            // Duplicate: our ACK was lost.
+            let p = self.last.clone();
+            self.transmit_packet(p);
+            return;
+        }
+        if pkt.seq != self.seq {
+            return;
+        }
+        match (self.stage, pkt.ptype) {
+            (Stage::RecvInit, b'S') => {
+                let mut p = self.parse_init(&pkt.data);
+                let field = |i: usize| pkt.data.get(i).copied().unwrap_or(b' ');
+                let qbin = if is_prefix_char(field(6)) {
+                    p.qbin = Some(field(6));
+                    b'Y'
+                } else {
+                    b'N'
+                };
+                let chkt = match field(7) {
+                    c @ b'1'..=b'3' => c,
+                    _ => b'1',
+                };
+                p.chkt = chkt - b'0';
+                let rept = if is_prefix_char(field(8)) {
+                    p.rept = Some(field(8));
+                    field(8)
+                } else {
+                    b' '
+                };
+                self.params = p;
+                self.send(b'Y', &our_init(qbin, chkt, rept));
+                self.chkt = p.chkt;
+                self.stage = Stage::RecvFile;
+            }
+            (Stage::RecvFile, b'F') | (Stage::RecvFile, b'X') => {
+                let name = decode_data(&pkt.data, &self.params);
+                self.files
+                    .push((String::from_utf8_lossy(&name).into_owned(), Vec::new()));
+                self.send(b'Y', &[]);
+                self.stage = Stage::RecvData;
+            }
+            (Stage::RecvFile, b'B') => {
+                self.send(b'Y', &[]);
+                self.stage = Stage::Complete;
+                self.status = TransferStatus::Done;
+                return;
+            }
+            (Stage::RecvData, b'A') => self.send(b'Y', &[]),
+            (Stage::RecvData, b'D') => {
+                let bytes = decode_data(&pkt.data, &self.params);
+                if let Some(f) = self.files.last_mut() {
+                    f.1.extend_from_slice(&bytes);
+                }
+                self.send(b'Y', &[]);
+            }
+            (Stage::RecvData, b'Z') => {
+                // "D" in the EOF packet means the sender discarded the file.
+                if decode_data(&pkt.data, &self.params) == b"D" {
+                    self.files.pop();
+                }
+                self.send(b'Y', &[]);
+                self.stage = Stage::RecvFile;
+            }
+            _ => return,
+        }
+        self.next_seq();
+    }
+}
+
+impl SerialTransport for Kermit {
+    fn transmit(&mut self, byte: u8) {
+        if byte == SOH {
+            self.rx.clear();
+        } else if self.rx.is_empty() {
+            return;
+        }
+        self.rx.push(byte);
+        if self.rx.len() >= 2 && self.rx.len() == 2 + unchar(self.rx[1]) as usize {
+            self.handle_packet();
+            self.rx.clear();
+        }
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.out.pop_front()
+    }
+}
+
+impl HostTransfer for Kermit {
+    fn begin(&mut self, now: f64) {
+        self.now = now;
+        self.last_sent = now;
+    }
+
+    fn poll(&mut self, now: f64) {
+        self.now = now;
+        if self.status != TransferStatus::Busy || self.now - self.last_sent < TIMEOUT_SECS {
+            return;
+        }
+        match self.stage {
+            // Nothing to retransmit until the calculator has started.
+            Stage::RecvInit => self.last_sent = now,
+            // Receiver: NAK the packet we're waiting for.
+            Stage::RecvFile | Stage::RecvData => {
+                if self.bump_retries() {
+                    self.send_nak();
+                }
+            }
+            _ => {
+                if self.bump_retries() {
+                    let p = self.last.clone();
+                    self.transmit_packet(p);
+                }
+            }
+        }
+    }
+
+    fn status(&self) -> &TransferStatus {
+        &self.status
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Emulator entry points
+// ---------------------------------------------------------------------------
+
+impl Emulator {
+    /// Send a file to the calculator, which must be running RECV or SERVER.
+    /// The firmware stores it in a variable called `name`.
+    pub fn send_file(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        self.run_kermit(Kermit::sender(name, data)).map(|_| ())
+    }
+
+    /// Receive the files sent by the calculator's SEND command.
+    pub fn receive_files(&mut self) -> Result<Vec<(String, Vec<u8>)>, String> {
+        self.run_kermit(Kermit::receiver())
+            .map(|mut k| k.take_files())
+    }
+
+    /// Receive a single file sent by the calculator's SEND command.
+    pub fn receive_file(&mut self) -> Result<(String, Vec<u8>), String> {
+        self.receive_files()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "no file received".to_string())
+    }
+
+    /// Start sending a file without waiting for it: frames carry the
+    /// transfer on and `finish_transfer::<Kermit>` collects it.
+    pub fn start_send_file(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        self.start_transfer(Kermit::sender(name, data), TRANSFER_BUDGET_SECS)
+    }
+
+    /// Start receiving what the calculator's SEND command sends, without
+    /// waiting; `Kermit::take_files` has the files once it has finished.
+    pub fn start_receive_files(&mut self) -> Result<(), String> {
+        self.start_transfer(Kermit::receiver(), TRANSFER_BUDGET_SECS)
+    }
+
+    fn run_kermit(&mut self, kermit: Kermit) -> Result<Box<Kermit>, String> {
+        self.run_transfer(kermit, TRANSFER_BUDGET_SECS, |_| {})
+            .map_err(|e| format!("Kermit: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, press, ALPHA, ENTER};
+
+    #[test]
+    fn test_block_checks() {
+        // Type 1 check of "# N" (LEN '#', SEQ ' ', TYPE 'N') is '3' per the
+        // Kermit protocol manual example.
+        assert_eq!(block_check(b"# N", 1), b"3");
+        assert_eq!(build_packet(0, b'N', &[], 1), b"\x01# N3\r");
+        assert_eq!(crc16(b"123456789"), 0x2189);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let p = Params {
+            rept: Some(b'~'),
+            qbin: Some(b'&'),
+            ..Params::default()
+        };
+        let data: Vec<u8> = (0..=255u8)
+            .chain([7, 7, 7, 7, 7, b'#', b'~', b'&'])
+            .collect();
+        let (enc, pos) = encode_data(&data, 0, 10_000, &p);
+        assert_eq!(pos, data.len());
+        assert!(enc.iter().all(|&c| (32..127).contains(&c)));
+        assert_eq!(decode_data(&enc, &p), data);
+    }
+
+    #[test]
+    fn test_sender_against_scripted_receiver() {
+        let mut k = Kermit::sender("X", b"1 2 +");
+        let s = std::iter::from_fn(|| k.receive()).collect::<Vec<u8>>();
+        assert_eq!(&s[..4], b"\x01, S");
+        // Receiver accepts type 1 checks, no prefixes
+        for b in build_packet(0, b'Y', b"~* @-#N1 ", 1) {
+            k.transmit(b);
+        }
+        let f = std::iter::from_fn(|| k.receive()).collect::<Vec<u8>>();
+        assert_eq!(f, build_packet(1, b'F', b"X", 1));
+        for (seq, expect) in [(1, b'D'), (2, b'Z'), (3, b'B')] {
+            for b in build_packet(seq, b'Y', &[], 1) {
+                k.transmit(b);
+            }
+            let p = std::iter::from_fn(|| k.receive()).collect::<Vec<u8>>();
+            assert_eq!(p[3], expect);
+        }
+        for b in build_packet(4, b'Y', &[], 1) {
+            k.transmit(b);
+        }
+        assert_eq!(*k.status(), TransferStatus::Done);
+    }
+
+    #[test]
+    fn test_round_trip_with_firmware() {
+        let mut emu = boot_gx();
+        // ATTN, then RECV
+        press(
+            &mut emu,
+            &[0x8000, ALPHA, ALPHA, 0x60, 0x81, 0x83, 0x52, ALPHA, ENTER],
+        );
+        emu.send_file("TST", b"%%HP: T(3)A(D)F(.);\r\n\\<< 1 2 + \\>>\r\n")
+            .unwrap();
+        // 'TST' SEND
+        press(
+            &mut emu,
+            &[0x04, ALPHA, ALPHA, 0x54, 0x34, 0x54, ALPHA, ENTER],
+        );
+        press(
+            &mut emu,
+            &[ALPHA, ALPHA, 0x34, 0x81, 0x64, 0x82, ALPHA, ENTER],
+        );
+        let (name, data) = emu.receive_file().unwrap();
+        assert_eq!(name, "TST");
+        assert!(data.ends_with(b"\xab 1 2 +\r\n\xbb\r\n"));
+    }
+
+    #[test]
+    fn test_transfer_in_frames() {
+        let mut emu = boot_gx();
+        // ATTN, then RECV
+        press(
+            &mut emu,
+            &[0x8000, ALPHA, ALPHA, 0x60, 0x81, 0x83, 0x52, ALPHA, ENTER],
+        );
+        let mut text = b"%%HP: T(3)A(D)F(.);\r\n\"".to_vec();
+        text.extend([b'x'; 4000]);
+        text.extend(b"\"\r\n");
+        emu.start_send_file("TST", &text).unwrap();
+        assert_eq!(
+            emu.start_receive_files(),
+            Err("a transfer is already running".to_string())
+        );
+        // Each frame comes back to the host with the transfer under way.
+        let mut host = emu.now;
+        let mut frames = 0;
+        let kermit = loop {
+            host += 0.016;
+            emu.run_frame(16.0, host);
+            frames += 1;
+            match emu.finish_transfer::<Kermit>() {
+                Some(result) => break result.unwrap(),
+                None => assert_eq!(emu.poll_transfer(), Some(TransferStatus::Busy)),
+            }
+        };
+        assert!(frames > 10);
+        assert_eq!(*kermit.status(), TransferStatus::Done);
+        assert!(emu.serial.wire.is_none());
+        assert!(emu.finish_transfer::<Kermit>().is_none());
+        emu.run_headless(2.0, |e| e.is_idle());
+        assert!(emu.recall_variable("TST").is_ok());
+    }
+}
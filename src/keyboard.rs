@@ -75,3 +75,165 @@ impl Keyboard {
         need_kbd_int
     }
 }
+
+/// Keys by the legend on their face (GX names), with the code
+/// `push_key_event` takes. The arithmetic keys also go by their symbols.
+///
+/// These names serve the SX as well: it has the same 49 keys with the same
+/// faces, letters and shifted characters (`SHIFTED_CHARS`). Its shift
+/// planes differ only in the command legends, which nothing here names, so
+/// there is no SX table.
+#[rustfmt::skip]
+pub const KEY_NAMES: &[(&str, u32)] = &[
+    ("A", 0x14), ("B", 0x84), ("C", 0x83), ("D", 0x82), ("E", 0x81), ("F", 0x80),
+    ("MTH", 0x24), ("PRG", 0x74), ("CST", 0x73), ("VAR", 0x72), ("UP", 0x71), ("NXT", 0x70),
+    ("'", 0x04), ("STO", 0x64), ("EVAL", 0x63), ("LEFT", 0x62), ("DOWN", 0x61), ("RIGHT", 0x60),
+    ("SIN", 0x34), ("COS", 0x54), ("TAN", 0x53), ("SQRT", 0x52), ("POWER", 0x51), ("INV", 0x50),
+    ("ENTER", 0x44), ("NEG", 0x43), ("EEX", 0x42), ("DEL", 0x41), ("BS", 0x40),
+    ("ALPHA", 0x35), ("7", 0x33), ("8", 0x32), ("9", 0x31), ("DIV", 0x30),
+    ("SHL", 0x25), ("4", 0x23), ("5", 0x22), ("6", 0x21), ("MUL", 0x20),
+    ("SHR", 0x15), ("1", 0x13), ("2", 0x12), ("3", 0x11), ("MINUS", 0x10),
+    ("ON", 0x8000), ("0", 0x03), (".", 0x02), ("SPC", 0x01), ("PLUS", 0x00),
+    ("/", 0x30), ("*", 0x20), ("-", 0x10), ("+", 0x00),
+];
+
+/// Code for a key named in `KEY_NAMES` (any case), or given in hex as `0x13`.
+pub fn key_code(name: &str) -> Option<u32> {
+    if let Some(hex) = name.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    KEY_NAMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, code)| code)
+}
+
+/// The first name `KEY_NAMES` gives the key with `code`.
+pub fn key_name(code: u32) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, c)| c == code)
+        .map(|&(name, _)| name)
+}
+
+/// The letters A..Z, by the key each is printed next to for alpha mode.
+#[rustfmt::skip]
+const ALPHA_KEYS: [&str; 26] = [
+    "A", "B", "C", "D", "E", "F", "MTH", "PRG", "CST", "VAR", "UP", "NXT", "'",
+    "STO", "EVAL", "LEFT", "DOWN", "RIGHT", "SIN", "COS", "TAN", "SQRT", "POWER", "INV",
+    "NEG", "EEX",
+];
+
+/// Characters on the shifted planes, with the shift and the key under them,
+/// the same on the SX and GX.
+#[rustfmt::skip]
+const SHIFTED_CHARS: &[(char, &str, &str)] = &[
+    ('«', "SHL", "MINUS"), ('»', "SHL", "MINUS"), ('"', "SHR", "MINUS"),
+    ('{', "SHL", "PLUS"), ('}', "SHL", "PLUS"), (':', "SHR", "PLUS"),
+    ('[', "SHL", "MUL"), (']', "SHL", "MUL"), ('_', "SHR", "MUL"),
+    ('(', "SHL", "DIV"), (')', "SHL", "DIV"), ('#', "SHR", "DIV"),
+    ('=', "SHL", "0"), ('→', "SHR", "0"),
+    (',', "SHL", "."), ('\n', "SHR", "."),
+    ('π', "SHL", "SPC"), ('∠', "SHR", "SPC"),
+];
+
+/// Keys to tap for one character on the command line, shift or alpha
+/// first: letters in alpha mode (lower case with left shift as well),
+/// digits and `. + - * / '` and space as printed, `^` on y^x, the rest from
+/// `SHIFTED_CHARS`. The shift and alpha keys act on the next key only.
+pub fn char_keys(c: char) -> Option<Vec<u32>> {
+    let names: Vec<&str> = match c {
+        ' ' => vec!["SPC"],
+        '^' => vec!["POWER"],
+        '0'..='9' | '.' | '+' | '-' | '*' | '/' | '\'' => {
+            return key_code(c.encode_utf8(&mut [0; 4])).map(|code| vec![code])
+        }
+        'A'..='Z' => vec!["ALPHA", ALPHA_KEYS[c as usize - 'A' as usize]],
+        'a'..='z' => vec!["ALPHA", "SHL", ALPHA_KEYS[c as usize - 'a' as usize]],
+        _ => {
+            let &(_, shift, key) = SHIFTED_CHARS.iter().find(|(ch, _, _)| *ch == c)?;
+            vec![shift, key]
+        }
+    };
+    names.iter().map(|n| key_code(n)).collect()
+}
+
+/// The delimiter the command line inserts after the cursor when an opening
+/// one is typed, and how many RIGHT presses step past it (« puts its »
+/// on a new line).
+fn closer(open: char) -> Option<(char, usize)> {
+    match open {
+        '«' => Some(('»', 2)),
+        '{' => Some(('}', 1)),
+        '[' => Some((']', 1)),
+        '(' => Some((')', 1)),
+        '"' | '\'' | ':' => Some((open, 1)),
+        _ => None,
+    }
+}
+
+/// Keys to tap to type `text` on the command line, `<<` and `>>` standing
+/// for « and ».
+///
+/// Typing an opening delimiter makes the firmware add its closer after the
+/// cursor, so a closing one in `text` steps over that with RIGHT instead
+/// of typing a second. The closers still open at the end are left for
+/// ENTER to take in.
+pub fn text_keys(text: &str) -> Result<Vec<u32>, String> {
+    let right = key_code("RIGHT").unwrap();
+    let text = text.replace("<<", "«").replace(">>", "»");
+    let mut keys = Vec::new();
+    let mut pending: Vec<(char, usize)> = Vec::new();
+    for c in text.chars() {
+        if pending.last().map(|&(close, _)| close) == Some(c) {
+            let (_, presses) = pending.pop().unwrap();
+            keys.extend(std::iter::repeat_n(right, presses));
+            continue;
+        }
+        if matches!(c, '»' | '}' | ']' | ')') {
+            return Err(format!("unmatched {c}"));
+        }
+        keys.extend(char_keys(c).ok_or_else(|| format!("cannot type {c:?}"))?);
+        pending.extend(closer(c));
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(names: &str) -> Vec<u32> {
+        names
+            .split_whitespace()
+            .map(|n| key_code(n).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_char_keys() {
+        assert_eq!(char_keys('7'), Some(codes("7")));
+        assert_eq!(char_keys('Q'), Some(codes("ALPHA DOWN")));
+        assert_eq!(char_keys('z'), Some(codes("ALPHA SHL EEX")));
+        assert_eq!(char_keys('→'), Some(codes("SHR 0")));
+        assert_eq!(char_keys('^'), Some(codes("POWER")));
+        assert_eq!(char_keys('~'), None);
+    }
+
+    #[test]
+    fn test_text_keys() {
+        assert_eq!(
+            text_keys("<< 1 2 + >>").unwrap(),
+            codes("SHL MINUS SPC 1 SPC 2 SPC + SPC RIGHT RIGHT")
+        );
+        assert_eq!(
+            text_keys("{ \"A\" }").unwrap(),
+            codes("SHL PLUS SPC SHR MINUS ALPHA A RIGHT SPC RIGHT")
+        );
+        // Left open: the firmware has already put the closers in.
+        assert_eq!(text_keys("'X").unwrap(), codes("' ALPHA INV"));
+        assert_eq!(text_keys("1 }").unwrap_err(), "unmatched }");
+        assert_eq!(text_keys("[ 1 )").unwrap_err(), "unmatched )");
+        assert_eq!(text_keys("1 < 2").unwrap_err(), "cannot type '<'");
+    }
+}
@@ -0,0 +1,329 @@
+// Keystroke macros — key events recorded with their instruction timestamps,
+// saved as text and played back at the same times.
+//
+// Times count instruction cycles from the first frame after recording (or
+// playback) started: the virtual clock's cycle count, or on the host clock
+// the emulated time at `TARGET_IPS`. Host keys arrive between frames, and
+// playback pushes each key at the start of the first frame slice at or
+// after its time, so on the virtual clock a macro played from the same
+// starting state repeats the recorded run exactly. Playback goes at the
+// pace of `run_frame`, real time in the frontends, or fast-forward with
+// `run_macro`.
+//
+// A macro file has one event per line; `#` starts a comment:
+//
+//   # 1 ENTER
+//   0           press 1
+//   500000      release 1
+//   +2500000    press ENTER     # `+`: cycles after the previous event
+//   +500000     release ENTER
+//
+// Keys are named as in `keyboard::KEY_NAMES` or given in hex as `0x13`.
+// Times must not go backwards; events at the same time keep their order.
+//
+// `KeyMacro::typing` builds a macro that taps out text, so the frontends
+// can type without blocking a frame: `play_text` plays it as frames run.
+
+use crate::emulator::{Emulator, TARGET_IPS};
+use crate::keyboard::{key_code, key_name, text_keys};
+
+const RELEASE: u32 = 0x8000_0000;
+
+/// How long `typing` holds each key down, then waits before the next. Keys
+/// that come while the firmware is still busy go into its key buffer.
+const TYPE_HOLD_SECS: f64 = 0.1;
+const TYPE_GAP_SECS: f64 = 0.2;
+
+/// Key events (`push_key_event` codes) by cycle since the macro's start.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyMacro {
+    pub events: Vec<(u64, u32)>,
+}
+
+impl KeyMacro {
+    pub fn parse(text: &str) -> Result<KeyMacro, String> {
+        let mut events = Vec::new();
+        let mut last = 0;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let event = parse_event(&words, last).map_err(|e| format!("line {}: {e}", i + 1))?;
+            last = event.0;
+            events.push(event);
+        }
+        Ok(KeyMacro { events })
+    }
+
+    /// The macro in the file format, with absolute times.
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# rust48 key macro: cycle, press/release, key\n");
+        for &(at, code) in &self.events {
+            let verb = if code & RELEASE != 0 {
+                "release"
+            } else {
+                "press"
+            };
+            let key = code & !RELEASE;
+            let name = key_name(key).map_or_else(|| format!("0x{key:x}"), String::from);
+            text += &format!("{at:<11} {verb:<7} {name}\n");
+        }
+        text
+    }
+
+    /// Cycle of the last event.
+    pub fn length(&self) -> u64 {
+        self.events.last().map_or(0, |&(at, _)| at)
+    }
+
+    /// Taps for `text` as `keyboard::text_keys` spells it out, timed for a
+    /// clock running `ips` instructions a second.
+    pub fn typing(text: &str, ips: f64) -> Result<KeyMacro, String> {
+        Ok(KeyMacro::taps(&text_keys(text)?, ips))
+    }
+
+    /// Taps of the keys `codes`, one after another, timed like `typing`.
+    pub fn taps(codes: &[u32], ips: f64) -> KeyMacro {
+        let hold = (TYPE_HOLD_SECS * ips) as u64;
+        let tap = ((TYPE_HOLD_SECS + TYPE_GAP_SECS) * ips) as u64;
+        let mut events = Vec::new();
+        for (i, &code) in codes.iter().enumerate() {
+            let at = i as u64 * tap;
+            events.push((at, code));
+            events.push((at + hold, code | RELEASE));
+        }
+        KeyMacro { events }
+    }
+}
+
+fn parse_event(words: &[&str], last: u64) -> Result<(u64, u32), String> {
+    let [time, verb, key] = words else {
+        return Err("expected: cycle press|release key".to_string());
+    };
+    let cycles = |n: &str| {
+        n.parse::<u64>()
+            .map_err(|_| format!("bad cycle count {time}"))
+    };
+    let at = match time.strip_prefix('+') {
+        Some(delta) => last + cycles(delta)?,
+        None => cycles(time)?,
+    };
+    if at < last {
+        return Err(format!("{at} is before the previous event"));
+    }
+    let code = key_code(key).ok_or_else(|| format!("unknown key {key}"))?;
+    match *verb {
+        "press" => Ok((at, code)),
+        "release" => Ok((at, code | RELEASE)),
+        _ => Err(format!("unknown event {verb}")),
+    }
+}
+
+/// A recording or playback in progress.
+pub(crate) struct MacroRun {
+    keys: KeyMacro,
+    /// Clock at the first frame, once there has been one.
+    start: Option<u64>,
+    /// Next event to play.
+    next: usize,
+}
+
+impl MacroRun {
+    fn new(keys: KeyMacro) -> Self {
+        MacroRun {
+            keys,
+            start: None,
+            next: 0,
+        }
+    }
+}
+
+impl Emulator {
+    /// Start recording the key events pushed from now on.
+    pub fn start_macro_recording(&mut self) {
+        self.macro_recording = Some(MacroRun::new(KeyMacro::default()));
+    }
+
+    /// Stop recording and return what was recorded.
+    pub fn stop_macro_recording(&mut self) -> Option<KeyMacro> {
+        self.macro_recording.take().map(|run| run.keys)
+    }
+
+    /// Play `keys` back as the emulator runs, starting with the next frame.
+    /// Keys pushed by the host meanwhile still go in.
+    pub fn play_macro(&mut self, keys: KeyMacro) {
+        self.macro_playback = Some(MacroRun::new(keys));
+    }
+
+    pub fn stop_macro(&mut self) {
+        self.macro_playback = None;
+    }
+
+    /// Whether a macro is still playing.
+    pub fn macro_playing(&self) -> bool {
+        self.macro_playback.is_some()
+    }
+
+    /// Type `text` as frames run, without waiting for it: plays
+    /// `KeyMacro::typing`. Refused while another macro is playing.
+    pub fn play_text(&mut self, text: &str) -> Result<(), String> {
+        if self.macro_playing() {
+            return Err("a macro is already playing".to_string());
+        }
+        let keys = KeyMacro::typing(text, self.macro_ips())?;
+        self.play_macro(keys);
+        Ok(())
+    }
+
+    /// Play `keys` on the headless clock, returning once the last event
+    /// has gone in.
+    pub fn run_macro(&mut self, keys: KeyMacro) -> Result<(), String> {
+        let secs = keys.length() as f64 / self.macro_ips() + 1.0;
+        self.play_macro(keys);
+        if !self.run_headless(secs, |emu| !emu.macro_playing()) {
+            self.stop_macro();
+            return Err("macro playback did not finish".to_string());
+        }
+        Ok(())
+    }
+
+    /// Instruction cycles per second of emulated time.
+    pub(crate) fn macro_ips(&self) -> f64 {
+        self.vclock.as_ref().map_or(TARGET_IPS, |c| c.ips)
+    }
+
+    /// Emulated time in instruction cycles.
+    fn macro_clock(&self) -> u64 {
+        match &self.vclock {
+            Some(clock) => clock.cycles,
+            None => (self.now * TARGET_IPS) as u64,
+        }
+    }
+
+    pub(crate) fn record_macro_key(&mut self, code: u32) {
+        let clock = self.macro_clock();
+        if let Some(run) = self.macro_recording.as_mut() {
+            let start = *run.start.get_or_insert(clock);
+            run.keys.events.push((clock.saturating_sub(start), code));
+        }
+    }
+
+    /// Called at the start of every frame slice: pushes the macro keys
+    /// that are due.
+    pub(crate) fn macro_frame(&mut self) {
+        let clock = self.macro_clock();
+        if let Some(run) = self.macro_recording.as_mut() {
+            run.start.get_or_insert(clock);
+        }
+        let Some(run) = self.macro_playback.as_mut() else {
+            return;
+        };
+        let start = *run.start.get_or_insert(clock);
+        let first = run.next;
+        while let Some(&(at, _)) = run.keys.events.get(run.next) {
+            if start + at > clock {
+                break;
+            }
+            run.next += 1;
+        }
+        let due: Vec<u32> = run.keys.events[first..run.next]
+            .iter()
+            .map(|&(_, code)| code)
+            .collect();
+        if run.next == run.keys.events.len() {
+            self.macro_playback = None;
+        }
+        for code in due {
+            self.push_key_event(code);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::Script;
+    use crate::test_support::boot_virtual;
+
+    #[test]
+    fn test_parse_and_write() {
+        let keys = KeyMacro::parse(
+            "# 1 ENTER\n0 press 1\n500000 release 1\n\n+2500000 press enter # later\n+0 release 0x44\n",
+        )
+        .unwrap();
+        assert_eq!(
+            keys.events,
+            vec![
+                (0, 0x13),
+                (500_000, 0x13 | RELEASE),
+                (3_000_000, 0x44),
+                (3_000_000, 0x44 | RELEASE),
+            ]
+        );
+        assert_eq!(keys.length(), 3_000_000);
+        assert_eq!(KeyMacro::parse(&keys.to_text()).unwrap(), keys);
+        assert!(keys.to_text().contains("\n3000000     release ENTER\n"));
+
+        assert_eq!(
+            KeyMacro::parse("5 press 1\n4 release 1\n").unwrap_err(),
+            "line 2: 4 is before the previous event"
+        );
+        assert!(KeyMacro::parse("0 tap 1\n").is_err());
+        assert!(KeyMacro::parse("0 press FOO\n").is_err());
+        assert!(KeyMacro::parse("x press 1\n").is_err());
+        assert!(KeyMacro::parse("0 press\n").is_err());
+    }
+
+    #[test]
+    fn test_playback_repeats_the_run() {
+        let mut emu = boot_virtual();
+        emu.run_headless(3.0, |_| false);
+        emu.start_macro_recording();
+        emu.run_script(&Script::parse("keys 1 ENTER 2 +\nwait 0.3\n").unwrap())
+            .unwrap();
+        let keys = emu.stop_macro_recording().unwrap();
+        assert_eq!(keys.events.len(), 8);
+        let end = emu.virtual_clock().unwrap().cycles;
+
+        let mut replay = boot_virtual();
+        replay.run_headless(3.0, |_| false);
+        replay
+            .run_macro(KeyMacro::parse(&keys.to_text()).unwrap())
+            .unwrap();
+        replay.run_headless(1.0, |emu| emu.virtual_clock().unwrap().cycles >= end);
+        assert_eq!(replay.virtual_clock().unwrap().cycles, end);
+        assert!(replay.mem.ram == emu.mem.ram);
+        assert_eq!(replay.register_dump(), emu.register_dump());
+    }
+
+    #[test]
+    fn test_play_text_in_frames() {
+        let keys = KeyMacro::typing("<< 1 >>", 1000.0).unwrap();
+        assert_eq!(keys.events.len(), 2 * text_keys("<< 1 >>").unwrap().len());
+        assert_eq!(
+            keys.events[..2],
+            [(0, keys.events[0].1), (100, keys.events[0].1 | RELEASE)]
+        );
+        assert_eq!(keys.events[2].0, 300);
+
+        let mut emu = boot_virtual();
+        emu.run_headless(3.0, |_| false);
+        let text = "<< \"ab\" SIZE { [ 1 2 ] (1,2) 'X' :T:5 } SIZE + >>";
+        emu.play_text(text).unwrap();
+        assert_eq!(
+            emu.play_text("1"),
+            Err("a macro is already playing".to_string())
+        );
+        let mut frames = 0;
+        while emu.macro_playing() {
+            emu.run_frame(16.0, 0.0);
+            frames += 1;
+        }
+        assert!(frames > 100);
+        emu.run_script(&Script::parse("idle 5\nkeys ENTER EVAL\nidle 5\n").unwrap())
+            .unwrap();
+        assert_eq!(emu.stack().unwrap()[0].to_string(), "6");
+    }
+}
@@ -9,8 +9,30 @@ pub mod keyboard;
 pub mod device;
 pub mod speaker;
 pub mod serial;
+pub mod card;
+pub mod kermit;
+pub mod xmodem;
 pub mod scheduler;
 pub mod decode;
+pub mod disasm;
+pub mod asm;
+pub mod rpl;
+pub mod rom;
+pub mod binio;
+pub mod vars;
+pub mod decompile;
+pub mod compile;
+pub mod debugger;
+pub mod trace;
+pub mod timetravel;
+pub mod script;
+pub mod keymacro;
+#[cfg(unix)]
+pub mod tui;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gdb;
 pub mod persist;
 pub mod emulator;
 pub mod platform;
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,412 @@
+// Command-line runner — loads ROM/RAM/state from files, types a key script,
+// runs until a condition holds and writes out the final state, RAM and LCD.
+//
+// Headless runs use the virtual clock, so the same files and script give
+// the same result on every machine. Exit status: 0 when done (or the
+// --until condition held), 1 on errors, 2 when --until timed out. Outputs
+// are written in every case, to help work out what went wrong.
+//
+// With --tui the calculator runs interactively in the terminal instead, on
+// the host clock, and the outputs are written on quitting. --stop illegal
+// halts it there on illegal opcodes and TRAPs until F10 resumes.
+//
+// --record saves the keys pushed during the run as a macro (see
+// src/keymacro.rs), which --play plays back: fast-forward after the script
+// in headless runs, in real time with --tui.
+
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rust48::binio::{file_object, write_binary};
+use rust48::emulator::{Emulator, TARGET_IPS};
+use rust48::keymacro::KeyMacro;
+use rust48::rom::identify;
+use rust48::script::Script;
+
+const USAGE: &str = "\
+usage: rust48 [options] ROM
+
+  --ram FILE            RAM image to load
+  --state FILE          saved CPU state to load
+  --compile SRC=FILE    compile a UserRPL source file to an HPHP48- binary
+                        first, for the loaded ROM (see src/compile.rs)
+  --push FILE           object to put on the stack first: an HPHP48- binary,
+                        or any other file as a string
+  --store NAME=FILE     then store a file's object in HOME as NAME
+  --script FILE         key script to type (see src/script.rs)
+  --type TEXT           text to type after the script, e.g. '<< 1 2 + >>'
+  --play FILE           key macro to play after the script (see src/keymacro.rs)
+  --record FILE         write the keys pushed during the run as a macro
+  --until idle          then run until the calculator waits for input
+  --until screen=FILE   then run until the LCD matches a PBM image
+  --timeout SECS        emulated seconds allowed for --until (default 60)
+  --epoch SECS          calendar start, seconds since 1970 (default now)
+  --ips N               emulated instructions per second (default 5000000)
+  --save-state FILE     write the CPU state when done
+  --save-ram FILE       write RAM when done
+  --screenshot FILE     write the LCD as a PBM image when done
+  --stack FILE          write the RPL stack as text when done
+  --vars FILE           write the HOME tree, port objects and libraries when done
+  --recall NAME=FILE    write HOME's variable NAME as an HPHP48- binary when done
+  --decompile NAME=FILE write HOME's variable NAME as ->STR text when done; a
+                        NAME of #ADDR (hex) decompiles the object there
+  --tui half|braille    run interactively in the terminal instead
+  --stop illegal        with --tui, halt on illegal opcodes and TRAPs
+";
+
+#[derive(Debug, PartialEq)]
+enum Until {
+    Idle,
+    Screen(String),
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    rom: String,
+    ram: Option<String>,
+    state: Option<String>,
+    compile: Vec<(String, String)>,
+    push: Option<String>,
+    store: Vec<(String, String)>,
+    script: Option<String>,
+    text: Option<String>,
+    play: Option<String>,
+    record: Option<String>,
+    until: Option<Until>,
+    timeout: Option<f64>,
+    epoch: Option<f64>,
+    ips: Option<f64>,
+    save_state: Option<String>,
+    save_ram: Option<String>,
+    screenshot: Option<String>,
+    stack: Option<String>,
+    vars: Option<String>,
+    recall: Vec<(String, String)>,
+    decompile: Vec<(String, String)>,
+    tui: Option<String>,
+    stop_illegal: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if rom.replace(arg.clone()).is_some() {
+                return Err("more than one ROM given".to_string());
+            }
+            continue;
+        }
+        let value = args
+            .next()
+            .cloned()
+            .ok_or_else(|| format!("{arg} needs a value"))?;
+        let number = |v: &str| {
+            v.parse::<f64>()
+                .ok()
+                .filter(|n| *n >= 0.0)
+                .ok_or_else(|| format!("bad number for {arg}: {v}"))
+        };
+        let variable = |v: &str| {
+            v.split_once('=')
+                .filter(|(name, file)| !name.is_empty() && !file.is_empty())
+                .map(|(name, file)| (name.to_string(), file.to_string()))
+                .ok_or_else(|| format!("{arg} needs NAME=FILE, not {v}"))
+        };
+        match arg.as_str() {
+            "--ram" => opts.ram = Some(value),
+            "--state" => opts.state = Some(value),
+            "--compile" => opts.compile.push(variable(&value)?),
+            "--push" => opts.push = Some(value),
+            "--store" => opts.store.push(variable(&value)?),
+            "--script" => opts.script = Some(value),
+            "--type" => opts.text = Some(value),
+            "--play" => opts.play = Some(value),
+            "--record" => opts.record = Some(value),
+            "--until" => {
+                opts.until = Some(match value.split_once('=') {
+                    None if value == "idle" => Until::Idle,
+                    Some(("screen", file)) => Until::Screen(file.to_string()),
+                    _ => return Err(format!("bad --until condition: {value}")),
+                })
+            }
+            "--timeout" => opts.timeout = Some(number(&value)?),
+            "--epoch" => opts.epoch = Some(number(&value)?),
+            "--ips" => opts.ips = Some(number(&value)?),
+            "--save-state" => opts.save_state = Some(value),
+            "--save-ram" => opts.save_ram = Some(value),
+            "--screenshot" => opts.screenshot = Some(value),
+            "--stack" => opts.stack = Some(value),
+            "--vars" => opts.vars = Some(value),
+            "--recall" => opts.recall.push(variable(&value)?),
+            "--decompile" => opts.decompile.push(variable(&value)?),
+            "--tui" => opts.tui = Some(value),
+            "--stop" if value == "illegal" => opts.stop_illegal = true,
+            "--stop" => return Err(format!("bad --stop condition: {value}")),
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    opts.rom = rom.ok_or("no ROM given")?;
+    if opts.tui.is_some()
+        && (opts.script.is_some()
+            || opts.text.is_some()
+            || opts.until.is_some()
+            || opts.push.is_some()
+            || !opts.store.is_empty())
+    {
+        return Err(
+            "--tui is interactive: no --push, --store, --script, --type or --until".to_string(),
+        );
+    }
+    if opts.stop_illegal && opts.tui.is_none() {
+        return Err("--stop illegal needs --tui".to_string());
+    }
+    Ok(opts)
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("{path}: {e}"))
+}
+
+fn read_macro(path: &str) -> Result<KeyMacro, String> {
+    let text = String::from_utf8(read(path)?).map_err(|_| format!("{path}: not UTF-8"))?;
+    KeyMacro::parse(&text).map_err(|e| format!("{path}: {e}"))
+}
+
+fn write(path: &Option<String>, data: &[u8]) -> Result<(), String> {
+    match path {
+        Some(path) => std::fs::write(path, data).map_err(|e| format!("{path}: {e}")),
+        None => Ok(()),
+    }
+}
+
+/// Whether the run reached its end, or timed out waiting for `--until`.
+fn run(emu: &mut Emulator, opts: &Options) -> Result<bool, String> {
+    if opts.push.is_some() || !opts.store.is_empty() {
+        emu.run_headless(opts.timeout.unwrap_or(60.0), |emu| emu.is_idle());
+    }
+    if let Some(path) = &opts.push {
+        let object = file_object(&read(path)?).map_err(|e| format!("{path}: {e}"))?;
+        emu.push_object(&object)
+            .map_err(|e| format!("{path}: {e}"))?;
+    }
+    for (name, path) in &opts.store {
+        let object = file_object(&read(path)?).map_err(|e| format!("{path}: {e}"))?;
+        emu.store_variable(name, &object)
+            .map_err(|e| format!("--store {name}: {e}"))?;
+    }
+    if let Some(path) = &opts.script {
+        let text = String::from_utf8(read(path)?).map_err(|_| format!("{path}: not UTF-8"))?;
+        let script = Script::parse(&text).map_err(|e| format!("{path}: {e}"))?;
+        emu.run_script(&script)
+            .map_err(|e| format!("{path}: {e}"))?;
+    }
+    if let Some(path) = &opts.play {
+        emu.run_macro(read_macro(path)?)
+            .map_err(|e| format!("{path}: {e}"))?;
+    }
+    if let Some(text) = &opts.text {
+        emu.type_text(text).map_err(|e| format!("--type: {e}"))?;
+    }
+    let timeout = opts.timeout.unwrap_or(60.0);
+    Ok(match &opts.until {
+        None => true,
+        Some(Until::Idle) => emu.run_headless(timeout, |emu| emu.is_idle()),
+        Some(Until::Screen(path)) => {
+            let image = read(path)?;
+            emu.update_display();
+            emu.display
+                .matches_pbm(&image)
+                .map_err(|e| format!("{path}: {e}"))?;
+            emu.run_headless(timeout, |emu| {
+                emu.update_display();
+                emu.display.matches_pbm(&image) == Ok(true)
+            })
+        }
+    })
+}
+
+#[cfg(unix)]
+fn tui(emu: &mut Emulator, style: &str) -> Result<bool, String> {
+    rust48::tui::run(emu, style.parse()?).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn tui(_emu: &mut Emulator, _style: &str) -> Result<bool, String> {
+    Err("--tui needs a unix terminal".to_string())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        eprint!("{USAGE}");
+        return ExitCode::from(if args.is_empty() { 1 } else { 0 });
+    }
+    let result = parse_args(&args).and_then(|opts| {
+        let rom = read(&opts.rom)?;
+        let ram = opts.ram.as_deref().map(read).transpose()?;
+        let state = opts.state.as_deref().map(read).transpose()?;
+        let revision = identify(&rom).map_err(|e| format!("{}: {e}", opts.rom))?;
+        let mut emu = Emulator::new(&rom, ram.as_deref(), state.as_deref(), revision.model);
+        for (source, path) in &opts.compile {
+            let text =
+                String::from_utf8(read(source)?).map_err(|_| format!("{source}: not UTF-8"))?;
+            let object = emu.compile(&text).map_err(|e| format!("{source}: {e}"))?;
+            write(
+                &Some(path.clone()),
+                &write_binary(&object, emu.rom_letter()),
+            )?;
+        }
+        if opts.record.is_some() {
+            emu.start_macro_recording();
+        }
+        let finished = match &opts.tui {
+            Some(style) => {
+                if let Some(path) = &opts.play {
+                    emu.play_macro(read_macro(path)?);
+                }
+                emu.set_stop_on_illegal(opts.stop_illegal);
+                tui(&mut emu, style)
+            }
+            None => {
+                let epoch = opts.epoch.unwrap_or_else(|| {
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0.0, |d| d.as_secs_f64())
+                });
+                emu.start_virtual(opts.ips.unwrap_or(TARGET_IPS), epoch);
+                run(&mut emu, &opts)
+            }
+        };
+        emu.update_display();
+        write(&opts.save_state, &emu.save_state())?;
+        write(&opts.save_ram, &emu.save_ram())?;
+        write(&opts.screenshot, &emu.display.to_pbm())?;
+        if opts.stack.is_some() {
+            write(&opts.stack, emu.stack_text()?.as_bytes())?;
+        }
+        if opts.vars.is_some() {
+            write(&opts.vars, emu.vars_text()?.as_bytes())?;
+        }
+        for (name, path) in &opts.recall {
+            let object = emu
+                .recall_variable(name)
+                .map_err(|e| format!("--recall {name}: {e}"))?;
+            write(
+                &Some(path.clone()),
+                &write_binary(&object, emu.rom_letter()),
+            )?;
+        }
+        for (name, path) in &opts.decompile {
+            let text = match name.strip_prefix('#') {
+                Some(hex) => u32::from_str_radix(hex, 16)
+                    .map_err(|_| format!("bad address {name}"))
+                    .and_then(|addr| emu.decompile_at(addr)),
+                None => emu.decompile_variable(name),
+            }
+            .map_err(|e| format!("--decompile {name}: {e}"))?;
+            write(&Some(path.clone()), text.as_bytes())?;
+        }
+        if let Some(keys) = emu.stop_macro_recording() {
+            write(&opts.record, keys.to_text().as_bytes())?;
+        }
+        finished
+    });
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!("rust48: timed out");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("rust48: {e}");
+            ExitCode::from(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let opts = parse_args(&args(
+            "--ram r --until screen=s.pbm gx.rom --timeout 5 --screenshot out.pbm --stack out.txt",
+        ))
+        .unwrap();
+        assert_eq!(
+            opts,
+            Options {
+                rom: "gx.rom".into(),
+                ram: Some("r".into()),
+                until: Some(Until::Screen("s.pbm".into())),
+                timeout: Some(5.0),
+                screenshot: Some("out.pbm".into()),
+                stack: Some("out.txt".into()),
+                ..Options::default()
+            }
+        );
+        assert_eq!(
+            parse_args(&args("--until idle rom")).unwrap().until,
+            Some(Until::Idle)
+        );
+        assert!(parse_args(&args("--until busy rom")).is_err());
+        assert!(parse_args(&args("--ram r")).is_err());
+        assert!(parse_args(&args("rom --timeout")).is_err());
+        assert!(parse_args(&args("rom --frobnicate 1")).is_err());
+        assert!(parse_args(&args("a b")).is_err());
+        assert!(parse_args(&args("--tui half --script s rom")).is_err());
+        assert!(parse_args(&args("--tui half --type 1 rom")).is_err());
+        assert!(parse_args(&args("--tui half --push p.hp rom")).is_err());
+        assert!(
+            parse_args(&args("--tui half --stop illegal rom"))
+                .unwrap()
+                .stop_illegal
+        );
+        assert!(parse_args(&args("--stop illegal rom")).is_err());
+        assert!(parse_args(&args("--tui half --stop trap rom")).is_err());
+        let opts = parse_args(&args("rom --play in.mac --record out.mac")).unwrap();
+        assert_eq!(opts.play, Some("in.mac".into()));
+        assert_eq!(opts.record, Some("out.mac".into()));
+        assert_eq!(
+            parse_args(&args("rom --push p.hp")).unwrap().push,
+            Some("p.hp".into())
+        );
+        assert!(parse_args(&args("--tui half --store A=a.hp rom")).is_err());
+        let opts = parse_args(&args("rom --store A=a.hp --store B=b.txt --recall C=c.hp")).unwrap();
+        assert_eq!(
+            opts.store,
+            [("A".into(), "a.hp".into()), ("B".into(), "b.txt".into())]
+        );
+        assert_eq!(opts.recall, [("C".into(), "c.hp".into())]);
+        assert_eq!(
+            parse_args(&args("rom --vars v.txt")).unwrap().vars,
+            Some("v.txt".into())
+        );
+        assert!(parse_args(&args("rom --store a.hp")).is_err());
+        assert!(parse_args(&args("rom --recall =c.hp")).is_err());
+        assert_eq!(
+            parse_args(&args("rom --compile p.rpl=p.hp --push p.hp"))
+                .unwrap()
+                .compile,
+            [("p.rpl".into(), "p.hp".into())]
+        );
+        assert_eq!(
+            parse_args(&args("rom --decompile P=p.txt"))
+                .unwrap()
+                .decompile,
+            [("P".into(), "p.txt".into())]
+        );
+        assert_eq!(
+            parse_args(&args("rom --type 1")).unwrap().text,
+            Some("1".into())
+        );
+    }
+}
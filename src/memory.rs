@@ -12,8 +12,10 @@ pub struct Memory {
     pub port2: Vec<u8>,
     pub port1_is_ram: bool,
     pub port1_mask: i32,
+    pub port1_write_protect: bool,
     pub port2_is_ram: bool,
     pub port2_mask: i32,
+    pub port2_write_protect: bool,
     pub line_counter: i32,
 }
 
@@ -26,15 +28,55 @@ impl Memory {
             port2: Vec::new(),
             port1_is_ram: false,
             port1_mask: 0,
+            port1_write_protect: false,
             port2_is_ram: false,
             port2_mask: 0,
+            port2_write_protect: false,
             line_counter: -1,
         }
     }
 
+    /// Writes to a port land only on RAM cards with the write-protect
+    /// switch off; anything else is dropped, as on the real bus.
+    #[inline]
+    fn port1_writable(&self) -> bool {
+        self.port1_is_ram && !self.port1_write_protect
+    }
+
+    #[inline]
+    fn port2_writable(&self) -> bool {
+        self.port2_is_ram && !self.port2_write_protect
+    }
+
+    /// Nibble at `offset` into the port 1 card. An empty port reads as 0,
+    /// so a card pulled while still mapped does not fault.
+    #[inline]
+    fn port1_nibble(&self, offset: i32) -> u8 {
+        self.port1
+            .get((offset & self.port1_mask) as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Nibble at `offset` into the port 2 card (bank already applied).
+    #[inline]
+    fn port2_nibble(&self, offset: i32) -> u8 {
+        self.port2
+            .get((offset & self.port2_mask) as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The CRC register after the hardware reads `nib`: the CRC-16 the
+    /// firmware also uses for object checksums (BYTES).
+    #[inline]
+    pub fn crc_step(crc: u16, nib: u8) -> u16 {
+        (crc >> 4) ^ (((crc ^ nib as u16) & 0xf) * 0x1081)
+    }
+
     #[inline]
     fn calc_crc(saturn: &mut Saturn, nib: u8) -> u8 {
-        saturn.crc = ((saturn.crc >> 4) ^ (((saturn.crc ^ nib as u16) & 0xf) * 0x1081)) as u16;
+        saturn.crc = Self::crc_step(saturn.crc, nib);
         nib
     }
 
@@ -128,7 +170,7 @@ impl Memory {
                 if saturn.card_ctrl & 0x02 != 0 {
                     saturn.mp = 1;
                 }
-                // card_ctrl & 0x01 triggers interrupt — handled by caller
+                // card_ctrl & 0x01 triggers interrupt — raised in check_devices
                 device.card_ctrl_touched = true;
             }
             0x10f => {
@@ -285,6 +327,27 @@ impl Memory {
         schedule_event: &mut i32,
         addr: i32,
     ) -> u8 {
+        match addr {
+            0x114 | 0x115 => {
+                saturn.rcs &= 0x0e;
+                device.rbr_touched = true;
+                *device_check = true;
+                *schedule_event = 0;
+            }
+            0x128 | 0x129 => {
+                self.line_counter += 1;
+                if self.line_counter > 0x3f {
+                    self.line_counter = -1;
+                }
+            }
+            _ => {}
+        }
+        self.peek_dev_mem(saturn, addr)
+    }
+
+    /// MMIO register value without read side effects (RBR clearing RCS,
+    /// the line counter advancing).
+    pub fn peek_dev_mem(&self, saturn: &Saturn, addr: i32) -> u8 {
         match addr {
             0x100 => saturn.disp_io & 0x0f,
             0x101 => saturn.contrast_ctrl & 0x0f,
@@ -308,10 +371,6 @@ impl Memory {
             0x112 => saturn.tcs & 0x0f,
             0x113 => 0x00,
             0x114 | 0x115 => {
-                saturn.rcs &= 0x0e;
-                device.rbr_touched = true;
-                *device_check = true;
-                *schedule_event = 0;
                 ((saturn.rbr >> ((addr - 0x114) * 4) as u8) & 0x0f) as u8
             }
             0x116 | 0x117 => 0x00,
@@ -331,10 +390,6 @@ impl Memory {
                 ((saturn.line_offset >> ((addr - 0x125) as u16 * 4)) & 0x0f) as u8
             }
             0x128 | 0x129 => {
-                self.line_counter += 1;
-                if self.line_counter > 0x3f {
-                    self.line_counter = -1;
-                }
                 let combined = (saturn.line_count as i32 & 0xc0) | (self.line_counter & 0x3f);
                 ((combined >> ((addr - 0x128) * 4)) & 0x0f) as u8
             }
@@ -408,13 +463,13 @@ impl Memory {
             }
             8..=0xb => {
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0x80000 {
-                    if self.port1_is_ram {
+                    if self.port1_writable() {
                         self.port1[((addr - 0x80000) & self.port1_mask) as usize] = val as u8;
                     }
                     return false;
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0x80000 {
-                    if self.port2_is_ram {
+                    if self.port2_writable() {
                         self.port2[((addr - 0x80000) & self.port2_mask) as usize] = val as u8;
                     }
                     return false;
@@ -423,13 +478,13 @@ impl Memory {
             }
             0xc..=0xe => {
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0xc0000_i32 {
-                    if self.port1_is_ram {
+                    if self.port1_writable() {
                         self.port1[((addr - 0xc0000) & self.port1_mask) as usize] = val as u8;
                     }
                     return false;
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0xc0000_i32 {
-                    if self.port2_is_ram {
+                    if self.port2_writable() {
                         self.port2[((addr - 0xc0000) & self.port2_mask) as usize] = val as u8;
                     }
                     return false;
@@ -440,12 +495,12 @@ impl Memory {
                 if saturn.mem_cntl[MCTL_SYSRAM_SX].config[0] == 0xf0000_i32 {
                     self.ram[(addr - 0xf0000) as usize] = val as u8;
                 } else if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0xc0000_i32 {
-                    if self.port1_is_ram {
+                    if self.port1_writable() {
                         self.port1[((addr - 0xc0000) & self.port1_mask) as usize] = val as u8;
                     }
                     return false;
                 } else if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0xc0000_i32 {
-                    if self.port2_is_ram {
+                    if self.port2_writable() {
                         self.port2[((addr - 0xc0000) & self.port2_mask) as usize] = val as u8;
                     }
                     return false;
@@ -493,19 +548,19 @@ impl Memory {
             }
             8..=0xb => {
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0x80000 {
-                    return self.port1[((addr - 0x80000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0x80000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0x80000 {
-                    return self.port2[((addr - 0x80000) & self.port2_mask) as usize];
+                    return self.port2_nibble(addr - 0x80000);
                 }
                 0x00
             }
             0xc..=0xe => {
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0xc0000_i32 {
-                    return self.port1[((addr - 0xc0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xc0000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0xc0000_i32 {
-                    return self.port2[((addr - 0xc0000) & self.port2_mask) as usize];
+                    return self.port2_nibble(addr - 0xc0000);
                 }
                 0x00
             }
@@ -514,10 +569,10 @@ impl Memory {
                     return self.ram[(addr - 0xf0000) as usize];
                 }
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0xc0000_i32 {
-                    return self.port1[((addr - 0xc0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xc0000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0xc0000_i32 {
-                    return self.port2[((addr - 0xc0000) & self.port2_mask) as usize];
+                    return self.port2_nibble(addr - 0xc0000);
                 }
                 0x00
             }
@@ -623,7 +678,7 @@ impl Memory {
                 {
                     self.ram[(addr - 0x80000) as usize] = val as u8;
                 } else if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xa0000_i32 {
-                    if self.port1_is_ram {
+                    if self.port1_writable() {
                         self.port1[((addr - 0xa0000) & self.port1_mask) as usize] = val as u8;
                     }
                     return false;
@@ -637,7 +692,7 @@ impl Memory {
                 {
                     self.ram[(addr - 0x80000) as usize] = val as u8;
                 } else if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xb0000_i32 {
-                    if self.port2_is_ram {
+                    if self.port2_writable() {
                         let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xb0000);
                         self.port2[(idx & self.port2_mask) as usize] = val as u8;
                     }
@@ -660,12 +715,12 @@ impl Memory {
                         self.ram[(addr - 0xc0000) as usize] = val as u8;
                     }
                 } else if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32 {
-                    if self.port1_is_ram {
+                    if self.port1_writable() {
                         self.port1[((addr - 0xc0000) & self.port1_mask) as usize] = val as u8;
                     }
                     return false;
                 } else if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32 {
-                    if self.port2_is_ram {
+                    if self.port2_writable() {
                         let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
                         self.port2[(idx & self.port2_mask) as usize] = val as u8;
                     }
@@ -682,14 +737,14 @@ impl Memory {
                 } else if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT1_GX].config[1] == 0xc0000_i32
                 {
-                    if self.port1_is_ram {
+                    if self.port1_writable() {
                         self.port1[((addr - 0xc0000) & self.port1_mask) as usize] = val as u8;
                     }
                     return false;
                 } else if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT2_GX].config[1] == 0xc0000_i32
                 {
-                    if self.port2_is_ram {
+                    if self.port2_writable() {
                         let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
                         self.port2[(idx & self.port2_mask) as usize] = val as u8;
                     }
@@ -797,7 +852,7 @@ impl Memory {
                     return self.ram[(addr - 0x80000) as usize];
                 }
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xa0000_i32 {
-                    return self.port1[((addr - 0xa0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xa0000);
                 }
                 self.rom[addr as usize]
             }
@@ -809,7 +864,7 @@ impl Memory {
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xb0000_i32 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xb0000);
-                    return self.port2[(idx & self.port2_mask) as usize];
+                    return self.port2_nibble(idx);
                 }
                 self.rom[addr as usize]
             }
@@ -828,11 +883,11 @@ impl Memory {
                     return self.ram[(addr - 0xc0000) as usize];
                 }
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32 {
-                    return self.port1[((addr - 0xc0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xc0000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
-                    return self.port2[(idx & self.port2_mask) as usize];
+                    return self.port2_nibble(idx);
                 }
                 self.rom[addr as usize]
             }
@@ -845,13 +900,13 @@ impl Memory {
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT1_GX].config[1] == 0xc0000_i32
                 {
-                    return self.port1[((addr - 0xc0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xc0000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT2_GX].config[1] == 0xc0000_i32
                 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
-                    return self.port2[(idx & self.port2_mask) as usize];
+                    return self.port2_nibble(idx);
                 }
                 self.rom[addr as usize]
             }
@@ -940,7 +995,7 @@ impl Memory {
                     return self.ram[(addr - 0x80000) as usize];
                 }
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xa0000_i32 {
-                    return self.port1[((addr - 0xa0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xa0000);
                 }
                 self.rom[addr as usize]
             }
@@ -952,7 +1007,7 @@ impl Memory {
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xb0000_i32 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xb0000);
-                    return self.port2[(idx & self.port2_mask) as usize];
+                    return self.port2_nibble(idx);
                 }
                 self.rom[addr as usize]
             }
@@ -971,11 +1026,11 @@ impl Memory {
                     return self.ram[(addr - 0xc0000) as usize];
                 }
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32 {
-                    return self.port1[((addr - 0xc0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xc0000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
-                    return self.port2[(idx & self.port2_mask) as usize];
+                    return self.port2_nibble(idx);
                 }
                 self.rom[addr as usize]
             }
@@ -988,13 +1043,13 @@ impl Memory {
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT1_GX].config[1] == 0xc0000_i32
                 {
-                    return self.port1[((addr - 0xc0000) & self.port1_mask) as usize];
+                    return self.port1_nibble(addr - 0xc0000);
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT2_GX].config[1] == 0xc0000_i32
                 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
-                    return self.port2[(idx & self.port2_mask) as usize];
+                    return self.port2_nibble(idx);
                 }
                 self.rom[addr as usize]
             }
@@ -1038,31 +1093,19 @@ impl Memory {
             }
             8..=0xb => {
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0x80000 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port1[((addr - 0x80000) & self.port1_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port1_nibble(addr - 0x80000));
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0x80000 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port2[((addr - 0x80000) & self.port2_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port2_nibble(addr - 0x80000));
                 }
                 0x00
             }
             0xc..=0xe => {
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0xc0000_i32 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port1[((addr - 0xc0000) & self.port1_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port1_nibble(addr - 0xc0000));
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0xc0000_i32 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port2[((addr - 0xc0000) & self.port2_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port2_nibble(addr - 0xc0000));
                 }
                 0x00
             }
@@ -1071,16 +1114,10 @@ impl Memory {
                     return Self::calc_crc(saturn, self.ram[(addr - 0xf0000) as usize]);
                 }
                 if saturn.mem_cntl[MCTL_PORT1_SX].config[0] == 0xc0000_i32 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port1[((addr - 0xc0000) & self.port1_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port1_nibble(addr - 0xc0000));
                 }
                 if saturn.mem_cntl[MCTL_PORT2_SX].config[0] == 0xc0000_i32 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port2[((addr - 0xc0000) & self.port2_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port2_nibble(addr - 0xc0000));
                 }
                 0x00
             }
@@ -1181,10 +1218,7 @@ impl Memory {
                     return Self::calc_crc(saturn, self.ram[(addr - 0x80000) as usize]);
                 }
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xa0000_i32 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port1[((addr - 0xa0000) & self.port1_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port1_nibble(addr - 0xa0000));
                 }
                 Self::calc_crc(saturn, self.rom[addr as usize])
             }
@@ -1196,10 +1230,7 @@ impl Memory {
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xb0000_i32 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xb0000);
-                    return Self::calc_crc(
-                        saturn,
-                        self.port2[(idx & self.port2_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port2_nibble(idx));
                 }
                 Self::calc_crc(saturn, self.rom[addr as usize])
             }
@@ -1218,17 +1249,11 @@ impl Memory {
                     return Self::calc_crc(saturn, self.ram[(addr - 0xc0000) as usize]);
                 }
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port1[((addr - 0xc0000) & self.port1_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port1_nibble(addr - 0xc0000));
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
-                    return Self::calc_crc(
-                        saturn,
-                        self.port2[(idx & self.port2_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port2_nibble(idx));
                 }
                 Self::calc_crc(saturn, self.rom[addr as usize])
             }
@@ -1241,19 +1266,13 @@ impl Memory {
                 if saturn.mem_cntl[MCTL_PORT1_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT1_GX].config[1] == 0xc0000_i32
                 {
-                    return Self::calc_crc(
-                        saturn,
-                        self.port1[((addr - 0xc0000) & self.port1_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port1_nibble(addr - 0xc0000));
                 }
                 if saturn.mem_cntl[MCTL_PORT2_GX].config[0] == 0xc0000_i32
                     && saturn.mem_cntl[MCTL_PORT2_GX].config[1] == 0xc0000_i32
                 {
                     let idx = ((saturn.bank_switch as i32) << 18) + (addr - 0xc0000);
-                    return Self::calc_crc(
-                        saturn,
-                        self.port2[(idx & self.port2_mask) as usize],
-                    );
+                    return Self::calc_crc(saturn, self.port2_nibble(idx));
                 }
                 Self::calc_crc(saturn, self.rom[addr as usize])
             }
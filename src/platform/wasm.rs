@@ -3,32 +3,74 @@
 
 use wasm_bindgen::prelude::*;
 
+use crate::card::{rom_card_image, CardKind, Port};
+use crate::debugger::{StopReason, Watch};
 use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use crate::emulator::Emulator;
-use crate::types::Model;
+use crate::keymacro::KeyMacro;
+use crate::binio::{file_object, write_binary};
+use crate::kermit::Kermit;
+use crate::rpl::{Object, Real};
+use crate::rom::identify;
+use crate::xmodem::{XModem, XModemCheck, XModemProgress};
+
+#[wasm_bindgen]
+extern "C" {
+    /// JS progress callback, called as `callback(done, total)` in bytes.
+    /// `total` is 0 when unknown (receiving).
+    #[wasm_bindgen(typescript_type = "(done: number, total: number) => void")]
+    pub type ProgressCallback;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call(this: &ProgressCallback, ctx: &JsValue, done: u32, total: u32);
+}
+
+/// Which file transfer is running on the wire port.
+enum Job {
+    KermitSend,
+    KermitReceive,
+    XModemSend(Option<ProgressCallback>),
+    XModemReceive(Option<ProgressCallback>),
+}
 
 #[wasm_bindgen]
 pub struct Hp48 {
     emu: Emulator,
+    /// The transfer `run_frame` is carrying on, and the progress last
+    /// reported for it.
+    job: Option<(Job, Option<XModemProgress>)>,
+    /// How the last transfer ended, until taken.
+    transfer_result: Option<TransferResult>,
+    /// How the last store ended, until taken.
+    store_result: Option<TransferResult>,
 }
 
 #[wasm_bindgen]
 impl Hp48 {
     /// Create a new emulator instance.
-    /// `rom` — ROM data (nibble or packed byte format).
+    /// `rom` — ROM data (nibble or packed byte format); throws if it is not
+    ///   a sound HP 48 ROM.
     /// `ram` — optional RAM data (nibble or packed byte format).
     /// `state` — optional saved state (binary format from save_state).
     #[wasm_bindgen(constructor)]
-    pub fn new(rom: &[u8], ram: Option<Vec<u8>>, state: Option<Vec<u8>>) -> Self {
-        // Auto-detect model from ROM size
-        let model = if rom.len() > ROM_SIZE_SX_PACKED {
-            Model::Gx
-        } else {
-            Model::Sx
-        };
-        Self {
-            emu: Emulator::new(rom, ram.as_deref(), state.as_deref(), model),
-        }
+    pub fn new(
+        rom: &[u8],
+        ram: Option<Vec<u8>>,
+        state: Option<Vec<u8>>,
+    ) -> Result<Hp48, JsValue> {
+        // The model comes from the ROM's version string
+        let revision = identify(rom).map_err(|e| JsValue::from_str(&e))?;
+        Ok(Self {
+            emu: Emulator::new(rom, ram.as_deref(), state.as_deref(), revision.model),
+            job: None,
+            transfer_result: None,
+            store_result: None,
+        })
+    }
+
+    /// The ROM revision, e.g. "HP 48GX revision R".
+    pub fn rom_revision(&self) -> String {
+        self.emu.revision.to_string()
     }
 
     /// Start emulation timers. Call once after construction.
@@ -39,11 +81,27 @@ impl Hp48 {
         self.emu.start(now_secs, unix_epoch_secs);
     }
 
+    /// Start on the virtual clock instead: emulated time is the instruction
+    /// count over `ips`, the calendar starts at `unix_epoch_secs`, and
+    /// `run_frame` ignores its `now_secs`. Same inputs, same run.
+    pub fn start_virtual(&mut self, ips: f64, unix_epoch_secs: f64) {
+        self.emu.start_virtual(ips, unix_epoch_secs);
+    }
+
     /// Push a key event into the queue.
     /// Bit 31 clear = press, bit 31 set = release.
     /// Bits [7:4] = row, bits [3:0] = column.
     pub fn push_key_event(&mut self, code: u32) {
-        self.emu.keyboard.push_key_event(code);
+        self.emu.push_key_event(code);
+    }
+
+    /// Type `text` on the command line, shifts and alpha included, with
+    /// `<<` and `>>` for « and ». The keys go in as frames run, like a
+    /// playing macro; `macro_playing` says when they are all in.
+    pub fn type_text(&mut self, text: &str) -> Result<(), JsValue> {
+        self.emu
+            .play_text(text)
+            .map_err(|e| JsValue::from_str(&e))
     }
 
     /// Get pointer to the RGBA display buffer (for use with WASM memory).
@@ -88,13 +146,584 @@ impl Hp48 {
         self.emu.save_ram()
     }
 
+    /// Start sending a file over Kermit to a calculator running RECV or
+    /// SERVER. Returns at once: `run_frame` carries the transfer on and
+    /// `take_transfer_result` tells how it ended.
+    pub fn send_file(&mut self, name: &str, data: &[u8]) -> Result<(), JsValue> {
+        self.emu
+            .start_send_file(name, data)
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.start_job(Job::KermitSend);
+        Ok(())
+    }
+
+    /// Start receiving a file sent by the calculator's SEND command over
+    /// Kermit. Returns at once; the file comes with `take_transfer_result`.
+    pub fn receive_file(&mut self) -> Result<(), JsValue> {
+        self.emu
+            .start_receive_files()
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.start_job(Job::KermitReceive);
+        Ok(())
+    }
+
+    /// Start sending `data` over XModem to a GX running XRECV. Returns at
+    /// once; `progress` is called from `run_frame` as blocks are
+    /// acknowledged.
+    pub fn xmodem_send(
+        &mut self,
+        data: &[u8],
+        progress: Option<ProgressCallback>,
+    ) -> Result<(), JsValue> {
+        self.emu
+            .start_xmodem_send(data)
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.start_job(Job::XModemSend(progress));
+        Ok(())
+    }
+
+    /// Start receiving over XModem from a GX running XSEND. `crc` asks for
+    /// CRC-16 blocks first; the padding of the last block is kept. Returns
+    /// at once, like `xmodem_send`.
+    pub fn xmodem_receive(
+        &mut self,
+        crc: bool,
+        progress: Option<ProgressCallback>,
+    ) -> Result<(), JsValue> {
+        let check = if crc {
+            XModemCheck::Crc
+        } else {
+            XModemCheck::Checksum
+        };
+        self.emu
+            .start_xmodem_receive(check)
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.start_job(Job::XModemReceive(progress));
+        Ok(())
+    }
+
+    /// Whether a file transfer is running.
+    pub fn transfer_busy(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// How the last transfer ended, once; undefined while it runs.
+    pub fn take_transfer_result(&mut self) -> Option<TransferResult> {
+        self.transfer_result.take()
+    }
+
+    /// Abandon the running transfer.
+    pub fn cancel_transfer(&mut self) {
+        if self.job.take().is_some() {
+            self.emu.cancel_transfer();
+            self.transfer_result = Some(TransferResult::failed("transfer cancelled"));
+        }
+    }
+
+    /// Plug a card into port 1 or 2 of the running calculator. `image` is
+    /// an x48 port file (32K–128K, or up to 4MB in GX port 2).
+    pub fn insert_card(&mut self, port: u8, image: &[u8], rom: bool) -> Result<(), JsValue> {
+        let kind = if rom { CardKind::Rom } else { CardKind::Ram };
+        self.emu
+            .hot_insert_card(card_port(port)?, image, kind)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Plug a read-only library card into port 1 or 2. The image is padded
+    /// up to the next card size.
+    pub fn insert_rom_card(&mut self, port: u8, image: &[u8]) -> Result<(), JsValue> {
+        self.emu
+            .hot_insert_card(card_port(port)?, &rom_card_image(image), CardKind::Rom)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Set the write-protect switch of the card in port 1 or 2.
+    pub fn set_card_write_protect(&mut self, port: u8, on: bool) -> Result<(), JsValue> {
+        self.emu.set_card_write_protect(card_port(port)?, on);
+        Ok(())
+    }
+
+    /// Pull the card out of port 1 or 2, returning its image.
+    pub fn eject_card(&mut self, port: u8) -> Result<Option<Vec<u8>>, JsValue> {
+        Ok(self.emu.hot_eject_card(card_port(port)?))
+    }
+
+    /// Image of the card in port 1 or 2, for saving alongside the RAM.
+    pub fn save_card(&self, port: u8) -> Result<Option<Vec<u8>>, JsValue> {
+        Ok(self.emu.save_card(card_port(port)?))
+    }
+
+    /// Disassembly of `count` instructions from `addr`, one per line.
+    pub fn disassemble(&self, addr: u32, count: u32) -> String {
+        crate::disasm::listing(&self.emu, addr as i32, count as usize)
+    }
+
+    /// The RPL stack as text, one `level: object` line per level, level 1
+    /// last.
+    pub fn stack_text(&self) -> Result<String, JsValue> {
+        self.emu.stack_text().map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Put the object in a file on the stack: an `HPHP48-` binary, or any
+    /// other file as a string. Only while idle at the stack prompt.
+    pub fn push_file(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        file_object(data)
+            .and_then(|object| self.emu.push_object(&object))
+            .map(|_| ())
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn push_real(&mut self, value: f64) -> Result<(), JsValue> {
+        let real =
+            Real::from_f64(value).ok_or_else(|| JsValue::from_str("real out of range"))?;
+        self.emu
+            .push(&Object::Real(real))
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn push_string(&mut self, text: &str) -> Result<(), JsValue> {
+        self.emu
+            .push(&Object::String(text.to_string()))
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Start storing the object in a dropped file in HOME as `name`: an
+    /// `HPHP48-` binary, or any other file as a string. Only while idle at
+    /// the stack prompt and not in USER mode. Returns at once: `run_frame`
+    /// carries the store on and `take_store_result` tells how it ended.
+    pub fn store_variable(&mut self, name: &str, data: &[u8]) -> Result<(), JsValue> {
+        file_object(data)
+            .and_then(|object| self.emu.start_store(name, &object))
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.store_result = None;
+        Ok(())
+    }
+
+    /// How the last store ended, once; undefined while it runs.
+    pub fn take_store_result(&mut self) -> Option<TransferResult> {
+        self.store_result.take()
+    }
+
+    /// HOME's variable `name` as an `HPHP48-` binary, for download.
+    pub fn recall_variable(&self, name: &str) -> Result<Vec<u8>, JsValue> {
+        let object = self
+            .emu
+            .recall_variable(name)
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(write_binary(&object, self.emu.rom_letter()))
+    }
+
+    /// The HOME tree, port objects and attached libraries as indented
+    /// text, with types, sizes and checksums.
+    pub fn vars_text(&self) -> Result<String, JsValue> {
+        self.emu.vars_text().map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// UserRPL `source` compiled for the loaded ROM, as an `HPHP48-`
+    /// binary for download or `push_file`.
+    pub fn compile(&self, source: &str) -> Result<Vec<u8>, JsValue> {
+        let object = self
+            .emu
+            .compile(source)
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(write_binary(&object, self.emu.rom_letter()))
+    }
+
+    /// HOME's variable `name` as ->STR writes it.
+    pub fn decompile_variable(&self, name: &str) -> Result<String, JsValue> {
+        self.emu
+            .decompile_variable(name)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Names of the variables in HOME, one per line, oldest first.
+    pub fn home_variables(&self) -> Result<String, JsValue> {
+        let entries = self
+            .emu
+            .home_directory()
+            .and_then(|home| self.emu.directory_entries(home))
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(entries
+            .into_iter()
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, _)| name + "\n")
+            .collect())
+    }
+
+    /// Add a breakpoint at `addr`, or on every instruction if omitted.
+    /// `condition` is a register test such as `C.A=#80000`, `P>=8` or
+    /// `D1=DSKTOP`.
+    pub fn add_breakpoint(
+        &mut self,
+        addr: Option<u32>,
+        condition: Option<String>,
+    ) -> Result<u32, JsValue> {
+        let condition = condition
+            .map(|c| self.emu.condition(&c))
+            .transpose()
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.emu
+            .add_breakpoint(addr.map(|a| a as i32), condition)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        self.emu.remove_breakpoint(id)
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        self.emu.set_breakpoint_enabled(id, enabled)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.emu.clear_breakpoints();
+    }
+
+    /// Address of the ROM revision's entry point `name` (DSKTOP, TEMPOB,
+    /// USEROB, ...), for watchpoints on system pointers.
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        self.emu.entry_points().symbol(name)
+    }
+
+    /// Watch `len` nibbles from `start`. `access` is "r", "w" or "rw";
+    /// with `halt` a hit stops execution after the accessing instruction.
+    pub fn add_watchpoint(
+        &mut self,
+        start: u32,
+        len: u32,
+        access: &str,
+        halt: bool,
+    ) -> Result<u32, JsValue> {
+        let watch = match access {
+            "r" => Watch::Read,
+            "w" => Watch::Write,
+            "rw" => Watch::ReadWrite,
+            _ => return Err(JsValue::from_str("access must be r, w or rw")),
+        };
+        self.emu
+            .add_watchpoint(start as i32, len as i32, watch, halt)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn remove_watchpoint(&mut self, id: u32) -> bool {
+        self.emu.remove_watchpoint(id)
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.emu.clear_watchpoints();
+    }
+
+    /// Watchpoint hits since the last call, one per line.
+    pub fn take_watch_hits(&mut self) -> String {
+        self.emu
+            .take_watch_hits()
+            .iter()
+            .map(|h| format!("{h}\n"))
+            .collect()
+    }
+
+    /// Why execution is stopped, or undefined while running.
+    pub fn halted(&self) -> Option<DebugStop> {
+        self.emu.halted().map(DebugStop)
+    }
+
+    /// Stop before the next instruction.
+    pub fn halt(&mut self) {
+        self.emu.halt();
+    }
+
+    /// Stop on illegal opcodes and TRAPs with no breakpoint set, for a
+    /// frontend that shows the stop. Off by default.
+    pub fn set_stop_on_illegal(&mut self, on: bool) {
+        self.emu.set_stop_on_illegal(on);
+    }
+
+    pub fn resume(&mut self) {
+        self.emu.resume();
+    }
+
+    pub fn step_into(&mut self) -> DebugStop {
+        DebugStop(self.emu.step_into())
+    }
+
+    /// Step over a call. Returns the stop at once unless the instruction is
+    /// a call, which finishes in a later `run_frame`.
+    pub fn step_over(&mut self) -> Option<DebugStop> {
+        self.emu.step_over().map(DebugStop)
+    }
+
+    pub fn step_out(&mut self) {
+        self.emu.step_out();
+    }
+
+    pub fn run_until_return(&mut self) {
+        self.emu.run_until_return();
+    }
+
+    /// Record the last `capacity` instructions.
+    pub fn start_trace(&mut self, capacity: u32) {
+        self.emu.start_trace(capacity as usize);
+    }
+
+    pub fn stop_trace(&mut self) {
+        self.emu.stop_trace();
+    }
+
+    /// The trace so far, one disassembled instruction per line.
+    pub fn trace_text(&self) -> String {
+        self.emu
+            .tracer
+            .as_ref()
+            .map_or_else(String::new, |t| t.to_text())
+    }
+
+    /// The trace so far as a binary trace file.
+    pub fn trace_binary(&self) -> Vec<u8> {
+        self.emu
+            .tracer
+            .as_ref()
+            .map_or_else(Vec::new, |t| t.to_binary())
+    }
+
+    /// Record the key events pushed from now on as a macro.
+    pub fn start_macro_recording(&mut self) {
+        self.emu.start_macro_recording();
+    }
+
+    /// Stop recording; the macro in its text format, or undefined when
+    /// not recording.
+    pub fn stop_macro_recording(&mut self) -> Option<String> {
+        self.emu.stop_macro_recording().map(|keys| keys.to_text())
+    }
+
+    /// Play a macro in the text format back in real time, as frames run.
+    pub fn play_macro(&mut self, text: &str) -> Result<(), JsValue> {
+        let keys = KeyMacro::parse(text).map_err(|e| JsValue::from_str(&e))?;
+        self.emu.play_macro(keys);
+        Ok(())
+    }
+
+    pub fn stop_macro(&mut self) {
+        self.emu.stop_macro();
+    }
+
+    pub fn macro_playing(&self) -> bool {
+        self.emu.macro_playing()
+    }
+
+    /// Play a macro fast-forward. Runs the emulator headless until the
+    /// last key has gone in.
+    pub fn run_macro(&mut self, text: &str) -> Result<(), JsValue> {
+        let keys = KeyMacro::parse(text).map_err(|e| JsValue::from_str(&e))?;
+        self.emu.run_macro(keys).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Record for reverse execution, snapshotting every `interval`
+    /// instructions and keeping the latest `max_snapshots`.
+    pub fn start_time_travel(&mut self, interval: u32, max_snapshots: u32) {
+        self.emu.start_time_travel(interval as u64, max_snapshots as usize);
+    }
+
+    pub fn stop_time_travel(&mut self) {
+        self.emu.stop_time_travel();
+    }
+
+    /// Instructions recorded so far, as `[position, end]`, or undefined
+    /// when time travel is off.
+    pub fn time_travel_position(&self) -> Option<Vec<f64>> {
+        self.emu
+            .time_travel_position()
+            .map(|(position, end)| vec![position as f64, end as f64])
+    }
+
+    pub fn step_back(&mut self) -> Result<DebugStop, JsValue> {
+        self.emu
+            .step_back()
+            .map(DebugStop)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Halt just before instruction `index` of the recording.
+    pub fn travel_to(&mut self, index: f64) -> Result<DebugStop, JsValue> {
+        self.emu
+            .travel_to(index as u64)
+            .map(DebugStop)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// CPU registers as text for a debugger panel.
+    pub fn registers(&self) -> String {
+        self.emu.register_dump()
+    }
+
     /// Run one frame of emulation.
     /// `elapsed_ms` — milliseconds since last frame.
     /// `now_secs` — current time in seconds.
-    pub fn run_frame(&mut self, elapsed_ms: f64, now_secs: f64) {
-        self.emu.run_frame(elapsed_ms, now_secs);
+    /// Returns why the debugger stopped execution during this frame, if it did.
+    pub fn run_frame(&mut self, elapsed_ms: f64, now_secs: f64) -> Option<DebugStop> {
+        let stop = self.emu.run_frame(elapsed_ms, now_secs).map(DebugStop);
+        self.advance_transfer();
+        if let Some(result) = self.emu.poll_store() {
+            self.store_result = Some(match result {
+                Ok(()) => TransferResult {
+                    error: None,
+                    name: String::new(),
+                    data: Vec::new(),
+                },
+                Err(e) => TransferResult::failed(&e),
+            });
+        }
+        stop
     }
 }
 
-/// Packed ROM size threshold for SX model detection
-const ROM_SIZE_SX_PACKED: usize = crate::types::ROM_SIZE_SX / 2;
+impl Hp48 {
+    fn start_job(&mut self, job: Job) {
+        self.job = Some((job, None));
+        self.transfer_result = None;
+    }
+
+    /// After a frame: report XModem progress, and collect the transfer once
+    /// it has ended.
+    fn advance_transfer(&mut self) {
+        let Some((job, last)) = self.job.as_mut() else {
+            return;
+        };
+        if let Job::XModemSend(Some(callback)) | Job::XModemReceive(Some(callback)) = job {
+            if let Some(p) = self.emu.serial.wire_as::<XModem>().map(|x| x.progress()) {
+                if *last != Some(p) {
+                    *last = Some(p);
+                    let total = p.total.unwrap_or(0) as u32;
+                    callback.call(&JsValue::NULL, p.done as u32, total);
+                }
+            }
+        }
+        let result = match job {
+            Job::KermitSend => self
+                .emu
+                .finish_transfer::<Kermit>()
+                .map(|r| r.map(|_| (String::new(), Vec::new()))),
+            Job::KermitReceive => self.emu.finish_transfer::<Kermit>().map(|r| {
+                r.and_then(|mut k| {
+                    k.take_files()
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| "no file received".to_string())
+                })
+            }),
+            Job::XModemSend(_) => self
+                .emu
+                .finish_transfer::<XModem>()
+                .map(|r| r.map(|_| (String::new(), Vec::new()))),
+            Job::XModemReceive(_) => self
+                .emu
+                .finish_transfer::<XModem>()
+                .map(|r| r.map(|mut x| (String::new(), x.take_received()))),
+        };
+        let Some(result) = result else {
+            return;
+        };
+        let protocol = match job {
+            Job::KermitSend | Job::KermitReceive => "Kermit",
+            _ => "XModem",
+        };
+        self.job = None;
+        self.transfer_result = Some(match result {
+            Ok((name, data)) => TransferResult {
+                error: None,
+                name,
+                data,
+            },
+            Err(e) => TransferResult::failed(&format!("{protocol}: {e}")),
+        });
+    }
+}
+
+fn card_port(port: u8) -> Result<Port, JsValue> {
+    match port {
+        1 => Ok(Port::Port1),
+        2 => Ok(Port::Port2),
+        _ => Err(JsValue::from_str("card port must be 1 or 2")),
+    }
+}
+
+/// Why the debugger stopped execution.
+#[wasm_bindgen]
+pub struct DebugStop(StopReason);
+
+#[wasm_bindgen]
+impl DebugStop {
+    /// breakpoint, watchpoint, step, halt, illegal or trap.
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> String {
+        self.0.kind().to_string()
+    }
+
+    /// The stop as text: "trap at 90003".
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.0.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pc(&self) -> u32 {
+        self.0.pc() as u32
+    }
+
+    /// Id of the breakpoint or watchpoint that was hit, 0 for other stops.
+    #[wasm_bindgen(getter)]
+    pub fn breakpoint(&self) -> u32 {
+        match self.0 {
+            StopReason::Breakpoint { id, .. } | StopReason::Watchpoint { id, .. } => id,
+            _ => 0,
+        }
+    }
+
+    /// Watched address accessed, for watchpoint stops.
+    #[wasm_bindgen(getter)]
+    pub fn addr(&self) -> Option<u32> {
+        match self.0 {
+            StopReason::Watchpoint { addr, .. } => Some(addr as u32),
+            _ => None,
+        }
+    }
+}
+
+/// How a file transfer or a store ended, with what was received.
+#[wasm_bindgen]
+pub struct TransferResult {
+    error: Option<String>,
+    name: String,
+    data: Vec<u8>,
+}
+
+impl TransferResult {
+    fn failed(error: &str) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            name: String::new(),
+            data: Vec::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl TransferResult {
+    /// Why the transfer failed, or undefined if it succeeded.
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    /// Name of the file received over Kermit; empty otherwise.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// The data received; empty for sends.
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
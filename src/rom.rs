@@ -0,0 +1,415 @@
+// ROM identification — which firmware an image holds, whether it is sound,
+// and where that revision keeps its system pointers in RAM.
+//
+// Images come one nibble to a byte, starting 02 03 06 09, or packed two to
+// a byte, low nibble first, starting 32 96 1B 80; romio.c tells them apart
+// the same way. The revision is the letter of the version string,
+// "Version HP48-R": A-J are SX ROMs of 512K nibbles, K-R GX ROMs of 1M.
+//
+// The checksum is the firmware's CRC (`Memory::crc_step`) over each 512K
+// half of the ROM, read the way its checksum routine at 044D6 reads memory:
+// sixteen nibbles from the lower 256K, then sixteen from the upper, and so
+// on. A sound half leaves FFFF. Dumps hold whatever the I/O registers read
+// at 00100-0013F when the ROM was copied, so those nibbles count as zeros.
+// Worked out on a GX R image. The routine takes the size of the region as a
+// parameter rather than knowing the GX's, and an SX ROM is one such 512K
+// region at the same addresses, so an SX image is held to the same sum.
+//
+// Each revision has its own table of entry points (`REVISIONS`). HP kept
+// the supported ones at the same RAM addresses through a model's
+// revisions, so today the tables of a model agree. The SX keeps its system
+// pointers in the same order as the GX, so the unnamed pointer after
+// EDITLINE is found the same way on both. The GX's system flags are where
+// x48's rpl.h has them; the rest of a table was found by experiment on a
+// GX R and is taken to hold for the other GX revisions. The outer loop's
+// return-stack depth is taken to be the SX's too, while its port 0 and
+// system flags are left unknown. The debugger knows the
+// supported entries by name (`EntryPoints::symbol`).
+
+use std::fmt;
+
+use crate::memory::Memory;
+use crate::types::{Model, ROM_SIZE_GX, ROM_SIZE_SX};
+
+const NIBBLE_MAGIC: [u8; 4] = [0x02, 0x03, 0x06, 0x09];
+const PACKED_MAGIC: [u8; 4] = [0x32, 0x96, 0x1b, 0x80];
+
+const VERSION: &[u8] = b"Version HP48-";
+
+/// Nibbles covered by one checksum.
+const HALF: usize = 0x80000;
+/// Nibbles read from one bank before switching to the other.
+const WORD: usize = 16;
+/// The I/O registers, which hide the ROM beneath them.
+const IO_WINDOW: std::ops::Range<usize> = 0x100..0x140;
+const SOUND: u16 = 0xffff;
+
+/// RAM addresses of the firmware's system pointers, of ACCESSTIME, and of
+/// the state this emulator reads from outside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPoints {
+    /// Start of temporary objects.
+    pub tempob: u32,
+    /// End of temporary objects.
+    pub temptop: u32,
+    /// Top of the return stack.
+    pub rsktop: u32,
+    /// Data stack level 1.
+    pub dsktop: u32,
+    /// The command line being edited.
+    pub editline: u32,
+    /// The pointer after EDITLINE, to where the command line ends and the
+    /// temporary environments begin.
+    pub tempenv: u32,
+    /// Start of user objects: HOME.
+    pub userob: u32,
+    /// Free memory, in 5-nibble units.
+    pub avmem: u32,
+    /// Table of the attached libraries.
+    pub romptab: u32,
+    /// The 13-nibble tick count the clock runs from (timer.c).
+    pub accesstime: u32,
+    /// The address of port 0's first object, where port 0 is known to be
+    /// at the top of main RAM.
+    pub port0: Option<u32>,
+    /// Return-stack levels while the outer loop waits for a key at the
+    /// stack prompt, menu or command line or not; a program halted, or an
+    /// application such as INPUT waiting for keys, runs deeper.
+    pub rest_depth: u32,
+    /// The system flags, x48's SYSTEMFLAGS: flag -n is bit (n-1) mod 4
+    /// of the nibble (n-1)/4 on.
+    pub sysflags: Option<u32>,
+}
+
+impl EntryPoints {
+    /// What the SX revisions share.
+    pub const SX: EntryPoints = EntryPoints {
+        tempob: 0x7056a,
+        temptop: 0x7056f,
+        rsktop: 0x70574,
+        dsktop: 0x70579,
+        editline: 0x7057e,
+        tempenv: 0x70583,
+        userob: 0x70592,
+        avmem: 0x7066e,
+        romptab: 0x707d9,
+        accesstime: 0x70052,
+        port0: None,
+        rest_depth: 8,
+        sysflags: None,
+    };
+
+    /// What the GX revisions share.
+    pub const GX: EntryPoints = EntryPoints {
+        tempob: 0x806e9,
+        temptop: 0x806ee,
+        rsktop: 0x806f3,
+        dsktop: 0x806f8,
+        editline: 0x806fd,
+        tempenv: 0x80702,
+        userob: 0x80711,
+        avmem: 0x807ed,
+        romptab: 0x809a3,
+        accesstime: 0x80058,
+        port0: Some(0x80716),
+        rest_depth: 8,
+        sysflags: Some(0x80843),
+    };
+
+    /// The entries by their names in HP's tables.
+    pub fn symbols(&self) -> [(&'static str, u32); 9] {
+        [
+            ("TEMPOB", self.tempob),
+            ("TEMPTOP", self.temptop),
+            ("RSKTOP", self.rsktop),
+            ("DSKTOP", self.dsktop),
+            ("EDITLINE", self.editline),
+            ("USEROB", self.userob),
+            ("AVMEM", self.avmem),
+            ("ROMPTAB", self.romptab),
+            ("ACCESSTIME", self.accesstime),
+        ]
+    }
+
+    /// Address of the entry `name`, in any case.
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        self.symbols()
+            .into_iter()
+            .find(|(symbol, _)| symbol.eq_ignore_ascii_case(name))
+            .map(|(_, addr)| addr)
+    }
+}
+
+/// Each revision's entry points, by version letter.
+static REVISIONS: [(char, EntryPoints); 18] = [
+    ('A', EntryPoints::SX),
+    ('B', EntryPoints::SX),
+    ('C', EntryPoints::SX),
+    ('D', EntryPoints::SX),
+    ('E', EntryPoints::SX),
+    ('F', EntryPoints::SX),
+    ('G', EntryPoints::SX),
+    ('H', EntryPoints::SX),
+    ('I', EntryPoints::SX),
+    ('J', EntryPoints::SX),
+    ('K', EntryPoints::GX),
+    ('L', EntryPoints::GX),
+    ('M', EntryPoints::GX),
+    ('N', EntryPoints::GX),
+    ('O', EntryPoints::GX),
+    ('P', EntryPoints::GX),
+    ('Q', EntryPoints::GX),
+    ('R', EntryPoints::GX),
+];
+
+/// A firmware revision: the model and its version letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+    pub model: Model,
+    pub letter: char,
+}
+
+impl Revision {
+    /// The revision in ROM nibbles that are taken on trust: the version
+    /// string's letter, or `model`'s last revision if there is none.
+    pub fn guess(rom: &[u8], model: Model) -> Revision {
+        let letter = version_letter(rom).unwrap_or(match model {
+            Model::Sx => 'J',
+            Model::Gx => 'R',
+        });
+        Revision { model, letter }
+    }
+
+    /// Where this revision keeps its system pointers; a letter outside
+    /// A-R gets its model's.
+    pub fn entry_points(&self) -> &'static EntryPoints {
+        REVISIONS
+            .iter()
+            .find(|(letter, _)| *letter == self.letter)
+            .map(|(_, points)| points)
+            .unwrap_or(match self.model {
+                Model::Sx => &EntryPoints::SX,
+                Model::Gx => &EntryPoints::GX,
+            })
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let model = match self.model {
+            Model::Sx => "SX",
+            Model::Gx => "GX",
+        };
+        write!(f, "HP 48{model} revision {}", self.letter)
+    }
+}
+
+/// The letter after "Version HP48-" in ROM nibbles.
+fn version_letter(rom: &[u8]) -> Option<char> {
+    let pattern: Vec<u8> = VERSION.iter().flat_map(|&b| [b & 0xf, b >> 4]).collect();
+    rom.windows(pattern.len() + 2)
+        .find(|w| w[..pattern.len()] == pattern[..])
+        .map(|w| (w[pattern.len()] | w[pattern.len() + 1] << 4) as char)
+}
+
+/// The ROM image as one nibble per byte, from either file format.
+pub fn rom_nibbles(image: &[u8]) -> Result<Vec<u8>, String> {
+    let nibbles = match image.get(..4) {
+        Some(magic) if magic == NIBBLE_MAGIC => image.to_vec(),
+        Some(magic) if magic == PACKED_MAGIC => {
+            image.iter().flat_map(|&b| [b & 0xf, b >> 4]).collect()
+        }
+        _ => return Err("not an HP 48 ROM: unknown file format".to_string()),
+    };
+    if nibbles.len() != ROM_SIZE_SX && nibbles.len() != ROM_SIZE_GX {
+        return Err(format!(
+            "not an HP 48 ROM: {:X} nibbles, expected {ROM_SIZE_SX:X} or {ROM_SIZE_GX:X}",
+            nibbles.len()
+        ));
+    }
+    Ok(nibbles)
+}
+
+/// The addresses of the 512K nibbles from `start`, in the order the
+/// checksum reads them.
+fn checksum_order(start: usize) -> impl Iterator<Item = usize> {
+    let bank = HALF / 2;
+    (start..start + bank)
+        .step_by(WORD)
+        .flat_map(move |at| (at..at + WORD).chain(at + bank..at + bank + WORD))
+}
+
+/// The checksum of the 512K nibbles of `rom` from `start`: FFFF if sound.
+pub fn checksum(rom: &[u8], start: usize) -> u16 {
+    let nibble = |at: usize| if IO_WINDOW.contains(&at) { 0 } else { rom[at] };
+    checksum_order(start).fold(0, |crc, at| Memory::crc_step(crc, nibble(at)))
+}
+
+/// The first 512K half of `rom` whose checksum does not come out, as a
+/// message.
+fn checksum_error(rom: &[u8]) -> Option<String> {
+    (0..rom.len()).step_by(HALF).find_map(|start| {
+        let crc = checksum(rom, start);
+        (crc != SOUND).then(|| {
+            format!(
+                "corrupt ROM: checksum of {start:05X}-{:05X} is {crc:04X}, not {SOUND:04X}",
+                start + HALF - 1
+            )
+        })
+    })
+}
+
+/// Identify a ROM image, in either file format, refusing anything that is
+/// not a sound HP 48 ROM.
+pub fn identify(image: &[u8]) -> Result<Revision, String> {
+    let rom = rom_nibbles(image)?;
+    let letter =
+        version_letter(&rom).ok_or_else(|| "not an HP 48 ROM: no version string".to_string())?;
+    let model = match letter {
+        'A'..='J' => Model::Sx,
+        'K'..='R' => Model::Gx,
+        _ => return Err(format!("unknown ROM revision {letter:?}")),
+    };
+    let size = match model {
+        Model::Sx => ROM_SIZE_SX,
+        Model::Gx => ROM_SIZE_GX,
+    };
+    if rom.len() != size {
+        return Err(format!(
+            "corrupt ROM: revision {letter} in {:X} nibbles, expected {size:X}",
+            rom.len()
+        ));
+    }
+    match checksum_error(&rom) {
+        Some(error) => Err(error),
+        None => Ok(Revision { model, letter }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binio::read;
+    use crate::test_support::boot_gx;
+
+    fn image() -> Vec<u8> {
+        std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/rom")).unwrap()
+    }
+
+    #[test]
+    fn test_identify() {
+        let packed = image();
+        let gx_r = Revision {
+            model: Model::Gx,
+            letter: 'R',
+        };
+        assert_eq!(identify(&packed), Ok(gx_r));
+        assert_eq!(gx_r.to_string(), "HP 48GX revision R");
+        let nibbles = rom_nibbles(&packed).unwrap();
+        assert_eq!(nibbles.len(), ROM_SIZE_GX);
+        assert_eq!(identify(&nibbles), Ok(gx_r));
+        assert_eq!(Revision::guess(&nibbles, Model::Gx), gx_r);
+        assert_eq!(Revision::guess(&[0; 16], Model::Sx).letter, 'J');
+
+        // The I/O registers cover 00100-0013F in a dump.
+        let mut dumped = nibbles.clone();
+        dumped[0x120] ^= 0x5;
+        assert_eq!(identify(&dumped), Ok(gx_r));
+    }
+
+    #[test]
+    fn test_rejects() {
+        let mut nibbles = rom_nibbles(&image()).unwrap();
+        assert_eq!(
+            identify(&[0; 0x80000]),
+            Err("not an HP 48 ROM: unknown file format".to_string())
+        );
+        assert_eq!(
+            identify(&nibbles[..0x1000]),
+            Err("not an HP 48 ROM: 1000 nibbles, expected 80000 or 100000".to_string())
+        );
+        // A GX ROM's first half is sound on its own.
+        assert_eq!(
+            identify(&nibbles[..ROM_SIZE_SX]),
+            Err("corrupt ROM: revision R in 80000 nibbles, expected 100000".to_string())
+        );
+        nibbles[0x01234] ^= 0x8;
+        assert!(identify(&nibbles)
+            .unwrap_err()
+            .starts_with("corrupt ROM: checksum of 00000-7FFFF is "));
+        nibbles[0x01234] ^= 0x8;
+        nibbles[0x91234] ^= 0x1;
+        assert!(identify(&nibbles)
+            .unwrap_err()
+            .starts_with("corrupt ROM: checksum of 80000-FFFFF is "));
+        nibbles[0x91234] ^= 0x1;
+        assert!(identify(&nibbles).is_ok());
+    }
+
+    #[test]
+    fn test_sx_checksum() {
+        // The GX's first half, relabelled revision E: its checksum no longer
+        // comes out, so the image is refused.
+        let mut sx = rom_nibbles(&image()).unwrap()[..ROM_SIZE_SX].to_vec();
+        let pattern: Vec<u8> = VERSION.iter().flat_map(|&b| [b & 0xf, b >> 4]).collect();
+        let at = sx
+            .windows(pattern.len())
+            .position(|w| w == pattern)
+            .unwrap()
+            + pattern.len();
+        let was = [sx[at], sx[at + 1]];
+        sx[at..at + 2].copy_from_slice(&[0x5, 0x4]);
+        assert!(identify(&sx)
+            .unwrap_err()
+            .starts_with("corrupt ROM: checksum of 00000-7FFFF is "));
+
+        // Feeding the CRC's low nibble back in shifts it out, so four
+        // nibbles read after the letter cancel the change.
+        let mut order = checksum_order(0).skip_while(|&p| p != at);
+        let mut crc = [0, 1].iter().fold(0, |crc, &i| {
+            order.next();
+            Memory::crc_step(crc, sx[at + i] ^ was[i])
+        });
+        for p in order.take(4) {
+            sx[p] ^= (crc & 0xf) as u8;
+            crc = Memory::crc_step(crc, (crc & 0xf) as u8);
+        }
+        assert_eq!(crc, 0);
+        assert_eq!(
+            identify(&sx),
+            Ok(Revision {
+                model: Model::Sx,
+                letter: 'E',
+            })
+        );
+    }
+
+    #[test]
+    fn test_entry_points() {
+        let gx = Revision {
+            model: Model::Gx,
+            letter: 'M',
+        };
+        assert_eq!(gx.entry_points(), &EntryPoints::GX);
+        let sx = Revision {
+            model: Model::Sx,
+            letter: 'E',
+        };
+        assert_eq!(sx.entry_points(), &EntryPoints::SX);
+        assert_eq!(sx.entry_points().port0, None);
+        let unknown = Revision {
+            model: Model::Gx,
+            letter: '?',
+        };
+        assert_eq!(unknown.entry_points(), &EntryPoints::GX);
+        assert_eq!(EntryPoints::GX.symbol("dsktop"), Some(0x806f8));
+        assert_eq!(EntryPoints::SX.symbol("ACCESSTIME"), Some(0x70052));
+        assert_eq!(EntryPoints::GX.symbol("PC"), None);
+
+        let emu = boot_gx();
+        let points = emu.entry_points();
+        assert_eq!(points, &EntryPoints::GX);
+        let home = read(&emu, points.userob, 5);
+        assert_eq!(read(&emu, home, 5), crate::rpl::DORRP);
+    }
+}
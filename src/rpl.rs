@@ -0,0 +1,1393 @@
+// RPL objects in emulated memory — the data stack and the objects on it,
+// decoded. Port of x48 rpl.c (dec_rpl_obj and friends, check_xlib,
+// xlib_name).
+//
+// The firmware keeps its system pointers (DSKTOP, TEMPOB, ...) at fixed RAM
+// addresses, which moved between the SX and GX; the ROM revision's
+// `EntryPoints` (rom.rs) has them. The data stack is a run of 5-nibble
+// object pointers upwards from DSKTOP, level 1 first, to a zero pointer
+// just below EDITLINE.
+//
+// Objects are read through `NibbleSource`, so the decoder serves memory and
+// object images alike. Pointers inside composites are followed in memory:
+// to a data object (the ROM keeps constants such as 1 and 2) they decode as
+// that object; to code or a program they become the command, named from the
+// library link and hash tables listed in ROMPTAB.
+//
+// Objects go onto the stack the way binio.c loads files: into a new
+// temporary object at the top of TEMPOB, the return stack moved up to make
+// room, and a pointer to it pushed. That is only safe while the firmware
+// sits at the stack prompt with its pointers saved to RAM, so
+// `check_push` refuses otherwise: unless the pointers are in order, the
+// return stack is as deep as the outer loop leaves it waiting for a key
+// (a halted program, or an application such as INPUT, runs deeper) and
+// the command line, between EDITLINE and the pointer after it, is empty.
+//
+// The text form follows the calculator's: reals as in STD mode, « », { },
+// ( , ), # 1Ah, :tag:obj. Algebraics keep their stored (postfix) order.
+//
+// HP 48 characters map one-to-one onto Unicode: ASCII and Latin-1 as they
+// are, the calculator's own symbols (127-159) onto their look-alikes.
+
+use std::fmt;
+
+use crate::disasm::NibbleSource;
+use crate::emulator::Emulator;
+use crate::rom::EntryPoints;
+use crate::types::Model;
+
+// Object prologs
+pub const DOBINT: u32 = 0x02911; // System binary
+pub const DOREAL: u32 = 0x02933; // Real
+pub const DOEREL: u32 = 0x02955; // Long real
+pub const DOCMP: u32 = 0x02977; // Complex
+pub const DOECMP: u32 = 0x0299d; // Long complex
+pub const DOCHAR: u32 = 0x029bf; // Character
+pub const DOARRY: u32 = 0x029e8; // Array
+pub const DOLNKARRY: u32 = 0x02a0a; // Linked array
+pub const DOCSTR: u32 = 0x02a2c; // String
+pub const DOHSTR: u32 = 0x02a4e; // Binary integer
+pub const DOLIST: u32 = 0x02a74; // List
+pub const DORRP: u32 = 0x02a96; // Directory
+pub const DOSYMB: u32 = 0x02ab8; // Algebraic
+pub const DOEXT: u32 = 0x02ada; // Unit
+pub const DOTAG: u32 = 0x02afc; // Tagged
+pub const DOGROB: u32 = 0x02b1e; // Graphic object
+pub const DOLIB: u32 = 0x02b40; // Library
+pub const DOBAK: u32 = 0x02b62; // Backup
+pub const DOEXT0: u32 = 0x02b88; // Library data
+pub const DOACPTR: u32 = 0x02baa; // Access pointer (GX), extended 1 (SX)
+pub const DOEXT2: u32 = 0x02bcc;
+pub const DOEXT3: u32 = 0x02bee;
+pub const DOEXT4: u32 = 0x02c10;
+pub const DOCOL: u32 = 0x02d9d; // Program
+pub const DOCODE: u32 = 0x02dcc; // Code
+pub const DOIDNT: u32 = 0x02e48; // Global name
+pub const DOLAM: u32 = 0x02e6d; // Local name
+pub const DOROMP: u32 = 0x02e92; // XLIB name
+
+/// Ends composite objects.
+pub const SEMI: u32 = 0x0312b;
+
+// Unit operators
+pub const UM_MUL: u32 = 0x10b5e;
+pub const UM_DIV: u32 = 0x10b68;
+pub const UM_POW: u32 = 0x10b72;
+pub const UM_PRE: u32 = 0x10b7c;
+pub const UM_END: u32 = 0x10b86;
+
+/// xSILENT': pushes the object after it rather than running it. A program
+/// inside a program is compiled after one; the calculator does not show it.
+pub const SILENT_QUOTE: u32 = 0x2349c;
+
+/// Composites nested deeper than this are taken for garbage.
+const MAX_DEPTH: usize = 64;
+/// Likewise composites, arrays and stacks with more items than this.
+const MAX_ITEMS: usize = 0x10000;
+
+/// The calculator's symbols, characters 127-159. Its x̄ has no code point
+/// of its own and is shown as ẋ.
+const HP_SYMBOLS: [char; 33] = [
+    '▒', '∠', 'ẋ', '∇', '√', '∫', 'Σ', '▶', 'π', '∂', '≤', '≥', '≠', 'α', '→', '←', '↓', '↑', 'γ',
+    'δ', 'ε', 'η', 'θ', 'λ', 'ρ', 'σ', 'τ', 'ω', 'Δ', 'Π', 'Ω', '■', '∞',
+];
+
+/// The Unicode character for HP 48 character `b`.
+pub fn hp_char(b: u8) -> char {
+    match b {
+        127..=159 => HP_SYMBOLS[b as usize - 127],
+        _ => b as char,
+    }
+}
+
+/// The HP 48 character for `c`, the reverse of `hp_char`.
+pub fn hp_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0..=126 | 160..=255 => Some(c as u8),
+        _ => HP_SYMBOLS
+            .iter()
+            .position(|&s| s == c)
+            .map(|i| i as u8 + 127),
+    }
+}
+
+/// A real number as stored: sign, mantissa digits and decimal exponent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Real {
+    pub negative: bool,
+    /// The mantissa digits read as an integer; the first digit is the one
+    /// before the decimal point.
+    pub mantissa: u64,
+    pub exponent: i32,
+    /// Mantissa digits: 12, or 15 in long reals.
+    pub digits: u8,
+}
+
+impl Real {
+    /// `value` rounded to 12 digits; `None` if it is not finite or out of
+    /// the calculator's range.
+    pub fn from_f64(value: f64) -> Option<Real> {
+        if !value.is_finite() {
+            return None;
+        }
+        let text = format!("{:.11e}", value.abs());
+        let (digits, exponent) = text.split_once('e')?;
+        let mantissa: u64 = digits.replace('.', "").parse().ok()?;
+        let exponent: i32 = exponent.parse().ok()?;
+        if mantissa != 0 && !(-499..=499).contains(&exponent) {
+            return None;
+        }
+        Some(Real {
+            negative: value < 0.0 && mantissa != 0,
+            mantissa,
+            exponent: if mantissa == 0 { 0 } else { exponent },
+            digits: 12,
+        })
+    }
+
+    /// Exponent, mantissa and sign nibbles, as stored.
+    fn nibbles(&self, out: &mut Vec<u8>) {
+        let exp_digits = if self.digits == 15 { 5 } else { 3 };
+        let exponent = self.exponent.rem_euclid(10i32.pow(exp_digits)) as u64;
+        put_bcd(out, exponent, exp_digits);
+        put_bcd(out, self.mantissa, self.digits as u32);
+        out.push(if self.negative { 9 } else { 0 });
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let value = self.mantissa as f64 * 10f64.powi(self.exponent - (self.digits as i32 - 1));
+        if self.negative {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+/// STD display format (x48's real_number).
+impl fmt::Display for Real {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n = self.digits as i32;
+        let m = format!("{:0width$}", self.mantissa, width = n as usize);
+        let sign = if self.negative { "-" } else { "" };
+        let re = self.exponent;
+        if (0..n).contains(&re) {
+            let (int, frac) = m.split_at(re as usize + 1);
+            return match frac.trim_end_matches('0') {
+                "" => write!(f, "{sign}{int}"),
+                frac => write!(f, "{sign}{int}.{frac}"),
+            };
+        }
+        let significant = m.trim_end_matches('0');
+        if re < 0 && -re <= n - significant.len() as i32 + 1 {
+            let zeros = "0".repeat(-re as usize - 1);
+            return write!(f, "{sign}.{zeros}{significant}");
+        }
+        write!(f, "{sign}{}.{}", &m[..1], m[1..].trim_end_matches('0'))?;
+        if re != 0 {
+            write!(f, "E{re}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An RPL object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Bint(u32),
+    Real(Real),
+    LongReal(Real),
+    Complex(Real, Real),
+    LongComplex(Real, Real),
+    Char(char),
+    /// Elements in row order, each of the array's element type.
+    Array {
+        dims: Vec<u32>,
+        items: Vec<Object>,
+    },
+    String(String),
+    /// Binary integer (hex string): its nibbles, least significant first.
+    Binary(Vec<u8>),
+    List(Vec<Object>),
+    Program(Vec<Object>),
+    /// Algebraic, in stored (postfix) order.
+    Algebraic(Vec<Object>),
+    /// Unit, in stored (postfix) order.
+    Unit(Vec<Object>),
+    /// Unit operator inside a unit: `*`, `/`, `^`, `_`, or ` ` for a prefix.
+    UnitOp(char),
+    Tagged(String, Box<Object>),
+    Global(String),
+    Local(String),
+    /// Library command by library and command number, and its name if the
+    /// library is attached.
+    Xlib {
+        library: u32,
+        command: u32,
+        name: Option<String>,
+    },
+    /// A pointer to a built-in command, by name.
+    Command(String),
+    /// A pointer to anything else.
+    Pointer(u32),
+    Grob {
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    },
+    Library {
+        number: u32,
+        title: String,
+    },
+    /// Objects shown by type only (directory, backup, code, ...) with
+    /// their prolog and size in nibbles.
+    Other {
+        prolog: u32,
+        size: u32,
+    },
+}
+
+fn join(items: &[Object]) -> String {
+    items
+        .iter()
+        .filter(|o| **o != Object::Pointer(SILENT_QUOTE))
+        .map(|o| o.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn put(out: &mut Vec<u8>, value: u32, n: u32) {
+    out.extend((0..n).map(|i| (value >> (4 * i)) as u8 & 0xf));
+}
+
+fn put_bcd(out: &mut Vec<u8>, mut value: u64, n: u32) {
+    for _ in 0..n {
+        out.push((value % 10) as u8);
+        value /= 10;
+    }
+}
+
+fn put_text(out: &mut Vec<u8>, text: &str) -> Result<(), String> {
+    for c in text.chars() {
+        let b = hp_byte(c).ok_or_else(|| format!("no HP 48 character for {c:?}"))?;
+        put(out, b as u32, 2);
+    }
+    Ok(())
+}
+
+/// An object with a length field, which counts itself and what follows.
+fn put_sized(
+    out: &mut Vec<u8>,
+    prolog: u32,
+    body: impl FnOnce(&mut Vec<u8>) -> Result<(), String>,
+) -> Result<(), String> {
+    put(out, prolog, 5);
+    let at = out.len();
+    body(out)?;
+    let len = (out.len() - at) as u32 + 5;
+    let mut field = Vec::new();
+    put(&mut field, len, 5);
+    out.splice(at..at, field);
+    Ok(())
+}
+
+/// A name's length byte and characters.
+fn put_name(out: &mut Vec<u8>, name: &str) -> Result<(), String> {
+    let len = name.chars().count();
+    if len > 255 {
+        return Err(format!("name too long: {name}"));
+    }
+    put(out, len as u32, 2);
+    put_text(out, name)
+}
+
+impl Object {
+    /// The object as stored in memory, one nibble per byte. Commands
+    /// known only by name cannot be encoded, nor libraries, directories
+    /// and the other objects kept by type only.
+    pub fn to_nibbles(&self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        self.encode(&mut out)?;
+        Ok(out)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), String> {
+        match self {
+            Object::Bint(n) => {
+                put(out, DOBINT, 5);
+                put(out, *n, 5);
+            }
+            Object::Real(r) | Object::LongReal(r) => {
+                put(out, if r.digits == 15 { DOEREL } else { DOREAL }, 5);
+                r.nibbles(out);
+            }
+            Object::Complex(re, im) | Object::LongComplex(re, im) => {
+                put(out, if re.digits == 15 { DOECMP } else { DOCMP }, 5);
+                re.nibbles(out);
+                im.nibbles(out);
+            }
+            Object::Char(c) => {
+                put(out, DOCHAR, 5);
+                put_text(out, c.encode_utf8(&mut [0; 4]))?;
+            }
+            Object::String(text) => put_sized(out, DOCSTR, |out| put_text(out, text))?,
+            Object::Binary(digits) => put_sized(out, DOHSTR, |out| {
+                out.extend(digits.iter().map(|d| d & 0xf));
+                Ok(())
+            })?,
+            Object::Array { dims, items } => put_sized(out, DOARRY, |out| {
+                let mut bodies = Vec::new();
+                let mut prolog = DOREAL;
+                for (i, item) in items.iter().enumerate() {
+                    let nibbles = item.to_nibbles()?;
+                    let kind = nibbles
+                        .iter()
+                        .take(5)
+                        .rev()
+                        .fold(0, |v, &n| v << 4 | n as u32);
+                    if i > 0 && kind != prolog {
+                        return Err("array elements of different types".to_string());
+                    }
+                    prolog = kind;
+                    bodies.extend_from_slice(&nibbles[5..]);
+                }
+                put(out, prolog, 5);
+                put(out, dims.len() as u32, 5);
+                dims.iter().for_each(|&d| put(out, d, 5));
+                out.extend(bodies);
+                Ok(())
+            })?,
+            Object::List(items)
+            | Object::Program(items)
+            | Object::Algebraic(items)
+            | Object::Unit(items) => {
+                let prolog = match self {
+                    Object::List(_) => DOLIST,
+                    Object::Program(_) => DOCOL,
+                    Object::Algebraic(_) => DOSYMB,
+                    _ => DOEXT,
+                };
+                put(out, prolog, 5);
+                for item in items {
+                    item.encode(out)?;
+                }
+                put(out, SEMI, 5);
+            }
+            Object::UnitOp(op) => {
+                let addr = match op {
+                    '*' => UM_MUL,
+                    '/' => UM_DIV,
+                    '^' => UM_POW,
+                    ' ' => UM_PRE,
+                    '_' => UM_END,
+                    _ => return Err(format!("no unit operator {op}")),
+                };
+                put(out, addr, 5);
+            }
+            Object::Tagged(tag, object) => {
+                put(out, DOTAG, 5);
+                put_name(out, tag)?;
+                object.encode(out)?;
+            }
+            Object::Global(name) | Object::Local(name) => {
+                put(
+                    out,
+                    if matches!(self, Object::Global(_)) {
+                        DOIDNT
+                    } else {
+                        DOLAM
+                    },
+                    5,
+                );
+                put_name(out, name)?;
+            }
+            Object::Xlib {
+                library, command, ..
+            } => {
+                put(out, DOROMP, 5);
+                put(out, *library, 3);
+                put(out, *command, 3);
+            }
+            Object::Pointer(addr) => put(out, *addr, 5),
+            Object::Grob {
+                width,
+                height,
+                data,
+            } => put_sized(out, DOGROB, |out| {
+                put(out, *height, 5);
+                put(out, *width, 5);
+                out.extend(data.iter().map(|d| d & 0xf));
+                Ok(())
+            })?,
+            Object::Command(name) => return Err(format!("cannot encode the command {name}")),
+            Object::Library { .. } | Object::Other { .. } => {
+                return Err(format!("cannot encode a {self}"))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A unit's stored form back in infix, as in 1_m/s^2.
+fn unit_text(items: &[Object]) -> Option<String> {
+    let mut stack: Vec<String> = Vec::new();
+    for item in items {
+        let Object::UnitOp(op) = item else {
+            stack.push(match item {
+                Object::String(s) => s.clone(),
+                Object::Char(c) => c.to_string(),
+                _ => item.to_string(),
+            });
+            continue;
+        };
+        let b = stack.pop()?;
+        let a = stack.pop()?;
+        stack.push(match op {
+            ' ' => format!("{a}{b}"),
+            _ => format!("{a}{op}{b}"),
+        });
+    }
+    match stack.as_slice() {
+        [text] => Some(text.clone()),
+        _ => None,
+    }
+}
+
+fn array_text(dims: &[u32], items: &[Object]) -> String {
+    match dims {
+        [] | [_] => format!("[ {} ]", join(items)),
+        [_, rest @ ..] => {
+            let row = rest.iter().product::<u32>().max(1) as usize;
+            let rows: Vec<String> = items
+                .chunks(row)
+                .map(|chunk| array_text(rest, chunk))
+                .collect();
+            format!("[{}]", rows.join(" "))
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::Bint(n) => write!(f, "<{n:X}h>"),
+            Object::Real(r) | Object::LongReal(r) => write!(f, "{r}"),
+            Object::Complex(re, im) | Object::LongComplex(re, im) => write!(f, "({re},{im})"),
+            Object::Char(c) => write!(f, "'{c}'"),
+            Object::Array { dims, items } => f.write_str(&array_text(dims, items)),
+            Object::String(s) => write!(f, "\"{s}\""),
+            Object::Binary(nibbles) if nibbles.len() <= 16 => {
+                let hex: String = nibbles
+                    .iter()
+                    .rev()
+                    .map(|&n| char::from_digit(n as u32, 16).unwrap().to_ascii_uppercase())
+                    .collect();
+                let hex = hex.trim_start_matches('0');
+                write!(f, "# {}h", if hex.is_empty() { "0" } else { hex })
+            }
+            Object::Binary(nibbles) => {
+                let hex: String = nibbles
+                    .iter()
+                    .map(|&n| char::from_digit(n as u32, 16).unwrap().to_ascii_uppercase())
+                    .collect();
+                write!(f, "C# {} {hex}", nibbles.len())
+            }
+            Object::List(items) if items.is_empty() => f.write_str("{ }"),
+            Object::List(items) => write!(f, "{{ {} }}", join(items)),
+            // User programs hold their « and » as commands.
+            Object::Program(items) if matches!(items.first(), Some(Object::Command(c)) if c == "«") => {
+                f.write_str(&join(items))
+            }
+            Object::Program(items) if items.is_empty() => f.write_str("« »"),
+            Object::Program(items) => write!(f, "« {} »", join(items)),
+            Object::Algebraic(items) => write!(f, "'{}'", join(items)),
+            Object::Unit(items) => match unit_text(items) {
+                Some(text) => f.write_str(&text),
+                None => f.write_str(&join(items)),
+            },
+            Object::UnitOp(op) => write!(f, "{op}"),
+            Object::Tagged(tag, obj) => write!(f, ":{tag}:{obj}"),
+            Object::Global(name) | Object::Local(name) | Object::Command(name) => f.write_str(name),
+            Object::Xlib {
+                name: Some(name), ..
+            } => f.write_str(name),
+            Object::Xlib {
+                library, command, ..
+            } => write!(f, "XLIB {library} {command}"),
+            Object::Pointer(addr) => write!(f, "PTR {addr:05X}"),
+            Object::Grob { width, height, .. } => write!(f, "Graphic {width} × {height}"),
+            Object::Library { number, title } => write!(f, "Library {number}: {title}"),
+            Object::Other { prolog, .. } => f.write_str(match *prolog {
+                DORRP => "Directory",
+                DOBAK => "Backup",
+                DOEXT0 => "Library Data",
+                DOCODE => "Code",
+                _ => "External",
+            }),
+        }
+    }
+}
+
+/// `n` nibbles (at most 8) at `addr`, least significant first.
+fn nibbles<S: NibbleSource + ?Sized>(src: &S, addr: u32, n: u32) -> u32 {
+    (0..n).rev().fold(0, |v, i| {
+        v << 4 | src.nibble((addr.wrapping_add(i) & 0xfffff) as i32) as u32
+    })
+}
+
+fn unit_op(addr: u32) -> Option<char> {
+    match addr {
+        UM_MUL => Some('*'),
+        UM_DIV => Some('/'),
+        UM_POW => Some('^'),
+        UM_PRE => Some(' '),
+        UM_END => Some('_'),
+        _ => None,
+    }
+}
+
+/// Whether objects with this prolog are data, as opposed to code.
+fn is_data(prolog: u32) -> bool {
+    matches!(
+        prolog,
+        DOBINT
+            | DOREAL
+            | DOEREL
+            | DOCMP
+            | DOECMP
+            | DOCHAR
+            | DOARRY
+            | DOLNKARRY
+            | DOCSTR
+            | DOHSTR
+            | DOLIST
+            | DORRP
+            | DOSYMB
+            | DOEXT
+            | DOTAG
+            | DOGROB
+            | DOLIB
+            | DOBAK
+            | DOEXT0
+            | DOACPTR
+            | DOEXT2
+            | DOEXT3
+            | DOEXT4
+            | DOIDNT
+            | DOLAM
+            | DOROMP
+    )
+}
+
+/// Size in nibbles of the object at `addr`, found without decoding it
+/// (binio.c's RPL_ObjectSize). An embedded pointer counts 5.
+pub fn object_size<S: NibbleSource + ?Sized>(src: &S, addr: u32) -> Result<u32, String> {
+    sized(src, addr, 0)
+}
+
+fn sized<S: NibbleSource + ?Sized>(src: &S, addr: u32, depth: usize) -> Result<u32, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("objects nested too deep at {addr:05X}"));
+    }
+    let read = |at: u32, n: u32| nibbles(src, at, n);
+    Ok(match read(addr, 5) {
+        DOBINT => 10,
+        DOREAL => 21,
+        DOEREL => 26,
+        DOCMP => 37,
+        DOECMP => 47,
+        DOCHAR => 7,
+        DOACPTR => 15,
+        DOROMP => 11,
+        DOLIST | DOSYMB | DOEXT | DOCOL => {
+            let mut at = addr + 5;
+            while read(at, 5) != SEMI {
+                if at - addr > 0x100000 {
+                    return Err(format!("no end to the composite at {addr:05X}"));
+                }
+                at += sized(src, at, depth + 1)?;
+            }
+            at + 5 - addr
+        }
+        DOIDNT | DOLAM => 7 + 2 * read(addr + 5, 2),
+        DOTAG => {
+            let tag = 7 + 2 * read(addr + 5, 2);
+            tag + sized(src, addr + tag, depth + 1)?
+        }
+        DORRP => match read(addr + 8, 5) {
+            0 => 13,
+            last => {
+                let entry = 8 + last;
+                let object = entry + 2 * read(addr + entry, 2) + 4;
+                object + sized(src, addr + object, depth + 1)?
+            }
+        },
+        DOARRY | DOLNKARRY | DOCSTR | DOHSTR | DOGROB | DOLIB | DOBAK | DOEXT0 | DOEXT2
+        | DOEXT3 | DOEXT4 | DOCODE => 5 + read(addr + 5, 5),
+        _ => 5,
+    })
+}
+
+/// Decodes objects from `src`, following pointers into `emu`'s memory.
+struct Decoder<'a, S: NibbleSource + ?Sized> {
+    emu: &'a Emulator,
+    src: &'a S,
+}
+
+impl<S: NibbleSource + ?Sized> Decoder<'_, S> {
+    fn read(&self, addr: u32, n: u32) -> u32 {
+        nibbles(self.src, addr, n)
+    }
+
+    fn text(&self, addr: u32, len: u32) -> String {
+        (0..len)
+            .map(|i| hp_char(self.read(addr + 2 * i, 2) as u8))
+            .collect()
+    }
+
+    /// The object at `addr`, or what the pointer there leads to, and the
+    /// address after it.
+    fn object(&self, addr: u32, depth: usize) -> Result<(Object, u32), String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("objects nested too deep at {addr:05X}"));
+        }
+        let prolog = self.read(addr, 5);
+        if is_data(prolog) || matches!(prolog, DOCOL | DOCODE) {
+            return self.body(prolog, addr + 5, depth);
+        }
+        // An embedded pointer.
+        let object = if let Some(op) = unit_op(prolog) {
+            Object::UnitOp(op)
+        } else if prolog == SILENT_QUOTE {
+            Object::Pointer(prolog)
+        } else if let Some((library, command)) = self.emu.library_command(prolog) {
+            match self.emu.command_name(library, command) {
+                Some(name) => Object::Command(name),
+                None => Object::Xlib {
+                    library,
+                    command,
+                    name: None,
+                },
+            }
+        } else if is_data(nibbles(self.emu, prolog, 5)) {
+            let memory = Decoder {
+                emu: self.emu,
+                src: self.emu,
+            };
+            memory.object(prolog, depth + 1)?.0
+        } else {
+            Object::Pointer(prolog)
+        };
+        Ok((object, addr + 5))
+    }
+
+    /// Objects up to the SEMI ending a composite at `addr`.
+    fn items(&self, mut addr: u32, depth: usize) -> Result<(Vec<Object>, u32), String> {
+        let mut items = Vec::new();
+        while self.read(addr, 5) != SEMI {
+            if items.len() >= MAX_ITEMS {
+                return Err(format!("no end to the composite before {addr:05X}"));
+            }
+            let (item, next) = self.object(addr, depth + 1)?;
+            items.push(item);
+            addr = next;
+        }
+        Ok((items, addr + 5))
+    }
+
+    fn real(&self, addr: u32, digits: u32, exp_digits: u32) -> Result<(Real, u32), String> {
+        let bcd = |at: u32, n: u32| {
+            (0..n)
+                .rev()
+                .try_fold(0u64, |v, i| match self.read(at + i, 1) {
+                    d @ 0..=9 => Ok(v * 10 + d as u64),
+                    _ => Err(format!("bad BCD digit in the real at {addr:05X}")),
+                })
+        };
+        let mut exponent = bcd(addr, exp_digits)? as i64;
+        let modulus = 10i64.pow(exp_digits);
+        if exponent >= modulus / 2 {
+            exponent -= modulus;
+        }
+        let mantissa = bcd(addr + exp_digits, digits)?;
+        let negative = self.read(addr + exp_digits + digits, 1) >= 5;
+        let real = Real {
+            negative,
+            mantissa,
+            exponent: exponent as i32,
+            digits: digits as u8,
+        };
+        Ok((real, addr + exp_digits + digits + 1))
+    }
+
+    /// The body of an object with `prolog`, at `addr` just after it.
+    fn body(&self, prolog: u32, addr: u32, depth: usize) -> Result<(Object, u32), String> {
+        let sized = || {
+            let len = self.read(addr, 5);
+            (len, addr + len)
+        };
+        Ok(match prolog {
+            DOBINT => (Object::Bint(self.read(addr, 5)), addr + 5),
+            DOREAL => {
+                let (r, end) = self.real(addr, 12, 3)?;
+                (Object::Real(r), end)
+            }
+            DOEREL => {
+                let (r, end) = self.real(addr, 15, 5)?;
+                (Object::LongReal(r), end)
+            }
+            DOCMP => {
+                let (re, mid) = self.real(addr, 12, 3)?;
+                let (im, end) = self.real(mid, 12, 3)?;
+                (Object::Complex(re, im), end)
+            }
+            DOECMP => {
+                let (re, mid) = self.real(addr, 15, 5)?;
+                let (im, end) = self.real(mid, 15, 5)?;
+                (Object::LongComplex(re, im), end)
+            }
+            DOCHAR => (Object::Char(hp_char(self.read(addr, 2) as u8)), addr + 2),
+            DOARRY | DOLNKARRY => self.array(prolog == DOLNKARRY, addr, depth)?,
+            DOCSTR => {
+                let (len, end) = sized();
+                let text = self.text(addr + 5, len.saturating_sub(5) / 2);
+                (Object::String(text), end)
+            }
+            DOHSTR => {
+                let (len, end) = sized();
+                let nibbles = (5..len.max(5)).map(|i| self.read(addr + i, 1) as u8);
+                (Object::Binary(nibbles.collect()), end)
+            }
+            DOLIST | DOCOL | DOSYMB | DOEXT => {
+                let (items, end) = self.items(addr, depth)?;
+                let object = match prolog {
+                    DOLIST => Object::List(items),
+                    DOCOL => Object::Program(items),
+                    DOSYMB => Object::Algebraic(items),
+                    _ => Object::Unit(items),
+                };
+                (object, end)
+            }
+            DOIDNT | DOLAM => {
+                let len = self.read(addr, 2);
+                let name = self.text(addr + 2, len);
+                let end = addr + 2 + 2 * len;
+                match prolog {
+                    DOIDNT => (Object::Global(name), end),
+                    _ => (Object::Local(name), end),
+                }
+            }
+            DOTAG => {
+                let len = self.read(addr, 2);
+                let tag = self.text(addr + 2, len);
+                let (obj, end) = self.object(addr + 2 + 2 * len, depth + 1)?;
+                (Object::Tagged(tag, Box::new(obj)), end)
+            }
+            DOROMP => {
+                let library = self.read(addr, 3);
+                let command = self.read(addr + 3, 3);
+                let name = self.emu.command_name(library, command);
+                let xlib = Object::Xlib {
+                    library,
+                    command,
+                    name,
+                };
+                (xlib, addr + 6)
+            }
+            DOGROB => {
+                let (len, end) = sized();
+                let height = self.read(addr + 5, 5);
+                let width = self.read(addr + 10, 5);
+                let data = (15..len.max(15)).map(|i| self.read(addr + i, 1) as u8);
+                let grob = Object::Grob {
+                    width,
+                    height,
+                    data: data.collect(),
+                };
+                (grob, end)
+            }
+            DOLIB => {
+                let (_, end) = sized();
+                let title_len = self.read(addr + 5, 2);
+                let title = self.text(addr + 7, title_len);
+                let number = self.read(addr + 2 * title_len + 9, 3);
+                (Object::Library { number, title }, end)
+            }
+            DORRP | DOACPTR => {
+                let size = object_size(self.src, addr - 5)?;
+                (Object::Other { prolog, size }, addr - 5 + size)
+            }
+            _ => {
+                let (len, end) = sized();
+                (
+                    Object::Other {
+                        prolog,
+                        size: len + 5,
+                    },
+                    end,
+                )
+            }
+        })
+    }
+
+    fn array(&self, linked: bool, addr: u32, depth: usize) -> Result<(Object, u32), String> {
+        let len = self.read(addr, 5);
+        let prolog = self.read(addr + 5, 5);
+        let rank = self.read(addr + 10, 5);
+        let dims: Vec<u32> = (0..rank.min(8))
+            .map(|i| self.read(addr + 15 + 5 * i, 5))
+            .collect();
+        let count = dims
+            .iter()
+            .try_fold(1usize, |n, &d| n.checked_mul(d as usize))
+            .filter(|&n| rank <= 8 && n <= MAX_ITEMS)
+            .ok_or_else(|| format!("bad array dimensions at {addr:05X}"))?;
+        let mut at = addr + 15 + 5 * rank;
+        let mut items = Vec::with_capacity(count);
+        for _ in 0..count {
+            if linked {
+                let item = at + self.read(at, 5);
+                items.push(self.body(prolog, item, depth + 1)?.0);
+                at += 5;
+            } else {
+                let (item, next) = self.body(prolog, at, depth + 1)?;
+                items.push(item);
+                at = next;
+            }
+        }
+        Ok((Object::Array { dims, items }, addr + len))
+    }
+}
+
+impl Emulator {
+    /// The system pointers of the ROM revision.
+    pub fn entry_points(&self) -> &'static EntryPoints {
+        self.revision.entry_points()
+    }
+
+    /// Addresses of the objects on the data stack, level 1 first.
+    /// The stack runs from DSKTOP to a zero pointer just below EDITLINE.
+    pub fn stack_addresses(&self) -> Result<Vec<u32>, String> {
+        let pointers = self.entry_points();
+        let top = nibbles(self, pointers.dsktop, 5);
+        let end = nibbles(self, pointers.editline, 5);
+        if end < top + 5 || !(end - top).is_multiple_of(5) || nibbles(self, end - 5, 5) != 0 {
+            return Err(format!("no stack between {top:05X} and {end:05X}"));
+        }
+        let levels: Vec<u32> = (top..end - 5)
+            .step_by(5)
+            .map(|at| nibbles(self, at, 5))
+            .collect();
+        if levels.contains(&0) {
+            return Err(format!("no stack between {top:05X} and {end:05X}"));
+        }
+        Ok(levels)
+    }
+
+    /// The data stack, level 1 first.
+    pub fn stack(&self) -> Result<Vec<Object>, String> {
+        self.stack_addresses()?
+            .into_iter()
+            .map(|addr| self.object_at(addr))
+            .collect()
+    }
+
+    /// The stack as the calculator lists it, one `level: object` line per
+    /// level, level 1 last.
+    pub fn stack_text(&self) -> Result<String, String> {
+        let stack = self.stack()?;
+        Ok(stack
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, object)| format!("{}: {object}\n", i + 1))
+            .collect())
+    }
+
+    /// The object at `addr` in memory.
+    pub fn object_at(&self, addr: u32) -> Result<Object, String> {
+        let decoder = Decoder {
+            emu: self,
+            src: self,
+        };
+        decoder.object(addr, 0).map(|(object, _)| object)
+    }
+
+    /// Where RAM is, as the firmware has it mapped at the stack prompt.
+    pub(crate) fn ram_range(&self) -> std::ops::Range<u32> {
+        let base = match self.model {
+            Model::Sx => 0x70000,
+            Model::Gx => 0x80000,
+        };
+        base..base + self.mem.ram.len() as u32
+    }
+
+    /// Whether objects can go onto the stack now: only while the firmware
+    /// waits for a key at the stack prompt, its pointers at rest and the
+    /// command line empty. Otherwise the error says why not.
+    pub fn check_push(&self) -> Result<(), String> {
+        if !self.is_idle() {
+            return Err("the calculator is busy".to_string());
+        }
+        let p = self.entry_points();
+        let [tempob, temptop, rsktop, dsktop, editline, tempenv] = [
+            p.tempob, p.temptop, p.rsktop, p.dsktop, p.editline, p.tempenv,
+        ]
+        .map(|at| nibbles(self, at, 5));
+        let ram = self.ram_range();
+        let in_ram = ram.start <= tempob && tempenv <= ram.end;
+        let ordered = tempob <= temptop
+            && temptop <= rsktop
+            && rsktop <= dsktop
+            && dsktop < editline
+            && editline <= tempenv;
+        if !in_ram || !ordered || nibbles(self, p.avmem, 5) != (dsktop - rsktop) / 5 {
+            return Err("the system pointers are not at rest".to_string());
+        }
+        match (rsktop - temptop) / 5 {
+            depth if depth > p.rest_depth => {
+                return Err("a program or application has the keyboard".to_string())
+            }
+            depth if depth < p.rest_depth => {
+                return Err("the system pointers are not at rest".to_string())
+            }
+            _ => {}
+        }
+        if (editline..tempenv).any(|at| self.peek_nibble(at as i32) != 0) {
+            return Err("the command line is open".to_string());
+        }
+        self.stack_addresses().map(|_| ())
+    }
+
+    /// Put `object`, as stored in memory, into a new temporary object and
+    /// push that onto the stack (binio.c's RPL_CreateTemp and RPL_Push).
+    /// Returns its address. The display shows it from the next key on.
+    pub fn push_object(&mut self, object: &[u8]) -> Result<u32, String> {
+        let size = object_size(object, 0)?;
+        if size as usize != object.len() {
+            return Err(format!(
+                "the object is {size} nibbles long, not {}",
+                object.len()
+            ));
+        }
+        self.check_push()?;
+        let p = self.entry_points();
+        // A temporary object is followed by a 5-nibble link back to its
+        // start, and a marker nibble comes first.
+        let len = size + 6;
+        let temptop = nibbles(self, p.temptop, 5);
+        let rsktop = nibbles(self, p.rsktop, 5);
+        let dsktop = nibbles(self, p.dsktop, 5);
+        if rsktop + len + 5 > dsktop {
+            return Err("not enough memory".to_string());
+        }
+        let rstack: Vec<u8> = (temptop..rsktop)
+            .map(|at| self.peek_nibble(at as i32))
+            .collect();
+        for (i, &n) in rstack.iter().enumerate() {
+            self.write_nibble((temptop + len) as i32 + i as i32, n as i32);
+        }
+        self.poke(p.temptop, temptop + len, 5);
+        self.poke(p.rsktop, rsktop + len, 5);
+        self.poke(temptop + len - 5, len, 5);
+        let addr = temptop + 1;
+        for (i, &n) in object.iter().enumerate() {
+            self.write_nibble(addr as i32 + i as i32, n as i32);
+        }
+        self.poke(p.avmem, (dsktop - rsktop - len) / 5 - 1, 5);
+        self.poke(dsktop - 5, addr, 5);
+        self.poke(p.dsktop, dsktop - 5, 5);
+        Ok(addr)
+    }
+
+    /// Push `object` onto the stack; see `push_object`.
+    pub fn push(&mut self, object: &Object) -> Result<(), String> {
+        self.push_object(&object.to_nibbles()?).map(|_| ())
+    }
+
+    fn poke(&mut self, addr: u32, value: u32, n: u32) {
+        for i in 0..n {
+            self.write_nibble((addr + i) as i32, (value >> (4 * i)) as i32 & 0xf);
+        }
+    }
+
+    /// `n` nibbles at `addr` as the library tables are read: on the SX,
+    /// tables in ROM are read with RAM moved out of the way.
+    fn library_nibbles(&self, addr: u32, n: u32, rom: bool) -> u32 {
+        if rom {
+            nibbles(self.mem.rom.as_slice(), addr, n)
+        } else {
+            nibbles(self, addr, n)
+        }
+    }
+
+    fn table_in_rom(&self, addr: u32) -> bool {
+        self.model == Model::Sx && addr < 0x70000
+    }
+
+    /// Attached libraries in ROMPTAB order, as (number, address), leaving
+    /// out those in covered port memory.
+    fn libraries(&self) -> Vec<(u32, u32)> {
+        let mut at = self.entry_points().romptab;
+        let count = nibbles(self, at, 3);
+        at += 3;
+        let mut libs = Vec::new();
+        for _ in 0..count {
+            let number = nibbles(self, at, 3);
+            let addr = nibbles(self, at + 3, 5);
+            at += 8;
+            if self.model == Model::Gx {
+                let acptr = nibbles(self, at, 5);
+                at += 8;
+                if acptr != 0 {
+                    continue;
+                }
+            }
+            libs.push((number, addr));
+        }
+        libs
+    }
+
+    /// Code addresses of library `lib_addr`'s commands by command number,
+    /// from its link table; `None` for commands without code.
+    fn command_addresses(&self, lib_addr: u32) -> Vec<Option<u32>> {
+        let offset = nibbles(self, lib_addr + 13, 5);
+        if offset == 0 {
+            return Vec::new();
+        }
+        let mut table = lib_addr + 13 + offset;
+        let rom = self.table_in_rom(table);
+        if self.library_nibbles(table, 5, rom) == DOBINT {
+            table = self.library_nibbles(table + 5, 5, rom);
+        }
+        table += 5;
+        let end = table + self.library_nibbles(table, 5, rom);
+        (table + 5..end)
+            .step_by(5)
+            .map(|entry| match self.library_nibbles(entry, 5, rom) {
+                0 => None,
+                offset => Some((entry + offset) & 0xfffff),
+            })
+            .collect()
+    }
+
+    /// Code address of command `command` of library `library`, from its
+    /// link table; `None` if the library is not attached or has no such
+    /// command.
+    pub fn command_address(&self, library: u32, command: u32) -> Option<u32> {
+        let (_, lib_addr) = self
+            .libraries()
+            .into_iter()
+            .find(|&(number, _)| number == library)?;
+        *self.command_addresses(lib_addr).get(command as usize)?
+    }
+
+    /// The library command whose code is at `addr`, from the libraries'
+    /// link tables (x48's check_xlib).
+    pub fn library_command(&self, addr: u32) -> Option<(u32, u32)> {
+        self.libraries().into_iter().find_map(|(library, lib_addr)| {
+            let commands = self.command_addresses(lib_addr);
+            let command = commands.iter().position(|&a| a == Some(addr))?;
+            Some((library, command as u32))
+        })
+    }
+
+    /// Code address and name of every named command of the attached
+    /// libraries, in ROMPTAB order: the ROM revision's own table of
+    /// command pointers, as the firmware's parser and decompiler use it.
+    pub fn command_table(&self) -> Vec<(u32, String)> {
+        let mut table = Vec::new();
+        for (library, lib_addr) in self.libraries() {
+            for (command, addr) in self.command_addresses(lib_addr).into_iter().enumerate() {
+                let name = addr.and_then(|_| self.command_name(library, command as u32));
+                if let (Some(addr), Some(name)) = (addr, name) {
+                    table.push((addr, name));
+                }
+            }
+        }
+        table
+    }
+
+    /// The name of command `command` of library `library`, from its hash
+    /// table (x48's xlib_name). `None` if the library is not attached or
+    /// the command has no name.
+    pub fn command_name(&self, library: u32, command: u32) -> Option<String> {
+        let (_, lib_addr) = self
+            .libraries()
+            .into_iter()
+            .find(|&(number, _)| number == library)?;
+        let offset = nibbles(self, lib_addr + 3, 5);
+        if offset == 0 {
+            return None;
+        }
+        let mut table = lib_addr + 3 + offset;
+        let rom = self.table_in_rom(table);
+        match self.library_nibbles(table, 5, rom) {
+            DOBINT => table = self.library_nibbles(table + 5, 5, rom),
+            DOACPTR => {
+                if self.library_nibbles(table + 10, 5, rom) != 0 {
+                    return None;
+                }
+                table = self.library_nibbles(table + 5, 5, rom);
+            }
+            _ => {}
+        }
+        table += 5;
+        let end = table + self.library_nibbles(table, 5, rom);
+        let names = table + 85;
+        let entry = names + self.library_nibbles(names, 5, rom) + 5 * command;
+        if entry >= end {
+            return None;
+        }
+        let offset = self.library_nibbles(entry, 5, rom);
+        if offset == 0 {
+            return None;
+        }
+        let name = entry - offset;
+        let len = self.library_nibbles(name, 2, rom);
+        Some(
+            (0..len)
+                .map(|i| hp_char(self.library_nibbles(name + 2 + 2 * i, 2, rom) as u8))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::key_code;
+    use crate::test_support::{boot_gx, press, ENTER, ON};
+    use crate::types::{MCTL_SYSRAM_SX, ROM_SIZE_SX};
+
+    fn real(text: &str) -> Real {
+        let (mantissa, exponent) = text.split_once('E').unwrap();
+        let negative = mantissa.starts_with('-');
+        let digits: String = mantissa.chars().filter(|c| c.is_ascii_digit()).collect();
+        Real {
+            negative,
+            mantissa: format!("{digits:0<12}").parse().unwrap(),
+            exponent: exponent.parse().unwrap(),
+            digits: 12,
+        }
+    }
+
+    #[test]
+    fn test_real_text() {
+        for (stored, shown) in [
+            ("1E0", "1"),
+            ("0E0", "0"),
+            ("-25E1", "-25"),
+            ("15E-1", ".15"),
+            ("123E-3", ".00123"),
+            ("1E20", "1.E20"),
+            ("15E-20", "1.5E-20"),
+            ("123456789012E-2", "1.23456789012E-2"),
+            ("314159265359E0", "3.14159265359"),
+        ] {
+            assert_eq!(real(stored).to_string(), shown, "{stored}");
+        }
+        assert_eq!(real("-25E1").to_f64(), -25.0);
+        assert_eq!(Real::from_f64(-0.25).unwrap().to_string(), "-.25");
+        assert_eq!(
+            Real::from_f64(2.0 / 3.0).unwrap().to_string(),
+            ".666666666667"
+        );
+        assert_eq!(Real::from_f64(-0.0), Real::from_f64(0.0));
+        assert_eq!(Real::from_f64(f64::INFINITY), None);
+        assert_eq!(Real::from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_chars() {
+        for b in 0..=255u8 {
+            assert_eq!(hp_byte(hp_char(b)), Some(b));
+        }
+        assert_eq!(hp_char(141), '→');
+        assert_eq!(hp_char(171), '«');
+        assert_eq!(hp_byte('€'), None);
+    }
+
+    #[test]
+    fn test_push() {
+        let mut emu = boot_gx();
+        let real = |v: f64| Object::Real(Real::from_f64(v).unwrap());
+        emu.push(&real(1.5)).unwrap();
+        emu.push(&real(2.0)).unwrap();
+        press(&mut emu, &[0x00]);
+        assert_eq!(emu.stack().unwrap(), vec![real(3.5)]);
+        emu.update_display();
+        let shown = emu.display.to_pbm();
+        let mut typed = boot_gx();
+        typed.type_text("3.5").unwrap();
+        press(&mut typed, &[ENTER]);
+        typed.update_display();
+        assert_eq!(typed.display.matches_pbm(&shown), Ok(true));
+
+        let objects = [
+            Object::String("«Hi» ∠".into()),
+            Object::List(vec![
+                Object::Complex(
+                    Real::from_f64(-1.0).unwrap(),
+                    Real::from_f64(2e-300).unwrap(),
+                ),
+                Object::Binary(vec![0xf, 1]),
+                Object::Tagged("T".into(), Box::new(Object::Global("X".into()))),
+            ]),
+            Object::Array {
+                dims: vec![2, 1],
+                items: vec![real(1.0), real(-0.25)],
+            },
+        ];
+        for object in &objects {
+            emu.push(object).unwrap();
+        }
+        let stack = emu.stack().unwrap();
+        assert_eq!(
+            stack[..3],
+            [objects[2].clone(), objects[1].clone(), objects[0].clone()]
+        );
+        assert_eq!(stack[3], real(3.5));
+        let level1 = emu.stack_addresses().unwrap()[0];
+        let stored: Vec<u8> = (0..objects[2].to_nibbles().unwrap().len() as u32)
+            .map(|i| emu.peek_nibble((level1 + i) as i32))
+            .collect();
+        assert_eq!(stored, objects[2].to_nibbles().unwrap());
+
+        let mut padded = real(1.0).to_nibbles().unwrap();
+        padded.push(0);
+        assert_eq!(
+            emu.push_object(&padded).unwrap_err(),
+            "the object is 21 nibbles long, not 22"
+        );
+    }
+
+    #[test]
+    fn test_push_refused() {
+        let mut emu = boot_gx();
+        let one = Object::Real(Real::from_f64(1.0).unwrap());
+        emu.type_text("5").unwrap();
+        assert_eq!(emu.push(&one), Err("the command line is open".to_string()));
+        press(&mut emu, &[ENTER]);
+        emu.push_key_event(0x13);
+        assert_eq!(emu.push(&one), Err("the calculator is busy".to_string()));
+        emu.push_key_event(0x13 | 0x8000_0000);
+        emu.run_headless(1.0, |emu| emu.is_idle());
+        emu.push(&one).unwrap();
+        let text = emu.stack_text().unwrap();
+        assert_eq!(text, "2: 5\n1: 1\n");
+        assert_eq!(
+            emu.push(&Object::Command("SIN".into())),
+            Err("cannot encode the command SIN".to_string())
+        );
+    }
+
+    #[test]
+    fn test_push_refused_in_applications() {
+        let mut emu = boot_gx();
+        let one = Object::Real(Real::from_f64(1.0).unwrap());
+        let refused = Err("a program or application has the keyboard".to_string());
+        // A soft menu leaves the firmware at the stack prompt.
+        press(&mut emu, &[key_code("MTH").unwrap()]);
+        emu.push(&one).unwrap();
+        // INPUT, a CHOOSE box and the interactive stack each wait for keys
+        // of their own.
+        for text in ["\"A\" \"\" INPUT", "\"T\" { 1 2 } 1 CHOOSE"] {
+            emu.type_text(text).unwrap();
+            press(&mut emu, &[ENTER]);
+            emu.run_headless(3.0, |emu| emu.is_idle());
+            assert_eq!(emu.push(&one), refused, "{text}");
+            press(&mut emu, &[ON]);
+        }
+        press(&mut emu, &[key_code("UP").unwrap()]);
+        assert_eq!(emu.push(&one), refused);
+        press(&mut emu, &[ON]);
+        emu.run_headless(1.0, |emu| emu.is_idle());
+        emu.push(&one).unwrap();
+    }
+
+    /// A bare SX with its RAM mapped and asleep, its pointers set as the
+    /// firmware leaves them at the stack prompt: nothing in TEMPOB, the
+    /// outer loop's return stack, an empty data stack and command line.
+    fn sx_at_rest() -> Emulator {
+        let mut sx = Emulator::new(&vec![0; ROM_SIZE_SX], None, None, Model::Sx);
+        sx.saturn.mem_cntl[MCTL_SYSRAM_SX].config = [0x70000, 0xf0000];
+        sx.is_shutdown = true;
+        let p = *sx.entry_points();
+        let (tempob, editline) = (0x71000, 0x7f000);
+        let rsktop = tempob + 5 * p.rest_depth;
+        let dsktop = editline - 5;
+        for (at, value) in [
+            (p.tempob, tempob),
+            (p.temptop, tempob),
+            (p.rsktop, rsktop),
+            (p.dsktop, dsktop),
+            (p.editline, editline),
+            (p.tempenv, editline + 0x30),
+            (p.avmem, (dsktop - rsktop) / 5),
+        ] {
+            sx.poke(at, value, 5);
+        }
+        sx
+    }
+
+    #[test]
+    fn test_push_sx() {
+        let one = Object::Real(Real::from_f64(1.0).unwrap());
+        let mut sx = sx_at_rest();
+        sx.push(&one).unwrap();
+        sx.push(&one).unwrap();
+        assert_eq!(sx.stack_text().unwrap(), "2: 1\n1: 1\n");
+
+        // "1" typed: the command line holds it, reversed, at its end.
+        let mut sx = sx_at_rest();
+        let editline = nibbles(&sx, sx.entry_points().editline, 5);
+        sx.poke(editline + 0x2e, 0x13, 2);
+        assert_eq!(sx.push(&one), Err("the command line is open".to_string()));
+
+        // A return level more than the outer loop's.
+        let mut sx = sx_at_rest();
+        let rsktop = sx.entry_points().rsktop;
+        let top = nibbles(&sx, rsktop, 5);
+        sx.poke(rsktop, top + 5, 5);
+        sx.poke(sx.entry_points().avmem, (editline - 5 - top - 5) / 5, 5);
+        assert_eq!(
+            sx.push(&one),
+            Err("a program or application has the keyboard".to_string())
+        );
+
+        let mut sx = sx_at_rest();
+        sx.is_shutdown = false;
+        assert_eq!(sx.push(&one), Err("the calculator is busy".to_string()));
+    }
+
+    #[test]
+    fn test_read_stack() {
+        let mut emu = boot_gx();
+        emu.type_text("1.5 (1,2) \"Hi\" #1Fh { 2 X } << 1 + SIN >> 'A+B' 3_m/s :T:7")
+            .unwrap();
+        press(&mut emu, &[ENTER]);
+        let stack: Vec<String> = emu.stack().unwrap().iter().map(|o| o.to_string()).collect();
+        assert_eq!(
+            stack,
+            [
+                ":T:7",
+                "3_m/s",
+                "'A B +'",
+                "« 1 + SIN »",
+                "{ 2 X }",
+                "# 1Fh",
+                "\"Hi\"",
+                "(1,2)",
+                "1.5",
+            ]
+        );
+        let stack = emu.stack().unwrap();
+        assert!(matches!(&stack[8], Object::Real(r) if r.to_f64() == 1.5));
+        assert!(matches!(&stack[3], Object::Program(items) if items.len() == 5));
+        assert_eq!(
+            stack[4],
+            Object::List(vec![Object::Real(real("2E0")), Object::Global("X".into()),])
+        );
+    }
+}
@@ -12,6 +12,7 @@ pub const SCHED_STATISTICS: i32 = 0x7ffff;
 pub const SCHED_NEVER: i32 = 0x7fffffff;
 pub const NR_SAMPLES: i32 = 10;
 
+#[derive(Clone)]
 pub struct Scheduler {
     pub instructions: u32,
     pub old_instr: u32,
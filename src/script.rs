@@ -0,0 +1,233 @@
+// Key scripts for headless runs.
+//
+// A script is a text file with one command per line; `#` starts a comment.
+//
+//   keys 1 ENTER 2 +     tap each key in turn
+//   type << 1 2 + >>     type the rest of the line, `#` included
+//   press SHL            hold a key down
+//   release SHL          let it go again
+//   wait 1.5             run for 1.5 s of emulated time
+//   idle 10              run until the calculator waits for input,
+//                        failing after 10 s (default 30)
+//   expect 1 « 1 2 + »   fail unless stack level 1 reads as the rest of
+//                        the line (`rpl::Object`'s text form)
+//
+// Keys are named as in `keyboard::KEY_NAMES` or given in hex as `0x13`. A
+// tap holds the key for 0.1 s, then lets the calculator settle for up to
+// 0.5 s before the next one; keys typed while it is still busy go into the
+// firmware's own key buffer. `type` taps out text with
+// `keyboard::text_keys`, shifts and alpha included.
+
+use crate::emulator::Emulator;
+use crate::keyboard::{key_code, text_keys};
+
+const HOLD_SECS: f64 = 0.1;
+const SETTLE_SECS: f64 = 0.5;
+const IDLE_TIMEOUT_SECS: f64 = 30.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Keys(Vec<u32>),
+    Type(String),
+    Press(u32),
+    Release(u32),
+    Wait(f64),
+    Idle(f64),
+    Expect(usize, String),
+}
+
+/// A parsed script: commands with their line numbers.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    pub commands: Vec<(usize, Command)>,
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Script, String> {
+        let mut commands = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if let Some(text) = line.trim_start().strip_prefix("type ") {
+                text_keys(text).map_err(|e| format!("line {}: {e}", i + 1))?;
+                commands.push((i + 1, Command::Type(text.to_string())));
+                continue;
+            }
+            if let Some(rest) = line.trim_start().strip_prefix("expect ") {
+                let (level, text) = rest.trim_start().split_once(' ').unwrap_or((rest, ""));
+                let level = level
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("line {}: bad stack level {level}", i + 1))?;
+                commands.push((i + 1, Command::Expect(level, text.trim().to_string())));
+                continue;
+            }
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let Some(verb) = words.next() else {
+                continue;
+            };
+            let args: Vec<&str> = words.collect();
+            let command = parse_command(verb, &args).map_err(|e| format!("line {}: {e}", i + 1))?;
+            commands.push((i + 1, command));
+        }
+        Ok(Script { commands })
+    }
+}
+
+fn parse_command(verb: &str, args: &[&str]) -> Result<Command, String> {
+    let key = |name: &str| key_code(name).ok_or_else(|| format!("unknown key {name}"));
+    let secs = |arg: &str| {
+        arg.parse::<f64>()
+            .ok()
+            .filter(|s| *s >= 0.0)
+            .ok_or_else(|| format!("bad number of seconds {arg}"))
+    };
+    match (verb, args) {
+        ("keys", [_, ..]) => Ok(Command::Keys(
+            args.iter().map(|a| key(a)).collect::<Result<_, _>>()?,
+        )),
+        ("press", [name]) => Ok(Command::Press(key(name)?)),
+        ("release", [name]) => Ok(Command::Release(key(name)?)),
+        ("wait", [arg]) => Ok(Command::Wait(secs(arg)?)),
+        ("idle", []) => Ok(Command::Idle(IDLE_TIMEOUT_SECS)),
+        ("idle", [arg]) => Ok(Command::Idle(secs(arg)?)),
+        ("keys" | "type" | "press" | "release" | "wait" | "idle", _) => {
+            Err(format!("wrong number of arguments to {verb}"))
+        }
+        _ => Err(format!("unknown command {verb}")),
+    }
+}
+
+impl Emulator {
+    /// Run `script` on the headless clock.
+    pub fn run_script(&mut self, script: &Script) -> Result<(), String> {
+        for (line, command) in &script.commands {
+            match command {
+                Command::Keys(codes) => codes.iter().for_each(|&code| self.tap_key(code)),
+                Command::Type(text) => self.type_text(text)?,
+                Command::Press(code) => {
+                    self.push_key_event(*code);
+                    self.run_headless(HOLD_SECS, |_| false);
+                }
+                Command::Release(code) => {
+                    self.push_key_event(code | 0x8000_0000);
+                    self.run_headless(SETTLE_SECS, |emu| emu.is_idle());
+                }
+                Command::Wait(secs) => {
+                    self.run_headless(*secs, |_| false);
+                }
+                Command::Idle(secs) => {
+                    if !self.run_headless(*secs, |emu| emu.is_idle()) {
+                        return Err(format!("line {line}: still busy after {secs} s"));
+                    }
+                }
+                Command::Expect(level, text) => {
+                    let stack = self.stack().map_err(|e| format!("line {line}: {e}"))?;
+                    let found = stack.get(level - 1).map(|o| o.to_string());
+                    if found.as_deref() != Some(text.as_str()) {
+                        let found = found.unwrap_or_else(|| "empty".to_string());
+                        return Err(format!("line {line}: level {level} is {found}"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Type `text` on the headless clock, as `keyboard::text_keys` spells
+    /// it out.
+    pub fn type_text(&mut self, text: &str) -> Result<(), String> {
+        text_keys(text)?
+            .into_iter()
+            .for_each(|code| self.tap_key(code));
+        Ok(())
+    }
+
+    pub(crate) fn tap_key(&mut self, code: u32) {
+        self.push_key_event(code);
+        self.run_headless(HOLD_SECS, |_| false);
+        self.push_key_event(code | 0x8000_0000);
+        self.run_headless(SETTLE_SECS, |emu| emu.is_idle());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, ENTER};
+
+    #[test]
+    fn test_parse() {
+        let script = Script::parse(
+            "# comment\nkeys 1 enter 0x44  # tap\n\nwait 0.5\nidle\ntype #1h\nexpect 2 # 1h \n",
+        )
+        .unwrap();
+        assert_eq!(
+            script.commands,
+            vec![
+                (2, Command::Keys(vec![0x13, ENTER, 0x44])),
+                (4, Command::Wait(0.5)),
+                (5, Command::Idle(IDLE_TIMEOUT_SECS)),
+                (6, Command::Type("#1h".into())),
+                (7, Command::Expect(2, "# 1h".into())),
+            ]
+        );
+        assert_eq!(
+            Script::parse("keys 1\npress FOO\n").unwrap_err(),
+            "line 2: unknown key FOO"
+        );
+        assert!(Script::parse("wait\n").is_err());
+        assert!(Script::parse("wait -1\n").is_err());
+        assert!(Script::parse("jump 3\n").is_err());
+        assert_eq!(
+            Script::parse("expect 0 1\n").unwrap_err(),
+            "line 1: bad stack level 0"
+        );
+        assert_eq!(
+            Script::parse("type 1 }\n").unwrap_err(),
+            "line 1: unmatched }"
+        );
+    }
+
+    #[test]
+    fn test_run_script_and_match_screen() {
+        let mut emu = boot_gx();
+        let script = Script::parse("keys 1 ENTER 2 +\nidle 5\nexpect 1 3\n").unwrap();
+        emu.run_script(&script).unwrap();
+        assert_eq!(
+            emu.run_script(&Script::parse("expect 1 4\nexpect 2 3\n").unwrap()),
+            Err("line 1: level 1 is 3".to_string())
+        );
+        assert_eq!(
+            emu.run_script(&Script::parse("expect 2 3\n").unwrap()),
+            Err("line 1: level 2 is empty".to_string())
+        );
+        assert!(emu.is_idle());
+        emu.update_display();
+        let shot = emu.display.to_pbm();
+        assert_eq!(emu.display.matches_pbm(&shot), Ok(true));
+
+        emu.run_script(&Script::parse("keys 3 ENTER\n").unwrap())
+            .unwrap();
+        emu.update_display();
+        assert_eq!(emu.display.matches_pbm(&shot), Ok(false));
+        assert!(emu.display.matches_pbm(b"P4\n1 1\n\0").is_err());
+    }
+
+    #[test]
+    fn test_type_text() {
+        let mut emu = boot_gx();
+        emu.type_text("<< \"ab\" SIZE { [ 1 2 ] (1,2) 'X' :T:5 } SIZE + >>")
+            .unwrap();
+        emu.run_script(&Script::parse("keys ENTER EVAL\nidle 5\n").unwrap())
+            .unwrap();
+        emu.update_display();
+        let typed = emu.display.to_pbm();
+
+        let mut emu = boot_gx();
+        emu.run_script(&Script::parse("keys 6 ENTER\nidle 5\n").unwrap())
+            .unwrap();
+        emu.update_display();
+        assert_eq!(emu.display.matches_pbm(&typed), Ok(true));
+    }
+}
@@ -1,18 +1,551 @@
-// Serial port — stub implementation
-// No actual serial I/O in web/Tauri builds.
+// Serial port — port of serial.c (transmit_char / receive_char / serial_baud)
+// The HP-48 side (TBR/RBR/TCS/RCS) is emulated here; the host side is a
+// pluggable `SerialTransport`. The wire and IR ports each take an optional
+// transport, selected by IR_CTRL bit 2 exactly like x48's wire_fd / ir_fd.
 
-pub struct Serial;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::cpu::Saturn;
+use crate::emulator::Emulator;
+use crate::scheduler::SCHED_RECEIVE;
+
+/// Bits per second for each BAUD register code (0-7).
+pub const BAUD_RATES: [u32; 8] = [1200, 1920, 2400, 3840, 4800, 7680, 9600, 15360];
+
+/// Baud rate at which `SCHED_RECEIVE` is calibrated.
+const BASE_BAUD: u32 = 9600;
+
+/// Host side of a serial line. Bytes are exchanged one at a time; `receive`
+/// must never block.
+pub trait SerialTransport: Any {
+    /// Byte sent by the calculator.
+    fn transmit(&mut self, byte: u8);
+    /// Next byte for the calculator, if one is available.
+    fn receive(&mut self) -> Option<u8>;
+    /// Called when the firmware changes the BAUD register.
+    fn set_baud(&mut self, _bps: u32) {}
+}
+
+/// State of a host-side protocol engine.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransferStatus {
+    Busy,
+    Done,
+    Failed(String),
+}
+
+/// A host-side protocol engine (Kermit, XModem) that sits on the wire port
+/// and is driven by the emulated clock.
+pub trait HostTransfer: SerialTransport {
+    /// Called once when the engine is attached.
+    fn begin(&mut self, now: f64);
+    /// Advance retransmission timers (emulated seconds).
+    fn poll(&mut self, now: f64);
+    fn status(&self) -> &TransferStatus;
+}
+
+// ---------------------------------------------------------------------------
+// In-memory backend
+// ---------------------------------------------------------------------------
+
+/// Byte queues in both directions. Used by tests and by the host-side
+/// protocol engines.
+#[derive(Default)]
+pub struct MemoryTransport {
+    pub input: VecDeque<u8>,
+    pub output: Vec<u8>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue bytes for the calculator to receive.
+    pub fn push_input(&mut self, bytes: &[u8]) {
+        self.input.extend(bytes);
+    }
+
+    /// Drain everything the calculator has transmitted.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl SerialTransport for MemoryTransport {
+    fn transmit(&mut self, byte: u8) {
+        self.output.push(byte);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// File backend
+// ---------------------------------------------------------------------------
+
+/// Feeds the contents of an input file to the calculator and appends
+/// everything it transmits to an output file.
+pub struct FileTransport {
+    input: Option<File>,
+    output: Option<File>,
+}
+
+impl FileTransport {
+    pub fn open(input: Option<&Path>, output: Option<&Path>) -> std::io::Result<Self> {
+        let input = match input {
+            Some(p) => Some(File::open(p)?),
+            None => None,
+        };
+        let output = match output {
+            Some(p) => Some(File::options().create(true).append(true).open(p)?),
+            None => None,
+        };
+        Ok(Self { input, output })
+    }
+}
+
+impl SerialTransport for FileTransport {
+    fn transmit(&mut self, byte: u8) {
+        if let Some(f) = self.output.as_mut() {
+            let _ = f.write_all(&[byte]);
+        }
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        let f = self.input.as_mut()?;
+        let mut buf = [0u8; 1];
+        match f.read(&mut buf) {
+            Ok(1) => Some(buf[0]),
+            _ => None,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Pseudo-terminal backend (port of serial_init's /dev/ptmx path)
+// ---------------------------------------------------------------------------
+
+/// Unix98 pseudo-terminal. Host programs (kermit, minicom, ...) open
+/// `slave_name()` and talk to the calculator as if over a cable.
+#[cfg(unix)]
+pub struct PtyTransport {
+    master: libc::c_int,
+    slave: libc::c_int,
+    name: String,
+}
+
+#[cfg(unix)]
+impl PtyTransport {
+    pub fn open() -> std::io::Result<Self> {
+        use std::io::Error;
+        // SAFETY: plain libc calls on descriptors owned by this struct.
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK);
+            if master < 0 {
+                return Err(Error::last_os_error());
+            }
+            if libc::grantpt(master) < 0 || libc::unlockpt(master) < 0 {
+                let err = Error::last_os_error();
+                libc::close(master);
+                return Err(err);
+            }
+            let p = libc::ptsname(master);
+            if p.is_null() {
+                let err = Error::last_os_error();
+                libc::close(master);
+                return Err(err);
+            }
+            let path = std::ffi::CStr::from_ptr(p).to_owned();
+            let name = path.to_string_lossy().into_owned();
+
+            // Keep the slave open so reads on the master don't fail with EIO
+            // while no host program is attached.
+            let slave = libc::open(
+                path.as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK,
+            );
+            if slave < 0 {
+                let err = Error::last_os_error();
+                libc::close(master);
+                return Err(err);
+            }
+            let mut tio: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(slave, &mut tio) == 0 {
+                libc::cfmakeraw(&mut tio);
+                tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+                libc::cfsetspeed(&mut tio, libc::B9600);
+                libc::tcsetattr(slave, libc::TCSANOW, &tio);
+            }
+            Ok(Self {
+                master,
+                slave,
+                name,
+            })
+        }
+    }
+
+    /// Path of the terminal device host programs should open.
+    pub fn slave_name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(unix)]
+impl SerialTransport for PtyTransport {
+    fn transmit(&mut self, byte: u8) {
+        // SAFETY: writing one byte from a valid buffer to our own descriptor.
+        unsafe {
+            libc::write(self.master, &byte as *const u8 as *const libc::c_void, 1);
+        }
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        let mut byte = 0u8;
+        // SAFETY: reading one byte into a valid buffer from our own descriptor.
+        let n = unsafe { libc::read(self.master, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if n == 1 {
+            Some(byte)
+        } else {
+            None
+        }
+    }
+
+    fn set_baud(&mut self, bps: u32) {
+        let speed = match bps {
+            1200 => libc::B1200,
+            2400 => libc::B2400,
+            4800 => libc::B4800,
+            9600 => libc::B9600,
+            _ => return, // non-standard HP rates: leave the line as is
+        };
+        // SAFETY: termios struct is fully initialised by tcgetattr.
+        unsafe {
+            let mut tio: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(self.slave, &mut tio) == 0 {
+                libc::cfsetspeed(&mut tio, speed);
+                libc::tcsetattr(self.slave, libc::TCSANOW, &tio);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for PtyTransport {
+    fn drop(&mut self) {
+        // SAFETY: both descriptors are owned by this struct.
+        unsafe {
+            libc::close(self.slave);
+            libc::close(self.master);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Serial device
+// ---------------------------------------------------------------------------
+
+pub struct Serial {
+    pub wire: Option<Box<dyn SerialTransport>>,
+    pub ir: Option<Box<dyn SerialTransport>>,
+    bps: u32,
+    /// Host transfer running on the wire port, if any.
+    transfer: Option<Transfer>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Serial {
     pub fn new() -> Self {
-        Self
+        Self {
+            wire: None,
+            ir: None,
+            bps: BASE_BAUD,
+            transfer: None,
+        }
+    }
+
+    pub fn init(&mut self) {
+        self.bps = BASE_BAUD;
+    }
+
+    /// Attach a transport to the wire port, returning the previous one.
+    pub fn attach_wire(
+        &mut self,
+        transport: Box<dyn SerialTransport>,
+    ) -> Option<Box<dyn SerialTransport>> {
+        let old = self.wire.replace(transport);
+        transport_set_baud(&mut self.wire, self.bps);
+        old
+    }
+
+    /// Attach a transport to the IR port, returning the previous one.
+    pub fn attach_ir(
+        &mut self,
+        transport: Box<dyn SerialTransport>,
+    ) -> Option<Box<dyn SerialTransport>> {
+        let old = self.ir.replace(transport);
+        transport_set_baud(&mut self.ir, self.bps);
+        old
+    }
+
+    /// Whether a host transfer is running on the wire port.
+    pub fn has_transfer(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// Downcast the wire transport to a concrete backend.
+    pub fn wire_as<T: SerialTransport>(&mut self) -> Option<&mut T> {
+        let t: &mut dyn Any = self.wire.as_deref_mut()?;
+        t.downcast_mut::<T>()
+    }
+
+    /// Downcast the IR transport to a concrete backend.
+    pub fn ir_as<T: SerialTransport>(&mut self) -> Option<&mut T> {
+        let t: &mut dyn Any = self.ir.as_deref_mut()?;
+        t.downcast_mut::<T>()
+    }
+
+    /// Current line speed in bits per second.
+    pub fn bps(&self) -> u32 {
+        self.bps
+    }
+
+    /// Instructions between receive polls, scaled so that one byte is
+    /// delivered per character time at the current baud rate.
+    pub fn receive_period(&self) -> i32 {
+        (SCHED_RECEIVE as i64 * BASE_BAUD as i64 / self.bps.max(1) as i64) as i32
+    }
+
+    pub fn set_baud(&mut self, baud: u8) {
+        self.bps = BAUD_RATES[(baud & 0x7) as usize];
+        transport_set_baud(&mut self.wire, self.bps);
+        transport_set_baud(&mut self.ir, self.bps);
+    }
+
+    fn active(&mut self, saturn: &Saturn) -> Option<&mut Box<dyn SerialTransport>> {
+        if saturn.ir_ctrl & 0x04 != 0 {
+            self.ir.as_mut()
+        } else {
+            self.wire.as_mut()
+        }
+    }
+
+    /// Send TBR to the active transport. Returns true if a transmit
+    /// interrupt should be raised.
+    pub fn transmit_char(&mut self, saturn: &mut Saturn) -> bool {
+        let byte = saturn.tbr;
+        if let Some(t) = self.active(saturn) {
+            t.transmit(byte);
+        }
+        saturn.tcs &= 0x0e;
+        saturn.io_ctrl & 0x04 != 0
+    }
+
+    /// Move the next byte from the active transport into RBR. Returns true
+    /// if a receive interrupt should be raised.
+    pub fn receive_char(&mut self, saturn: &mut Saturn) -> bool {
+        if saturn.rcs & 0x01 != 0 {
+            return false;
+        }
+        let Some(t) = self.active(saturn) else {
+            return false;
+        };
+        let Some(byte) = t.receive() else {
+            return false;
+        };
+        if saturn.io_ctrl & 0x08 == 0 {
+            // Receiver disabled: the byte is lost, as in x48.
+            return false;
+        }
+        saturn.rbr = byte;
+        saturn.rcs |= 0x01;
+        saturn.io_ctrl & 0x02 != 0
     }
+}
+
+fn transport_set_baud(t: &mut Option<Box<dyn SerialTransport>>, bps: u32) {
+    if let Some(t) = t.as_mut() {
+        t.set_baud(bps);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Host transfers
+// ---------------------------------------------------------------------------
+
+/// A `HostTransfer` attached to the wire port, advanced by each frame.
+struct Transfer {
+    /// The wire transport to put back afterwards.
+    prev: Option<Box<dyn SerialTransport>>,
+    /// Emulated time after which the transfer has timed out.
+    deadline: f64,
+    status: TransferStatus,
+    /// `poll_wire` for the engine's type.
+    poll: fn(&mut Serial, f64) -> Option<TransferStatus>,
+}
+
+/// Advance the engine on the wire port and return its status, or `None`
+/// if it is no longer there.
+fn poll_wire<T: HostTransfer>(serial: &mut Serial, now: f64) -> Option<TransferStatus> {
+    let transfer = serial.wire_as::<T>()?;
+    transfer.poll(now);
+    Some(transfer.status().clone())
+}
+
+impl Emulator {
+    /// Attach `transfer` to the wire port and return at once; frames run
+    /// it from then on, for up to `budget_secs` of emulated time. Check on
+    /// it with `poll_transfer` and collect it with `finish_transfer`.
+    pub fn start_transfer<T: HostTransfer>(
+        &mut self,
+        mut transfer: T,
+        budget_secs: f64,
+    ) -> Result<(), String> {
+        if self.serial.transfer.is_some() {
+            return Err("a transfer is already running".to_string());
+        }
+        transfer.begin(self.now);
+        let prev = self.serial.wire.replace(Box::new(transfer));
+        self.serial.transfer = Some(Transfer {
+            prev,
+            deadline: self.now + budget_secs,
+            status: TransferStatus::Busy,
+            poll: poll_wire::<T>,
+        });
+        Ok(())
+    }
+
+    /// Advance the running transfer's timers to the current emulated time
+    /// and return its status; `None` if no transfer is running. Frames
+    /// call this before running, and `finish_transfer` before checking.
+    pub fn poll_transfer(&mut self) -> Option<TransferStatus> {
+        let now = self.now;
+        let poll = self.serial.transfer.as_ref()?.poll;
+        let status = match poll(&mut self.serial, now) {
+            None => TransferStatus::Failed("serial transport replaced during transfer".to_string()),
+            Some(TransferStatus::Busy) if now >= self.serial.transfer.as_ref()?.deadline => {
+                TransferStatus::Failed("timed out waiting for the calculator".to_string())
+            }
+            Some(status) => status,
+        };
+        self.serial.transfer.as_mut()?.status = status.clone();
+        Some(status)
+    }
+
+    /// Detach the transfer once it is over and put the previous wire
+    /// transport back: the engine if it succeeded, why not if it failed.
+    /// `None` while it is still busy, or if none was started.
+    pub fn finish_transfer<T: HostTransfer>(&mut self) -> Option<Result<Box<T>, String>> {
+        if self.poll_transfer()? == TransferStatus::Busy {
+            return None;
+        }
+        let transfer = self.serial.transfer.take()?;
+        let ours = std::mem::replace(&mut self.serial.wire, transfer.prev);
+        transport_set_baud(&mut self.serial.wire, self.serial.bps);
+        Some(match transfer.status {
+            TransferStatus::Failed(msg) => Err(msg),
+            _ => ours
+                .map(|t| t as Box<dyn Any>)
+                .and_then(|any| any.downcast::<T>().ok())
+                .ok_or_else(|| "serial transport replaced during transfer".to_string()),
+        })
+    }
+
+    /// Abandon the running transfer and put the previous wire transport
+    /// back.
+    pub fn cancel_transfer(&mut self) {
+        if let Some(transfer) = self.serial.transfer.take() {
+            self.serial.wire = transfer.prev;
+            transport_set_baud(&mut self.serial.wire, self.serial.bps);
+        }
+    }
+
+    /// Attach `transfer` to the wire port and run headless until it finishes
+    /// or `budget_secs` of emulated time have passed. `on_frame` sees the
+    /// engine after every frame. The previous wire transport is restored.
+    pub fn run_transfer<T: HostTransfer>(
+        &mut self,
+        transfer: T,
+        budget_secs: f64,
+        mut on_frame: impl FnMut(&T),
+    ) -> Result<Box<T>, String> {
+        self.start_transfer(transfer, budget_secs)?;
+        self.run_headless(budget_secs, |emu| {
+            let busy = emu.poll_transfer() == Some(TransferStatus::Busy);
+            if let Some(t) = emu.serial.wire_as::<T>() {
+                on_frame(t);
+            }
+            !busy
+        });
+        self.finish_transfer().unwrap_or_else(|| {
+            self.cancel_transfer();
+            Err("timed out waiting for the calculator".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn init(&mut self) {}
+    fn serial_with_memory() -> Serial {
+        let mut s = Serial::new();
+        s.attach_wire(Box::new(MemoryTransport::new()));
+        s
+    }
+
+    #[test]
+    fn test_transmit_clears_tcs_and_interrupts() {
+        let mut s = serial_with_memory();
+        let mut saturn = Saturn {
+            tbr: 0x41,
+            tcs: 0x01,
+            io_ctrl: 0x04,
+            ..Saturn::default()
+        };
+        assert!(s.transmit_char(&mut saturn));
+        assert_eq!(saturn.tcs, 0);
+        assert_eq!(s.wire_as::<MemoryTransport>().unwrap().take_output(), b"A");
+    }
+
+    #[test]
+    fn test_receive_respects_rcs_and_enable() {
+        let mut s = serial_with_memory();
+        let mut saturn = Saturn::default();
+        s.wire_as::<MemoryTransport>().unwrap().push_input(b"xy");
 
-    pub fn set_baud(&mut self, _baud: u8) {}
+        // Receiver disabled: byte dropped
+        assert!(!s.receive_char(&mut saturn));
+        assert_eq!(saturn.rcs & 1, 0);
 
-    pub fn transmit_char(&mut self) {}
+        saturn.io_ctrl = 0x08 | 0x02;
+        assert!(s.receive_char(&mut saturn));
+        assert_eq!(saturn.rbr, b'y' as _);
+        assert_eq!(saturn.rcs & 1, 1);
 
-    pub fn receive_char(&mut self) {}
+        // Buffer full: nothing consumed
+        s.wire_as::<MemoryTransport>().unwrap().push_input(b"z");
+        assert!(!s.receive_char(&mut saturn));
+        assert_eq!(s.wire_as::<MemoryTransport>().unwrap().input.len(), 1);
+    }
+
+    #[test]
+    fn test_receive_period_scales_with_baud() {
+        let mut s = Serial::new();
+        assert_eq!(s.receive_period(), SCHED_RECEIVE);
+        s.set_baud(0);
+        assert_eq!(s.bps(), 1200);
+        assert_eq!(s.receive_period(), SCHED_RECEIVE * 8);
+    }
 }
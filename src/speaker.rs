@@ -3,6 +3,7 @@
 
 const HP48_IPS: i64 = 169000;
 
+#[derive(Clone)]
 pub struct Speaker {
     pub last_state: bool,
     pub last_toggle_instr: i64,
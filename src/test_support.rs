@@ -0,0 +1,52 @@
+// Fixtures shared by the crate's tests: a GX booted from the bundled ROM,
+// RAM and state images in `assets/`, on either clock, and helpers to drive
+// its keyboard.
+
+use crate::emulator::Emulator;
+use crate::types::Model;
+
+/// A GX built from the bundled ROM, RAM and state images, not started.
+fn load_gx() -> Emulator {
+    let asset = |name: &str| {
+        std::fs::read(format!("{}/assets/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+    };
+    let (rom, ram, state) = (asset("rom"), asset("ram"), asset("hp48"));
+    Emulator::new(&rom, Some(&ram), Some(&state), Model::Gx)
+}
+
+/// A GX booted from the bundled images on the host clock, ready for keys.
+pub(crate) fn boot_gx() -> Emulator {
+    let mut emu = load_gx();
+    emu.start(0.0, 1.7e9);
+    emu.run_headless(3.0, |_| false);
+    emu
+}
+
+/// The same GX on the virtual clock at 1 MHz, not yet run: time only
+/// moves as the test feeds it frames.
+pub(crate) fn boot_virtual() -> Emulator {
+    let mut emu = load_gx();
+    emu.start_virtual(1_000_000.0, 1.7e9);
+    emu
+}
+
+/// Type `codes` slowly enough for the firmware to see every key.
+pub(crate) fn press(emu: &mut Emulator, codes: &[u32]) {
+    for &code in codes {
+        emu.push_key_event(code);
+        emu.run_headless(0.1, |_| false);
+        emu.push_key_event(code | 0x8000_0000);
+        emu.run_headless(0.5, |_| false);
+    }
+}
+
+/// Type `text` and ENTER, then wait until the calculator is idle.
+pub(crate) fn run(emu: &mut Emulator, text: &str) {
+    emu.type_text(text).unwrap();
+    press(emu, &[ENTER]);
+    emu.run_headless(5.0, |emu| emu.is_idle());
+}
+
+pub(crate) const ALPHA: u32 = 0x35;
+pub(crate) const ENTER: u32 = 0x44;
+pub(crate) const ON: u32 = 0x8000;
@@ -46,6 +46,7 @@ impl Default for Timer {
     }
 }
 
+#[derive(Clone)]
 pub struct Timers {
     timers: [Timer; NUM_TIMERS],
     access_time: f64,
@@ -147,3 +148,114 @@ impl Timers {
         self.timers[n].running
     }
 }
+
+/// Length of one frame of emulated time on the virtual clock.
+pub const VIRTUAL_FRAME_MS: f64 = 16.0;
+
+/// Emulated time taken from the instruction count instead of the host:
+/// `now` is `cycles / ips` seconds and the calendar starts at
+/// `start_epoch`, so runs with the same inputs are identical.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    /// Instructions per emulated second.
+    pub ips: f64,
+    /// Local epoch seconds at cycle 0.
+    pub start_epoch: f64,
+    /// Instructions executed, plus those the calculator slept through.
+    pub cycles: u64,
+    /// Host time not yet turned into frames.
+    pending_ms: f64,
+}
+
+impl VirtualClock {
+    pub fn new(ips: f64, start_epoch: f64) -> Self {
+        Self {
+            ips: ips.max(1.0),
+            start_epoch,
+            cycles: 0,
+            pending_ms: 0.0,
+        }
+    }
+
+    /// Emulated seconds since cycle 0.
+    pub fn now(&self) -> f64 {
+        self.cycles as f64 / self.ips
+    }
+
+    /// Credit host time towards the next frames.
+    pub fn add_host_time(&mut self, elapsed_ms: f64) {
+        self.pending_ms += elapsed_ms;
+    }
+
+    pub fn clear_host_time(&mut self) {
+        self.pending_ms = 0.0;
+    }
+
+    /// Take one frame's worth of pending host time, if there is enough, and
+    /// return the cycle at which that frame ends. Frames end on multiples
+    /// of the frame length, whatever happened in between.
+    pub fn next_frame(&mut self) -> Option<u64> {
+        if self.pending_ms < VIRTUAL_FRAME_MS {
+            return None;
+        }
+        self.pending_ms -= VIRTUAL_FRAME_MS;
+        let len = ((self.ips * VIRTUAL_FRAME_MS / 1000.0) as u64).max(1);
+        Some((self.cycles / len + 1) * len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::Emulator;
+    use crate::test_support::{boot_virtual, ENTER};
+
+    /// Feed host frames of the given lengths, round and round, until the
+    /// emulated time reaches `secs`. The host `now` is garbage on purpose.
+    fn run_until(emu: &mut Emulator, secs: f64, frames: &[f64]) {
+        for &ms in frames.iter().cycle() {
+            if emu.now >= secs {
+                break;
+            }
+            emu.run_frame(ms, -ms);
+        }
+    }
+
+    /// 1 ENTER 2 + typed at fixed emulated times; returns RAM and screen.
+    fn script(frames: &[f64]) -> (Vec<u8>, Vec<u8>) {
+        let mut emu = boot_virtual();
+        let mut t = 3.0;
+        run_until(&mut emu, t, frames);
+        for code in [0x13, ENTER, 0x12, 0x00] {
+            emu.push_key_event(code);
+            t += 0.1;
+            run_until(&mut emu, t, frames);
+            emu.push_key_event(code | 0x8000_0000);
+            t += 0.5;
+            run_until(&mut emu, t, frames);
+        }
+        (emu.mem.ram.clone(), emu.display.rgba.clone())
+    }
+
+    #[test]
+    fn test_headless_time_is_paid_back() {
+        // Booting runs 3 s headless from host time 0.
+        let mut emu = crate::test_support::boot_gx();
+        let ahead = emu.now;
+        assert!(ahead >= 3.0);
+        let (mut host, mut last) = (0.0, emu.now);
+        while host < 2.0 * ahead + 0.1 {
+            host += 0.016;
+            emu.run_frame(16.0, host);
+            assert!(emu.now >= last);
+            last = emu.now;
+        }
+        assert!((emu.now - host).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_virtual_clock_is_deterministic() {
+        let steady = script(&[16.0]);
+        let ragged = script(&[5.0, 11.0, 16.0, 3.0, 13.0, 0.0]);
+        assert!(steady == ragged);
+    }
+}
@@ -0,0 +1,385 @@
+// Reverse execution — periodic snapshots plus recorded inputs, replayed.
+//
+// While on, every `interval` instructions the machine state (Saturn, RAM,
+// port memory, Scheduler, Timers, DisplayState and the small runtime flags)
+// is copied into a bounded list of snapshots. Host inputs are logged with
+// the instruction count at which they arrived: frame starts, which carry
+// the frame's `now` (the only time source `get_t1_t2` and the timers see)
+// and, on the virtual clock, its cycle count; debugger steps taken between
+// frames; and key events pushed through `Emulator::push_key_event`.
+//
+// `travel_to(n)` restores the nearest snapshot at or before instruction n
+// and replays forward, re-applying the logged inputs at their instruction
+// counts, so the wall clock is never consulted during replay. The history
+// after n is kept until execution or input continues from there, which
+// starts a new timeline.
+//
+// Not recorded: serial data from transports, card changes and memory or
+// register edits made by tools while halted.
+
+use std::collections::VecDeque;
+
+use crate::cpu::{DisplayState, Saturn};
+use crate::debugger::StopReason;
+use crate::device::DeviceFlags;
+use crate::emulator::Emulator;
+use crate::scheduler::Scheduler;
+use crate::speaker::Speaker;
+use crate::timer::{Timers, VirtualClock};
+
+/// A host input between two instructions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    /// `run_frame` started at emulated time `now`, at `cycles` on the
+    /// virtual clock.
+    Frame {
+        now: f64,
+        cycles: Option<u64>,
+    },
+    /// A debugger step ran at emulated time `now`, outside any frame.
+    Clock {
+        now: f64,
+    },
+    Key(u32),
+}
+
+/// Machine state at an instruction boundary.
+struct Snapshot {
+    /// Instructions executed since time travel started.
+    index: u64,
+    saturn: Saturn,
+    ram: Vec<u8>,
+    port1: Vec<u8>,
+    port2: Vec<u8>,
+    line_counter: i32,
+    sched: Scheduler,
+    timers: Timers,
+    display_state: DisplayState,
+    device: DeviceFlags,
+    speaker: Speaker,
+    key_queue: Vec<u32>,
+    now: f64,
+    got_alarm: bool,
+    interrupt_called: bool,
+    is_shutdown: bool,
+    first_press: bool,
+    time_offset: u64,
+    set_0_time: u64,
+    vclock: Option<VirtualClock>,
+}
+
+impl Snapshot {
+    fn take(emu: &Emulator, index: u64) -> Self {
+        Snapshot {
+            index,
+            saturn: emu.saturn.clone(),
+            ram: emu.mem.ram.clone(),
+            port1: emu.mem.port1.clone(),
+            port2: emu.mem.port2.clone(),
+            line_counter: emu.mem.line_counter,
+            sched: emu.sched.clone(),
+            timers: emu.timers.clone(),
+            display_state: emu.display_state.clone(),
+            device: emu.device.clone(),
+            speaker: emu.speaker.clone(),
+            key_queue: emu.keyboard.event_queue.clone(),
+            now: emu.now,
+            got_alarm: emu.got_alarm,
+            interrupt_called: emu.interrupt_called,
+            is_shutdown: emu.is_shutdown,
+            first_press: emu.first_press,
+            time_offset: emu.time_offset,
+            set_0_time: emu.set_0_time,
+            vclock: emu.vclock.clone(),
+        }
+    }
+
+    fn restore(&self, emu: &mut Emulator) {
+        emu.saturn = self.saturn.clone();
+        emu.mem.ram.clone_from(&self.ram);
+        emu.mem.port1.clone_from(&self.port1);
+        emu.mem.port2.clone_from(&self.port2);
+        emu.mem.line_counter = self.line_counter;
+        emu.sched = self.sched.clone();
+        emu.timers = self.timers.clone();
+        emu.display_state = self.display_state.clone();
+        emu.device = self.device.clone();
+        emu.speaker = self.speaker.clone();
+        emu.keyboard.event_queue.clone_from(&self.key_queue);
+        emu.now = self.now;
+        emu.got_alarm = self.got_alarm;
+        emu.interrupt_called = self.interrupt_called;
+        emu.is_shutdown = self.is_shutdown;
+        emu.first_press = self.first_press;
+        emu.time_offset = self.time_offset;
+        emu.set_0_time = self.set_0_time;
+        emu.vclock.clone_from(&self.vclock);
+    }
+}
+
+/// Snapshot and input history.
+pub struct TimeTravel {
+    interval: u64,
+    max_snapshots: usize,
+    snapshots: VecDeque<Snapshot>,
+    /// Inputs by instruction count, in arrival order.
+    events: Vec<(u64, Event)>,
+    /// Instructions executed since time travel started.
+    position: u64,
+    /// Furthest instruction count recorded.
+    end: u64,
+}
+
+impl TimeTravel {
+    /// About to add to the history at `position`: if we travelled back,
+    /// the old future is no longer reachable.
+    fn branch(&mut self) {
+        if self.position < self.end {
+            let at = self.position;
+            self.events.retain(|&(i, _)| i <= at);
+            self.snapshots.retain(|s| s.index <= at);
+            self.end = at;
+        }
+    }
+
+    fn record(&mut self, event: Event) {
+        self.branch();
+        self.events.push((self.position, event));
+    }
+}
+
+impl Emulator {
+    /// Start recording for reverse execution: a snapshot every `interval`
+    /// instructions, keeping the latest `max_snapshots` of them. History
+    /// reaches back about `interval * max_snapshots` instructions.
+    pub fn start_time_travel(&mut self, interval: u64, max_snapshots: usize) {
+        let mut tt = TimeTravel {
+            interval: interval.max(1),
+            max_snapshots: max_snapshots.max(1),
+            snapshots: VecDeque::new(),
+            events: Vec::new(),
+            position: 0,
+            end: 0,
+        };
+        tt.snapshots.push_back(Snapshot::take(self, 0));
+        self.timetravel = Some(Box::new(tt));
+    }
+
+    pub fn stop_time_travel(&mut self) {
+        self.timetravel = None;
+    }
+
+    /// Current instruction count and the furthest one recorded, or `None`
+    /// when time travel is off.
+    pub fn time_travel_position(&self) -> Option<(u64, u64)> {
+        self.timetravel.as_ref().map(|tt| (tt.position, tt.end))
+    }
+
+    /// Earliest instruction count that can still be reached.
+    pub fn time_travel_start(&self) -> Option<u64> {
+        self.timetravel
+            .as_ref()
+            .and_then(|tt| tt.snapshots.front())
+            .map(|s| s.index)
+    }
+
+    pub(crate) fn record_frame(&mut self) {
+        let now = self.now;
+        let cycles = self.vclock.as_ref().map(|c| c.cycles);
+        if let Some(tt) = self.timetravel.as_deref_mut() {
+            tt.record(Event::Frame { now, cycles });
+        }
+    }
+
+    pub(crate) fn record_clock(&mut self) {
+        let now = self.now;
+        if let Some(tt) = self.timetravel.as_deref_mut() {
+            tt.record(Event::Clock { now });
+        }
+    }
+
+    pub(crate) fn record_key(&mut self, code: u32) {
+        if let Some(tt) = self.timetravel.as_deref_mut() {
+            tt.record(Event::Key(code));
+        }
+    }
+
+    /// Called after every instruction while recording.
+    pub(crate) fn time_travel_tick(&mut self) {
+        let Some(tt) = self.timetravel.as_deref_mut() else {
+            return;
+        };
+        tt.branch();
+        tt.position += 1;
+        tt.end = tt.position;
+        if tt.position % tt.interval != 0 {
+            return;
+        }
+        let index = tt.position;
+        let snap = Snapshot::take(self, index);
+        let tt = self.timetravel.as_deref_mut().unwrap();
+        tt.snapshots.push_back(snap);
+        if tt.snapshots.len() > tt.max_snapshots {
+            tt.snapshots.pop_front();
+            let first = tt.snapshots[0].index;
+            tt.events.retain(|&(i, _)| i >= first);
+        }
+    }
+
+    fn apply_event(&mut self, event: Event) {
+        match event {
+            Event::Frame { now, cycles } => {
+                // The part of run_frame that runs before its instruction loop.
+                self.now = now;
+                if let (Some(clock), Some(cycles)) = (self.vclock.as_mut(), cycles) {
+                    clock.cycles = cycles;
+                }
+                self.got_alarm = true;
+                if self.is_shutdown {
+                    self.do_shutdown_check(now);
+                }
+            }
+            Event::Clock { now } => self.now = now,
+            Event::Key(code) => self.keyboard.push_key_event(code),
+        }
+    }
+
+    /// Move to just before instruction `index` (counted from when time
+    /// travel started), backwards or forwards within the recorded history,
+    /// and halt there.
+    pub fn travel_to(&mut self, index: u64) -> Result<StopReason, String> {
+        let tt = self.timetravel.take().ok_or("time travel is off")?;
+        let result = self.replay(&tt, index);
+        self.timetravel = Some(tt);
+        let reached = result?;
+        let tt = self.timetravel.as_deref_mut().unwrap();
+        tt.position = reached;
+        self.update_display();
+        let reason = StopReason::Step { pc: self.saturn.pc };
+        self.halt();
+        Ok(self.stop(reason))
+    }
+
+    /// Undo the last instruction.
+    pub fn step_back(&mut self) -> Result<StopReason, String> {
+        let (position, _) = self.time_travel_position().ok_or("time travel is off")?;
+        if position == 0 {
+            return Err("at the start of the recording".to_string());
+        }
+        self.travel_to(position - 1)
+    }
+
+    /// Restore and re-execute up to `index` with `tt` detached, so nothing
+    /// is recorded. Tracing and watchpoints are paused meanwhile.
+    fn replay(&mut self, tt: &TimeTravel, index: u64) -> Result<u64, String> {
+        if index > tt.end {
+            return Err(format!(
+                "instruction {index} not recorded yet (at {})",
+                tt.end
+            ));
+        }
+        let snap = tt
+            .snapshots
+            .iter()
+            .rev()
+            .find(|s| s.index <= index)
+            .ok_or_else(|| format!("instruction {index} is before the recorded history"))?;
+        snap.restore(self);
+
+        let tracer = self.tracer.take();
+        let watching = std::mem::replace(&mut self.debugger.watching, false);
+        let mut ev = tt.events.partition_point(|&(i, _)| i < snap.index);
+        let mut i = snap.index;
+        let result = loop {
+            while let Some(&(at, event)) = tt.events.get(ev) {
+                if at != i {
+                    break;
+                }
+                self.apply_event(event);
+                ev += 1;
+            }
+            if i == index {
+                break Ok(i);
+            }
+            if self.is_shutdown {
+                break Err(format!("replay diverged at instruction {i}"));
+            }
+            self.execute_instruction(self.now);
+            i += 1;
+        };
+        self.tracer = tracer;
+        self.debugger.watching = watching;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, press, ENTER};
+
+    /// Registers and memory that must match after a replay.
+    fn state(emu: &Emulator) -> (Vec<u8>, String, u32) {
+        (
+            emu.mem.ram.clone(),
+            emu.register_dump(),
+            emu.sched.instructions,
+        )
+    }
+
+    #[test]
+    fn test_replay_is_exact() {
+        let mut emu = boot_gx();
+        emu.start_time_travel(20_000, 1000);
+        // Keys 1 ENTER 2 ENTER: typed through push_key_event, so recorded.
+        press(&mut emu, &[0x13, ENTER, 0x12, ENTER]);
+        emu.run_headless(0.3, |_| false);
+        emu.halt();
+        let (end, reach) = emu.time_travel_position().unwrap();
+        assert_eq!(end, reach);
+        assert!(end > 100_000);
+        let at_end = state(&emu);
+
+        // Back to the middle, where the stack was still empty, then
+        // forward again: the same machine, bit for bit.
+        emu.travel_to(end / 3).unwrap();
+        assert_eq!(emu.time_travel_position(), Some((end / 3, end)));
+        assert_ne!(state(&emu), at_end);
+        emu.travel_to(end).unwrap();
+        assert_eq!(state(&emu), at_end);
+        assert!(emu.travel_to(end + 1).is_err());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut emu = boot_gx();
+        emu.saturn.pc = 0x90000;
+        let code = crate::asm::assemble("A=0 A\nA=A+1 A\nA=A+1 A\nC=A A\n", 0x90000).unwrap();
+        for (i, &n) in code.iter().enumerate() {
+            emu.write_nibble(0x90000 + i as i32, n as i32);
+        }
+        emu.is_shutdown = false;
+        emu.start_time_travel(2, 8);
+        emu.halt();
+        let c0 = emu.saturn.c[0];
+        for _ in 0..4 {
+            emu.step_into();
+        }
+        assert_eq!((emu.saturn.a[0], emu.saturn.c[0]), (2, 2));
+        let pc = emu.saturn.pc;
+
+        assert_eq!(emu.step_back(), Ok(StopReason::Step { pc: pc - 2 }));
+        assert_eq!((emu.saturn.a[0], emu.saturn.c[0]), (2, c0));
+        emu.step_back().unwrap();
+        assert_eq!(emu.saturn.a[0], 1);
+        emu.travel_to(0).unwrap();
+        assert_eq!(emu.saturn.pc, 0x90000);
+        assert!(emu.step_back().is_err());
+
+        // Stepping from the past starts a new timeline.
+        emu.step_into();
+        assert_eq!(emu.time_travel_position(), Some((1, 1)));
+        assert!(emu.travel_to(3).is_err());
+        assert!(emu.halted().is_some());
+    }
+}
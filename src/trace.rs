@@ -0,0 +1,396 @@
+// Execution trace — the last N instructions in a ring buffer.
+//
+// Opt-in: `Emulator::start_trace` boxes a `Tracer` onto the emulator and
+// `execute_instruction` only takes the traced path while one is present, so
+// a disabled tracer costs one `Option` test per instruction.
+//
+// Each entry keeps the PC, a window of nibbles at the PC (enough for the
+// longest opcode; the instruction length is decoded on export), a mask of
+// the registers the instruction changed, and carry and P after it. Dumps
+// are text (through the disassembler) or a compact binary file.
+//
+// Binary format, little-endian:
+//   "R48TRACE" version:u8 count:u32
+//   then per entry: pc:u24 size:u8 nibbles:ceil(size/2) bytes (low nibble
+//   first) touched:u16 flags:u8 (P in bits 0-3, carry in bit 4)
+
+use std::fmt;
+
+use crate::cpu::Saturn;
+use crate::disasm::{self, NibbleSource};
+use crate::emulator::Emulator;
+
+/// Longest Saturn instruction: LA with 16 digits (8082 n + 16).
+pub const MAX_OPCODE: usize = 21;
+
+const MAGIC: &[u8; 8] = b"R48TRACE";
+const VERSION: u8 = 1;
+
+/// Names of the `touched` bits, bit 0 first.
+pub const REG_NAMES: [&str; 16] = [
+    "A", "B", "C", "D", "R0", "R1", "R2", "R3", "R4", "D0", "D1", "P", "ST", "CY", "RSTK", "HST",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: i32,
+    /// Nibbles at `pc` when the instruction ran; only the first
+    /// instruction-length of them are meaningful.
+    pub nibbles: [u8; MAX_OPCODE],
+    /// Registers the instruction changed, one bit per `REG_NAMES` entry.
+    pub touched: u16,
+    /// Carry and P after the instruction.
+    pub carry: u8,
+    pub p: u8,
+}
+
+/// Decodes an entry's nibbles at its own PC.
+struct Window<'a>(&'a TraceEntry);
+
+impl NibbleSource for Window<'_> {
+    fn nibble(&self, addr: i32) -> u8 {
+        let off = ((addr - self.0.pc) & 0xfffff) as usize;
+        self.0.nibbles.get(off).copied().unwrap_or(0)
+    }
+}
+
+impl TraceEntry {
+    pub fn instruction(&self) -> disasm::Instruction {
+        disasm::disassemble(&Window(self), self.pc)
+    }
+
+    /// Names of the touched registers, space separated.
+    pub fn touched_names(&self) -> String {
+        REG_NAMES
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.touched & (1 << i) != 0)
+            .map(|(_, n)| *n)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let insn = self.instruction().to_string();
+        write!(f, "{insn:<44} P={:X} CY={}", self.p, self.carry)?;
+        if self.touched != 0 {
+            write!(f, "  {}", self.touched_names())?;
+        }
+        Ok(())
+    }
+}
+
+/// Ring buffer of the last `capacity` instructions.
+pub struct Tracer {
+    entries: Vec<TraceEntry>,
+    capacity: usize,
+    /// Slot the next entry goes into once the buffer is full.
+    next: usize,
+    /// Instructions recorded since the tracer started.
+    pub total: u64,
+}
+
+impl Tracer {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Tracer {
+            entries: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+            total: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.next = 0;
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() < self.capacity {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.next] = entry;
+            self.next = (self.next + 1) % self.capacity;
+        }
+        self.total += 1;
+    }
+
+    /// Entries oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer)
+    }
+
+    /// One disassembled line per entry, oldest first.
+    pub fn to_text(&self) -> String {
+        self.entries().map(|e| format!("{e}\n")).collect()
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(13 + self.len() * 12);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        for e in self.entries() {
+            out.extend_from_slice(&e.pc.to_le_bytes()[..3]);
+            let size = e.instruction().size as usize;
+            out.push(size as u8);
+            for pair in e.nibbles[..size].chunks(2) {
+                out.push(pair[0] | pair.get(1).map_or(0, |n| n << 4));
+            }
+            out.extend_from_slice(&e.touched.to_le_bytes());
+            out.push(e.p | (e.carry << 4));
+        }
+        out
+    }
+}
+
+/// Parse a file written by `Tracer::to_binary`, oldest entry first.
+pub fn read_binary(data: &[u8]) -> Result<Vec<TraceEntry>, String> {
+    if data.len() < 13 || &data[..8] != MAGIC {
+        return Err("not a trace file".to_string());
+    }
+    if data[8] != VERSION {
+        return Err(format!("unsupported trace version {}", data[8]));
+    }
+    let count = u32::from_le_bytes(data[9..13].try_into().unwrap()) as usize;
+    let mut pos = 13;
+    let mut take = |n: usize| {
+        let bytes = data.get(pos..pos + n).ok_or("truncated trace file")?;
+        pos += n;
+        Ok::<_, String>(bytes)
+    };
+    let mut entries = Vec::with_capacity(count.min(1 << 20));
+    for _ in 0..count {
+        let pc = take(3)?;
+        let pc = pc[0] as i32 | (pc[1] as i32) << 8 | (pc[2] as i32) << 16;
+        let size = take(1)?[0] as usize;
+        if size > MAX_OPCODE {
+            return Err(format!("bad instruction size {size}"));
+        }
+        let mut nibbles = [0u8; MAX_OPCODE];
+        for (i, b) in take(size.div_ceil(2))?.iter().enumerate() {
+            nibbles[2 * i] = b & 0xf;
+            if 2 * i + 1 < size {
+                nibbles[2 * i + 1] = b >> 4;
+            }
+        }
+        let touched = take(2)?;
+        let touched = u16::from_le_bytes([touched[0], touched[1]]);
+        let flags = take(1)?[0];
+        entries.push(TraceEntry {
+            pc,
+            nibbles,
+            touched,
+            carry: (flags >> 4) & 1,
+            p: flags & 0xf,
+        });
+    }
+    Ok(entries)
+}
+
+/// The registers an instruction can change, captured before it runs.
+struct Snapshot {
+    regs: [[u8; 16]; 9],
+    d0: i32,
+    d1: i32,
+    p: u8,
+    pstat: [u8; 16],
+    carry: u8,
+    rstkp: i16,
+    rstk_top: i32,
+    hst: [u8; 4],
+}
+
+impl Snapshot {
+    fn take(s: &Saturn) -> Self {
+        Snapshot {
+            regs: [s.a, s.b, s.c, s.d, s.r0, s.r1, s.r2, s.r3, s.r4],
+            d0: s.d0,
+            d1: s.d1,
+            p: s.p,
+            pstat: s.pstat,
+            carry: s.carry,
+            rstkp: s.rstkp,
+            rstk_top: Self::rstk_top(s),
+            hst: [s.xm, s.sb, s.sr, s.mp],
+        }
+    }
+
+    fn rstk_top(s: &Saturn) -> i32 {
+        if s.rstkp >= 0 {
+            s.rstk[s.rstkp as usize]
+        } else {
+            0
+        }
+    }
+
+    fn touched(&self, s: &Saturn) -> u16 {
+        let regs = [s.a, s.b, s.c, s.d, s.r0, s.r1, s.r2, s.r3, s.r4];
+        let changed = [
+            self.d0 != s.d0,
+            self.d1 != s.d1,
+            self.p != s.p,
+            self.pstat != s.pstat,
+            self.carry != s.carry,
+            self.rstkp != s.rstkp || self.rstk_top != Self::rstk_top(s),
+            self.hst != [s.xm, s.sb, s.sr, s.mp],
+        ];
+        let mut mask = 0u16;
+        for (i, bit) in regs
+            .iter()
+            .zip(&self.regs)
+            .map(|(now, before)| now != before)
+            .chain(changed)
+            .enumerate()
+        {
+            mask |= (bit as u16) << i;
+        }
+        mask
+    }
+}
+
+impl Emulator {
+    /// Record the last `capacity` instructions from now on, dropping any
+    /// previous trace.
+    pub fn start_trace(&mut self, capacity: usize) {
+        self.tracer = Some(Box::new(Tracer::new(capacity)));
+    }
+
+    /// Stop tracing and hand back what was recorded.
+    pub fn stop_trace(&mut self) -> Option<Box<Tracer>> {
+        self.tracer.take()
+    }
+
+    /// `step_instruction` with the instruction recorded in the tracer.
+    pub(crate) fn step_traced(&mut self) -> bool {
+        let pc = self.saturn.pc;
+        let mut nibbles = [0u8; MAX_OPCODE];
+        for (i, n) in nibbles.iter_mut().enumerate() {
+            *n = self.peek_nibble(pc + i as i32);
+        }
+        let before = Snapshot::take(&self.saturn);
+        let stop = self.step_instruction();
+        let entry = TraceEntry {
+            pc,
+            nibbles,
+            touched: before.touched(&self.saturn),
+            carry: self.saturn.carry,
+            p: self.saturn.p,
+        };
+        if let Some(tracer) = self.tracer.as_deref_mut() {
+            tracer.push(entry);
+        }
+        stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: i32, hex: &str) -> TraceEntry {
+        let mut nibbles = [0u8; MAX_OPCODE];
+        for (n, c) in nibbles.iter_mut().zip(hex.chars()) {
+            *n = c.to_digit(16).unwrap() as u8;
+        }
+        TraceEntry {
+            pc,
+            nibbles,
+            touched: 0,
+            carry: 0,
+            p: 0,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut t = Tracer::new(3);
+        for pc in 0..5 {
+            t.push(entry(pc, "01"));
+        }
+        let pcs: Vec<i32> = t.entries().map(|e| e.pc).collect();
+        assert_eq!(pcs, [2, 3, 4]);
+        assert_eq!((t.len(), t.total), (3, 5));
+        t.clear();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut t = Tracer::new(8);
+        let mut e = entry(0x05143, "808234567");
+        e.touched = 0x4004;
+        e.carry = 1;
+        e.p = 0xc;
+        t.push(e);
+        t.push(entry(0xfffff, "6A00F"));
+        let data = t.to_binary();
+        // Header, then 3 + 1 + 5 + 2 + 1 and 3 + 1 + 2 + 2 + 1 bytes.
+        assert_eq!(data.len(), 13 + 12 + 9);
+        let back = read_binary(&data).unwrap();
+        // Nibbles past the instruction are not stored.
+        let mut expect: Vec<TraceEntry> = t.entries().cloned().collect();
+        expect[1].nibbles = entry(0xfffff, "6A00").nibbles;
+        assert_eq!(back, expect);
+
+        assert!(read_binary(b"R48TRACE").is_err());
+        assert!(read_binary(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_trace_cpu() {
+        let src = "
+        A=0     W
+        LC(2)   #12
+        GOSUB   SUB
+END     GOTO    END
+SUB     P=      3
+        RTNSC
+";
+        let mut emu = crate::test_support::boot_gx();
+        let base = 0x90000;
+        let code = crate::asm::assemble(src, base).unwrap();
+        for (i, &n) in code.iter().enumerate() {
+            emu.write_nibble(base + i as i32, n as i32);
+        }
+        emu.saturn.pc = base;
+        emu.start_trace(16);
+        for _ in 0..6 {
+            emu.execute_instruction(emu.now);
+        }
+        let tracer = emu.stop_trace().unwrap();
+        let text = tracer.to_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("90000  AF0          A=0     W"));
+        assert!(lines[0].ends_with("CY=0  A"));
+        assert!(lines[2].contains("GOSUB   #9000F"));
+        assert!(lines[2].ends_with("RSTK"));
+        assert!(lines[3].ends_with("P=3 CY=0  P"));
+        assert!(lines[4].contains("RTNSC"));
+        assert!(lines[4].ends_with("P=3 CY=1  CY RSTK"));
+        assert!(lines[5].contains("GOTO    #9000B"));
+        assert!(lines[5].ends_with("P=3 CY=1"));
+        assert_eq!(tracer.total, 6);
+
+        // Disabled again: nothing more is recorded.
+        emu.execute_instruction(emu.now);
+        assert!(emu.tracer.is_none());
+    }
+}
@@ -0,0 +1,430 @@
+// Terminal frontend — the LCD drawn with Unicode half-block or braille
+// characters, annunciators on a status line, PC keys mapped onto the
+// calculator's keyboard. For SSH sessions and quick checks; unix only.
+//
+// Terminals report key presses but not releases, so every key becomes a tap:
+// pressed, held for `HOLD_MS`, released. Shifted characters and letters are
+// typed as a shift or alpha tap followed by the key, like on the calculator.
+//
+//   digits . + - * / ' space   the same keys
+//   letters                    alpha + letter key
+//   ( [ { < , =                left shift + key, as in keyboard::char_keys
+//   # _ " :                    right shift + key
+//   Enter Backspace Delete     ENTER, backspace, DEL
+//   arrows                     cursor keys
+//   F1–F6                      menu keys A–F
+//   F7 F8 F9                   left shift, right shift, alpha
+//   Esc                        ON
+//   F10                        resume after a debugger stop
+//   Ctrl-C                     quit
+//
+// A debugger stop (see `Emulator::set_stop_on_illegal`) freezes the screen
+// and is shown in place of the help line until F10.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::time::Instant;
+
+use crate::display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::emulator::Emulator;
+use crate::keyboard::{char_keys, key_code};
+use crate::types::{ANN_ALPHA, ANN_BATTERY, ANN_BUSY, ANN_IO, ANN_LEFT, ANN_RIGHT};
+
+const HOLD_MS: f64 = 80.0;
+const GAP_MS: f64 = 40.0;
+const FRAME_MS: f64 = 16.0;
+
+/// How LCD pixels map onto character cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// 1×2 pixels per cell: 131×32 cells.
+    HalfBlock,
+    /// 2×4 pixels per cell: 66×16 cells.
+    Braille,
+}
+
+impl std::str::FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "half" => Ok(Style::HalfBlock),
+            "braille" => Ok(Style::Braille),
+            _ => Err(format!("unknown display style {s} (half or braille)")),
+        }
+    }
+}
+
+fn pixel(display: &Display, x: u32, y: u32) -> bool {
+    x < DISPLAY_WIDTH && y < DISPLAY_HEIGHT && display.pixel(x, y)
+}
+
+/// The LCD as lines of text, dark pixels drawn.
+pub fn render(display: &Display, style: Style) -> Vec<String> {
+    match style {
+        Style::HalfBlock => (0..DISPLAY_HEIGHT)
+            .step_by(2)
+            .map(|y| {
+                (0..DISPLAY_WIDTH)
+                    .map(|x| match (pixel(display, x, y), pixel(display, x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect()
+            })
+            .collect(),
+        Style::Braille => {
+            // Dot bits of a braille cell, by column then row.
+            const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+            (0..DISPLAY_HEIGHT)
+                .step_by(4)
+                .map(|y| {
+                    (0..DISPLAY_WIDTH)
+                        .step_by(2)
+                        .map(|x| {
+                            let mut bits = 0;
+                            for (dx, column) in DOTS.iter().enumerate() {
+                                for (dy, bit) in column.iter().enumerate() {
+                                    if pixel(display, x + dx as u32, y + dy as u32) {
+                                        bits |= bit;
+                                    }
+                                }
+                            }
+                            char::from_u32(0x2800 + bits).unwrap()
+                        })
+                        .collect()
+                })
+                .collect()
+        }
+    }
+}
+
+/// Annunciators from `Emulator::annunciator_state`, each shown in a
+/// fixed place so the line doesn't jump about.
+pub fn annunciator_line(state: u32) -> String {
+    const NAMES: [(u8, &str); 6] = [
+        (ANN_LEFT, "◀ SHIFT"),
+        (ANN_RIGHT, "SHIFT ▶"),
+        (ANN_ALPHA, "α"),
+        (ANN_BATTERY, "BATT"),
+        (ANN_BUSY, "BUSY"),
+        (ANN_IO, "I/O"),
+    ];
+    let words: Vec<String> = NAMES
+        .iter()
+        .map(|&(bit, name)| {
+            if state & bit as u32 == bit as u32 {
+                name.to_string()
+            } else {
+                " ".repeat(name.chars().count())
+            }
+        })
+        .collect();
+    words.join("  ")
+}
+
+/// Something typed at the terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Calculator keys to tap, in order.
+    Keys(Vec<u32>),
+    /// Carry on after a debugger stop.
+    Resume,
+    Quit,
+}
+
+fn keys(names: &[&str]) -> Input {
+    Input::Keys(names.iter().map(|n| key_code(n).unwrap()).collect())
+}
+
+/// Decode terminal input. Returns the inputs and how many bytes they used;
+/// an escape sequence cut off at the end of `bytes` is left for next time.
+pub fn decode(bytes: &[u8]) -> (Vec<Input>, usize) {
+    let mut inputs = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest[0] == 0x1b {
+            match escape(rest) {
+                Some((input, len)) => {
+                    inputs.extend(input);
+                    i += len;
+                    continue;
+                }
+                None => break,
+            }
+        }
+        let c = rest[0] as char;
+        i += 1;
+        let input = match c {
+            '\x03' => Input::Quit,
+            '\r' | '\n' => keys(&["ENTER"]),
+            '\x7f' | '\x08' => keys(&["BS"]),
+            ' ' => keys(&["SPC"]),
+            // Letters come out in upper case, for typing commands.
+            c => {
+                let c = match c {
+                    '<' => '«',
+                    '>' => '»',
+                    c => c.to_ascii_uppercase(),
+                };
+                match char_keys(c) {
+                    Some(codes) => Input::Keys(codes),
+                    None => continue,
+                }
+            }
+        };
+        inputs.push(input);
+    }
+    (inputs, i)
+}
+
+/// An escape sequence at the start of `bytes`: the input, if it means
+/// anything, and its length. `None` while incomplete.
+fn escape(bytes: &[u8]) -> Option<(Option<Input>, usize)> {
+    if !matches!(bytes.get(1), Some(b'[' | b'O')) {
+        // A lone ESC is the Escape key.
+        return Some((Some(keys(&["ON"])), 1));
+    }
+    // Parameters, then a final byte in 0x40..=0x7e.
+    let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 2;
+    if &bytes[2..=end] == b"21~" {
+        return Some((Some(Input::Resume), end + 1));
+    }
+    let key = match &bytes[2..=end] {
+        b"A" => Some("UP"),
+        b"B" => Some("DOWN"),
+        b"C" => Some("RIGHT"),
+        b"D" => Some("LEFT"),
+        b"P" | b"11~" => Some("A"),
+        b"Q" | b"12~" => Some("B"),
+        b"R" | b"13~" => Some("C"),
+        b"S" | b"14~" => Some("D"),
+        b"15~" => Some("E"),
+        b"17~" => Some("F"),
+        b"18~" => Some("SHL"),
+        b"19~" => Some("SHR"),
+        b"20~" => Some("ALPHA"),
+        b"3~" => Some("DEL"),
+        _ => None,
+    };
+    Some((key.map(|k| keys(&[k])), end + 1))
+}
+
+/// Local time as seconds since 1970, the calendar `Emulator::start` wants.
+pub fn local_epoch() -> f64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64());
+    // SAFETY: localtime_r only writes the tm struct we own.
+    let offset = unsafe {
+        let t = now as libc::time_t;
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&t, &mut tm).is_null() {
+            0
+        } else {
+            tm.tm_gmtoff
+        }
+    };
+    now + offset as f64
+}
+
+/// Raw mode and the alternate screen while alive.
+struct Terminal {
+    saved: libc::termios,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Self> {
+        // SAFETY: termios structs are filled in by tcgetattr before use.
+        unsafe {
+            if libc::isatty(0) == 0 {
+                return Err(io::Error::other("standard input is not a terminal"));
+            }
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(0, &mut saved) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = saved;
+            libc::cfmakeraw(&mut raw);
+            // Keep output post-processing so "\n" still returns the carriage.
+            raw.c_oflag = saved.c_oflag;
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(0, libc::TCSANOW, &raw) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+            Ok(Terminal { saved })
+        }
+    }
+
+    /// Wait up to `ms` for input and return whatever arrived.
+    fn read(&self, ms: f64, buf: &mut Vec<u8>) -> io::Result<()> {
+        let mut fd = libc::pollfd {
+            fd: 0,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: one valid pollfd.
+        let ready = unsafe { libc::poll(&mut fd, 1, ms.max(0.0) as libc::c_int) };
+        if ready > 0 {
+            let mut chunk = [0u8; 256];
+            let n = io::stdin().read(&mut chunk)?;
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: restoring the settings saved in `open`.
+        unsafe {
+            libc::tcsetattr(0, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+/// Run `emu` in the terminal on the host clock until Ctrl-C. Call instead
+/// of `Emulator::start`.
+pub fn run(emu: &mut Emulator, style: Style) -> io::Result<()> {
+    let term = Terminal::open()?;
+    let t0 = Instant::now();
+    emu.start(0.0, local_epoch());
+
+    // Calculator keys waiting to be tapped, and the tap in progress: the
+    // key and when its release (then the gap after it) is due.
+    let mut pending: VecDeque<u32> = VecDeque::new();
+    let mut tap: Option<(u32, f64, bool)> = None;
+    let mut input = Vec::new();
+    let mut last = 0.0;
+    let mut annunc = u32::MAX;
+    let mut stop = None;
+    let mut out = io::stdout();
+    loop {
+        let now = t0.elapsed().as_secs_f64() * 1000.0;
+        emu.run_frame(now - last, now / 1000.0);
+        last = now;
+
+        let (inputs, used) = decode(&input);
+        input.drain(..used);
+        for i in inputs {
+            match i {
+                Input::Keys(codes) => pending.extend(codes),
+                Input::Resume => emu.resume(),
+                Input::Quit => return Ok(()),
+            }
+        }
+        tap = match tap {
+            Some((code, due, true)) if now >= due => {
+                emu.push_key_event(code | 0x8000_0000);
+                Some((code, now + GAP_MS, false))
+            }
+            Some((_, due, false)) if now >= due => None,
+            tap => tap,
+        };
+        if tap.is_none() {
+            if let Some(code) = pending.pop_front() {
+                emu.push_key_event(code);
+                tap = Some((code, now + HOLD_MS, true));
+            }
+        }
+
+        if emu.is_display_dirty() || emu.annunciator_state() != annunc || emu.halted() != stop {
+            annunc = emu.annunciator_state();
+            stop = emu.halted();
+            emu.clear_display_dirty();
+            // LCD colours on the screen, help under it.
+            let mut frame = String::from("\x1b[H\x1b[38;2;16;32;16m\x1b[48;2;188;196;165m");
+            frame += &annunciator_line(annunc);
+            frame += "\x1b[K\r\n";
+            for line in render(&emu.display, style) {
+                frame += &line;
+                frame += "\r\n";
+            }
+            frame += "\x1b[0m\r\n";
+            frame += &match stop {
+                Some(reason) => format!("Stopped: {reason}  F10 resume  Ctrl-C quit"),
+                None => "Ctrl-C quit  Esc ON  F1-F6 menu  F7/F8 shift  F9 alpha".to_string(),
+            };
+            frame += "\x1b[K";
+            out.write_all(frame.as_bytes())?;
+            out.flush()?;
+        }
+
+        let elapsed = t0.elapsed().as_secs_f64() * 1000.0 - now;
+        term.read(FRAME_MS - elapsed, &mut input)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(name: &str) -> u32 {
+        key_code(name).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let mut display = Display::new();
+        // Dark pixels at (0,0), (1,1) and (130,63).
+        for (x, y) in [(0, 0), (1, 1), (130, 63)] {
+            display.rgba[((y * DISPLAY_WIDTH + x) * 4) as usize] = 0x10;
+        }
+        let half = render(&display, Style::HalfBlock);
+        assert_eq!(half.len(), 32);
+        assert!(half[0].starts_with("▀▄ "));
+        assert!(half[31].ends_with(" ▄"));
+        assert_eq!(half[31].chars().count(), 131);
+
+        let braille = render(&display, Style::Braille);
+        assert_eq!(braille.len(), 16);
+        assert!(braille[0].starts_with("\u{2811}\u{2800}"));
+        assert!(braille[15].ends_with('\u{2840}'));
+        assert_eq!(braille[15].chars().count(), 66);
+    }
+
+    #[test]
+    fn test_annunciator_line() {
+        let off = annunciator_line(0);
+        let on = annunciator_line(0x80 | ANN_ALPHA as u32 | ANN_BUSY as u32);
+        assert_eq!(off.trim(), "");
+        assert_eq!(on.chars().count(), off.chars().count());
+        assert!(on.contains('α') && on.contains("BUSY") && !on.contains("I/O"));
+    }
+
+    #[test]
+    fn test_decode() {
+        let (inputs, used) = decode(b"1+a(\r\x1b[A\x1bOP\x1b[17~\x1b[21~\x1b\x03");
+        assert_eq!(used, 23);
+        assert_eq!(
+            inputs,
+            vec![
+                Input::Keys(vec![code("1")]),
+                Input::Keys(vec![code("+")]),
+                Input::Keys(vec![code("ALPHA"), code("A")]),
+                Input::Keys(vec![code("SHL"), code("DIV")]),
+                Input::Keys(vec![code("ENTER")]),
+                Input::Keys(vec![code("UP")]),
+                Input::Keys(vec![code("A")]),
+                Input::Keys(vec![code("F")]),
+                Input::Resume,
+                Input::Keys(vec![code("ON")]),
+                Input::Quit,
+            ]
+        );
+        // z is on EEX; an unfinished sequence waits for more bytes.
+        assert_eq!(
+            decode(b"z\x1b[1"),
+            (vec![Input::Keys(vec![code("ALPHA"), code("EEX")])], 1)
+        );
+        // Unknown sequences are skipped whole.
+        assert_eq!(decode(b"\x1b[99~2").0, vec![Input::Keys(vec![code("2")])]);
+    }
+}
@@ -0,0 +1,324 @@
+// Variables, ports and libraries in emulated RAM, read-only — what the VAR
+// menu, PVARS and LIBS would show, without pressing a key.
+//
+// The HOME tree comes from the directory walk in binio.rs; HOME's hidden
+// directory (alarms, user keys) is left out, as VAR leaves it out.
+//
+// A port holds backup objects (a name and the object stored under it) and
+// libraries, one after another, ended by a zero nibble field where the
+// next prolog would be. On the GX, port 0 sits at the top of main RAM, from
+// the address at `EntryPoints::port0` up to the end; independent cards hold theirs
+// from the start of the card, and each 128K bank of a GX port 2 card is a
+// port of its own (2, 3, ...). A merged card holds main RAM instead, which
+// does not start with a port object, so it lists as empty.
+//
+// Checksums are the firmware's CRC (`Memory::crc_step`, as BYTES
+// computes) over the object as stored: the variable's object, the object
+// inside a backup, or the whole library.
+
+use std::fmt::Write;
+
+use crate::binio::{read, type_name};
+use crate::card::Port;
+use crate::disasm::NibbleSource;
+use crate::emulator::Emulator;
+use crate::memory::Memory;
+use crate::rpl::*;
+use crate::types::Model;
+
+/// Nibbles in a GX port 2 bank, each a port of its own.
+const BANK_NIBBLES: usize = 0x40000;
+
+/// Directories nest no deeper than this in a sound tree.
+const MAX_DEPTH: usize = 64;
+
+/// A variable in the HOME tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub prolog: u32,
+    /// In nibbles.
+    pub size: u32,
+    pub crc: u16,
+    /// A directory's variables, in memory order (oldest first).
+    pub entries: Vec<Variable>,
+}
+
+/// A backup object or library in a port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortObject {
+    pub port: u32,
+    /// The backup's name, or the library's title.
+    pub name: String,
+    /// Prolog of the backed-up object, or `DOLIB`.
+    pub prolog: u32,
+    pub library: Option<u32>,
+    /// In nibbles.
+    pub size: u32,
+    pub crc: u16,
+}
+
+/// The firmware's checksum of an object's nibbles.
+pub fn crc(nibbles: &[u8]) -> u16 {
+    nibbles.iter().fold(0, |crc, &n| Memory::crc_step(crc, n))
+}
+
+/// A size in nibbles as BYTES shows it, in bytes: 10.5.
+pub fn bytes_text(size: u32) -> String {
+    format!("{}{}", size / 2, if size % 2 == 1 { ".5" } else { "" })
+}
+
+fn type_text(prolog: u32) -> &'static str {
+    type_name(prolog).unwrap_or("Unknown")
+}
+
+fn name_size(len: u32) -> u32 {
+    if len == 0 {
+        2
+    } else {
+        4 + 2 * len
+    }
+}
+
+fn checksum<S: NibbleSource + ?Sized>(src: &S, addr: u32, size: u32) -> u16 {
+    (addr..addr + size).fold(0, |crc, at| {
+        Memory::crc_step(crc, src.nibble((at & 0xfffff) as i32))
+    })
+}
+
+/// Objects in the port held by `src` from `start` on, up to `end`.
+fn port_objects_in<S: NibbleSource + ?Sized>(
+    src: &S,
+    port: u32,
+    start: u32,
+    end: u32,
+) -> Vec<PortObject> {
+    let mut objects = Vec::new();
+    let mut at = start;
+    while at + 10 <= end {
+        let prolog = read(src, at, 5);
+        let Ok(size) = object_size(src, at) else {
+            break;
+        };
+        if !matches!(prolog, DOBAK | DOLIB) || at + size > end {
+            break;
+        }
+        let len = read(src, at + 10, 2);
+        let name = (0..len)
+            .map(|i| hp_char(read(src, at + 12 + 2 * i, 2) as u8))
+            .collect();
+        let after_name = at + 10 + name_size(len);
+        let object = if prolog == DOBAK {
+            let inner = object_size(src, after_name).unwrap_or(0);
+            PortObject {
+                port,
+                name,
+                prolog: read(src, after_name, 5),
+                library: None,
+                size: inner,
+                crc: checksum(src, after_name, inner),
+            }
+        } else {
+            PortObject {
+                port,
+                name,
+                prolog,
+                library: Some(read(src, after_name, 3)),
+                size,
+                crc: checksum(src, at, size),
+            }
+        };
+        objects.push(object);
+        at += size;
+    }
+    objects
+}
+
+impl Emulator {
+    /// The variables in HOME, directories with their contents.
+    pub fn variable_tree(&self) -> Result<Vec<Variable>, String> {
+        let home = self.home_directory()?;
+        let mut tree = self.variables_in(home, 0)?;
+        tree.retain(|v| !v.name.is_empty());
+        Ok(tree)
+    }
+
+    fn variables_in(&self, dir: u32, depth: usize) -> Result<Vec<Variable>, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("directories nested too deep at {dir:05X}"));
+        }
+        self.directory_entries(dir)?
+            .into_iter()
+            .map(|(name, addr)| {
+                let prolog = read(self, addr, 5);
+                let size = object_size(self, addr)?;
+                let entries = match prolog {
+                    DORRP if !name.is_empty() => self.variables_in(addr, depth + 1)?,
+                    _ => Vec::new(),
+                };
+                Ok(Variable {
+                    name,
+                    prolog,
+                    size,
+                    crc: checksum(self, addr, size),
+                    entries,
+                })
+            })
+            .collect()
+    }
+
+    /// Numbers of the libraries attached to HOME.
+    pub fn attached_libraries(&self) -> Result<Vec<u32>, String> {
+        let home = self.home_directory()?;
+        let count = read(self, home + 5, 3);
+        Ok((0..count)
+            .map(|i| read(self, home + 8 + 13 * i, 3))
+            .collect())
+    }
+
+    /// Backup objects and libraries in every port, port 0 first.
+    pub fn port_objects(&self) -> Vec<PortObject> {
+        let mut objects = Vec::new();
+        if let Some(port0) = self.entry_points().port0 {
+            let ram = self.ram_range();
+            let start = read(self, port0, 5);
+            if ram.contains(&start) {
+                objects.extend(port_objects_in(self, 0, start, ram.end));
+            }
+        }
+        for (port, card) in [
+            (Port::Port1, &self.mem.port1),
+            (Port::Port2, &self.mem.port2),
+        ] {
+            if !self.card_present(port) {
+                continue;
+            }
+            let first = if port == Port::Port1 { 1 } else { 2 };
+            let banks = match (self.model, port) {
+                (Model::Gx, Port::Port2) => card.chunks(BANK_NIBBLES).collect(),
+                _ => vec![&card[..]],
+            };
+            for (i, bank) in banks.into_iter().enumerate() {
+                let end = bank.len() as u32;
+                objects.extend(port_objects_in(bank, first + i as u32, 0, end));
+            }
+        }
+        objects
+    }
+
+    /// The HOME tree, the ports and the attached libraries as indented
+    /// text: name, type, size in bytes and checksum, one per line.
+    pub fn vars_text(&self) -> Result<String, String> {
+        fn tree(out: &mut String, vars: &[Variable], indent: usize) {
+            for v in vars {
+                let name = format!("{:indent$}{}", "", v.name);
+                let _ = writeln!(
+                    out,
+                    "{name:<20} {:<16} {:>8}  # {:X}h",
+                    type_text(v.prolog),
+                    bytes_text(v.size),
+                    v.crc
+                );
+                tree(out, &v.entries, indent + 2);
+            }
+        }
+        let mut out = "HOME\n".to_string();
+        tree(&mut out, &self.variable_tree()?, 2);
+        let mut port = None;
+        for object in self.port_objects() {
+            if port != Some(object.port) {
+                port = Some(object.port);
+                let _ = writeln!(out, "Port {}", object.port);
+            }
+            let name = match object.library {
+                Some(number) => format!("  Library {number}: {}", object.name),
+                None => format!("  {}", object.name),
+            };
+            let _ = writeln!(
+                out,
+                "{name:<20} {:<16} {:>8}  # {:X}h",
+                type_text(object.prolog),
+                bytes_text(object.size),
+                object.crc
+            );
+        }
+        let libraries = self.attached_libraries()?;
+        if !libraries.is_empty() {
+            let numbers: Vec<String> = libraries.iter().map(|n| n.to_string()).collect();
+            let _ = writeln!(out, "Attached libraries: {}", numbers.join(" "));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::CardKind;
+    use crate::test_support::{boot_gx, press, run, ON};
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc(&[]), 0);
+        assert_eq!(bytes_text(21), "10.5");
+        assert_eq!(bytes_text(20), "10");
+        // Checked against BYTES on the calculator.
+        let mut emu = boot_gx();
+        let text = Object::String("hello".into());
+        let nibbles = text.to_nibbles().unwrap();
+        emu.push(&text).unwrap();
+        run(&mut emu, "BYTES");
+        assert_eq!(
+            emu.stack_text().unwrap(),
+            format!("2: # {:X}h\n1: 10\n", crc(&nibbles))
+        );
+    }
+
+    #[test]
+    fn test_variable_tree() {
+        let mut emu = boot_gx();
+        assert_eq!(emu.variable_tree(), Ok(Vec::new()));
+        run(&mut emu, "'WORK' CRDIR 1.5 'X' STO");
+        run(&mut emu, "WORK \"hi\" 'Y' STO HOME");
+        run(&mut emu, "\"hello\" :0:ABC STO");
+        emu.hot_insert_card(Port::Port1, &vec![0; 0x20000], CardKind::Ram)
+            .unwrap();
+        // The firmware drops the first key after a card goes in.
+        emu.run_headless(5.0, |emu| emu.is_idle());
+        press(&mut emu, &[ON]);
+        run(&mut emu, "7 :1:SEVEN STO");
+
+        let tree = emu.variable_tree().unwrap();
+        let names: Vec<&str> = tree.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["WORK", "X"]);
+        assert_eq!(tree[0].prolog, DORRP);
+        assert_eq!(tree[0].entries.len(), 1);
+        let y = &tree[0].entries[0];
+        let hi = Object::String("hi".into()).to_nibbles().unwrap();
+        assert_eq!((y.name.as_str(), y.prolog), ("Y", DOCSTR));
+        assert_eq!((y.size, y.crc), (hi.len() as u32, crc(&hi)));
+
+        let ports = emu.port_objects();
+        let listed: Vec<(u32, &str, u32)> = ports
+            .iter()
+            .map(|o| (o.port, o.name.as_str(), o.prolog))
+            .collect();
+        assert_eq!(listed, [(0, "ABC", DOCSTR), (1, "SEVEN", DOREAL)]);
+        let hello = Object::String("hello".into()).to_nibbles().unwrap();
+        assert_eq!(ports[0].crc, crc(&hello));
+
+        let text = emu.vars_text().unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "HOME");
+        assert!(lines[1].starts_with("  WORK  "));
+        assert!(lines[1].contains("Directory"));
+        assert!(lines[2].starts_with("    Y  "));
+        assert!(lines[2].ends_with(&format!("7  # {:X}h", crc(&hi))));
+        assert!(lines[3].starts_with("  X  "));
+        assert_eq!(lines[4], "Port 0");
+        assert!(lines[5].starts_with("  ABC  "));
+        assert_eq!(lines[6], "Port 1");
+        assert!(lines[7].contains("Real Number"));
+        assert!(lines[8].starts_with("Attached libraries: "));
+    }
+}
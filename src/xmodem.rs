@@ -0,0 +1,553 @@
+// XModem host — the far end of the GX firmware's XSEND / XRECV commands.
+// Sender and receiver for 128-byte blocks with either the one-byte
+// checksum or CRC-16; the receiver also accepts 1K (STX) blocks.
+// Transfers run headless through `Emulator::run_transfer`, like Kermit, or
+// frame by frame from `start_xmodem_send` / `start_xmodem_receive`.
+
+use std::collections::VecDeque;
+
+use crate::emulator::Emulator;
+use crate::serial::{HostTransfer, SerialTransport, TransferStatus};
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const CPMEOF: u8 = 0x1a;
+const CRC_REQUEST: u8 = b'C';
+
+const BLOCK_SIZE: usize = 128;
+/// Emulated seconds without an answer before we retry.
+const TIMEOUT_SECS: f64 = 3.0;
+const MAX_RETRIES: u32 = 10;
+/// 'C' requests sent before a receiver falls back to checksums.
+const CRC_ATTEMPTS: u32 = 3;
+/// Interval between those requests; XSEND times out well before
+/// `TIMEOUT_SECS` per request would get through to a NAK.
+const REQUEST_SECS: f64 = 1.0;
+/// Emulated seconds a blocking transfer may take before giving up.
+const TRANSFER_BUDGET_SECS: f64 = 600.0;
+
+/// Block check used by a transfer. The GX firmware's XSEND only answers
+/// NAK, so a CRC receiver falls back to checksums after a few requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XModemCheck {
+    Checksum,
+    Crc,
+}
+
+/// Bytes transferred so far; `total` is known only when sending.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XModemProgress {
+    pub done: usize,
+    pub total: Option<usize>,
+}
+
+fn crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |s, &b| s.wrapping_add(b))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    WaitStart,
+    WaitAck,
+    WaitEotAck,
+    Receiving,
+    Complete,
+}
+
+pub struct XModem {
+    stage: Stage,
+    status: TransferStatus,
+    sending: bool,
+    check: XModemCheck,
+    out: VecDeque<u8>,
+    now: f64,
+    last_sent: f64,
+    retries: u32,
+    cancels: u32,
+    /// Next block number (1-based, wraps at 256).
+    block: u8,
+    // Sending
+    data: Vec<u8>,
+    pos: usize,
+    // Receiving
+    rx: Vec<u8>,
+    received: Vec<u8>,
+}
+
+impl XModem {
+    fn new(stage: Stage, check: XModemCheck) -> Self {
+        Self {
+            stage,
+            status: TransferStatus::Busy,
+            sending: stage != Stage::Receiving,
+            check,
+            out: VecDeque::new(),
+            now: 0.0,
+            last_sent: 0.0,
+            retries: 0,
+            cancels: 0,
+            block: 1,
+            data: Vec::new(),
+            pos: 0,
+            rx: Vec::new(),
+            received: Vec::new(),
+        }
+    }
+
+    /// Send `data` to a calculator running XRECV. The block check is chosen
+    /// by the receiver.
+    pub fn sender(data: &[u8]) -> Self {
+        let mut x = Self::new(Stage::WaitStart, XModemCheck::Checksum);
+        x.data = data.to_vec();
+        x
+    }
+
+    /// Receive from a calculator running XSEND, asking for `check`.
+    pub fn receiver(check: XModemCheck) -> Self {
+        Self::new(Stage::Receiving, check)
+    }
+
+    pub fn check(&self) -> XModemCheck {
+        self.check
+    }
+
+    pub fn progress(&self) -> XModemProgress {
+        if self.sending {
+            XModemProgress {
+                done: self.pos.min(self.data.len()),
+                total: Some(self.data.len()),
+            }
+        } else {
+            XModemProgress {
+                done: self.received.len(),
+                total: None,
+            }
+        }
+    }
+
+    /// Data received so far. XModem pads the last block, usually with
+    /// CP/M EOF (0x1a); the padding is kept.
+    pub fn received(&self) -> &[u8] {
+        &self.received
+    }
+
+    pub fn take_received(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.received)
+    }
+
+    fn fail(&mut self, msg: &str) {
+        self.stage = Stage::Complete;
+        self.status = TransferStatus::Failed(msg.to_string());
+    }
+
+    fn bump_retries(&mut self) -> bool {
+        self.retries += 1;
+        if self.retries > MAX_RETRIES {
+            self.out.extend([CAN, CAN]);
+            self.fail("too many retries");
+            return false;
+        }
+        true
+    }
+
+    fn send_bytes(&mut self, bytes: &[u8]) {
+        self.out.extend(bytes.iter().copied());
+        self.last_sent = self.now;
+    }
+
+    // --- Sender ---
+
+    fn block_packet(&self) -> Vec<u8> {
+        let end = (self.pos + BLOCK_SIZE).min(self.data.len());
+        let mut payload = self.data[self.pos..end].to_vec();
+        payload.resize(BLOCK_SIZE, CPMEOF);
+        let mut p = vec![SOH, self.block, !self.block];
+        p.extend_from_slice(&payload);
+        match self.check {
+            XModemCheck::Crc => p.extend(crc16_xmodem(&payload).to_be_bytes()),
+            XModemCheck::Checksum => p.push(checksum(&payload)),
+        }
+        p
+    }
+
+    /// Send the current block, or EOT once all data has been acknowledged.
+    fn send_current(&mut self) {
+        if self.pos < self.data.len() {
+            let p = self.block_packet();
+            self.send_bytes(&p);
+            self.stage = Stage::WaitAck;
+        } else {
+            self.send_bytes(&[EOT]);
+            self.stage = Stage::WaitEotAck;
+        }
+    }
+
+    fn sender_byte(&mut self, byte: u8) {
+        if byte == CAN {
+            self.cancels += 1;
+            if self.cancels >= 2 {
+                self.fail("cancelled by the calculator");
+            }
+            return;
+        }
+        self.cancels = 0;
+        match (self.stage, byte) {
+            (Stage::WaitStart, CRC_REQUEST) | (Stage::WaitStart, NAK) => {
+                self.check = if byte == NAK {
+                    XModemCheck::Checksum
+                } else {
+                    XModemCheck::Crc
+                };
+                self.retries = 0;
+                self.send_current();
+            }
+            (Stage::WaitAck, ACK) => {
+                self.pos += BLOCK_SIZE;
+                self.block = self.block.wrapping_add(1);
+                self.retries = 0;
+                self.send_current();
+            }
+            (Stage::WaitAck, NAK) | (Stage::WaitEotAck, NAK) if self.bump_retries() => {
+                self.send_current();
+            }
+            (Stage::WaitEotAck, ACK) => {
+                self.stage = Stage::Complete;
+                self.status = TransferStatus::Done;
+            }
+            _ => {}
+        }
+    }
+
+    // --- Receiver ---
+
+    fn request_byte(&self) -> u8 {
+        match self.check {
+            XModemCheck::Crc => CRC_REQUEST,
+            XModemCheck::Checksum => NAK,
+        }
+    }
+
+    fn receiver_byte(&mut self, byte: u8) {
+        if self.rx.is_empty() {
+            match byte {
+                SOH | STX => self.rx.push(byte),
+                EOT => {
+                    self.send_bytes(&[ACK]);
+                    self.stage = Stage::Complete;
+                    self.status = TransferStatus::Done;
+                }
+                CAN => {
+                    self.cancels += 1;
+                    if self.cancels >= 2 {
+                        self.fail("cancelled by the calculator");
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+        self.rx.push(byte);
+        let size = if self.rx[0] == STX { 1024 } else { BLOCK_SIZE };
+        let check_len = match self.check {
+            XModemCheck::Crc => 2,
+            XModemCheck::Checksum => 1,
+        };
+        if self.rx.len() < 3 + size + check_len {
+            return;
+        }
+        let blk = std::mem::take(&mut self.rx);
+        self.cancels = 0;
+        self.last_sent = self.now;
+        let payload = &blk[3..3 + size];
+        let good = blk[1] == !blk[2]
+            && match self.check {
+                XModemCheck::Crc => blk[3 + size..] == crc16_xmodem(payload).to_be_bytes(),
+                XModemCheck::Checksum => blk[3 + size] == checksum(payload),
+            };
+        if !good {
+            if self.bump_retries() {
+                self.send_bytes(&[NAK]);
+            }
+        } else if blk[1] == self.block {
+            self.received.extend_from_slice(payload);
+            self.block = self.block.wrapping_add(1);
+            self.retries = 0;
+            self.send_bytes(&[ACK]);
+        } else if blk[1] == self.block.wrapping_sub(1) {
+            // Our ACK was lost; the sender repeated the last block.
+            self.send_bytes(&[ACK]);
+        } else {
+            self.out.extend([CAN, CAN]);
+            self.fail("block sequence lost");
+        }
+    }
+}
+
+impl SerialTransport for XModem {
+    fn transmit(&mut self, byte: u8) {
+        if self.stage == Stage::Complete {
+            return;
+        }
+        if self.sending {
+            self.sender_byte(byte);
+        } else {
+            self.receiver_byte(byte);
+        }
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.out.pop_front()
+    }
+}
+
+impl HostTransfer for XModem {
+    fn begin(&mut self, now: f64) {
+        self.now = now;
+        // Receivers open with a request right away.
+        self.last_sent = if self.stage == Stage::Receiving {
+            now - TIMEOUT_SECS
+        } else {
+            now
+        };
+    }
+
+    fn poll(&mut self, now: f64) {
+        self.now = now;
+        if self.status != TransferStatus::Busy {
+            return;
+        }
+        let waiting = self.stage == Stage::Receiving && self.received.is_empty() && self.block == 1;
+        if now - self.last_sent < if waiting { REQUEST_SECS } else { TIMEOUT_SECS } {
+            return;
+        }
+        match self.stage {
+            Stage::Receiving if waiting => {
+                // Still waiting for the first block: keep asking, falling
+                // back from CRC to checksums like a classic receiver.
+                self.retries += 1;
+                if self.check == XModemCheck::Crc && self.retries > CRC_ATTEMPTS {
+                    self.check = XModemCheck::Checksum;
+                }
+                self.rx.clear();
+                let req = self.request_byte();
+                self.send_bytes(&[req]);
+                self.last_sent = now;
+            }
+            Stage::Receiving => {
+                self.rx.clear();
+                if self.bump_retries() {
+                    self.send_bytes(&[NAK]);
+                }
+            }
+            Stage::WaitStart => self.last_sent = now,
+            Stage::WaitAck | Stage::WaitEotAck => {
+                if self.bump_retries() {
+                    self.send_current();
+                }
+            }
+            Stage::Complete => {}
+        }
+    }
+
+    fn status(&self) -> &TransferStatus {
+        &self.status
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Emulator entry points
+// ---------------------------------------------------------------------------
+
+impl Emulator {
+    /// Send `data` to a GX running XRECV. `progress` is called as blocks
+    /// are acknowledged.
+    pub fn xmodem_send(
+        &mut self,
+        data: &[u8],
+        progress: impl FnMut(XModemProgress),
+    ) -> Result<(), String> {
+        self.run_xmodem(XModem::sender(data), progress).map(|_| ())
+    }
+
+    /// Receive from a GX running XSEND. The result keeps the padding of the
+    /// last block.
+    pub fn xmodem_receive(
+        &mut self,
+        check: XModemCheck,
+        progress: impl FnMut(XModemProgress),
+    ) -> Result<Vec<u8>, String> {
+        self.run_xmodem(XModem::receiver(check), progress)
+            .map(|mut x| x.take_received())
+    }
+
+    /// Start sending `data` without waiting for it: frames carry the
+    /// transfer on and `finish_transfer::<XModem>` collects it.
+    pub fn start_xmodem_send(&mut self, data: &[u8]) -> Result<(), String> {
+        self.start_transfer(XModem::sender(data), TRANSFER_BUDGET_SECS)
+    }
+
+    /// Start receiving without waiting; `XModem::take_received` has the
+    /// data once it has finished.
+    pub fn start_xmodem_receive(&mut self, check: XModemCheck) -> Result<(), String> {
+        self.start_transfer(XModem::receiver(check), TRANSFER_BUDGET_SECS)
+    }
+
+    fn run_xmodem(
+        &mut self,
+        xmodem: XModem,
+        mut progress: impl FnMut(XModemProgress),
+    ) -> Result<Box<XModem>, String> {
+        let mut last = None;
+        self.run_transfer(xmodem, TRANSFER_BUDGET_SECS, |x| {
+            let p = x.progress();
+            if last != Some(p) {
+                last = Some(p);
+                progress(p);
+            }
+        })
+        .map_err(|e| format!("XModem: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{boot_gx, press, ALPHA, ENTER};
+
+    fn drain(x: &mut XModem) -> Vec<u8> {
+        std::iter::from_fn(|| x.receive()).collect()
+    }
+
+    fn feed(x: &mut XModem, bytes: &[u8]) {
+        for &b in bytes {
+            x.transmit(b);
+        }
+    }
+
+    #[test]
+    fn test_crc16_xmodem() {
+        assert_eq!(crc16_xmodem(b"123456789"), 0x31c3);
+        assert_eq!(checksum(&[0xff, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn test_sender_against_scripted_receiver() {
+        let data = vec![0x55u8; 200];
+        let mut x = XModem::sender(&data);
+        x.begin(0.0);
+        assert!(drain(&mut x).is_empty());
+
+        feed(&mut x, b"C");
+        let b1 = drain(&mut x);
+        assert_eq!(b1.len(), 3 + BLOCK_SIZE + 2);
+        assert_eq!(&b1[..3], &[SOH, 1, 0xfe]);
+        assert_eq!(x.check(), XModemCheck::Crc);
+
+        // A NAK repeats the block, an ACK moves on to the padded tail.
+        feed(&mut x, &[NAK]);
+        assert_eq!(drain(&mut x), b1);
+        feed(&mut x, &[ACK]);
+        let b2 = drain(&mut x);
+        assert_eq!(&b2[..3], &[SOH, 2, 0xfd]);
+        assert_eq!(b2[3 + 72], CPMEOF);
+        assert_eq!(x.progress().done, BLOCK_SIZE);
+
+        feed(&mut x, &[ACK]);
+        assert_eq!(drain(&mut x), [EOT]);
+        feed(&mut x, &[ACK]);
+        assert_eq!(*x.status(), TransferStatus::Done);
+        assert_eq!(x.progress().done, 200);
+    }
+
+    #[test]
+    fn test_receiver_against_scripted_sender() {
+        let mut x = XModem::receiver(XModemCheck::Checksum);
+        x.begin(0.0);
+        x.poll(0.0);
+        assert_eq!(drain(&mut x), [NAK]);
+
+        let payload = [7u8; BLOCK_SIZE];
+        let mut blk = vec![SOH, 1, 0xfe];
+        blk.extend_from_slice(&payload);
+        blk.push(checksum(&payload));
+
+        // Corrupt check: NAK
+        let mut bad = blk.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        feed(&mut x, &bad);
+        assert_eq!(drain(&mut x), [NAK]);
+
+        feed(&mut x, &blk);
+        assert_eq!(drain(&mut x), [ACK]);
+        // Repeated block after a lost ACK is acknowledged, not stored.
+        feed(&mut x, &blk);
+        assert_eq!(drain(&mut x), [ACK]);
+        feed(&mut x, &[EOT]);
+        assert_eq!(drain(&mut x), [ACK]);
+        assert_eq!(*x.status(), TransferStatus::Done);
+        assert_eq!(x.received(), payload);
+    }
+
+    #[test]
+    fn test_receiver_falls_back_to_checksum() {
+        let mut x = XModem::receiver(XModemCheck::Crc);
+        x.begin(0.0);
+        let mut sent = Vec::new();
+        for i in 0..5 {
+            x.poll(i as f64 * REQUEST_SECS);
+            sent.extend(drain(&mut x));
+        }
+        assert_eq!(sent, b"CCC\x15\x15");
+        assert_eq!(x.check(), XModemCheck::Checksum);
+    }
+
+    #[test]
+    fn test_round_trip_with_firmware() {
+        let mut emu = boot_gx();
+        // ATTN 'TST' XRECV
+        press(
+            &mut emu,
+            &[0x8000, 0x04, ALPHA, ALPHA, 0x54, 0x34, 0x54, ALPHA, ENTER],
+        );
+        press(
+            &mut emu,
+            &[ALPHA, ALPHA, 0x50, 0x60, 0x81, 0x83, 0x52, ALPHA, ENTER],
+        );
+        let text = b"%%HP: T(3)A(D)F(.);\r\n\"XMODEM\"\r\n";
+        let mut reports = Vec::new();
+        emu.xmodem_send(text, |p| reports.push(p)).unwrap();
+        assert_eq!(reports.last().unwrap().done, text.len());
+
+        // 'TST' XSEND: the firmware sends the stored object in binary form.
+        press(
+            &mut emu,
+            &[0x04, ALPHA, ALPHA, 0x54, 0x34, 0x54, ALPHA, ENTER],
+        );
+        press(
+            &mut emu,
+            &[ALPHA, ALPHA, 0x50, 0x34, 0x81, 0x64, 0x82, ALPHA, ENTER],
+        );
+        let data = emu.xmodem_receive(XModemCheck::Crc, |_| {}).unwrap();
+        assert!(data.starts_with(b"HPHP48-"));
+        assert!(data.windows(text.len()).any(|w| w == text));
+    }
+}
@@ -364,6 +364,13 @@ function showCalculator(): void {
   if (loading) loading.style.display = "none";
 }
 
+function showLoadError(text: string): void {
+  const loading = document.getElementById("loading");
+  loading?.querySelector(".spinner")?.remove();
+  const message = loading?.querySelector("p");
+  if (message) message.textContent = text;
+}
+
 // ---------------------------------------------------------------------------
 // SVG Button Generation
 // (Identical to hp48.ts — pure DOM code, no emulator dependency)
@@ -893,13 +900,44 @@ function startAutoSave(): void {
 // Emulation loop
 // ---------------------------------------------------------------------------
 
+// A debugger stop (breakpoint, or with ?stop=illegal an illegal opcode or
+// TRAP) freezes the calculator; a banner over the display says why, and
+// clicking it or pressing F10 resumes.
+function setupDebugStop(): void {
+  if (new URLSearchParams(location.search).get("stop") === "illegal") {
+    hp48.set_stop_on_illegal(true);
+  }
+  document.getElementById("debug-stop")?.addEventListener("click", resumeFromStop);
+  document.addEventListener("keydown", (e) => {
+    if (e.key !== "F10" || !hp48.halted()) return;
+    e.preventDefault();
+    resumeFromStop();
+  });
+}
+
+function showStop(text: string): void {
+  const banner = document.getElementById("debug-stop");
+  if (!banner) return;
+  banner.textContent = `Stopped: ${text} — click or F10 to resume`;
+  banner.classList.add("shown");
+}
+
+function resumeFromStop(): void {
+  hp48.resume();
+  document.getElementById("debug-stop")?.classList.remove("shown");
+}
+
 function startEmulationLoop(): void {
   let lastTime = performance.now();
 
   function frame(now: number): void {
     const elapsed = now - lastTime;
     lastTime = now;
-    hp48.run_frame(elapsed, now / 1000.0);
+    const stop = hp48.run_frame(elapsed, now / 1000.0);
+    if (stop) {
+      showStop(stop.text);
+      stop.free();
+    }
     requestAnimationFrame(frame);
   }
 
@@ -942,7 +980,15 @@ async function main(): Promise<void> {
     try { state = await fetchAsset("hp48"); console.log(`[hp48] loaded state from assets: ${state.byteLength} bytes`); } catch { console.log("[hp48] no state found, starting fresh"); }
   }
 
-  hp48 = new Hp48(rom, ram, state);
+  // An unknown or corrupt ROM is refused: report it on the console, and on
+  // the page instead of the spinner.
+  try {
+    hp48 = new Hp48(rom, ram, state);
+  } catch (e) {
+    console.error(`[hp48] ${e}`);
+    showLoadError(`Cannot start: ${e}`);
+    return;
+  }
   // C set_accesstime() uses local time (gettimeofday - timezone offset).
   // Date.now() is UTC ms; subtract timezone offset to get local epoch seconds.
   const localEpochSecs = Date.now() / 1000 - new Date().getTimezoneOffset() * 60;
@@ -952,6 +998,7 @@ async function main(): Promise<void> {
   startDisplayLoop();
   setupButtonInput();
   setupKeyboardInput();
+  setupDebugStop();
   initAudioEarly();
   setupAudioUnlock();
   showCalculator();